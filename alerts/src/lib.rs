@@ -0,0 +1,7 @@
+//! Alert webhook payloads and delivery targets
+//!
+//! Shared by the `alerts` dispatcher, which delivers the alerts, and the API, which checks the
+//! URLs webhooks are registered with.
+
+pub mod payload;
+pub mod target;
@@ -0,0 +1,602 @@
+use alerts::payload::{AlertPayload, SIGNATURE_HEADER, sign};
+use alerts::target::{self, PublicResolver};
+use chrono::Utc;
+use db::{DbPool, DeliveryStatus, DueAlertDelivery, NewDeliveryAttempt};
+use reqwest::Client;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::redirect::Policy;
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::task::JoinSet;
+use tokio::time::sleep;
+
+const SLEEP: Duration = Duration::from_secs(2);
+
+/// Longest delay between two attempts of a delivery
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(3600);
+
+/// Settings of the dispatcher, read from the environment
+#[derive(Debug, Clone)]
+struct Config {
+    /// Number of exchanges and deliveries claimed at once
+    batch_size: i64,
+    /// Seconds a claim is held before another dispatcher may take over
+    lease_seconds: i64,
+    /// Exchanges older than this are not evaluated, so backfills do not trigger alerts
+    max_age: Duration,
+    /// Number of attempts before a delivery is given up
+    max_attempts: i32,
+    /// Delay before the first retry, doubled for every further retry
+    retry_delay: Duration,
+    /// Whether webhooks may target non-public addresses
+    allow_private_targets: bool,
+}
+
+fn env_or<T: std::str::FromStr>(name: &str, default: &str) -> T {
+    env::var(name)
+        .unwrap_or_else(|_| default.to_string())
+        .parse::<T>()
+        .unwrap_or_else(|_| panic!("{} must be a number", name))
+}
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+
+    let startup_delay = Duration::from_millis(env_or("STARTUP_DELAY", "100"));
+    sleep(startup_delay).await;
+
+    env_logger::Builder::new()
+        .filter(None, log::LevelFilter::Info)
+        .init();
+
+    let config = Config {
+        batch_size: env_or("ALERT_BATCH_SIZE", "100"),
+        lease_seconds: env_or("ALERT_LEASE_SECONDS", "60"),
+        max_age: Duration::from_secs(env_or("ALERT_MAX_AGE_SECONDS", "3600")),
+        max_attempts: env_or::<i32>("ALERT_MAX_ATTEMPTS", "8").max(1),
+        retry_delay: Duration::from_secs(env_or("ALERT_RETRY_SECONDS", "30")),
+        allow_private_targets: target::allow_private_targets(),
+    };
+    let client = build_client(
+        Duration::from_secs(env_or("ALERT_TIMEOUT_SECONDS", "10")),
+        config.allow_private_targets,
+    )?;
+
+    let pool = db::establish_connection().await?;
+
+    log::info!("Started alert dispatcher ({:?})", config);
+
+    loop {
+        let evaluated = evaluate_exchanges(&pool, &config).await?;
+        let delivered = deliver_alerts(&pool, &client, &config).await?;
+
+        if evaluated == 0 && delivered == 0 {
+            sleep(SLEEP).await;
+        }
+    }
+}
+
+/// Builds the HTTP client deliveries are posted with
+///
+/// Redirects are not followed and, unless private targets are allowed, only public addresses
+/// are connected to, so webhooks cannot reach internal services.
+fn build_client(timeout: Duration, allow_private_targets: bool) -> reqwest::Result<Client> {
+    let builder = Client::builder().timeout(timeout).redirect(Policy::none());
+    if allow_private_targets {
+        builder.build()
+    } else {
+        builder.dns_resolver(Arc::new(PublicResolver)).build()
+    }
+}
+
+/// Delay before the next attempt of a delivery that failed `attempts` times
+///
+/// Starts at `retry_delay` and doubles with every attempt, up to `MAX_RETRY_DELAY`.
+fn retry_delay(config: &Config, attempts: i32) -> Duration {
+    config
+        .retry_delay
+        .saturating_mul(1 << (attempts - 1).clamp(0, 16))
+        .min(MAX_RETRY_DELAY)
+}
+
+/// Evaluates a batch of new exchanges against the conditions of every webhook and queues
+/// a delivery for every triggered condition
+///
+/// Returns the number of exchanges evaluated.
+async fn evaluate_exchanges(pool: &DbPool, config: &Config) -> anyhow::Result<usize> {
+    let exchanges =
+        db::claim_queued_exchanges(pool, config.batch_size, config.lease_seconds).await?;
+    if exchanges.is_empty() {
+        return Ok(0);
+    }
+
+    let now = Utc::now();
+    for exchange in &exchanges {
+        if (now - exchange.timestamp).to_std().unwrap_or_default() > config.max_age {
+            continue;
+        }
+
+        for condition in db::get_triggered_alert_conditions(pool, exchange).await? {
+            let payload = serde_json::to_string(&AlertPayload::new(&condition, exchange))?;
+            if let Some(delivery) =
+                db::create_alert_delivery(pool, &condition, exchange, &payload).await?
+            {
+                log::info!(
+                    "Exchange {} triggered {} condition {} of webhook {} (delivery {})",
+                    exchange.id,
+                    condition.kind,
+                    condition.id,
+                    condition.webhook_id,
+                    delivery.id
+                );
+            }
+        }
+    }
+
+    let ids: Vec<i32> = exchanges.iter().map(|exchange| exchange.id).collect();
+    db::complete_queued_exchanges(pool, &ids).await?;
+
+    Ok(exchanges.len())
+}
+
+/// Posts a batch of due deliveries to their webhooks and records the outcome
+///
+/// Returns the number of deliveries attempted.
+async fn deliver_alerts(pool: &DbPool, client: &Client, config: &Config) -> anyhow::Result<usize> {
+    let deliveries =
+        db::claim_due_alert_deliveries(pool, config.batch_size, config.lease_seconds).await?;
+    let attempted = deliveries.len();
+
+    let mut tasks = JoinSet::new();
+    for delivery in deliveries {
+        tasks.spawn(deliver_alert(
+            pool.clone(),
+            client.clone(),
+            config.clone(),
+            delivery,
+        ));
+    }
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
+
+    Ok(attempted)
+}
+
+/// Posts a delivery to its webhook and records the attempt
+///
+/// Deliveries the webhook did not accept with a 2xx status are retried with exponential
+/// backoff until `max_attempts` is reached.
+async fn deliver_alert(
+    pool: DbPool,
+    client: Client,
+    config: Config,
+    delivery: DueAlertDelivery,
+) -> anyhow::Result<()> {
+    let started = Instant::now();
+    let signature = sign(&delivery.secret, Utc::now().timestamp(), &delivery.payload);
+
+    // Webhooks registered before URLs were checked, or with an IP literal, are refused here
+    let (status_code, error) = match target::check_url(&delivery.url, config.allow_private_targets)
+        .await
+    {
+        Err(e) => (None, Some(e.to_string())),
+        Ok(url) => {
+            let response = client
+                .post(url)
+                .header(CONTENT_TYPE, "application/json")
+                .header(SIGNATURE_HEADER, signature)
+                .header("X-Rogue-Delivery", delivery.id.to_string())
+                .body(delivery.payload.clone())
+                .send()
+                .await;
+
+            match response {
+                Ok(response) if response.status().is_success() => (Some(response.status()), None),
+                Ok(response) => (
+                    Some(response.status()),
+                    Some(format!("Webhook responded with {}", response.status())),
+                ),
+                Err(e) => (e.status(), Some(e.to_string())),
+            }
+        }
+    };
+
+    let attempt = NewDeliveryAttempt {
+        delivery_id: delivery.id,
+        status_code: status_code.map(|status| status.as_u16() as i32),
+        error,
+        duration_ms: started.elapsed().as_millis() as i32,
+    };
+
+    let attempts = delivery.attempts + 1;
+    let retry_delay = retry_delay(&config, attempts);
+    let status = match &attempt.error {
+        None => DeliveryStatus::Delivered,
+        Some(_) if attempts >= config.max_attempts => DeliveryStatus::Failed,
+        Some(_) => DeliveryStatus::Pending,
+    };
+
+    match &attempt.error {
+        None => log::info!("Delivered alert {} to {}", delivery.id, delivery.url),
+        Some(e) => log::warn!(
+            "Attempt {} of alert {} to {} failed, now {}: {}",
+            attempts,
+            delivery.id,
+            delivery.url,
+            status,
+            e
+        ),
+    }
+
+    db::record_alert_delivery_attempt(&pool, &attempt, status, Utc::now() + retry_delay).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::DateTime;
+    use db::{AlertConditionKind, ExchangeWithDependencies, NewAlertCondition, NewWebhook};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::task::JoinHandle;
+
+    fn config(max_attempts: i32, allow_private_targets: bool) -> Config {
+        Config {
+            batch_size: 10,
+            lease_seconds: 60,
+            max_age: Duration::from_secs(3600),
+            max_attempts,
+            retry_delay: Duration::from_secs(30),
+            allow_private_targets,
+        }
+    }
+
+    /// Accepts one HTTP request on a local port, answers it with `status` and returns the
+    /// raw request
+    async fn receive_once(status: u16) -> (String, JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/hook", listener.local_addr().unwrap());
+
+        let request = tokio::spawn(async move {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = vec![];
+            let mut buffer = [0; 4096];
+            loop {
+                let read = socket.read(&mut buffer).await.unwrap();
+                request.extend_from_slice(&buffer[..read]);
+                let text = String::from_utf8_lossy(&request);
+                let complete = text.split_once("\r\n\r\n").is_some_and(|(head, body)| {
+                    let length: usize = head
+                        .lines()
+                        .find_map(|line| {
+                            line.to_lowercase()
+                                .strip_prefix("content-length: ")?
+                                .parse()
+                                .ok()
+                        })
+                        .unwrap_or(0);
+                    body.len() >= length
+                });
+                if complete || read == 0 {
+                    break;
+                }
+            }
+            socket
+                .write_all(
+                    format!("HTTP/1.1 {status} Status\r\ncontent-length: 0\r\n\r\n").as_bytes(),
+                )
+                .await
+                .unwrap();
+            String::from_utf8(request).unwrap()
+        });
+
+        (url, request)
+    }
+
+    /// Value of a header of a raw HTTP request
+    fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+        request.lines().find_map(|line| {
+            let (key, value) = line.split_once(": ")?;
+            key.eq_ignore_ascii_case(name).then_some(value)
+        })
+    }
+
+    /// Fixture webhook, exchange and delivery with names unique to the run
+    struct Fixture {
+        pool: DbPool,
+        prefix: String,
+    }
+
+    impl Fixture {
+        /// Connects to the database of DATABASE_URL, None without one
+        async fn new(prefix: &str) -> Option<Self> {
+            dotenv::dotenv().ok();
+            if env::var("DATABASE_URL").is_err() {
+                eprintln!("DATABASE_URL not set, skipping");
+                return None;
+            }
+            Some(Self {
+                pool: db::establish_connection().await.unwrap(),
+                prefix: format!("{}{}", prefix, Utc::now().timestamp_micros()),
+            })
+        }
+
+        fn name(&self, name: &str) -> String {
+            format!("{}{}", self.prefix, name)
+        }
+
+        /// Creates a pending delivery to `url`
+        async fn delivery(&self, url: &str) -> DueAlertDelivery {
+            let exchange = db::create_exchange_with_dependencies(
+                &self.pool,
+                &ExchangeWithDependencies {
+                    slot: 1,
+                    signature: self.name("sig"),
+                    index: 0,
+                    timestamp: Utc::now(),
+                    side: "BUY".to_string(),
+                    buyer_wallet: self.name("buyer"),
+                    seller_wallet: self.name("seller"),
+                    asset_mint: self.name("asset"),
+                    pair_mint: self.name("atlas"),
+                    asset_decimals: Some(0),
+                    pair_decimals: Some(8),
+                    price: 10.0,
+                    size: 1,
+                    volume: 10.0,
+                    fee: 0.0,
+                    buddy: 0.0,
+                    processor_version: 1,
+                },
+            )
+            .await
+            .unwrap();
+            let exchange = db::get_exchange_detailed_by_id(&self.pool, exchange.id)
+                .await
+                .unwrap()
+                .unwrap();
+
+            let (webhook, conditions) = db::create_webhook(
+                &self.pool,
+                &NewWebhook {
+                    url: url.to_string(),
+                    secret: self.name("secret"),
+                    description: Some(self.prefix.clone()),
+                },
+                &[NewAlertCondition {
+                    kind: AlertConditionKind::WalletTraded,
+                    asset_mint: None,
+                    pair_mint: None,
+                    wallet: Some(self.name("buyer")),
+                    threshold: None,
+                    window_seconds: 3600,
+                    cooldown_seconds: 0,
+                }],
+            )
+            .await
+            .unwrap();
+            let payload =
+                serde_json::to_string(&AlertPayload::new(&conditions[0], &exchange)).unwrap();
+            let delivery =
+                db::create_alert_delivery(&self.pool, &conditions[0], &exchange, &payload)
+                    .await
+                    .unwrap()
+                    .unwrap();
+
+            DueAlertDelivery {
+                id: delivery.id,
+                attempts: delivery.attempts,
+                payload,
+                url: webhook.url,
+                secret: webhook.secret,
+            }
+        }
+
+        /// Status, attempts, next attempt and last status code of a delivery
+        async fn recorded(&self, id: i32) -> (DeliveryStatus, i32, DateTime<Utc>, Option<i32>) {
+            sqlx::query_as(
+                r#"
+                SELECT status, attempts, next_attempt_at, last_status_code
+                FROM alerts.deliveries
+                WHERE id = $1
+                "#,
+            )
+            .bind(id)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap()
+        }
+
+        /// Status codes and errors of the recorded attempts of a delivery
+        async fn attempts(&self, id: i32) -> Vec<(Option<i32>, Option<String>)> {
+            sqlx::query_as(
+                r#"
+                SELECT status_code, error
+                FROM alerts.delivery_attempts
+                WHERE delivery_id = $1
+                ORDER BY id
+                "#,
+            )
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+            .unwrap()
+        }
+
+        async fn clean_up(&self) {
+            for query in [
+                "DELETE FROM alerts.webhooks WHERE description = $1",
+                "DELETE FROM market.exchanges WHERE signature LIKE $1 || '%'",
+                "DELETE FROM staratlas.tokens WHERE mint LIKE $1 || '%'",
+                "DELETE FROM staratlas.players WHERE wallet_address LIKE $1 || '%'",
+            ] {
+                sqlx::query(query)
+                    .bind(&self.prefix)
+                    .execute(&self.pool)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    #[test]
+    fn retry_delay_doubles_up_to_the_maximum() {
+        let config = config(8, false);
+
+        assert_eq!(retry_delay(&config, 1), Duration::from_secs(30));
+        assert_eq!(retry_delay(&config, 2), Duration::from_secs(60));
+        assert_eq!(retry_delay(&config, 4), Duration::from_secs(240));
+        assert_eq!(retry_delay(&config, 8), MAX_RETRY_DELAY);
+        assert_eq!(retry_delay(&config, 100), MAX_RETRY_DELAY);
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn accepted_delivery_is_signed_and_recorded() {
+        let Some(fixture) = Fixture::new("signed").await else {
+            return;
+        };
+        let (url, request) = receive_once(200).await;
+        let delivery = fixture.delivery(&url).await;
+        let client = build_client(Duration::from_secs(5), true).unwrap();
+
+        let delivered = deliver_alert(
+            fixture.pool.clone(),
+            client,
+            config(8, true),
+            delivery.clone(),
+        )
+        .await;
+        let request = request.await.unwrap();
+        let recorded = fixture.recorded(delivery.id).await;
+        let attempts = fixture.attempts(delivery.id).await;
+        fixture.clean_up().await;
+
+        assert!(delivered.is_ok());
+        assert!(request.starts_with("POST /hook "));
+        assert!(request.ends_with(&delivery.payload));
+        assert_eq!(
+            header(&request, "X-Rogue-Delivery"),
+            Some(delivery.id.to_string().as_str())
+        );
+        let signature = header(&request, SIGNATURE_HEADER).unwrap();
+        let timestamp: i64 = signature
+            .strip_prefix("t=")
+            .and_then(|rest| rest.split(',').next())
+            .unwrap()
+            .parse()
+            .unwrap();
+        assert_eq!(
+            signature,
+            sign(&delivery.secret, timestamp, &delivery.payload)
+        );
+        assert_eq!(recorded.0, DeliveryStatus::Delivered);
+        assert_eq!(recorded.1, 1);
+        assert_eq!(recorded.3, Some(200));
+        assert_eq!(attempts, [(Some(200), None)]);
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn rejected_delivery_is_retried_with_backoff() {
+        let Some(fixture) = Fixture::new("retried").await else {
+            return;
+        };
+        let (url, request) = receive_once(500).await;
+        let mut delivery = fixture.delivery(&url).await;
+        let client = build_client(Duration::from_secs(5), true).unwrap();
+        // The second attempt waits twice the retry delay
+        delivery.attempts = 1;
+
+        let before = Utc::now();
+        let delivered = deliver_alert(
+            fixture.pool.clone(),
+            client,
+            config(8, true),
+            delivery.clone(),
+        )
+        .await;
+        request.await.unwrap();
+        let (status, attempts, next_attempt_at, last_status_code) =
+            fixture.recorded(delivery.id).await;
+        let recorded_attempts = fixture.attempts(delivery.id).await;
+        fixture.clean_up().await;
+
+        assert!(delivered.is_ok());
+        assert_eq!(status, DeliveryStatus::Pending);
+        assert_eq!(attempts, 1);
+        assert_eq!(last_status_code, Some(500));
+        let delay = (next_attempt_at - before).num_seconds();
+        assert!((60..=65).contains(&delay), "retried after {delay}s");
+        assert_eq!(recorded_attempts.len(), 1);
+        assert_eq!(recorded_attempts[0].0, Some(500));
+        assert!(recorded_attempts[0].1.is_some());
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn delivery_fails_after_the_last_attempt() {
+        let Some(fixture) = Fixture::new("failed").await else {
+            return;
+        };
+        let (url, request) = receive_once(503).await;
+        let delivery = fixture.delivery(&url).await;
+        let client = build_client(Duration::from_secs(5), true).unwrap();
+
+        let delivered = deliver_alert(
+            fixture.pool.clone(),
+            client,
+            config(1, true),
+            delivery.clone(),
+        )
+        .await;
+        request.await.unwrap();
+        let (status, ..) = fixture.recorded(delivery.id).await;
+        fixture.clean_up().await;
+
+        assert!(delivered.is_ok());
+        assert_eq!(status, DeliveryStatus::Failed);
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn private_target_is_refused_unless_allowed() {
+        let Some(fixture) = Fixture::new("private").await else {
+            return;
+        };
+        let (url, request) = receive_once(200).await;
+        let delivery = fixture.delivery(&url).await;
+        let client = build_client(Duration::from_secs(5), false).unwrap();
+
+        let delivered = deliver_alert(
+            fixture.pool.clone(),
+            client,
+            config(8, false),
+            delivery.clone(),
+        )
+        .await;
+        let (status, ..) = fixture.recorded(delivery.id).await;
+        let attempts = fixture.attempts(delivery.id).await;
+        fixture.clean_up().await;
+
+        assert!(delivered.is_ok());
+        // Nothing was posted to the receiver
+        assert!(!request.is_finished());
+        request.abort();
+        assert_eq!(status, DeliveryStatus::Pending);
+        assert_eq!(attempts.len(), 1);
+        assert_eq!(attempts[0].0, None);
+        assert!(
+            attempts[0]
+                .1
+                .as_deref()
+                .unwrap()
+                .contains("non-public address")
+        );
+    }
+}
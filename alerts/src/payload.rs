@@ -0,0 +1,143 @@
+//! Alert payloads and their signatures
+
+use chrono::{DateTime, Utc};
+use db::{AlertCondition, ExchangeDetailed};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+
+/// Header carrying the signature of a payload
+pub const SIGNATURE_HEADER: &str = "X-Rogue-Signature";
+
+/// JSON body posted to a webhook when one of its conditions is triggered
+#[derive(Debug, Serialize)]
+pub struct AlertPayload<'a> {
+    /// Webhook the alert is posted to
+    pub webhook_id: i32,
+    /// Condition that was triggered
+    pub condition: ConditionPayload<'a>,
+    /// Exchange that triggered the condition
+    pub exchange: ExchangePayload<'a>,
+}
+
+/// Condition part of an alert payload
+#[derive(Debug, Serialize)]
+pub struct ConditionPayload<'a> {
+    /// ID of the condition
+    pub id: i32,
+    /// Kind of the condition
+    pub kind: String,
+    /// Asset mint address the condition is limited to (if set)
+    pub asset: Option<&'a str>,
+    /// Pair mint address the condition is limited to (if set)
+    pub pair: Option<&'a str>,
+    /// Wallet address the condition is limited to (if set)
+    pub wallet: Option<&'a str>,
+    /// Price or volume threshold of the condition
+    pub threshold: Option<f64>,
+    /// Length of the volume window in seconds
+    pub window_seconds: i32,
+}
+
+/// Exchange part of an alert payload
+#[derive(Debug, Serialize)]
+pub struct ExchangePayload<'a> {
+    /// ID of the exchange
+    pub id: i32,
+    /// Slot of the transaction
+    pub slot: i32,
+    /// Signature of the transaction
+    pub signature: &'a str,
+    /// Index of the instruction within the transaction
+    pub index: i32,
+    /// Block time of the transaction
+    pub timestamp: DateTime<Utc>,
+    /// Side of the exchange (BUY or SELL)
+    pub side: &'a str,
+    /// Wallet address of the buyer
+    pub buyer: &'a str,
+    /// Wallet address of the seller
+    pub seller: &'a str,
+    /// Asset mint address
+    pub asset: &'a str,
+    /// Asset symbol (if available)
+    pub asset_symbol: Option<&'a str>,
+    /// Pair mint address
+    pub pair: &'a str,
+    /// Pair symbol (if available)
+    pub pair_symbol: Option<&'a str>,
+    /// Price per unit
+    pub price: f64,
+    /// Number of units exchanged
+    pub size: i32,
+    /// Total volume of the exchange
+    pub volume: f64,
+}
+
+impl<'a> AlertPayload<'a> {
+    /// Builds the payload of a condition triggered by an exchange
+    pub fn new(condition: &'a AlertCondition, exchange: &'a ExchangeDetailed) -> Self {
+        Self {
+            webhook_id: condition.webhook_id,
+            condition: ConditionPayload {
+                id: condition.id,
+                kind: condition.kind.to_string(),
+                asset: condition.asset_mint.as_deref(),
+                pair: condition.pair_mint.as_deref(),
+                wallet: condition.wallet.as_deref(),
+                threshold: condition.threshold,
+                window_seconds: condition.window_seconds,
+            },
+            exchange: ExchangePayload {
+                id: exchange.id,
+                slot: exchange.slot,
+                signature: &exchange.signature,
+                index: exchange.index,
+                timestamp: exchange.timestamp,
+                side: &exchange.side,
+                buyer: &exchange.buyer_wallet,
+                seller: &exchange.seller_wallet,
+                asset: &exchange.asset_mint,
+                asset_symbol: exchange.asset_symbol.as_deref(),
+                pair: &exchange.pair_mint,
+                pair_symbol: exchange.pair_symbol.as_deref(),
+                price: exchange.price,
+                size: exchange.size,
+                volume: exchange.volume,
+            },
+        }
+    }
+}
+
+/// Signs a payload with the secret of its webhook
+///
+/// Returns the value of the signature header, `t=<unix timestamp>,v1=<hex HMAC-SHA256>`,
+/// where the HMAC covers `<unix timestamp>.<payload>`. Receivers recompute it to verify
+/// the sender and reject old timestamps to prevent replays.
+pub fn sign(secret: &str, timestamp: i64, payload: &str) -> String {
+    let mut mac =
+        Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(payload.as_bytes());
+
+    format!(
+        "t={},v1={}",
+        timestamp,
+        hex::encode(mac.finalize().into_bytes())
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign_matches_known_vector() {
+        // HMAC-SHA256 of `1700000000.{"webhook_id":1}` keyed with `whsec_test`
+        assert_eq!(
+            sign("whsec_test", 1700000000, r#"{"webhook_id":1}"#),
+            "t=1700000000,v1=4f640405274d6d1c1ee681f8afe52900baa51e5bb01c4e833094f30e11d46bf2"
+        );
+    }
+}
@@ -0,0 +1,222 @@
+//! Checks that webhook URLs point to public hosts
+//!
+//! Webhook URLs are supplied by API clients, so without a check the dispatcher could be made to
+//! post to services only reachable from inside the deployment (SSRF). URLs are checked when a
+//! webhook is registered and again before every delivery, and the dispatcher connects only to
+//! addresses returned by [`PublicResolver`], so a host that resolves to a public address at
+//! registration and to an internal one later (DNS rebinding) is still refused.
+//!
+//! Deployments whose receivers run next to the dispatcher can opt out of the check with
+//! `ALERT_ALLOW_PRIVATE_TARGETS=true`, see [`allow_private_targets`].
+
+use reqwest::Url;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use std::env;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use thiserror::Error;
+
+/// Reasons a webhook URL is refused
+#[derive(Debug, Error)]
+pub enum TargetError {
+    #[error("url is not a valid URL: {0}")]
+    InvalidUrl(#[from] url::ParseError),
+    #[error("url must be an http or https URL")]
+    UnsupportedScheme,
+    #[error("url has no host")]
+    MissingHost,
+    #[error("host {0} could not be resolved: {1}")]
+    Unresolvable(String, io::Error),
+    #[error("host {0} resolves to the non-public address {1}")]
+    NonPublicAddress(String, IpAddr),
+}
+
+/// Environment variable that lets webhooks target non-public addresses, off by default
+pub const ALLOW_PRIVATE_TARGETS: &str = "ALERT_ALLOW_PRIVATE_TARGETS";
+
+/// Returns whether webhooks may target non-public addresses, as set by
+/// `ALERT_ALLOW_PRIVATE_TARGETS`
+///
+/// Both the API, which checks URLs at registration, and the dispatcher must be configured alike.
+///
+/// # Panics
+/// Panics if the variable is set to something other than `true` or `false`
+pub fn allow_private_targets() -> bool {
+    env::var(ALLOW_PRIVATE_TARGETS)
+        .map(|value| {
+            value
+                .parse()
+                .unwrap_or_else(|_| panic!("{} must be true or false", ALLOW_PRIVATE_TARGETS))
+        })
+        .unwrap_or(false)
+}
+
+/// Returns whether an address may be posted to
+///
+/// Loopback, private, link-local, unspecified, shared (carrier-grade NAT), broadcast,
+/// multicast and documentation addresses are refused, IPv4-mapped IPv6 addresses by their
+/// IPv4 address.
+pub fn is_public_ip(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_ipv4(ip),
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(mapped) => is_public_ipv4(mapped),
+            None => is_public_ipv6(ip),
+        },
+    }
+}
+
+fn is_public_ipv4(ip: Ipv4Addr) -> bool {
+    let [a, b, ..] = ip.octets();
+    !(ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_unspecified()
+        || ip.is_broadcast()
+        || ip.is_multicast()
+        || ip.is_documentation()
+        || a == 0
+        || (a == 100 && (64..128).contains(&b)))
+}
+
+fn is_public_ipv6(ip: Ipv6Addr) -> bool {
+    let first = ip.segments()[0];
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local fc00::/7 and link-local fe80::/10
+        || (first & 0xfe00) == 0xfc00
+        || (first & 0xffc0) == 0xfe80)
+}
+
+/// Checks that a webhook URL is http(s) and its host resolves to public addresses only
+///
+/// # Arguments
+/// * `url` - The webhook URL
+/// * `allow_private` - Whether non-public addresses are accepted, see [`allow_private_targets`]
+///
+/// # Returns
+/// The parsed URL
+///
+/// # Errors
+/// Returns an error if the URL is invalid, not http(s), or its host cannot be resolved or
+/// resolves to any non-public address
+pub async fn check_url(url: &str, allow_private: bool) -> Result<Url, TargetError> {
+    let url = Url::parse(url)?;
+    if url.scheme() != "http" && url.scheme() != "https" {
+        return Err(TargetError::UnsupportedScheme);
+    }
+    let host = url.host_str().ok_or(TargetError::MissingHost)?;
+    if allow_private {
+        return Ok(url);
+    }
+    let port = url.port_or_known_default().unwrap_or(443);
+
+    // IP literals never reach the resolver of the dispatcher
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        if !is_public_ip(ip) {
+            return Err(TargetError::NonPublicAddress(host.to_string(), ip));
+        }
+        return Ok(url);
+    }
+
+    let addrs = tokio::net::lookup_host((host, port))
+        .await
+        .map_err(|e| TargetError::Unresolvable(host.to_string(), e))?
+        .collect::<Vec<_>>();
+    if addrs.is_empty() {
+        return Err(TargetError::Unresolvable(
+            host.to_string(),
+            io::Error::new(io::ErrorKind::NotFound, "no addresses"),
+        ));
+    }
+    if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+        return Err(TargetError::NonPublicAddress(host.to_string(), addr.ip()));
+    }
+
+    Ok(url)
+}
+
+/// DNS resolver of the dispatcher's HTTP client that only returns public addresses
+///
+/// Resolution fails if the host resolves to any non-public address. Not installed when
+/// private targets are allowed.
+#[derive(Debug, Default)]
+pub struct PublicResolver;
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(async move {
+            let host = name.as_str().to_string();
+            let addrs: Vec<SocketAddr> =
+                tokio::net::lookup_host((host.as_str(), 0)).await?.collect();
+            if let Some(addr) = addrs.iter().find(|addr| !is_public_ip(addr.ip())) {
+                return Err(TargetError::NonPublicAddress(host, addr.ip()).into());
+            }
+            let addrs: Addrs = Box::new(addrs.into_iter());
+            Ok(addrs)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_are_not_public() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "0.0.0.0",
+            "100.64.0.1",
+            "::1",
+            "::",
+            "fe80::1",
+            "fd00::1",
+            "::ffff:127.0.0.1",
+        ] {
+            assert!(!is_public_ip(ip.parse().unwrap()), "{} is not public", ip);
+        }
+        for ip in ["1.1.1.1", "8.8.8.8", "2606:4700:4700::1111"] {
+            assert!(is_public_ip(ip.parse().unwrap()), "{} is public", ip);
+        }
+    }
+
+    #[tokio::test]
+    async fn urls_of_internal_hosts_are_refused() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://169.254.169.254/latest/meta-data",
+            "http://localhost/hook",
+        ] {
+            assert!(check_url(url, false).await.is_err(), "{} is refused", url);
+        }
+        assert!(matches!(
+            check_url("ftp://example.com", false).await,
+            Err(TargetError::UnsupportedScheme)
+        ));
+        assert!(check_url("https://1.1.1.1/hook", false).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn internal_hosts_are_accepted_when_private_targets_are_allowed() {
+        for url in [
+            "http://127.0.0.1:8080/hook",
+            "http://[::1]/hook",
+            "http://localhost/hook",
+        ] {
+            assert!(check_url(url, true).await.is_ok(), "{} is accepted", url);
+        }
+        // The scheme is still checked
+        assert!(matches!(
+            check_url("file:///etc/passwd", true).await,
+            Err(TargetError::UnsupportedScheme)
+        ));
+    }
+}
@@ -0,0 +1,530 @@
+//! API implementation for the alert webhook endpoints
+//!
+//! This module provides the alerts-webhooks [POST], alerts-webhooks-{id} [GET, DELETE],
+//! and alerts-webhooks-{id}-deliveries [GET] endpoints as defined in the guidelines.
+//! Alerts are evaluated and delivered by the alerts dispatcher, not by the API. All of them
+//! require an API key with the read scope.
+
+use db::{
+    AlertCondition, AlertConditionKind, AlertDelivery, ApiKeyScope, DbPool, NewAlertCondition,
+    NewWebhook, Webhook,
+};
+use poem_openapi::{
+    ApiResponse, Enum, Object, OpenApi, Tags,
+    param::{Header, Path, Query},
+    payload::{Json, PlainText},
+};
+use rand::{Rng, distributions::Alphanumeric, thread_rng};
+use subtle::ConstantTimeEq;
+
+use crate::auth::ApiKeyAuth;
+
+/// Length of the generated webhook secrets
+const SECRET_LENGTH: usize = 48;
+
+/// Default length of the volume window in seconds
+const DEFAULT_WINDOW_SECONDS: i32 = 3600;
+
+/// Tags for the alerts API
+#[derive(Tags)]
+enum AlertsTags {
+    /// Operations related to alert webhooks
+    Alerts,
+}
+
+/// API implementation for the alert webhook endpoints
+pub struct AlertsApi {
+    /// Database connection pool
+    db_pool: DbPool,
+    /// Whether webhooks may target non-public addresses
+    allow_private_targets: bool,
+}
+
+/// Kind of condition that triggers a webhook
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "AlertConditionKind", rename_all = "snake_case")]
+enum ConditionKind {
+    /// An exchange of the asset had a price below the threshold
+    PriceBelow,
+    /// An exchange of the asset had a price above the threshold
+    PriceAbove,
+    /// The wallet bought or sold
+    WalletTraded,
+    /// The volume of the market within the window exceeded the threshold
+    VolumeAbove,
+}
+
+/// Condition request object
+#[derive(Debug, Object)]
+struct AlertConditionRequest {
+    /// Kind of the condition
+    kind: ConditionKind,
+    /// Asset mint address (required for price and volume conditions)
+    asset: Option<String>,
+    /// Pair mint address (required for volume conditions)
+    pair: Option<String>,
+    /// Wallet address of the buyer or seller (required for wallet conditions)
+    wallet: Option<String>,
+    /// Price or volume threshold (required for price and volume conditions)
+    threshold: Option<f64>,
+    /// Length of the volume window in seconds (defaults to one hour)
+    window_seconds: Option<i32>,
+    /// Minimum time between two alerts in seconds (defaults to the volume window for
+    /// volume conditions and 0 otherwise)
+    cooldown_seconds: Option<i32>,
+}
+
+/// Webhook request object
+#[derive(Debug, Object)]
+struct CreateWebhookRequest {
+    /// URL the alerts are posted to
+    url: String,
+    /// Description of the webhook
+    description: Option<String>,
+    /// Conditions that trigger the webhook
+    conditions: Vec<AlertConditionRequest>,
+}
+
+/// Condition response object
+#[derive(Debug, Object)]
+struct AlertConditionResponse {
+    /// ID of the condition
+    id: i32,
+    /// Kind of the condition
+    kind: ConditionKind,
+    /// Asset mint address (if set)
+    asset: Option<String>,
+    /// Pair mint address (if set)
+    pair: Option<String>,
+    /// Wallet address (if set)
+    wallet: Option<String>,
+    /// Price or volume threshold (if set)
+    threshold: Option<f64>,
+    /// Length of the volume window in seconds
+    window_seconds: i32,
+    /// Minimum time between two alerts in seconds
+    cooldown_seconds: i32,
+    /// Block time of the exchange that last triggered the condition (ISO 8601 format)
+    last_triggered_at: Option<String>,
+}
+
+/// Webhook response object
+#[derive(Debug, Object)]
+struct WebhookResponse {
+    /// ID of the webhook
+    id: i32,
+    /// URL the alerts are posted to
+    url: String,
+    /// Description of the webhook (if available)
+    description: Option<String>,
+    /// Secret the payloads are signed with, only returned when the webhook is created
+    secret: Option<String>,
+    /// Whether alerts are delivered to the webhook
+    active: bool,
+    /// Creation timestamp (ISO 8601 format)
+    created_at: String,
+    /// Conditions that trigger the webhook
+    conditions: Vec<AlertConditionResponse>,
+}
+
+/// Delivery response object
+#[derive(Debug, Object)]
+struct AlertDeliveryResponse {
+    /// ID of the delivery
+    id: i32,
+    /// ID of the triggered condition
+    condition_id: i32,
+    /// ID of the exchange that triggered the condition
+    exchange_id: i32,
+    /// Status of the delivery (pending, delivered or failed)
+    status: String,
+    /// Number of attempts made
+    attempts: i32,
+    /// Time of the next attempt of pending deliveries (ISO 8601 format)
+    next_attempt_at: String,
+    /// HTTP status code of the last attempt (if a response was received)
+    last_status_code: Option<i32>,
+    /// Error of the last attempt (if it failed)
+    last_error: Option<String>,
+    /// Creation timestamp (ISO 8601 format)
+    created_at: String,
+    /// Time the webhook accepted the delivery (ISO 8601 format)
+    delivered_at: Option<String>,
+}
+
+#[derive(ApiResponse)]
+enum CreateWebhookResponse {
+    #[oai(status = 201)]
+    Webhook(Json<WebhookResponse>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetWebhookResponse {
+    #[oai(status = 200)]
+    Webhook(Json<WebhookResponse>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum DeleteWebhookResponse {
+    #[oai(status = 204)]
+    Deleted,
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetDeliveriesResponse {
+    #[oai(status = 200)]
+    Deliveries(Json<Vec<AlertDeliveryResponse>>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<ConditionKind> for AlertConditionKind {
+    fn from(kind: ConditionKind) -> Self {
+        match kind {
+            ConditionKind::PriceBelow => AlertConditionKind::PriceBelow,
+            ConditionKind::PriceAbove => AlertConditionKind::PriceAbove,
+            ConditionKind::WalletTraded => AlertConditionKind::WalletTraded,
+            ConditionKind::VolumeAbove => AlertConditionKind::VolumeAbove,
+        }
+    }
+}
+
+impl From<AlertConditionKind> for ConditionKind {
+    fn from(kind: AlertConditionKind) -> Self {
+        match kind {
+            AlertConditionKind::PriceBelow => ConditionKind::PriceBelow,
+            AlertConditionKind::PriceAbove => ConditionKind::PriceAbove,
+            AlertConditionKind::WalletTraded => ConditionKind::WalletTraded,
+            AlertConditionKind::VolumeAbove => ConditionKind::VolumeAbove,
+        }
+    }
+}
+
+impl TryFrom<AlertConditionRequest> for NewAlertCondition {
+    type Error = String;
+
+    /// Checks that the condition has every field its kind requires
+    fn try_from(request: AlertConditionRequest) -> Result<Self, Self::Error> {
+        let (needs_asset, needs_pair, needs_wallet, needs_threshold) = match request.kind {
+            ConditionKind::PriceBelow | ConditionKind::PriceAbove => (true, false, false, true),
+            ConditionKind::WalletTraded => (false, false, true, false),
+            // Volumes are only comparable within one currency
+            ConditionKind::VolumeAbove => (true, true, false, true),
+        };
+
+        let kind = AlertConditionKind::from(request.kind);
+        if needs_asset && request.asset.is_none() {
+            return Err(format!("{} conditions require an asset", kind));
+        }
+        if needs_pair && request.pair.is_none() {
+            return Err(format!("{} conditions require a pair", kind));
+        }
+        if needs_wallet && request.wallet.is_none() {
+            return Err(format!("{} conditions require a wallet", kind));
+        }
+        if needs_threshold && request.threshold.is_none() {
+            return Err(format!("{} conditions require a threshold", kind));
+        }
+
+        let window_seconds = request.window_seconds.unwrap_or(DEFAULT_WINDOW_SECONDS);
+        let cooldown_seconds = request.cooldown_seconds.unwrap_or(match request.kind {
+            ConditionKind::VolumeAbove => window_seconds,
+            _ => 0,
+        });
+        if window_seconds <= 0 || cooldown_seconds < 0 {
+            return Err("window_seconds must be positive and cooldown_seconds not negative".into());
+        }
+
+        Ok(Self {
+            kind,
+            asset_mint: request.asset,
+            pair_mint: request.pair,
+            wallet: request.wallet,
+            threshold: request.threshold,
+            window_seconds,
+            cooldown_seconds,
+        })
+    }
+}
+
+impl From<AlertCondition> for AlertConditionResponse {
+    fn from(condition: AlertCondition) -> Self {
+        Self {
+            id: condition.id,
+            kind: condition.kind.into(),
+            asset: condition.asset_mint,
+            pair: condition.pair_mint,
+            wallet: condition.wallet,
+            threshold: condition.threshold,
+            window_seconds: condition.window_seconds,
+            cooldown_seconds: condition.cooldown_seconds,
+            last_triggered_at: condition
+                .last_triggered_at
+                .map(|timestamp| timestamp.to_rfc3339()),
+        }
+    }
+}
+
+impl From<AlertDelivery> for AlertDeliveryResponse {
+    fn from(delivery: AlertDelivery) -> Self {
+        Self {
+            id: delivery.id,
+            condition_id: delivery.condition_id,
+            exchange_id: delivery.exchange_id,
+            status: delivery.status.to_string(),
+            attempts: delivery.attempts,
+            next_attempt_at: delivery.next_attempt_at.to_rfc3339(),
+            last_status_code: delivery.last_status_code,
+            last_error: delivery.last_error,
+            created_at: delivery.created_at.to_rfc3339(),
+            delivered_at: delivery
+                .delivered_at
+                .map(|timestamp| timestamp.to_rfc3339()),
+        }
+    }
+}
+
+impl WebhookResponse {
+    /// Creates the response of a webhook without its secret
+    fn new(webhook: Webhook, conditions: Vec<AlertCondition>) -> Self {
+        Self {
+            id: webhook.id,
+            url: webhook.url,
+            description: webhook.description,
+            secret: None,
+            active: webhook.active,
+            created_at: webhook.created_at.to_rfc3339(),
+            conditions: conditions
+                .into_iter()
+                .map(AlertConditionResponse::from)
+                .collect(),
+        }
+    }
+}
+
+impl AlertsApi {
+    /// Creates a new instance of the alerts API
+    pub fn new(db_pool: DbPool, allow_private_targets: bool) -> Self {
+        Self {
+            db_pool,
+            allow_private_targets,
+        }
+    }
+
+    /// Loads a webhook if the secret matches
+    ///
+    /// Webhooks with another secret are treated as missing, so IDs cannot be probed. Secrets
+    /// are compared in constant time, so they cannot be guessed from response times.
+    async fn authorized_webhook(&self, id: i32, secret: &str) -> db::Result<Option<Webhook>> {
+        let webhook = db::get_webhook_by_id(&self.db_pool, id).await?;
+        Ok(
+            webhook
+                .filter(|webhook| bool::from(webhook.secret.as_bytes().ct_eq(secret.as_bytes()))),
+        )
+    }
+}
+
+#[OpenApi]
+impl AlertsApi {
+    /// Create an alert webhook
+    ///
+    /// Registers a URL that is sent a signed JSON POST whenever a newly processed exchange
+    /// triggers one of the conditions. The response contains the secret of the webhook. It
+    /// is only returned once and is required to manage the webhook. Every payload carries
+    /// an `X-Rogue-Signature: t=<unix timestamp>,v1=<signature>` header, where the signature
+    /// is the hex HMAC-SHA256 of `<unix timestamp>.<body>` keyed with the secret. Failed
+    /// deliveries are retried with exponential backoff. The URL must be http(s) and its host
+    /// must resolve to public addresses only, unless the deployment allows private targets;
+    /// redirects are not followed.
+    #[oai(path = "/alerts/webhooks", method = "post", tag = "AlertsTags::Alerts")]
+    async fn create_alert_webhook(
+        &self,
+        auth: ApiKeyAuth,
+        request: Json<CreateWebhookRequest>,
+    ) -> CreateWebhookResponse {
+        if !auth.has_scope(ApiKeyScope::Read) {
+            return CreateWebhookResponse::Forbidden;
+        }
+        let request = request.0;
+        // The dispatcher checks the URL again before every delivery, the host may be
+        // re-pointed after registration
+        if let Err(e) = alerts::target::check_url(&request.url, self.allow_private_targets).await {
+            return CreateWebhookResponse::BadRequest(PlainText(e.to_string()));
+        }
+        if request.conditions.is_empty() {
+            return CreateWebhookResponse::BadRequest(PlainText(
+                "at least one condition is required".to_string(),
+            ));
+        }
+
+        let conditions = match request
+            .conditions
+            .into_iter()
+            .map(NewAlertCondition::try_from)
+            .collect::<Result<Vec<_>, _>>()
+        {
+            Ok(conditions) => conditions,
+            Err(e) => return CreateWebhookResponse::BadRequest(PlainText(e)),
+        };
+
+        let secret: String = thread_rng()
+            .sample_iter(&Alphanumeric)
+            .take(SECRET_LENGTH)
+            .map(char::from)
+            .collect();
+        let new_webhook = NewWebhook {
+            url: request.url,
+            secret: secret.clone(),
+            description: request.description,
+        };
+
+        match db::create_webhook(&self.db_pool, &new_webhook, &conditions).await {
+            Ok((webhook, conditions)) => {
+                let mut response = WebhookResponse::new(webhook, conditions);
+                response.secret = Some(secret);
+                CreateWebhookResponse::Webhook(Json(response))
+            }
+            Err(_) => CreateWebhookResponse::DBError,
+        }
+    }
+
+    /// Get an alert webhook
+    ///
+    /// Returns the webhook and its conditions. Requires the secret of the webhook.
+    #[oai(
+        path = "/alerts/webhooks/:id",
+        method = "get",
+        tag = "AlertsTags::Alerts"
+    )]
+    async fn get_alert_webhook(
+        &self,
+        auth: ApiKeyAuth,
+        /// ID of the webhook
+        id: Path<i32>,
+        /// Secret of the webhook
+        #[oai(name = "X-Webhook-Secret")]
+        secret: Header<String>,
+    ) -> GetWebhookResponse {
+        if !auth.has_scope(ApiKeyScope::Read) {
+            return GetWebhookResponse::Forbidden;
+        }
+        let webhook = match self.authorized_webhook(id.0, &secret.0).await {
+            Ok(Some(webhook)) => webhook,
+            Ok(None) => return GetWebhookResponse::NotFound,
+            Err(_) => return GetWebhookResponse::DBError,
+        };
+
+        match db::get_alert_conditions_by_webhook_id(&self.db_pool, webhook.id).await {
+            Ok(conditions) => {
+                GetWebhookResponse::Webhook(Json(WebhookResponse::new(webhook, conditions)))
+            }
+            Err(_) => GetWebhookResponse::DBError,
+        }
+    }
+
+    /// Delete an alert webhook
+    ///
+    /// Deletes the webhook with its conditions and deliveries. Requires the secret of the
+    /// webhook.
+    #[oai(
+        path = "/alerts/webhooks/:id",
+        method = "delete",
+        tag = "AlertsTags::Alerts"
+    )]
+    async fn delete_alert_webhook(
+        &self,
+        auth: ApiKeyAuth,
+        /// ID of the webhook
+        id: Path<i32>,
+        /// Secret of the webhook
+        #[oai(name = "X-Webhook-Secret")]
+        secret: Header<String>,
+    ) -> DeleteWebhookResponse {
+        if !auth.has_scope(ApiKeyScope::Read) {
+            return DeleteWebhookResponse::Forbidden;
+        }
+        match self.authorized_webhook(id.0, &secret.0).await {
+            Ok(Some(webhook)) => match db::delete_webhook(&self.db_pool, webhook.id).await {
+                Ok(true) => DeleteWebhookResponse::Deleted,
+                Ok(false) => DeleteWebhookResponse::NotFound,
+                Err(_) => DeleteWebhookResponse::DBError,
+            },
+            Ok(None) => DeleteWebhookResponse::NotFound,
+            Err(_) => DeleteWebhookResponse::DBError,
+        }
+    }
+
+    /// Get the deliveries of an alert webhook
+    ///
+    /// Returns the delivery log of the webhook, newest first. Requires the secret of the
+    /// webhook.
+    #[oai(
+        path = "/alerts/webhooks/:id/deliveries",
+        method = "get",
+        tag = "AlertsTags::Alerts"
+    )]
+    async fn get_alert_webhook_deliveries(
+        &self,
+        auth: ApiKeyAuth,
+        /// ID of the webhook
+        id: Path<i32>,
+        /// Secret of the webhook
+        #[oai(name = "X-Webhook-Secret")]
+        secret: Header<String>,
+
+        offset: Query<Option<i32>>,
+        limit: Query<Option<i32>>,
+    ) -> GetDeliveriesResponse {
+        if !auth.has_scope(ApiKeyScope::Read) {
+            return GetDeliveriesResponse::Forbidden;
+        }
+        let limit_value: i32 = limit.0.unwrap_or(100);
+        let offset_value: i32 = offset.0.unwrap_or(0);
+
+        let webhook = match self.authorized_webhook(id.0, &secret.0).await {
+            Ok(Some(webhook)) => webhook,
+            Ok(None) => return GetDeliveriesResponse::NotFound,
+            Err(_) => return GetDeliveriesResponse::DBError,
+        };
+
+        match db::get_alert_deliveries_by_webhook_id(
+            &self.db_pool,
+            webhook.id,
+            limit_value,
+            offset_value,
+        )
+        .await
+        {
+            Ok(deliveries) => {
+                let delivery_responses = deliveries
+                    .into_iter()
+                    .map(AlertDeliveryResponse::from)
+                    .collect();
+                GetDeliveriesResponse::Deliveries(Json(delivery_responses))
+            }
+            Err(_) => GetDeliveriesResponse::DBError,
+        }
+    }
+}
@@ -0,0 +1,128 @@
+//! API implementation for the crafting endpoints
+//!
+//! This module provides the crafting-costs [GET] endpoint as defined in the guidelines.
+
+use chrono::{Duration, Utc};
+use db::{DbPool, RecipeCost};
+use poem_openapi::{
+    ApiResponse, Object, OpenApi, Tags,
+    param::Query,
+    payload::{Json, PlainText},
+};
+
+use crate::auth::ApiAccess;
+
+/// Longest period the market prices can be averaged over
+const MAX_HOURS: u32 = 24 * 30;
+
+/// Tags for the crafting API
+#[derive(Tags)]
+enum CraftingTags {
+    /// Operations related to crafting recipes and processes
+    Crafting,
+}
+
+/// API implementation for the crafting endpoints
+pub struct CraftingApi {
+    /// Database connection pool
+    db_pool: DbPool,
+}
+
+/// Recipe cost response object
+#[derive(Debug, Object)]
+struct RecipeCostResponse {
+    /// Recipe address
+    recipe: String,
+    /// Mint of the produced item
+    output_mint: String,
+    /// Units produced per crafted unit
+    output_amount: i64,
+    /// Number of consumed inputs
+    inputs: i64,
+    /// Number of consumed inputs with a market price
+    priced_inputs: i64,
+    /// Market value of the consumed inputs per crafted unit (if all inputs have a price)
+    input_cost: Option<f64>,
+    /// Production cost per produced unit (if all inputs have a price)
+    unit_cost: Option<f64>,
+    /// Average market price of the produced item
+    market_price: Option<f64>,
+    /// Market price minus production cost per produced unit
+    margin: Option<f64>,
+}
+
+#[derive(ApiResponse)]
+enum GetRecipeCostsResponse {
+    #[oai(status = 200)]
+    RecipeCosts(Json<Vec<RecipeCostResponse>>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<RecipeCost> for RecipeCostResponse {
+    fn from(cost: RecipeCost) -> Self {
+        Self {
+            recipe: cost.recipe,
+            output_mint: cost.output_mint,
+            output_amount: cost.output_amount,
+            inputs: cost.inputs,
+            priced_inputs: cost.priced_inputs,
+            input_cost: cost.input_cost,
+            unit_cost: cost.unit_cost,
+            market_price: cost.market_price,
+            margin: cost
+                .market_price
+                .zip(cost.unit_cost)
+                .map(|(price, cost)| price - cost),
+        }
+    }
+}
+
+impl CraftingApi {
+    /// Creates a new instance of the crafting API
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[OpenApi]
+impl CraftingApi {
+    /// Get production cost versus market price per recipe
+    ///
+    /// Prices the consumed inputs and the output of every recipe at their volume weighted
+    /// average exchange price in the given currency. Non-consumable inputs and recipe fees are
+    /// not included in the cost.
+    #[oai(
+        path = "/crafting/costs",
+        method = "get",
+        tag = "CraftingTags::Crafting"
+    )]
+    async fn get_crafting_costs(
+        &self,
+        _access: ApiAccess,
+        /// Mint of the currency to price in
+        currency: Query<String>,
+        /// Only include recipes producing this mint
+        mint: Query<Option<String>>,
+        /// Number of hours of exchanges to average the prices over (default 168, at most 720)
+        hours: Query<Option<u32>>,
+    ) -> GetRecipeCostsResponse {
+        let hours = hours.0.unwrap_or(24 * 7);
+        if hours == 0 || hours > MAX_HOURS {
+            return GetRecipeCostsResponse::BadRequest(PlainText(format!(
+                "hours must be between 1 and {}",
+                MAX_HOURS
+            )));
+        }
+        let since = Utc::now() - Duration::hours(hours as i64);
+
+        match db::get_recipe_costs(&self.db_pool, &currency.0, mint.0.as_deref(), since).await {
+            Ok(costs) => GetRecipeCostsResponse::RecipeCosts(Json(
+                costs.into_iter().map(RecipeCostResponse::from).collect(),
+            )),
+            Err(_) => GetRecipeCostsResponse::DBError,
+        }
+    }
+}
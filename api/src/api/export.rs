@@ -0,0 +1,191 @@
+//! API implementation for the exchange export endpoint
+//!
+//! This module provides the staratlas-exchanges-export [GET] endpoint as defined in the
+//! guidelines. Exports are streamed from a server-side cursor while they are encoded, so
+//! their size is not limited by a page size or by the memory of the server. The cursor holds
+//! a connection for the whole download, so exports use their own small pool and only as many
+//! run at once as it has connections.
+
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use db::{ApiKeyScope, DbPool, ExchangeExport, ExchangeFilter};
+use export::{DEFAULT_BATCH_SIZE, ExportEncoder, ExportFormat};
+use futures_util::TryStreamExt;
+use log::warn;
+use poem::Body;
+use poem_openapi::{
+    ApiResponse, Enum, OpenApi, Tags,
+    param::Query,
+    payload::{Binary, PlainText},
+};
+use tokio::sync::Semaphore;
+
+use crate::auth::ApiKeyAuth;
+
+/// Tags for the export API
+#[derive(Tags)]
+enum ExportTags {
+    /// Operations related to bulk exports of Star Atlas data
+    Export,
+}
+
+/// API implementation for the exchange export endpoint
+pub struct ExportApi {
+    /// Connection pool reserved for exports
+    db_pool: DbPool,
+    /// Permits of the exports that may run at once
+    permits: Arc<Semaphore>,
+}
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "ExportFormat", rename_all = "lowercase")]
+enum Format {
+    /// Comma separated values with a header row
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+    /// Apache Parquet
+    Parquet,
+}
+
+#[derive(ApiResponse)]
+enum ExportResponse {
+    #[oai(status = 200, content_type = "text/csv")]
+    Csv(Binary<Body>, #[oai(header = "Content-Disposition")] String),
+    #[oai(status = 200, content_type = "application/x-ndjson")]
+    Ndjson(Binary<Body>, #[oai(header = "Content-Disposition")] String),
+    #[oai(status = 200, content_type = "application/vnd.apache.parquet")]
+    Parquet(Binary<Body>, #[oai(header = "Content-Disposition")] String),
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 503)]
+    Busy(PlainText<String>),
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<Format> for ExportFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Csv => ExportFormat::Csv,
+            Format::Ndjson => ExportFormat::Ndjson,
+            Format::Parquet => ExportFormat::Parquet,
+        }
+    }
+}
+
+impl ExportApi {
+    /// Creates a new instance of the export API
+    ///
+    /// # Arguments
+    /// * `db_pool` - Connection pool reserved for exports
+    /// * `max_concurrent` - Number of exports that may run at once
+    pub fn new(db_pool: DbPool, max_concurrent: usize) -> Self {
+        Self {
+            db_pool,
+            permits: Arc::new(Semaphore::new(max_concurrent)),
+        }
+    }
+}
+
+#[OpenApi]
+impl ExportApi {
+    /// Export Star Atlas exchanges
+    ///
+    /// Streams every exchange matching the filters, oldest first, as CSV, NDJSON or Parquet.
+    /// Unlike `/staratlas/exchanges` the export is not paged, so a single request returns
+    /// the full history. If the export fails midway, the response ends early. Requires an
+    /// API key with the read scope. Only a few exports run at once, further requests are
+    /// answered with 503 until one of them finishes.
+    #[oai(
+        path = "/staratlas/exchanges/export",
+        method = "get",
+        tag = "ExportTags::Export"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn export_staratlas_exchanges(
+        &self,
+        auth: ApiKeyAuth,
+        /// Format of the export (defaults to csv)
+        format: Query<Option<Format>>,
+        /// Filter by buyer wallet address
+        buyer: Query<Option<String>>,
+        /// Filter by seller wallet address
+        seller: Query<Option<String>>,
+        /// Filter by wallet address of either the buyer or the seller
+        wallet: Query<Option<String>>,
+        /// Filter by asset mint address
+        asset: Query<Option<String>>,
+        /// Filter by pair mint address
+        pair: Query<Option<String>>,
+        /// Filter by side (buy/sell)
+        side: Query<Option<String>>,
+        /// Earliest timestamp (ISO 8601 format)
+        from_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Latest timestamp (ISO 8601 format)
+        to_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Lowest block number
+        from_slot: Query<Option<i32>>,
+        /// Highest block number
+        to_slot: Query<Option<i32>>,
+    ) -> ExportResponse {
+        if !auth.has_scope(ApiKeyScope::Read) {
+            return ExportResponse::Forbidden;
+        }
+        // The permit is held until the response body is dropped
+        let Ok(permit) = self.permits.clone().try_acquire_owned() else {
+            return ExportResponse::Busy(PlainText(
+                "Too many exports are running, retry later".to_string(),
+            ));
+        };
+
+        let format = format.0.unwrap_or(Format::Csv);
+        let export_format = ExportFormat::from(format);
+
+        let filter = ExchangeFilter {
+            buyer: buyer.0,
+            seller: seller.0,
+            wallet: wallet.0,
+            asset_mint: asset.0,
+            pair_mint: pair.0,
+            side: side.0,
+            from_timestamp: from_timestamp.0,
+            to_timestamp: to_timestamp.0,
+            from_slot: from_slot.0,
+            to_slot: to_slot.0,
+            ..Default::default()
+        };
+
+        let export = match ExchangeExport::open(&self.db_pool, &filter, DEFAULT_BATCH_SIZE).await {
+            Ok(export) => export,
+            Err(_) => return ExportResponse::DBError,
+        };
+        let encoder = match ExportEncoder::new(export_format) {
+            Ok(encoder) => encoder,
+            Err(_) => return ExportResponse::DBError,
+        };
+
+        let chunks = export::export_stream(export, encoder)
+            .map_ok(move |chunk| {
+                let _ = &permit;
+                chunk
+            })
+            .map_err(|e| {
+                warn!("Exchange export failed: {}", e);
+                std::io::Error::other(e)
+            });
+        let body = Binary(Body::from_bytes_stream(chunks));
+        let disposition = format!(
+            "attachment; filename=\"exchanges.{}\"",
+            export_format.extension()
+        );
+
+        match format {
+            Format::Csv => ExportResponse::Csv(body, disposition),
+            Format::Ndjson => ExportResponse::Ndjson(body, disposition),
+            Format::Parquet => ExportResponse::Parquet(body, disposition),
+        }
+    }
+}
@@ -0,0 +1,320 @@
+//! API implementation for the live exchange feed
+//!
+//! This module provides the staratlas-exchanges-stream [GET] (server-sent events) and
+//! staratlas-exchanges-ws [GET] (WebSocket) endpoints as defined in the guidelines.
+//! Both push every new exchange the processor inserts, as announced by the database on
+//! `db::NEW_EXCHANGE_CHANNEL`.
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use db::{DbPool, ExchangeDetailed, ExchangeListener};
+use futures_util::{SinkExt, StreamExt, stream::BoxStream};
+use log::{info, warn};
+use poem::{
+    IntoResponse, handler,
+    web::{
+        Data, Query as PoemQuery,
+        websocket::{Message, WebSocket},
+    },
+};
+use poem_openapi::{OpenApi, Tags, param::Query, payload::EventStream, types::ToJSON};
+use serde::Deserialize;
+use tokio::sync::broadcast;
+use tokio_stream::wrappers::BroadcastStream;
+
+use super::staratlas::ExchangeResponse;
+use crate::auth::ApiAccess;
+
+/// Number of exchanges buffered per subscriber before it starts missing exchanges
+const FEED_CAPACITY: usize = 1024;
+
+/// Delay before listening again after the listener failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Tags for the feed API
+#[derive(Tags)]
+enum FeedTags {
+    /// Operations related to the live Star Atlas exchange feed
+    Feed,
+}
+
+/// Broadcasts new exchanges to every connected subscriber
+#[derive(Clone)]
+pub struct ExchangeFeed {
+    /// Sender half of the broadcast channel
+    sender: broadcast::Sender<Arc<ExchangeDetailed>>,
+}
+
+impl ExchangeFeed {
+    /// Creates the feed and starts listening for new exchanges in the background
+    pub fn start(db_pool: DbPool) -> Self {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        tokio::spawn(Self::listen(db_pool, sender.clone()));
+        Self { sender }
+    }
+
+    /// Subscribes to the exchanges inserted from now on
+    fn subscribe(&self) -> broadcast::Receiver<Arc<ExchangeDetailed>> {
+        self.sender.subscribe()
+    }
+
+    /// Forwards every new exchange from the database to the subscribers
+    async fn listen(db_pool: DbPool, sender: broadcast::Sender<Arc<ExchangeDetailed>>) {
+        loop {
+            let mut listener = match ExchangeListener::connect(&db_pool).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!("Failed to listen for new exchanges: {}", e);
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    continue;
+                }
+            };
+            info!("Listening for new exchanges");
+
+            loop {
+                let id = match listener.recv().await {
+                    Ok(id) => id,
+                    Err(e) => {
+                        warn!("Failed to receive new exchange: {}", e);
+                        break;
+                    }
+                };
+
+                match db::get_exchange_detailed_by_id(&db_pool, id).await {
+                    // Sending only fails while nobody is subscribed
+                    Ok(Some(exchange)) => {
+                        let _ = sender.send(Arc::new(exchange));
+                    }
+                    Ok(None) => {}
+                    Err(e) => warn!("Failed to load new exchange {}: {}", id, e),
+                }
+            }
+
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
+
+/// Filter for the exchanges pushed to a subscriber
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct FeedFilter {
+    /// Asset mint address
+    asset: Option<String>,
+    /// Pair mint address
+    pair: Option<String>,
+    /// Wallet address of either the buyer or the seller
+    wallet: Option<String>,
+}
+
+impl FeedFilter {
+    /// Returns true if the exchange matches every set field
+    fn matches(&self, exchange: &ExchangeDetailed) -> bool {
+        self.asset
+            .as_ref()
+            .is_none_or(|asset| &exchange.asset_mint == asset)
+            && self
+                .pair
+                .as_ref()
+                .is_none_or(|pair| &exchange.pair_mint == pair)
+            && self.wallet.as_ref().is_none_or(|wallet| {
+                &exchange.buyer_wallet == wallet || &exchange.seller_wallet == wallet
+            })
+    }
+
+    /// Subscribes to the feed and yields the matching exchanges
+    ///
+    /// Exchanges a slow subscriber missed are skipped.
+    fn stream(self, feed: &ExchangeFeed) -> BoxStream<'static, ExchangeResponse> {
+        BroadcastStream::new(feed.subscribe())
+            .filter_map(move |exchange| {
+                let response = exchange
+                    .ok()
+                    .filter(|exchange| self.matches(exchange))
+                    .map(|exchange| ExchangeResponse::from(ExchangeDetailed::clone(&exchange)));
+                async move { response }
+            })
+            .boxed()
+    }
+}
+
+/// API implementation for the live exchange feed
+pub struct FeedApi {
+    /// Feed of new exchanges
+    feed: ExchangeFeed,
+}
+
+impl FeedApi {
+    /// Creates a new instance of the feed API
+    pub fn new(feed: ExchangeFeed) -> Self {
+        Self { feed }
+    }
+}
+
+#[OpenApi]
+impl FeedApi {
+    /// Stream new Star Atlas exchanges
+    ///
+    /// Pushes every new exchange as a server-sent event once the processor inserted it.
+    /// Can be filtered by asset, pair, or wallet. The same feed is available as a
+    /// WebSocket at `/staratlas/exchanges/ws` with the same query parameters.
+    #[oai(
+        path = "/staratlas/exchanges/stream",
+        method = "get",
+        tag = "FeedTags::Feed"
+    )]
+    async fn stream_staratlas_exchanges(
+        &self,
+        access: ApiAccess,
+        /// Filter by asset mint address
+        asset: Query<Option<String>>,
+        /// Filter by pair mint address
+        pair: Query<Option<String>>,
+        /// Filter by wallet address of either the buyer or the seller
+        wallet: Query<Option<String>>,
+    ) -> EventStream<BoxStream<'static, ExchangeResponse>> {
+        let filter = FeedFilter {
+            asset: asset.0,
+            pair: pair.0,
+            wallet: wallet.0,
+        };
+        // The stream stays open past the rate limit of the request that opened it
+        info!("Exchange stream opened by {} ({:?})", access, filter);
+
+        EventStream::new(filter.stream(&self.feed)).keep_alive(Duration::from_secs(15))
+    }
+}
+
+/// WebSocket endpoint pushing every new exchange matching the query filter as a JSON text
+/// message
+#[handler]
+pub fn exchanges_ws(
+    ws: WebSocket,
+    feed: Data<&ExchangeFeed>,
+    PoemQuery(filter): PoemQuery<FeedFilter>,
+) -> impl IntoResponse {
+    let mut exchanges = feed.subscribe();
+
+    ws.on_upgrade(move |socket| async move {
+        let (mut sink, mut incoming) = socket.split();
+
+        loop {
+            tokio::select! {
+                exchange = exchanges.recv() => match exchange {
+                    Ok(exchange) if filter.matches(&exchange) => {
+                        let response = ExchangeResponse::from(ExchangeDetailed::clone(&exchange));
+                        if sink.send(Message::Text(response.to_json_string())).await.is_err() {
+                            break;
+                        }
+                    }
+                    // Exchanges a slow subscriber missed are skipped
+                    Ok(_) | Err(broadcast::error::RecvError::Lagged(_)) => {}
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                message = incoming.next() => match message {
+                    Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                    Some(Ok(_)) => {}
+                },
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    const ATLAS: &str = "ATLASXmbPQxBUYbxPsV97usA3fPQYEqzQBUHgiFCUsXx";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const SHIP: &str = "Fw8PqtznYtg4swMk7Yjj89Tsj23u5CJLfW5Bk8ro4G1s";
+    const BUYER: &str = "9Wf1bXo7Mjb7EQiQXdpmv5gtDcRbLrFeQhK9JjjF9wE7";
+    const SELLER: &str = "3Xq1bYLmSgAUSvybjKyJMx1wKbTnHsodwqKyb66rnc3i";
+
+    fn exchange(id: i32, asset: &str, pair: &str) -> ExchangeDetailed {
+        ExchangeDetailed {
+            id,
+            slot: 0,
+            signature: String::new(),
+            index: 0,
+            timestamp: Utc.timestamp_opt(0, 0).unwrap(),
+            side: "BUY".to_string(),
+            buyer_id: 1,
+            buyer_wallet: BUYER.to_string(),
+            buyer_username: None,
+            seller_id: 2,
+            seller_wallet: SELLER.to_string(),
+            seller_username: None,
+            asset_id: 3,
+            asset_mint: asset.to_string(),
+            asset_symbol: None,
+            asset_name: None,
+            pair_id: 4,
+            pair_mint: pair.to_string(),
+            pair_symbol: None,
+            pair_name: None,
+            price: 1.0,
+            size: 1,
+            volume: 1.0,
+            fee: 0.0,
+            buddy: 0.0,
+            processor_version: 1,
+        }
+    }
+
+    fn filter(asset: Option<&str>, pair: Option<&str>, wallet: Option<&str>) -> FeedFilter {
+        FeedFilter {
+            asset: asset.map(str::to_string),
+            pair: pair.map(str::to_string),
+            wallet: wallet.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn empty_filter_matches_every_exchange() {
+        assert!(FeedFilter::default().matches(&exchange(1, SHIP, ATLAS)));
+    }
+
+    #[test]
+    fn filter_matches_asset_and_pair() {
+        let exchange = exchange(1, SHIP, ATLAS);
+
+        assert!(filter(Some(SHIP), None, None).matches(&exchange));
+        assert!(filter(None, Some(ATLAS), None).matches(&exchange));
+        assert!(filter(Some(SHIP), Some(ATLAS), None).matches(&exchange));
+        assert!(!filter(Some(ATLAS), None, None).matches(&exchange));
+        assert!(!filter(Some(SHIP), Some(USDC), None).matches(&exchange));
+    }
+
+    #[test]
+    fn wallet_filter_matches_buyer_or_seller() {
+        let exchange = exchange(1, SHIP, ATLAS);
+
+        assert!(filter(None, None, Some(BUYER)).matches(&exchange));
+        assert!(filter(None, None, Some(SELLER)).matches(&exchange));
+        assert!(!filter(None, None, Some(SHIP)).matches(&exchange));
+        assert!(!filter(Some(ATLAS), None, Some(BUYER)).matches(&exchange));
+    }
+
+    #[tokio::test]
+    async fn stream_yields_only_matching_exchanges() {
+        let (sender, _) = broadcast::channel(FEED_CAPACITY);
+        let feed = ExchangeFeed { sender };
+        let stream = filter(None, Some(USDC), None).stream(&feed);
+
+        for exchange in [
+            exchange(1, SHIP, ATLAS),
+            exchange(2, SHIP, USDC),
+            exchange(3, ATLAS, USDC),
+        ] {
+            feed.sender.send(Arc::new(exchange)).unwrap();
+        }
+        drop(feed);
+
+        let ids: Vec<i64> = stream
+            .map(|response| response.to_json().unwrap()["id"].as_i64().unwrap())
+            .collect()
+            .await;
+        assert_eq!(ids, [2, 3]);
+    }
+}
@@ -5,10 +5,12 @@
 
 use db::{DbPool, Indexer as IndexerDB, PublicKeyType, SignatureType};
 use poem_openapi::{
-    payload::{Html, Json}, ApiResponse, Object, OpenApi,
-    Tags,
+    ApiResponse, Object, OpenApi, Tags,
+    payload::{Html, Json},
 };
 
+use crate::auth::ApiAccess;
+
 /// Tags for the indexer API
 #[derive(Tags)]
 enum IndexerTags {
@@ -124,14 +126,36 @@ struct Indexer {
     fetch_limit: i32,
 }
 
+/// Processed-slot watermark of a program
+#[derive(Object)]
+struct Watermark {
+    /// Program ID
+    program_id: PublicKeyType,
+    /// Highest slot up to which all indexed signatures are processed
+    slot: Option<i64>,
+    /// Block time of the watermark slot (ISO 8601 format)
+    timestamp: Option<String>,
+    /// When the watermark was last recalculated (ISO 8601 format)
+    updated_at: String,
+}
+
+#[derive(ApiResponse)]
+enum GetWatermarkResponse {
+    #[oai(status = 200)]
+    Watermarks(Json<Vec<Watermark>>),
+    #[oai(status = 500)]
+    DBError,
+}
+
 #[derive(ApiResponse)]
+#[allow(dead_code)]
 enum GetIndexerResponse {
     #[oai(status = 200)]
     Indexer(Json<Indexer>),
     #[oai(status = 200)]
     Indexers(Json<Vec<Indexer>>),
     #[oai(status = 200)]
-    HTML(Html<String>),
+    Html(Html<String>),
     #[oai(status = 404)]
     NotFound,
     #[oai(status = 500)]
@@ -144,7 +168,7 @@ impl IndexerApi {
     ///
     /// Returns a list of all indexers in the database as JSON.
     #[oai(path = "/indexers", method = "get", tag = "IndexerTags::Indexers")]
-    async fn get_indexers_json(&self) -> GetIndexerResponse {
+    async fn get_indexers_json(&self, _access: ApiAccess) -> GetIndexerResponse {
         match db::get_all_indexers(&self.db_pool).await {
             Ok(indexers) => {
                 let response = indexers
@@ -155,10 +179,7 @@ impl IndexerApi {
                         program_id: indexer.program_id,
                         signature: indexer.signature,
                         block: indexer.block,
-                        timestamp: match indexer.timestamp {
-                            Some(ts) => Some(ts.to_string()),
-                            None => None,
-                        },
+                        timestamp: indexer.timestamp.map(|ts| ts.to_string()),
                         finished: indexer.finished,
                         fetch_limit: indexer.fetch_limit,
                     })
@@ -174,14 +195,40 @@ impl IndexerApi {
     ///
     /// Returns a simple HTML table to view the indexers.
     #[oai(path = "/indexer", method = "get", tag = "IndexerTags::Indexers")]
-    async fn get_indexers_html(&self) -> GetIndexerResponse {
+    async fn get_indexers_html(&self, _access: ApiAccess) -> GetIndexerResponse {
         match db::get_all_indexers(&self.db_pool).await {
             Ok(indexers) => {
                 let html = Self::generate_html_table(&indexers);
-                GetIndexerResponse::HTML(Html(html))
+                GetIndexerResponse::Html(Html(html))
             }
 
             Err(_) => GetIndexerResponse::DBError,
         }
     }
+
+    /// Get processed-slot watermarks
+    ///
+    /// Returns, per program, the slot up to which all indexed signatures are processed.
+    /// Data derived from a program is complete up to and including that slot.
+    #[oai(
+        path = "/indexer/watermarks",
+        method = "get",
+        tag = "IndexerTags::Indexers"
+    )]
+    async fn get_watermarks(&self, _access: ApiAccess) -> GetWatermarkResponse {
+        match db::get_all_program_watermarks(&self.db_pool).await {
+            Ok(watermarks) => GetWatermarkResponse::Watermarks(Json(
+                watermarks
+                    .into_iter()
+                    .map(|watermark| Watermark {
+                        program_id: watermark.program_id,
+                        slot: watermark.slot,
+                        timestamp: watermark.timestamp.map(|ts| ts.to_rfc3339()),
+                        updated_at: watermark.updated_at.to_rfc3339(),
+                    })
+                    .collect(),
+            )),
+            Err(_) => GetWatermarkResponse::DBError,
+        }
+    }
 }
@@ -0,0 +1,243 @@
+//! API implementation for the API key management endpoints
+//!
+//! This module provides the admin-api-keys [GET, POST] and admin-api-keys-{id} [DELETE]
+//! endpoints as defined in the guidelines. They require an API key with the admin scope.
+
+use db::{ApiKey, ApiKeyScope, DbPool, NewApiKey};
+use poem_openapi::{
+    ApiResponse, Enum, Object, OpenApi, Tags,
+    param::Path,
+    payload::{Json, PlainText},
+};
+
+use crate::auth::{ApiKeyAuth, api_key_prefix, generate_api_key, hash_api_key};
+
+/// Tags for the API key API
+#[derive(Tags)]
+enum ApiKeysTags {
+    /// Operations related to API keys, requiring the admin scope
+    Admin,
+}
+
+/// API implementation for the API key management endpoints
+pub struct ApiKeysApi {
+    /// Database connection pool
+    db_pool: DbPool,
+}
+
+/// Scope of an API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "ApiKeyScope", rename_all = "lowercase")]
+enum Scope {
+    /// May call the public endpoints
+    Read,
+    /// May also manage API keys
+    Admin,
+}
+
+/// API key request object
+#[derive(Debug, Object)]
+struct CreateApiKeyRequest {
+    /// Name describing the owner or purpose of the key
+    name: String,
+    /// Scope of the key (defaults to read)
+    scope: Option<Scope>,
+    /// Requests per minute the key may make (defaults to the server limit)
+    rate_limit: Option<i32>,
+}
+
+/// API key response object
+#[derive(Debug, Object)]
+struct ApiKeyResponse {
+    /// ID of the key
+    id: i32,
+    /// Name describing the owner or purpose of the key
+    name: String,
+    /// The key, only returned when it is created
+    key: Option<String>,
+    /// First characters of the key
+    key_prefix: String,
+    /// Scope of the key
+    scope: Scope,
+    /// Requests per minute the key may make (server limit if not set)
+    rate_limit: Option<i32>,
+    /// Number of requests made with the key (updated periodically)
+    request_count: i64,
+    /// Timestamp of the last request made with the key (ISO 8601 format)
+    last_used_at: Option<String>,
+    /// Timestamp the key was revoked at (ISO 8601 format)
+    revoked_at: Option<String>,
+    /// Creation timestamp (ISO 8601 format)
+    created_at: String,
+}
+
+#[derive(ApiResponse)]
+enum CreateApiKeyResponse {
+    #[oai(status = 201)]
+    ApiKey(Json<ApiKeyResponse>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetApiKeysResponse {
+    #[oai(status = 200)]
+    ApiKeys(Json<Vec<ApiKeyResponse>>),
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum RevokeApiKeyResponse {
+    #[oai(status = 204)]
+    Revoked,
+    #[oai(status = 403)]
+    Forbidden,
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<Scope> for ApiKeyScope {
+    fn from(scope: Scope) -> Self {
+        match scope {
+            Scope::Read => ApiKeyScope::Read,
+            Scope::Admin => ApiKeyScope::Admin,
+        }
+    }
+}
+
+impl From<ApiKeyScope> for Scope {
+    fn from(scope: ApiKeyScope) -> Self {
+        match scope {
+            ApiKeyScope::Read => Scope::Read,
+            ApiKeyScope::Admin => Scope::Admin,
+        }
+    }
+}
+
+impl From<ApiKey> for ApiKeyResponse {
+    fn from(api_key: ApiKey) -> Self {
+        Self {
+            id: api_key.id,
+            name: api_key.name,
+            key: None,
+            key_prefix: api_key.key_prefix,
+            scope: api_key.scope.into(),
+            rate_limit: api_key.rate_limit,
+            request_count: api_key.request_count,
+            last_used_at: api_key.last_used_at.map(|timestamp| timestamp.to_rfc3339()),
+            revoked_at: api_key.revoked_at.map(|timestamp| timestamp.to_rfc3339()),
+            created_at: api_key.created_at.to_rfc3339(),
+        }
+    }
+}
+
+impl ApiKeysApi {
+    /// Creates a new instance of the API key API
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+/// Whether the API key of a request may manage API keys
+fn is_admin(auth: &ApiKeyAuth) -> bool {
+    auth.0.scope == ApiKeyScope::Admin
+}
+
+#[OpenApi]
+impl ApiKeysApi {
+    /// Create an API key
+    ///
+    /// The response contains the key. It is only returned once, as only a hash of it is
+    /// stored. Clients send it in the `X-API-Key` header.
+    #[oai(path = "/admin/api-keys", method = "post", tag = "ApiKeysTags::Admin")]
+    async fn create_api_key(
+        &self,
+        auth: ApiKeyAuth,
+        request: Json<CreateApiKeyRequest>,
+    ) -> CreateApiKeyResponse {
+        if !is_admin(&auth) {
+            return CreateApiKeyResponse::Forbidden;
+        }
+
+        let request = request.0;
+        if request.name.trim().is_empty() || request.name.len() > 100 {
+            return CreateApiKeyResponse::BadRequest(PlainText(
+                "name must be between 1 and 100 characters".to_string(),
+            ));
+        }
+        if request.rate_limit.is_some_and(|limit| limit <= 0) {
+            return CreateApiKeyResponse::BadRequest(PlainText(
+                "rate_limit must be positive".to_string(),
+            ));
+        }
+
+        let key = generate_api_key();
+        let new_api_key = NewApiKey {
+            name: request.name,
+            key_prefix: api_key_prefix(&key),
+            key_hash: hash_api_key(&key),
+            scope: request.scope.unwrap_or(Scope::Read).into(),
+            rate_limit: request.rate_limit,
+        };
+
+        match db::create_api_key(&self.db_pool, &new_api_key).await {
+            Ok(api_key) => {
+                let mut response = ApiKeyResponse::from(api_key);
+                response.key = Some(key);
+                CreateApiKeyResponse::ApiKey(Json(response))
+            }
+            Err(_) => CreateApiKeyResponse::DBError,
+        }
+    }
+
+    /// Get all API keys
+    ///
+    /// Returns every key including revoked ones, newest first, with their usage.
+    #[oai(path = "/admin/api-keys", method = "get", tag = "ApiKeysTags::Admin")]
+    async fn get_api_keys(&self, auth: ApiKeyAuth) -> GetApiKeysResponse {
+        if !is_admin(&auth) {
+            return GetApiKeysResponse::Forbidden;
+        }
+
+        match db::get_api_keys(&self.db_pool).await {
+            Ok(api_keys) => GetApiKeysResponse::ApiKeys(Json(
+                api_keys.into_iter().map(ApiKeyResponse::from).collect(),
+            )),
+            Err(_) => GetApiKeysResponse::DBError,
+        }
+    }
+
+    /// Revoke an API key
+    ///
+    /// Requests with the key are rejected once the servers' key caches expire.
+    #[oai(
+        path = "/admin/api-keys/:id",
+        method = "delete",
+        tag = "ApiKeysTags::Admin"
+    )]
+    async fn revoke_api_key(
+        &self,
+        auth: ApiKeyAuth,
+        /// ID of the key
+        id: Path<i32>,
+    ) -> RevokeApiKeyResponse {
+        if !is_admin(&auth) {
+            return RevokeApiKeyResponse::Forbidden;
+        }
+
+        match db::revoke_api_key(&self.db_pool, id.0).await {
+            Ok(true) => RevokeApiKeyResponse::Revoked,
+            Ok(false) => RevokeApiKeyResponse::NotFound,
+            Err(_) => RevokeApiKeyResponse::DBError,
+        }
+    }
+}
@@ -0,0 +1,288 @@
+//! API implementation for the leaderboard endpoint
+//!
+//! This module provides the staratlas-leaderboard [GET] endpoint as defined in the guidelines.
+
+use chrono::{DateTime, Duration, Utc};
+use db::{
+    DbPool, GroupLeaderboardEntry, LeaderboardEntry, LeaderboardFilter, LeaderboardMetric,
+    LeaderboardMode,
+};
+use poem_openapi::{
+    ApiResponse, Enum, Object, OpenApi, Tags,
+    param::Query,
+    payload::{Json, PlainText},
+};
+
+use super::staratlas::GroupBy;
+use crate::auth::ApiAccess;
+
+/// Tags for the leaderboard API
+#[derive(Tags)]
+enum LeaderboardTags {
+    /// Operations related to Star Atlas trader leaderboards
+    Leaderboard,
+}
+
+/// API implementation for the leaderboard endpoint
+pub struct LeaderboardApi {
+    /// Database connection pool
+    db_pool: DbPool,
+}
+
+/// Value to rank players by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "LeaderboardMetric", rename_all = "lowercase")]
+enum Metric {
+    /// Traded volume
+    Volume,
+    /// Number of exchanges
+    Trades,
+    /// Marketplace fees
+    Fees,
+    /// Buddy fees
+    Buddy,
+}
+
+/// Side of the exchanges to count
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "LeaderboardMode", rename_all = "lowercase")]
+enum Mode {
+    /// Exchanges in which the player was the buyer
+    Buyer,
+    /// Exchanges in which the player was the seller
+    Seller,
+    /// Exchanges in which the player was the buyer or the seller
+    Combined,
+}
+
+/// Period ending now to rank over
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "LeaderboardPeriod")]
+enum Period {
+    /// The last 24 hours
+    #[oai(rename = "24h")]
+    Day,
+    /// The last 7 days
+    #[oai(rename = "7d")]
+    Week,
+    /// The last 30 days
+    #[oai(rename = "30d")]
+    Month,
+    /// All time
+    #[oai(rename = "all")]
+    All,
+}
+
+/// Leaderboard entry response object
+#[derive(Debug, Object)]
+struct LeaderboardEntryResponse {
+    /// Rank of the player, shared by players with the same value
+    rank: i64,
+    /// Wallet address of the player
+    wallet_address: String,
+    /// Username of the player (if available)
+    username: Option<String>,
+    /// Number of exchanges counted
+    trade_count: i64,
+    /// Volume of the exchanges counted
+    volume: f64,
+    /// Marketplace fees of the exchanges counted
+    fees: f64,
+    /// Buddy fees of the exchanges counted
+    buddy: f64,
+}
+
+/// Faction or guild leaderboard entry response object
+#[derive(Debug, Object)]
+struct GroupLeaderboardEntryResponse {
+    /// Rank of the group, shared by groups with the same value
+    rank: i64,
+    /// Faction or guild (null for the players without one)
+    group: Option<String>,
+    /// Number of players of the group with exchanges counted
+    players: i64,
+    /// Number of exchanges counted
+    trade_count: i64,
+    /// Volume of the exchanges counted
+    volume: f64,
+    /// Marketplace fees of the exchanges counted
+    fees: f64,
+    /// Buddy fees of the exchanges counted
+    buddy: f64,
+}
+
+#[derive(ApiResponse)]
+enum GetLeaderboardResponse {
+    #[oai(status = 200)]
+    Leaderboard(Json<Vec<LeaderboardEntryResponse>>),
+    #[oai(status = 200)]
+    GroupLeaderboard(Json<Vec<GroupLeaderboardEntryResponse>>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<Metric> for LeaderboardMetric {
+    fn from(metric: Metric) -> Self {
+        match metric {
+            Metric::Volume => LeaderboardMetric::Volume,
+            Metric::Trades => LeaderboardMetric::Trades,
+            Metric::Fees => LeaderboardMetric::Fees,
+            Metric::Buddy => LeaderboardMetric::Buddy,
+        }
+    }
+}
+
+impl From<Mode> for LeaderboardMode {
+    fn from(mode: Mode) -> Self {
+        match mode {
+            Mode::Buyer => LeaderboardMode::Buyer,
+            Mode::Seller => LeaderboardMode::Seller,
+            Mode::Combined => LeaderboardMode::Combined,
+        }
+    }
+}
+
+impl Period {
+    /// Start of the period, or None for all time
+    fn start(self) -> Option<DateTime<Utc>> {
+        let length = match self {
+            Period::Day => Duration::days(1),
+            Period::Week => Duration::days(7),
+            Period::Month => Duration::days(30),
+            Period::All => return None,
+        };
+        Some(Utc::now() - length)
+    }
+}
+
+impl From<LeaderboardEntry> for LeaderboardEntryResponse {
+    fn from(entry: LeaderboardEntry) -> Self {
+        Self {
+            rank: entry.rank,
+            wallet_address: entry.wallet_address,
+            username: entry.username,
+            trade_count: entry.trade_count,
+            volume: entry.volume,
+            fees: entry.fees,
+            buddy: entry.buddy,
+        }
+    }
+}
+
+impl From<GroupLeaderboardEntry> for GroupLeaderboardEntryResponse {
+    fn from(entry: GroupLeaderboardEntry) -> Self {
+        Self {
+            rank: entry.rank,
+            group: entry.group,
+            players: entry.players,
+            trade_count: entry.trade_count,
+            volume: entry.volume,
+            fees: entry.fees,
+            buddy: entry.buddy,
+        }
+    }
+}
+
+impl LeaderboardApi {
+    /// Creates a new instance of the leaderboard API
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[OpenApi]
+impl LeaderboardApi {
+    /// Get Star Atlas trader leaderboard
+    ///
+    /// Ranks wallets by volume, trade count, fees or buddy fees of their exchanges as buyer,
+    /// seller or both. Amounts are only comparable within one currency, so `currency` is
+    /// required for every metric but `trades`. `from_timestamp` overrides the start of
+    /// `period`. With `group_by` the factions or guilds of the wallets are ranked instead.
+    #[oai(
+        path = "/staratlas/leaderboard",
+        method = "get",
+        tag = "LeaderboardTags::Leaderboard"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn get_staratlas_leaderboard(
+        &self,
+        _access: ApiAccess,
+        /// Value to rank by
+        metric: Query<Option<Metric>>,
+        /// Side of the exchanges to count
+        mode: Query<Option<Mode>>,
+        /// Pair mint address the exchanges were paid in
+        currency: Query<Option<String>>,
+        /// Period ending now to rank over
+        period: Query<Option<Period>>,
+        /// Earliest timestamp (ISO 8601 format)
+        from_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Latest timestamp (ISO 8601 format)
+        to_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Rank factions or guilds instead of wallets
+        group_by: Query<Option<GroupBy>>,
+
+        offset: Query<Option<i32>>,
+        limit: Query<Option<i32>>,
+    ) -> GetLeaderboardResponse {
+        let limit_value: i32 = limit.0.unwrap_or(100);
+        let offset_value: i32 = offset.0.unwrap_or(0);
+
+        let metric = metric.0.unwrap_or(Metric::Volume);
+        if metric != Metric::Trades && currency.0.is_none() {
+            return GetLeaderboardResponse::BadRequest(PlainText(
+                "currency is required to rank by an amount".to_string(),
+            ));
+        }
+
+        let filter = LeaderboardFilter {
+            metric: metric.into(),
+            mode: mode.0.unwrap_or(Mode::Combined).into(),
+            currency_mint: currency.0,
+            from_timestamp: from_timestamp
+                .0
+                .or_else(|| period.0.unwrap_or(Period::All).start()),
+            to_timestamp: to_timestamp.0,
+        };
+
+        if let Some(group) = group_by.0 {
+            return match db::get_group_leaderboard(
+                &self.db_pool,
+                &filter,
+                group.into(),
+                limit_value,
+                offset_value,
+            )
+            .await
+            {
+                Ok(entries) if entries.is_empty() => GetLeaderboardResponse::NotFound,
+                Ok(entries) => GetLeaderboardResponse::GroupLeaderboard(Json(
+                    entries
+                        .into_iter()
+                        .map(GroupLeaderboardEntryResponse::from)
+                        .collect(),
+                )),
+                Err(_) => GetLeaderboardResponse::DBError,
+            };
+        }
+
+        match db::get_leaderboard(&self.db_pool, &filter, limit_value, offset_value).await {
+            Ok(entries) => {
+                if entries.is_empty() {
+                    GetLeaderboardResponse::NotFound
+                } else {
+                    let entry_responses = entries
+                        .into_iter()
+                        .map(LeaderboardEntryResponse::from)
+                        .collect();
+                    GetLeaderboardResponse::Leaderboard(Json(entry_responses))
+                }
+            }
+            Err(_) => GetLeaderboardResponse::DBError,
+        }
+    }
+}
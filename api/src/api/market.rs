@@ -0,0 +1,230 @@
+//! API implementation for the market statistics endpoints
+//!
+//! This module provides the staratlas-markets [GET], staratlas-markets-movers [GET],
+//! and staratlas-markets-most-traded [GET] endpoints as defined in the guidelines.
+
+use db::{DbPool, MarketStats};
+use poem_openapi::{ApiResponse, Enum, Object, OpenApi, Tags, param::Query, payload::Json};
+
+use crate::auth::ApiAccess;
+
+/// Tags for the market API
+#[derive(Tags)]
+enum MarketTags {
+    /// Operations related to Star Atlas market statistics
+    Markets,
+}
+
+/// API implementation for the market statistics endpoints
+pub struct MarketApi {
+    /// Database connection pool
+    db_pool: DbPool,
+}
+
+/// Period of the market statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "MarketPeriod")]
+enum Period {
+    /// The last 24 hours
+    #[oai(rename = "24h")]
+    Day,
+    /// The last 7 days
+    #[oai(rename = "7d")]
+    Week,
+}
+
+/// Market statistics response object
+#[derive(Debug, Object)]
+struct MarketStatsResponse {
+    /// Asset mint address
+    asset: String,
+    /// Asset symbol (if available)
+    asset_symbol: Option<String>,
+    /// Asset name (if available)
+    asset_name: Option<String>,
+    /// Pair mint address
+    pair: String,
+    /// Pair symbol (if available)
+    pair_symbol: Option<String>,
+    /// Price of the last exchange
+    last_price: Option<f64>,
+    /// Timestamp of the last exchange (ISO 8601 format)
+    last_timestamp: Option<String>,
+    /// Number of exchanges in the last 24 hours
+    trade_count_24h: i64,
+    /// Volume of the last 24 hours
+    volume_24h: f64,
+    /// Volume weighted average price of the last 24 hours
+    vwap_24h: Option<f64>,
+    /// Highest price of the last 24 hours
+    high_24h: Option<f64>,
+    /// Lowest price of the last 24 hours
+    low_24h: Option<f64>,
+    /// Price change of the last 24 hours in percent
+    price_change_24h: Option<f64>,
+    /// Number of distinct buyers and sellers in the last 24 hours
+    unique_traders_24h: i64,
+    /// Number of exchanges in the last 7 days
+    trade_count_7d: i64,
+    /// Volume of the last 7 days
+    volume_7d: f64,
+    /// Volume weighted average price of the last 7 days
+    vwap_7d: Option<f64>,
+}
+
+#[derive(ApiResponse)]
+enum GetMarketStatsResponse {
+    #[oai(status = 200)]
+    Markets(Json<Vec<MarketStatsResponse>>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<MarketStats> for MarketStatsResponse {
+    fn from(stats: MarketStats) -> Self {
+        Self {
+            asset: stats.asset_mint,
+            asset_symbol: stats.asset_symbol,
+            asset_name: stats.asset_name,
+            pair: stats.pair_mint,
+            pair_symbol: stats.pair_symbol,
+            last_price: stats.last_price,
+            last_timestamp: stats.last_timestamp.map(|timestamp| timestamp.to_rfc3339()),
+            trade_count_24h: stats.trade_count_24h,
+            volume_24h: stats.volume_24h,
+            vwap_24h: stats.vwap_24h,
+            high_24h: stats.high_24h,
+            low_24h: stats.low_24h,
+            price_change_24h: stats.price_change_24h,
+            unique_traders_24h: stats.unique_traders_24h,
+            trade_count_7d: stats.trade_count_7d,
+            volume_7d: stats.volume_7d,
+            vwap_7d: stats.vwap_7d,
+        }
+    }
+}
+
+impl MarketApi {
+    /// Creates a new instance of the market API
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+
+    /// Loads the market statistics, keeps the ones matching `keep` and sorts them by `key`
+    /// in descending order
+    async fn ranked_markets(
+        &self,
+        keep: impl Fn(&MarketStats) -> bool,
+        key: impl Fn(&MarketStats) -> f64,
+        limit: Option<usize>,
+    ) -> GetMarketStatsResponse {
+        match db::get_market_stats(&self.db_pool).await {
+            Ok(mut markets) => {
+                markets.retain(|market| keep(market));
+                markets.sort_by(|a, b| key(b).total_cmp(&key(a)));
+                if let Some(limit) = limit {
+                    markets.truncate(limit);
+                }
+
+                if markets.is_empty() {
+                    GetMarketStatsResponse::NotFound
+                } else {
+                    let market_responses =
+                        markets.into_iter().map(MarketStatsResponse::from).collect();
+                    GetMarketStatsResponse::Markets(Json(market_responses))
+                }
+            }
+            Err(_) => GetMarketStatsResponse::DBError,
+        }
+    }
+}
+
+#[OpenApi]
+impl MarketApi {
+    /// Get Star Atlas market statistics
+    ///
+    /// Returns the last price, 24 hour and 7 day volume and trade count, VWAP, high, low,
+    /// price change and unique traders of every market, ordered by 24 hour volume. Windows
+    /// are aligned to full hours.
+    #[oai(
+        path = "/staratlas/markets",
+        method = "get",
+        tag = "MarketTags::Markets"
+    )]
+    async fn get_staratlas_markets(
+        &self,
+        _access: ApiAccess,
+        /// Filter by asset mint address
+        asset: Query<Option<String>>,
+        /// Filter by pair mint address
+        pair: Query<Option<String>>,
+    ) -> GetMarketStatsResponse {
+        self.ranked_markets(
+            |market| {
+                asset
+                    .0
+                    .as_ref()
+                    .is_none_or(|asset| &market.asset_mint == asset)
+                    && pair.0.as_ref().is_none_or(|pair| &market.pair_mint == pair)
+            },
+            |market| market.volume_24h,
+            None,
+        )
+        .await
+    }
+
+    /// Get Star Atlas top movers
+    ///
+    /// Returns the markets that traded in the last 24 hours, ordered by the absolute value
+    /// of their 24 hour price change.
+    #[oai(
+        path = "/staratlas/markets/movers",
+        method = "get",
+        tag = "MarketTags::Markets"
+    )]
+    async fn get_staratlas_markets_movers(
+        &self,
+        _access: ApiAccess,
+        /// Maximum number of markets to return
+        limit: Query<Option<u32>>,
+    ) -> GetMarketStatsResponse {
+        self.ranked_markets(
+            |market| market.trade_count_24h > 0 && market.price_change_24h.is_some(),
+            |market| market.price_change_24h.unwrap_or_default().abs(),
+            Some(limit.0.unwrap_or(10) as usize),
+        )
+        .await
+    }
+
+    /// Get Star Atlas most traded markets
+    ///
+    /// Returns the markets with the most exchanges in the given period.
+    #[oai(
+        path = "/staratlas/markets/most-traded",
+        method = "get",
+        tag = "MarketTags::Markets"
+    )]
+    async fn get_staratlas_markets_most_traded(
+        &self,
+        _access: ApiAccess,
+        /// Period to rank by (24h or 7d)
+        period: Query<Option<Period>>,
+        /// Maximum number of markets to return
+        limit: Query<Option<u32>>,
+    ) -> GetMarketStatsResponse {
+        let period = period.0.unwrap_or(Period::Day);
+        let trade_count = move |market: &MarketStats| match period {
+            Period::Day => market.trade_count_24h,
+            Period::Week => market.trade_count_7d,
+        };
+
+        self.ranked_markets(
+            |market| trade_count(market) > 0,
+            |market| trade_count(market) as f64,
+            Some(limit.0.unwrap_or(10) as usize),
+        )
+        .await
+    }
+}
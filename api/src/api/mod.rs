@@ -1,10 +1,39 @@
 //! API implementations for the Star Atlas Data API
 //!
-//! This module contains the API implementations for the indexer, marketplace, and Star Atlas endpoints.
+//! This module contains the API implementations for the alert webhooks, API keys, crafting costs,
+//! exchange export, live exchange feed, indexer, leaderboard, market statistics, program logs,
+//! SCORE staking, and Star Atlas endpoints.
+
+mod alerts;
+
+mod crafting;
+
+mod export;
+
+mod feed;
+
+mod keys;
 
 mod indexer;
 
+mod leaderboard;
+
+mod market;
+
+mod programs;
+
+mod score;
+
 mod staratlas;
 
+pub use alerts::AlertsApi;
+pub use crafting::CraftingApi;
+pub use export::ExportApi;
+pub use feed::{ExchangeFeed, FeedApi, exchanges_ws};
 pub use indexer::IndexerApi;
+pub use keys::ApiKeysApi;
+pub use leaderboard::LeaderboardApi;
+pub use market::MarketApi;
+pub use programs::ProgramsApi;
+pub use score::ScoreApi;
 pub use staratlas::StarAtlasApi;
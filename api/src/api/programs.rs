@@ -0,0 +1,123 @@
+//! API implementation for the program log endpoints
+//!
+//! This module provides the programs-compute-units [GET] endpoint as defined in the guidelines.
+
+use chrono::{Duration, Utc};
+use db::{ComputeUnitStats, DbPool};
+use poem_openapi::{
+    ApiResponse, Object, OpenApi, Tags,
+    param::Query,
+    payload::{Json, PlainText},
+};
+
+use crate::auth::ApiAccess;
+
+/// Longest period the compute unit statistics can cover
+const MAX_HOURS: u32 = 24 * 30;
+
+/// Tags for the programs API
+#[derive(Tags)]
+enum ProgramTags {
+    /// Operations related to the invocations of the indexed programs
+    Programs,
+}
+
+/// API implementation for the program log endpoints
+pub struct ProgramsApi {
+    /// Database connection pool
+    db_pool: DbPool,
+}
+
+/// Compute unit statistics response object
+#[derive(Debug, Object)]
+struct ComputeUnitStatsResponse {
+    /// Program address
+    program: String,
+    /// Instruction name (if the program logs it)
+    instruction: Option<String>,
+    /// Number of invocations
+    invocations: i64,
+    /// Number of failed invocations
+    failed: i64,
+    /// Average compute units consumed
+    avg_units: f64,
+    /// Median compute units consumed
+    p50_units: f64,
+    /// 95th percentile of the compute units consumed
+    p95_units: f64,
+    /// Most compute units consumed
+    max_units: i64,
+}
+
+#[derive(ApiResponse)]
+enum GetComputeUnitStatsResponse {
+    #[oai(status = 200)]
+    ComputeUnits(Json<Vec<ComputeUnitStatsResponse>>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl From<ComputeUnitStats> for ComputeUnitStatsResponse {
+    fn from(stats: ComputeUnitStats) -> Self {
+        Self {
+            program: stats.program_id,
+            instruction: stats.instruction_name,
+            invocations: stats.invocations,
+            failed: stats.failed,
+            avg_units: stats.avg_units,
+            p50_units: stats.p50_units,
+            p95_units: stats.p95_units,
+            max_units: stats.max_units,
+        }
+    }
+}
+
+impl ProgramsApi {
+    /// Creates a new instance of the programs API
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[OpenApi]
+impl ProgramsApi {
+    /// Get compute unit usage per instruction
+    ///
+    /// Returns the average, median, 95th percentile and maximum compute units consumed by the
+    /// transaction instructions of processed transactions, grouped by program and instruction
+    /// name. Usage includes the cross-program invocations an instruction makes.
+    #[oai(
+        path = "/programs/compute-units",
+        method = "get",
+        tag = "ProgramTags::Programs"
+    )]
+    async fn get_programs_compute_units(
+        &self,
+        _access: ApiAccess,
+        /// Filter by program address
+        program: Query<Option<String>>,
+        /// Number of hours to look back (default 24, at most 720)
+        hours: Query<Option<u32>>,
+    ) -> GetComputeUnitStatsResponse {
+        let hours = hours.0.unwrap_or(24);
+        if hours == 0 || hours > MAX_HOURS {
+            return GetComputeUnitStatsResponse::BadRequest(PlainText(format!(
+                "hours must be between 1 and {}",
+                MAX_HOURS
+            )));
+        }
+        let since = Utc::now() - Duration::hours(hours as i64);
+
+        match db::get_compute_unit_stats(&self.db_pool, program.0.as_deref(), since).await {
+            Ok(stats) => GetComputeUnitStatsResponse::ComputeUnits(Json(
+                stats
+                    .into_iter()
+                    .map(ComputeUnitStatsResponse::from)
+                    .collect(),
+            )),
+            Err(_) => GetComputeUnitStatsResponse::DBError,
+        }
+    }
+}
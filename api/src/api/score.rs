@@ -0,0 +1,210 @@
+//! API implementation for the SCORE fleet staking endpoints
+//!
+//! This module provides the score-roi [GET] and score-player [GET] endpoints as defined in the
+//! guidelines.
+
+use chrono::{DateTime, Duration, Utc};
+use db::{DbPool, StakingPosition, StakingRoi};
+use poem_openapi::{
+    ApiResponse, Object, OpenApi, Tags,
+    param::{Path, Query},
+    payload::{Json, PlainText},
+};
+
+use crate::auth::ApiAccess;
+
+/// Mint of ATLAS, the staking reward and the currency ships and resources are priced in
+const ATLAS_MINT: &str = "ATLASXmbPQxBUYbxPsV97usA3fPQYEqzQBUHgiFCUsXx";
+
+/// Decimals of ATLAS
+const ATLAS_DECIMALS: u8 = 8;
+
+/// Longest period the staking return can be computed over
+const MAX_HOURS: u32 = 24 * 30;
+
+/// Tags for the SCORE API
+#[derive(Tags)]
+enum ScoreTags {
+    /// Operations related to SCORE fleet staking
+    Score,
+}
+
+/// API implementation for the SCORE endpoints
+pub struct ScoreApi {
+    /// Database connection pool
+    db_pool: DbPool,
+}
+
+/// Staking return response object
+#[derive(Debug, Object)]
+struct StakingRoiResponse {
+    /// Mint of the ship
+    ship_mint: String,
+    /// Ships staked now, in base units of the ship mint
+    ships_staked: i64,
+    /// Players with ships staked now
+    stakers: i64,
+    /// ATLAS claimed in the period
+    rewards: f64,
+    /// Market value in ATLAS of the resources supplied in the period (if all have a price and
+    /// known decimals)
+    resupply_cost: Option<f64>,
+    /// Average market price of the ship in ATLAS
+    ship_price: Option<f64>,
+    /// Rewards minus resupply cost relative to the market value of the staked ships
+    roi: Option<f64>,
+    /// Return extrapolated to a year
+    annualized_roi: Option<f64>,
+}
+
+/// Staking position response object
+#[derive(Debug, Object)]
+struct StakingPositionResponse {
+    /// Mint of the ship
+    ship_mint: String,
+    /// Ships staked since the last withdrawal
+    ships_staked: i64,
+    /// Time of the first stake since the last withdrawal
+    staked_at: Option<DateTime<Utc>>,
+    /// ATLAS claimed in total
+    rewards: f64,
+    /// Time of the last claim
+    last_claim_at: Option<DateTime<Utc>>,
+    /// Food supplied minus food withdrawn
+    food: i64,
+    /// Fuel supplied minus fuel withdrawn
+    fuel: i64,
+    /// Ammo supplied minus ammo withdrawn
+    ammo: i64,
+    /// Toolkits supplied
+    toolkit: i64,
+}
+
+#[derive(ApiResponse)]
+enum GetStakingRoiResponse {
+    #[oai(status = 200)]
+    Roi(Json<Vec<StakingRoiResponse>>),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetStakingPositionsResponse {
+    #[oai(status = 200)]
+    Positions(Json<Vec<StakingPositionResponse>>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+impl StakingRoiResponse {
+    fn new(roi: StakingRoi, hours: u32) -> Self {
+        Self {
+            ship_mint: roi.ship_mint,
+            ships_staked: roi.ships_staked,
+            stakers: roi.stakers,
+            rewards: roi.rewards,
+            resupply_cost: roi.resupply_cost,
+            ship_price: roi.ship_price,
+            roi: roi.roi,
+            annualized_roi: roi.roi.map(|roi| roi * (24.0 * 365.0) / hours as f64),
+        }
+    }
+}
+
+impl From<StakingPosition> for StakingPositionResponse {
+    fn from(position: StakingPosition) -> Self {
+        Self {
+            ship_mint: position.ship_mint,
+            ships_staked: position.ships_staked,
+            staked_at: position.staked_at,
+            rewards: position.rewards,
+            last_claim_at: position.last_claim_at,
+            food: position.food,
+            fuel: position.fuel,
+            ammo: position.ammo,
+            toolkit: position.toolkit,
+        }
+    }
+}
+
+impl ScoreApi {
+    /// Creates a new instance of the SCORE API
+    pub fn new(db_pool: DbPool) -> Self {
+        Self { db_pool }
+    }
+}
+
+#[OpenApi]
+impl ScoreApi {
+    /// Get the staking return per ship
+    ///
+    /// Compares the ATLAS claimed in the period, minus the market value of the resources
+    /// supplied, to the market value of the ships staked now. Ships and resources are priced at
+    /// their volume weighted average exchange price in ATLAS over the period.
+    #[oai(path = "/score/roi", method = "get", tag = "ScoreTags::Score")]
+    async fn get_score_roi(
+        &self,
+        _access: ApiAccess,
+        /// Only include this ship mint
+        ship_mint: Query<Option<String>>,
+        /// Number of hours the return is computed over (default 168, at most 720)
+        hours: Query<Option<u32>>,
+    ) -> GetStakingRoiResponse {
+        let hours = hours.0.unwrap_or(24 * 7);
+        if hours == 0 || hours > MAX_HOURS {
+            return GetStakingRoiResponse::BadRequest(PlainText(format!(
+                "hours must be between 1 and {}",
+                MAX_HOURS
+            )));
+        }
+        let since = Utc::now() - Duration::hours(hours as i64);
+
+        match db::get_staking_roi(
+            &self.db_pool,
+            ATLAS_MINT,
+            ATLAS_DECIMALS,
+            ship_mint.0.as_deref(),
+            since,
+        )
+        .await
+        {
+            Ok(roi) => GetStakingRoiResponse::Roi(Json(
+                roi.into_iter()
+                    .map(|roi| StakingRoiResponse::new(roi, hours))
+                    .collect(),
+            )),
+            Err(_) => GetStakingRoiResponse::DBError,
+        }
+    }
+
+    /// Get the staking positions of a player
+    ///
+    /// Returns the staked ships, claimed rewards and supplied resources per ship the player
+    /// ever staked.
+    #[oai(
+        path = "/score/player/:wallet",
+        method = "get",
+        tag = "ScoreTags::Score"
+    )]
+    async fn get_score_player(
+        &self,
+        _access: ApiAccess,
+        /// Wallet address of the player
+        wallet: Path<String>,
+    ) -> GetStakingPositionsResponse {
+        match db::get_staking_positions(&self.db_pool, &wallet.0, ATLAS_DECIMALS).await {
+            Ok(positions) if positions.is_empty() => GetStakingPositionsResponse::NotFound,
+            Ok(positions) => GetStakingPositionsResponse::Positions(Json(
+                positions
+                    .into_iter()
+                    .map(StakingPositionResponse::from)
+                    .collect(),
+            )),
+            Err(_) => GetStakingPositionsResponse::DBError,
+        }
+    }
+}
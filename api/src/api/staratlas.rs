@@ -1,12 +1,26 @@
 //! API implementation for the Star Atlas endpoints
 //!
 //! This module provides the staratlas-exchanges [GET], staratlas-player [GET],
+//! staratlas-player-trades [GET], staratlas-player-summary [GET],
+//! staratlas-player-profiles [GET], staratlas-profile [GET], staratlas-exchanges-flows [GET],
 //! and staratlas-tokens [GET] endpoints as defined in the guidelines.
 
+use crate::auth::ApiAccess;
+use crate::portfolio::{self, MarketSummary};
+use chrono::{DateTime, Utc};
 use db::queries::staratlas;
-use db::{DbPool, Player, Token};
+use db::{
+    DbPool, ExchangeCursor, ExchangeDetailed, ExchangeFilter, ExchangeFlow, Player, PlayerGroup,
+    PlayerTrade, PlayerVolume, Profile, ProfileKey, Token,
+};
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
 
-use poem_openapi::{param::Query, payload::Json, ApiResponse, Object, OpenApi, Tags};
+use poem_openapi::{
+    ApiResponse, Enum, Object, OpenApi, Tags,
+    param::{Path, Query},
+    payload::{Json, PlainText},
+};
 
 /// Tags for the Star Atlas API
 #[derive(Tags)]
@@ -25,6 +39,25 @@ pub struct StarAtlasApi {
     db_pool: DbPool,
 }
 
+/// Player attribute to group by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Enum)]
+#[oai(rename = "PlayerGroup", rename_all = "lowercase")]
+pub(super) enum GroupBy {
+    /// Faction of the player's profile (MUD, ONI or USTUR)
+    Faction,
+    /// Guild the player is a member of
+    Guild,
+}
+
+impl From<GroupBy> for PlayerGroup {
+    fn from(group: GroupBy) -> Self {
+        match group {
+            GroupBy::Faction => PlayerGroup::Faction,
+            GroupBy::Guild => PlayerGroup::Guild,
+        }
+    }
+}
+
 /// Player response object
 #[derive(Debug, Object)]
 struct PlayerResponse {
@@ -38,6 +71,197 @@ struct PlayerResponse {
     first_seen: String,
     /// Last active timestamp (ISO 8601 format)
     last_active: String,
+    /// Number of exchanges in which the player was the buyer
+    buy_count: i32,
+    /// Number of exchanges in which the player was the seller
+    sell_count: i32,
+    /// Number of distinct assets the player traded
+    assets_traded: i32,
+    /// Faction of the player's profile (MUD, ONI or USTUR, if known)
+    faction: Option<String>,
+    /// Guild the player is a member of (if known)
+    guild: Option<String>,
+    /// Traded volume per currency
+    volumes: Vec<PlayerVolumeResponse>,
+}
+
+/// Player volume response object
+#[derive(Debug, Object)]
+struct PlayerVolumeResponse {
+    /// Currency mint address
+    currency: String,
+    /// Currency symbol (if available)
+    symbol: Option<String>,
+    /// Volume the player bought for
+    buy_volume: f64,
+    /// Volume the player sold for
+    sell_volume: f64,
+}
+
+/// Player trade response object
+#[derive(Debug, Object)]
+struct PlayerTradeResponse {
+    /// Unique identifier for the exchange
+    id: i32,
+    /// Block number of the exchange
+    slot: i32,
+    /// Transaction signature
+    signature: String,
+    /// Index within the transaction
+    index: i32,
+    /// Timestamp of the exchange (ISO 8601 format)
+    timestamp: String,
+    /// Side of the exchange (buy/sell)
+    side: String,
+    /// Role of the player in the exchange (BUY or SELL)
+    role: String,
+    /// Wallet address of the other party
+    counterparty: String,
+    /// Asset mint address
+    asset: String,
+    /// Asset symbol (if available)
+    asset_symbol: Option<String>,
+    /// Pair mint address
+    pair: String,
+    /// Pair symbol (if available)
+    pair_symbol: Option<String>,
+    /// Price of the exchange
+    price: f64,
+    /// Size of the exchange
+    size: i32,
+    /// Volume of the exchange
+    volume: f64,
+    /// Fee of the exchange
+    fee: f64,
+    /// Buddy fee of the exchange
+    buddy: f64,
+}
+
+/// Player trading summary response object
+#[derive(Debug, Object)]
+struct PlayerSummaryResponse {
+    /// Wallet address of the player
+    wallet_address: String,
+    /// Username of the player (if available)
+    username: Option<String>,
+    /// Number of trades of the player
+    trade_count: i32,
+    /// Net position per asset across all currencies
+    positions: Vec<AssetPositionResponse>,
+    /// Trading summary per asset and currency
+    markets: Vec<MarketSummaryResponse>,
+    /// Realized PnL and fees per currency
+    currencies: Vec<CurrencySummaryResponse>,
+}
+
+/// Asset position response object
+#[derive(Debug, Object)]
+struct AssetPositionResponse {
+    /// Asset mint address
+    asset: String,
+    /// Asset symbol (if available)
+    symbol: Option<String>,
+    /// Units bought
+    bought: i64,
+    /// Units sold
+    sold: i64,
+    /// Units bought minus units sold
+    net_position: i64,
+}
+
+/// Market summary response object
+#[derive(Debug, Object)]
+struct MarketSummaryResponse {
+    /// Asset mint address
+    asset: String,
+    /// Asset symbol (if available)
+    asset_symbol: Option<String>,
+    /// Pair mint address
+    pair: String,
+    /// Pair symbol (if available)
+    pair_symbol: Option<String>,
+    /// Units bought
+    bought: i64,
+    /// Units sold
+    sold: i64,
+    /// Average price paid per unit, including fees
+    average_buy_price: Option<f64>,
+    /// Average amount received per unit, after fees
+    average_sell_price: Option<f64>,
+    /// Realized PnL of the sold units (FIFO)
+    realized_pnl: f64,
+    /// Sold units without a matching buy, which are excluded from the realized PnL
+    unmatched_sold: i64,
+    /// Bought units that are still held
+    open_position: i64,
+    /// Cost basis of the units still held
+    open_cost: f64,
+}
+
+/// Currency summary response object
+#[derive(Debug, Object)]
+struct CurrencySummaryResponse {
+    /// Currency mint address
+    currency: String,
+    /// Currency symbol (if available)
+    symbol: Option<String>,
+    /// Amount spent on buys, including fees
+    spent: f64,
+    /// Amount received from sells, after fees
+    received: f64,
+    /// Realized PnL (FIFO)
+    realized_pnl: f64,
+    /// Marketplace fees paid
+    fees_paid: f64,
+    /// Buddy fees paid
+    buddy_paid: f64,
+}
+
+/// Exchange flow response object
+#[derive(Debug, Object)]
+struct ExchangeFlowResponse {
+    /// Faction or guild of the buyers (null for players without one)
+    buyer_group: Option<String>,
+    /// Faction or guild of the sellers (null for players without one)
+    seller_group: Option<String>,
+    /// Pair mint address the exchanges were paid in
+    pair_mint: String,
+    /// Pair symbol (if available)
+    pair_symbol: Option<String>,
+    /// Number of exchanges
+    trade_count: i64,
+    /// Number of asset units exchanged
+    size: i64,
+    /// Volume of the exchanges
+    volume: f64,
+}
+
+/// Player profile response object
+#[derive(Debug, Object)]
+struct ProfileResponse {
+    /// Address of the profile account
+    address: String,
+    /// Username of the profile (if available)
+    name: Option<String>,
+    /// Creation timestamp (ISO 8601 format, if available)
+    created_at: Option<String>,
+    /// Number of auth keys required to change the profile
+    key_threshold: i16,
+    /// Keys of the profile, auth keys (the owning wallets) first
+    keys: Vec<ProfileKeyResponse>,
+}
+
+/// Player profile key response object
+#[derive(Debug, Object)]
+struct ProfileKeyResponse {
+    /// The key (a wallet for auth keys)
+    key: String,
+    /// Program the key may be used with
+    scope: String,
+    /// Expiry timestamp (ISO 8601 format, if the key expires)
+    expires_at: Option<String>,
+    /// Whether the key is an auth key, owning the profile
+    is_auth: bool,
 }
 
 /// Token response object
@@ -57,7 +281,7 @@ struct TokenResponse {
 
 /// Exchange response object
 #[derive(Debug, Object)]
-struct ExchangeResponse {
+pub(super) struct ExchangeResponse {
     /// Unique identifier for the exchange
     id: i32,
     /// Block number of the exchange
@@ -72,12 +296,24 @@ struct ExchangeResponse {
     side: String,
     /// Buyer wallet address
     buyer: String,
+    /// Buyer username (if available)
+    buyer_username: Option<String>,
     /// Seller wallet address
     seller: String,
+    /// Seller username (if available)
+    seller_username: Option<String>,
     /// Asset mint address
     asset: String,
+    /// Asset symbol (if available)
+    asset_symbol: Option<String>,
+    /// Asset name (if available)
+    asset_name: Option<String>,
     /// Pair mint address
     pair: String,
+    /// Pair symbol (if available)
+    pair_symbol: Option<String>,
+    /// Pair name (if available)
+    pair_name: Option<String>,
     /// Price of the exchange
     price: f64,
     /// Size of the exchange
@@ -91,6 +327,7 @@ struct ExchangeResponse {
 }
 
 #[derive(ApiResponse)]
+#[allow(dead_code)]
 enum GetPlayerResponse {
     #[oai(status = 200)]
     Player(Json<PlayerResponse>),
@@ -103,6 +340,57 @@ enum GetPlayerResponse {
 }
 
 #[derive(ApiResponse)]
+enum GetPlayerTradesResponse {
+    #[oai(status = 200)]
+    Trades(Json<Vec<PlayerTradeResponse>>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetPlayerSummaryResponse {
+    #[oai(status = 200)]
+    Summary(Json<PlayerSummaryResponse>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetExchangeFlowsResponse {
+    #[oai(status = 200)]
+    Flows(Json<Vec<ExchangeFlowResponse>>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetProfilesResponse {
+    #[oai(status = 200)]
+    Profiles(Json<Vec<ProfileResponse>>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+enum GetProfileResponse {
+    #[oai(status = 200)]
+    Profile(Json<ProfileResponse>),
+    #[oai(status = 404)]
+    NotFound,
+    #[oai(status = 500)]
+    DBError,
+}
+
+#[derive(ApiResponse)]
+#[allow(dead_code)]
 enum GetTokenResponse {
     #[oai(status = 200)]
     Token(Json<TokenResponse>),
@@ -115,25 +403,175 @@ enum GetTokenResponse {
 }
 
 #[derive(ApiResponse)]
+#[allow(dead_code)]
 enum GetExchangeResponse {
     #[oai(status = 200)]
-    Exchange(Json<ExchangeResponse>),
+    Exchange(Json<Box<ExchangeResponse>>),
     #[oai(status = 200)]
-    Exchanges(Json<Vec<ExchangeResponse>>),
+    Exchanges(
+        Json<Vec<ExchangeResponse>>,
+        /// Cursor of the next page
+        #[oai(header = "X-Next-Cursor")]
+        Option<String>,
+    ),
+    #[oai(status = 400)]
+    BadRequest(PlainText<String>),
     #[oai(status = 404)]
     NotFound,
     #[oai(status = 500)]
     DBError,
 }
 
-impl From<Player> for PlayerResponse {
-    fn from(player: Player) -> Self {
+impl PlayerResponse {
+    fn new(player: Player, volumes: Vec<PlayerVolume>) -> Self {
         Self {
             id: player.id,
             wallet_address: player.wallet_address,
             username: player.username,
             first_seen: player.first_seen.to_rfc3339(),
             last_active: player.last_active.to_rfc3339(),
+            buy_count: player.buy_count,
+            sell_count: player.sell_count,
+            assets_traded: player.assets_traded,
+            faction: player.faction,
+            guild: player.guild,
+            volumes: volumes
+                .into_iter()
+                .map(PlayerVolumeResponse::from)
+                .collect(),
+        }
+    }
+}
+
+impl From<PlayerVolume> for PlayerVolumeResponse {
+    fn from(volume: PlayerVolume) -> Self {
+        Self {
+            currency: volume.currency_mint,
+            symbol: volume.currency_symbol,
+            buy_volume: volume.buy_volume,
+            sell_volume: volume.sell_volume,
+        }
+    }
+}
+
+impl From<PlayerTrade> for PlayerTradeResponse {
+    fn from(trade: PlayerTrade) -> Self {
+        Self {
+            id: trade.id,
+            slot: trade.slot,
+            signature: trade.signature,
+            index: trade.index,
+            timestamp: trade.timestamp.to_rfc3339(),
+            side: trade.side,
+            role: trade.role,
+            counterparty: trade.counterparty,
+            asset: trade.asset_mint,
+            asset_symbol: trade.asset_symbol,
+            pair: trade.pair_mint,
+            pair_symbol: trade.pair_symbol,
+            price: trade.price,
+            size: trade.size,
+            volume: trade.volume,
+            fee: trade.fee,
+            buddy: trade.buddy,
+        }
+    }
+}
+
+impl PlayerSummaryResponse {
+    fn new(player: Player, trades: &[PlayerTrade]) -> Self {
+        let markets = portfolio::summarize_markets(trades);
+
+        let mut positions: BTreeMap<&str, AssetPositionResponse> = BTreeMap::new();
+        let mut currencies: BTreeMap<&str, CurrencySummaryResponse> = BTreeMap::new();
+        for market in &markets {
+            let position =
+                positions
+                    .entry(&market.asset_mint)
+                    .or_insert_with(|| AssetPositionResponse {
+                        asset: market.asset_mint.clone(),
+                        symbol: market.asset_symbol.clone(),
+                        bought: 0,
+                        sold: 0,
+                        net_position: 0,
+                    });
+            position.bought += market.bought;
+            position.sold += market.sold;
+            position.net_position += market.bought - market.sold;
+
+            let currency =
+                currencies
+                    .entry(&market.pair_mint)
+                    .or_insert_with(|| CurrencySummaryResponse {
+                        currency: market.pair_mint.clone(),
+                        symbol: market.pair_symbol.clone(),
+                        spent: 0.0,
+                        received: 0.0,
+                        realized_pnl: 0.0,
+                        fees_paid: 0.0,
+                        buddy_paid: 0.0,
+                    });
+            currency.spent += market.spent;
+            currency.received += market.received;
+            currency.realized_pnl += market.realized_pnl;
+            currency.fees_paid += market.fees_paid;
+            currency.buddy_paid += market.buddy_paid;
+        }
+
+        Self {
+            wallet_address: player.wallet_address,
+            username: player.username,
+            trade_count: trades.len() as i32,
+            positions: positions.into_values().collect(),
+            currencies: currencies.into_values().collect(),
+            markets: markets.iter().map(MarketSummaryResponse::from).collect(),
+        }
+    }
+}
+
+impl From<&MarketSummary> for MarketSummaryResponse {
+    fn from(market: &MarketSummary) -> Self {
+        Self {
+            asset: market.asset_mint.clone(),
+            asset_symbol: market.asset_symbol.clone(),
+            pair: market.pair_mint.clone(),
+            pair_symbol: market.pair_symbol.clone(),
+            bought: market.bought,
+            sold: market.sold,
+            average_buy_price: market.average_buy_price(),
+            average_sell_price: market.average_sell_price(),
+            realized_pnl: market.realized_pnl,
+            unmatched_sold: market.unmatched_sold,
+            open_position: market.open_units(),
+            open_cost: market.open_cost,
+        }
+    }
+}
+
+impl From<ExchangeDetailed> for ExchangeResponse {
+    fn from(exchange: ExchangeDetailed) -> Self {
+        Self {
+            id: exchange.id,
+            slot: exchange.slot,
+            signature: exchange.signature,
+            index: exchange.index,
+            timestamp: exchange.timestamp.to_rfc3339(),
+            side: exchange.side,
+            buyer: exchange.buyer_wallet,
+            buyer_username: exchange.buyer_username,
+            seller: exchange.seller_wallet,
+            seller_username: exchange.seller_username,
+            asset: exchange.asset_mint,
+            asset_symbol: exchange.asset_symbol,
+            asset_name: exchange.asset_name,
+            pair: exchange.pair_mint,
+            pair_symbol: exchange.pair_symbol,
+            pair_name: exchange.pair_name,
+            price: exchange.price,
+            size: exchange.size,
+            volume: exchange.volume,
+            fee: exchange.fee,
+            buddy: exchange.buddy,
         }
     }
 }
@@ -150,18 +588,82 @@ impl From<Token> for TokenResponse {
     }
 }
 
+impl From<ExchangeFlow> for ExchangeFlowResponse {
+    fn from(flow: ExchangeFlow) -> Self {
+        Self {
+            buyer_group: flow.buyer_group,
+            seller_group: flow.seller_group,
+            pair_mint: flow.pair_mint,
+            pair_symbol: flow.pair_symbol,
+            trade_count: flow.trade_count,
+            size: flow.size,
+            volume: flow.volume,
+        }
+    }
+}
+
+impl ProfileResponse {
+    fn new(profile: Profile, keys: Vec<ProfileKey>) -> Self {
+        Self {
+            address: profile.address,
+            name: profile.name,
+            created_at: profile.created_at.map(|created_at| created_at.to_rfc3339()),
+            key_threshold: profile.key_threshold,
+            keys: keys.into_iter().map(ProfileKeyResponse::from).collect(),
+        }
+    }
+}
+
+impl From<ProfileKey> for ProfileKeyResponse {
+    fn from(key: ProfileKey) -> Self {
+        Self {
+            key: key.key,
+            scope: key.scope,
+            expires_at: (key.expire_time >= 0)
+                .then(|| DateTime::from_timestamp(key.expire_time, 0))
+                .flatten()
+                .map(|expires_at| expires_at.to_rfc3339()),
+            is_auth: key.is_auth,
+        }
+    }
+}
+
 impl StarAtlasApi {
     /// Creates a new instance of the Star Atlas API
     pub fn new(db_pool: DbPool) -> Self {
         Self { db_pool }
     }
+
+    /// Loads the keys of profiles and builds their responses
+    async fn profile_responses(&self, profiles: Vec<Profile>) -> db::Result<Vec<ProfileResponse>> {
+        let addresses: Vec<String> = profiles
+            .iter()
+            .map(|profile| profile.address.clone())
+            .collect();
+        let mut keys_by_profile: HashMap<String, Vec<ProfileKey>> = HashMap::new();
+        for key in db::get_profile_keys(&self.db_pool, &addresses).await? {
+            keys_by_profile
+                .entry(key.profile.clone())
+                .or_default()
+                .push(key);
+        }
+
+        Ok(profiles
+            .into_iter()
+            .map(|profile| {
+                let keys = keys_by_profile.remove(&profile.address).unwrap_or_default();
+                ProfileResponse::new(profile, keys)
+            })
+            .collect())
+    }
 }
 
 #[OpenApi]
 impl StarAtlasApi {
     /// Get Star Atlas players
     ///
-    /// Returns a list of Star Atlas players. Can be filtered by wallet address.
+    /// Returns a list of Star Atlas players with their trading activity. Can be filtered by
+    /// wallet address.
     #[oai(
         path = "/staratlas/player",
         method = "get",
@@ -169,36 +671,190 @@ impl StarAtlasApi {
     )]
     async fn get_staratlas_players(
         &self,
+        _access: ApiAccess,
         /// Filter by wallet address
         #[oai(name = "wallet_address")]
         wallet_address: Query<Option<String>>,
     ) -> GetPlayerResponse {
         let players = if let Some(wallet_address) = wallet_address.0 {
             match staratlas::get_player_by_wallet_address(&self.db_pool, &wallet_address).await {
-                Ok(Some(player)) => Some(vec![player]),
-                Ok(None) => Some(vec![]),
+                Ok(Some(player)) => {
+                    match staratlas::get_player_volumes_by_player_id(&self.db_pool, player.id).await
+                    {
+                        Ok(volumes) => Some((vec![player], volumes)),
+                        Err(_) => None,
+                    }
+                }
+                Ok(None) => Some((vec![], vec![])),
                 Err(_) => None,
             }
         } else {
-            match staratlas::get_all_players(&self.db_pool).await {
-                Ok(players) => Some(players),
-                Err(_) => None,
+            match (
+                staratlas::get_all_players(&self.db_pool).await,
+                staratlas::get_all_player_volumes(&self.db_pool).await,
+            ) {
+                (Ok(players), Ok(volumes)) => Some((players, volumes)),
+                _ => None,
             }
         };
 
         match players {
             None => GetPlayerResponse::DBError,
-            Some(players) => {
+            Some((players, volumes)) => {
                 if players.is_empty() {
                     GetPlayerResponse::NotFound
                 } else {
-                    let player_responses = players.into_iter().map(PlayerResponse::from).collect();
+                    let mut volumes_by_player: HashMap<i32, Vec<PlayerVolume>> = HashMap::new();
+                    for volume in volumes {
+                        volumes_by_player
+                            .entry(volume.player_id)
+                            .or_default()
+                            .push(volume);
+                    }
+
+                    let player_responses = players
+                        .into_iter()
+                        .map(|player| {
+                            let volumes = volumes_by_player.remove(&player.id).unwrap_or_default();
+                            PlayerResponse::new(player, volumes)
+                        })
+                        .collect();
                     GetPlayerResponse::Players(Json(player_responses))
                 }
             }
         }
     }
 
+    /// Get Star Atlas player trades
+    ///
+    /// Returns the trades of a player, newest first. An exchange in which the player was
+    /// both buyer and seller is returned once per role.
+    #[oai(
+        path = "/staratlas/player/:wallet/trades",
+        method = "get",
+        tag = "StarAtlasTags::Players"
+    )]
+    async fn get_staratlas_player_trades(
+        &self,
+        _access: ApiAccess,
+        /// Wallet address of the player
+        wallet: Path<String>,
+
+        offset: Query<Option<i32>>,
+        limit: Query<Option<i32>>,
+    ) -> GetPlayerTradesResponse {
+        let limit_value: i32 = limit.0.unwrap_or(1000);
+        let offset_value: i32 = offset.0.unwrap_or(0);
+
+        let player = match staratlas::get_player_by_wallet_address(&self.db_pool, &wallet.0).await {
+            Ok(Some(player)) => player,
+            Ok(None) => return GetPlayerTradesResponse::NotFound,
+            Err(_) => return GetPlayerTradesResponse::DBError,
+        };
+
+        match db::get_trades_by_player_id(&self.db_pool, player.id, limit_value, offset_value).await
+        {
+            Ok(trades) => {
+                if trades.is_empty() {
+                    GetPlayerTradesResponse::NotFound
+                } else {
+                    let trade_responses =
+                        trades.into_iter().map(PlayerTradeResponse::from).collect();
+                    GetPlayerTradesResponse::Trades(Json(trade_responses))
+                }
+            }
+            Err(_) => GetPlayerTradesResponse::DBError,
+        }
+    }
+
+    /// Get Star Atlas player trading summary
+    ///
+    /// Returns the net position per asset, the average buy and sell price per market, and
+    /// the realized PnL (FIFO) and fees paid per currency of a player. Buys are valued at
+    /// the full price including fees, sells at the volume received after the marketplace fee.
+    #[oai(
+        path = "/staratlas/player/:wallet/summary",
+        method = "get",
+        tag = "StarAtlasTags::Players"
+    )]
+    async fn get_staratlas_player_summary(
+        &self,
+        _access: ApiAccess,
+        /// Wallet address of the player
+        wallet: Path<String>,
+    ) -> GetPlayerSummaryResponse {
+        let player = match staratlas::get_player_by_wallet_address(&self.db_pool, &wallet.0).await {
+            Ok(Some(player)) => player,
+            Ok(None) => return GetPlayerSummaryResponse::NotFound,
+            Err(_) => return GetPlayerSummaryResponse::DBError,
+        };
+
+        match db::get_all_trades_by_player_id(&self.db_pool, player.id).await {
+            Ok(trades) => {
+                GetPlayerSummaryResponse::Summary(Json(PlayerSummaryResponse::new(player, &trades)))
+            }
+            Err(_) => GetPlayerSummaryResponse::DBError,
+        }
+    }
+
+    /// Get Star Atlas player profiles
+    ///
+    /// Returns the player profiles a wallet is a key of, newest first. By default only the
+    /// profiles the wallet owns (is an auth key of) are returned.
+    #[oai(
+        path = "/staratlas/player/:wallet/profiles",
+        method = "get",
+        tag = "StarAtlasTags::Players"
+    )]
+    async fn get_staratlas_player_profiles(
+        &self,
+        _access: ApiAccess,
+        /// Wallet address of the player
+        wallet: Path<String>,
+        /// Also include profiles the wallet is a scoped (non-auth) key of
+        #[oai(name = "include_scoped")]
+        include_scoped: Query<Option<bool>>,
+    ) -> GetProfilesResponse {
+        let auth_only = !include_scoped.0.unwrap_or(false);
+        let profiles = match db::get_profiles_by_key(&self.db_pool, &wallet.0, auth_only).await {
+            Ok(profiles) if profiles.is_empty() => return GetProfilesResponse::NotFound,
+            Ok(profiles) => profiles,
+            Err(_) => return GetProfilesResponse::DBError,
+        };
+
+        match self.profile_responses(profiles).await {
+            Ok(profiles) => GetProfilesResponse::Profiles(Json(profiles)),
+            Err(_) => GetProfilesResponse::DBError,
+        }
+    }
+
+    /// Get a Star Atlas player profile
+    ///
+    /// Returns a player profile with its username and keys. The auth keys are the wallets
+    /// owning the profile.
+    #[oai(
+        path = "/staratlas/profile/:profile",
+        method = "get",
+        tag = "StarAtlasTags::Players"
+    )]
+    async fn get_staratlas_profile(
+        &self,
+        _access: ApiAccess,
+        /// Address of the profile account
+        profile: Path<String>,
+    ) -> GetProfileResponse {
+        let profile = match db::get_profile(&self.db_pool, &profile.0).await {
+            Ok(Some(profile)) => profile,
+            Ok(None) => return GetProfileResponse::NotFound,
+            Err(_) => return GetProfileResponse::DBError,
+        };
+
+        match self.profile_responses(vec![profile]).await {
+            Ok(mut profiles) => GetProfileResponse::Profile(Json(profiles.remove(0))),
+            Err(_) => GetProfileResponse::DBError,
+        }
+    }
+
     /// Get Star Atlas tokens
     ///
     /// Returns a list of Star Atlas tokens.
@@ -207,7 +863,7 @@ impl StarAtlasApi {
         method = "get",
         tag = "StarAtlasTags::Tokens"
     )]
-    async fn get_staratlas_tokens(&self) -> GetTokenResponse {
+    async fn get_staratlas_tokens(&self, _access: ApiAccess) -> GetTokenResponse {
         match staratlas::get_all_tokens(&self.db_pool).await {
             Ok(tokens) => {
                 if tokens.is_empty() {
@@ -223,121 +879,159 @@ impl StarAtlasApi {
 
     /// Get Star Atlas exchanges
     ///
-    /// Returns a list of Star Atlas exchanges. Can be filtered by buyer, seller, or asset.
+    /// Returns a list of Star Atlas exchanges, newest first. All filters can be combined and
+    /// ranges are inclusive. When a full page is returned, the `X-Next-Cursor` header holds
+    /// the cursor to pass as `cursor` to fetch the next page.
     #[oai(
         path = "/staratlas/exchanges",
         method = "get",
         tag = "StarAtlasTags::Exchanges"
     )]
+    #[allow(clippy::too_many_arguments)]
     async fn get_staratlas_exchanges(
         &self,
+        _access: ApiAccess,
         buyer_id: Query<Option<i32>>,
         seller_id: Query<Option<i32>>,
         asset_id: Query<Option<i32>>,
-
+        /// Filter by buyer wallet address
+        buyer: Query<Option<String>>,
+        /// Filter by seller wallet address
+        seller: Query<Option<String>>,
+        /// Filter by wallet address of either the buyer or the seller
+        wallet: Query<Option<String>>,
+        /// Filter by asset mint address
+        asset: Query<Option<String>>,
+        /// Filter by pair mint address
+        pair: Query<Option<String>>,
+        /// Filter by side (buy/sell)
+        side: Query<Option<String>>,
+        /// Earliest timestamp (ISO 8601 format)
+        from_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Latest timestamp (ISO 8601 format)
+        to_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Lowest block number
+        from_slot: Query<Option<i32>>,
+        /// Highest block number
+        to_slot: Query<Option<i32>>,
+        min_price: Query<Option<f64>>,
+        max_price: Query<Option<f64>>,
+        min_volume: Query<Option<f64>>,
+        max_volume: Query<Option<f64>>,
+        /// Cursor returned in the `X-Next-Cursor` header of the previous page
+        cursor: Query<Option<String>>,
+        /// Number of exchanges to skip, not allowed together with `cursor`
         offset: Query<Option<i32>>,
         limit: Query<Option<i32>>,
     ) -> GetExchangeResponse {
         let limit_value: i32 = limit.0.unwrap_or(1000);
         let offset_value: i32 = offset.0.unwrap_or(0);
 
-        let exchanges = if let Some(buyer_id) = buyer_id.0 {
-            match db::get_exchanges_by_buyer_id(&self.db_pool, buyer_id, limit_value, offset_value)
-                .await
-            {
-                Ok(exchanges) => Some(exchanges),
-                Err(_) => None,
-            }
-        } else if let Some(seller_id) = seller_id.0 {
-            match db::get_exchanges_by_seller_id(
-                &self.db_pool,
-                seller_id,
-                limit_value,
-                offset_value,
-            )
-            .await
-            {
-                Ok(exchanges) => Some(exchanges),
-                Err(_) => None,
-            }
-        } else if let Some(asset_id) = asset_id.0 {
-            match db::get_exchanges_by_asset_id(&self.db_pool, asset_id, limit_value, offset_value)
-                .await
-            {
-                Ok(exchanges) => Some(exchanges),
-                Err(_) => None,
-            }
-        } else {
-            match db::get_exchanges(&self.db_pool, limit_value, offset_value).await {
-                Ok(exchanges) => Some(exchanges),
-                Err(_) => None,
-            }
+        if cursor.0.is_some() && offset.0.is_some() {
+            return GetExchangeResponse::BadRequest(PlainText(
+                "offset cannot be combined with cursor".to_string(),
+            ));
+        }
+
+        let after = match cursor
+            .0
+            .as_deref()
+            .map(ExchangeCursor::from_str)
+            .transpose()
+        {
+            Ok(after) => after,
+            Err(message) => return GetExchangeResponse::BadRequest(PlainText(message)),
+        };
+
+        let filter = ExchangeFilter {
+            buyer: buyer.0,
+            seller: seller.0,
+            wallet: wallet.0,
+            buyer_id: buyer_id.0,
+            seller_id: seller_id.0,
+            asset_id: asset_id.0,
+            asset_mint: asset.0,
+            pair_mint: pair.0,
+            side: side.0,
+            signature: None,
+            from_timestamp: from_timestamp.0,
+            to_timestamp: to_timestamp.0,
+            from_slot: from_slot.0,
+            to_slot: to_slot.0,
+            min_price: min_price.0,
+            max_price: max_price.0,
+            min_volume: min_volume.0,
+            max_volume: max_volume.0,
+            after,
         };
 
-        match exchanges {
-            None => GetExchangeResponse::DBError,
-            Some(exchanges) => {
+        match db::get_exchanges_filtered(&self.db_pool, &filter, limit_value, offset_value).await {
+            Err(_) => GetExchangeResponse::DBError,
+            Ok(exchanges) => {
                 if exchanges.is_empty() {
                     GetExchangeResponse::NotFound
                 } else {
-                    let mut exchange_responses = Vec::new();
-
-                    for exchange in exchanges {
-                        // Resolve buyer ID to wallet address
-                        let buyer_wallet = match staratlas::get_player_by_id(
-                            &self.db_pool,
-                            exchange.buyer,
-                        )
-                        .await
-                        {
-                            Ok(Some(player)) => player.wallet_address,
-                            _ => exchange.buyer.to_string(), // Fallback to ID as string if player not found
-                        };
-
-                        // Resolve seller ID to wallet address
-                        let seller_wallet =
-                            match staratlas::get_player_by_id(&self.db_pool, exchange.seller).await
-                            {
-                                Ok(Some(player)) => player.wallet_address,
-                                _ => exchange.seller.to_string(), // Fallback to ID as string if player not found
-                            };
-
-                        // Resolve asset ID to mint address
-                        let asset_mint =
-                            match staratlas::get_token_by_id(&self.db_pool, exchange.asset).await {
-                                Ok(Some(token)) => token.mint,
-                                _ => exchange.asset.to_string(), // Fallback to ID as string if token not found
-                            };
-
-                        // Resolve pair ID to mint address
-                        let pair_mint =
-                            match staratlas::get_token_by_id(&self.db_pool, exchange.pair).await {
-                                Ok(Some(token)) => token.mint,
-                                _ => exchange.pair.to_string(), // Fallback to ID as string if token not found
-                            };
-
-                        exchange_responses.push(ExchangeResponse {
-                            id: exchange.id,
-                            slot: exchange.slot,
-                            signature: exchange.signature.to_string(),
-                            index: exchange.index,
-                            timestamp: exchange.timestamp.to_rfc3339(),
-                            side: exchange.side.to_string(),
-                            buyer: buyer_wallet,
-                            seller: seller_wallet,
-                            asset: asset_mint,
-                            pair: pair_mint,
-                            price: exchange.price,
-                            size: exchange.size,
-                            volume: exchange.volume,
-                            fee: exchange.fee,
-                            buddy: exchange.buddy,
+                    let next_cursor = (exchanges.len() as i32 >= limit_value)
+                        .then(|| exchanges.last())
+                        .flatten()
+                        .map(|exchange| {
+                            ExchangeCursor {
+                                slot: exchange.slot,
+                                signature: exchange.signature.clone(),
+                                index: exchange.index,
+                            }
+                            .to_string()
                         });
-                    }
 
-                    GetExchangeResponse::Exchanges(Json(exchange_responses))
+                    let exchange_responses =
+                        exchanges.into_iter().map(ExchangeResponse::from).collect();
+                    GetExchangeResponse::Exchanges(Json(exchange_responses), next_cursor)
                 }
             }
         }
     }
+
+    /// Get Star Atlas exchange flows between factions or guilds
+    ///
+    /// Sums the exchanges per faction or guild of the buyer and of the seller, split by the
+    /// pair currency. Players without a faction or guild are summed together.
+    #[oai(
+        path = "/staratlas/exchanges/flows",
+        method = "get",
+        tag = "StarAtlasTags::Exchanges"
+    )]
+    #[allow(clippy::too_many_arguments)]
+    async fn get_staratlas_exchange_flows(
+        &self,
+        _access: ApiAccess,
+        /// Player attribute to group the buyers and sellers by
+        group_by: Query<GroupBy>,
+        /// Filter by asset mint address
+        asset: Query<Option<String>>,
+        /// Filter by pair mint address
+        pair: Query<Option<String>>,
+        /// Filter by side (buy/sell)
+        side: Query<Option<String>>,
+        /// Earliest timestamp (ISO 8601 format)
+        from_timestamp: Query<Option<DateTime<Utc>>>,
+        /// Latest timestamp (ISO 8601 format)
+        to_timestamp: Query<Option<DateTime<Utc>>>,
+    ) -> GetExchangeFlowsResponse {
+        let filter = ExchangeFilter {
+            asset_mint: asset.0,
+            pair_mint: pair.0,
+            side: side.0,
+            from_timestamp: from_timestamp.0,
+            to_timestamp: to_timestamp.0,
+            ..Default::default()
+        };
+
+        match db::get_exchange_flows(&self.db_pool, &filter, group_by.0.into()).await {
+            Ok(flows) if flows.is_empty() => GetExchangeFlowsResponse::NotFound,
+            Ok(flows) => GetExchangeFlowsResponse::Flows(Json(
+                flows.into_iter().map(ExchangeFlowResponse::from).collect(),
+            )),
+            Err(_) => GetExchangeFlowsResponse::DBError,
+        }
+    }
 }
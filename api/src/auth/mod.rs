@@ -0,0 +1,446 @@
+//! API key authentication and rate limiting
+//!
+//! Requests may carry an API key in the `X-API-Key` header. Keys are stored as SHA-256
+//! hashes and looked up once per cache period, so revoking a key takes effect within that
+//! period. Requests with a key are rate limited per key, requests without one per client IP.
+//! Keys that are not cached cost a database lookup, so those requests are first counted
+//! against the client IP, which keeps clients sending random keys from flooding the
+//! database. Request counts of the keys are buffered in memory and written to the database
+//! periodically.
+
+use std::collections::HashMap;
+use std::env;
+use std::net::{IpAddr, Ipv4Addr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use db::{ApiKey, DbPool};
+use log::{info, warn};
+use poem::http::StatusCode;
+use poem::{Endpoint, IntoResponse, Middleware, Request, Response};
+use rand::{Rng, distributions::Alphanumeric, thread_rng};
+use sha2::{Digest, Sha256};
+
+mod rate_limit;
+mod scheme;
+
+use rate_limit::{RateLimitDecision, RateLimitKey, RateLimiter};
+pub use scheme::{ApiAccess, ApiKeyAuth};
+
+/// Header the API key is sent in
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// Prefix of generated API keys, to make them recognisable
+const API_KEY_PREFIX: &str = "rh_";
+
+/// Number of random characters of generated API keys
+const API_KEY_LENGTH: usize = 40;
+
+/// Number of characters of a key stored in plain text to identify it
+const API_KEY_PREFIX_LENGTH: usize = 10;
+
+/// Generates a new random API key
+pub fn generate_api_key() -> String {
+    let random: String = thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(API_KEY_LENGTH)
+        .map(char::from)
+        .collect();
+    format!("{}{}", API_KEY_PREFIX, random)
+}
+
+/// Returns the hex encoded SHA-256 hash an API key is stored as
+pub fn hash_api_key(key: &str) -> String {
+    hex::encode(Sha256::digest(key.as_bytes()))
+}
+
+/// Returns the characters of an API key stored in plain text to identify it
+pub fn api_key_prefix(key: &str) -> String {
+    key.chars().take(API_KEY_PREFIX_LENGTH).collect()
+}
+
+/// Configuration of the authentication middleware
+#[derive(Debug, Clone)]
+pub struct AuthConfig {
+    /// Whether requests without an API key are rejected
+    pub require_key: bool,
+    /// Requests per minute of API keys without their own limit
+    pub key_rate_limit: u32,
+    /// Requests per minute of each client IP without an API key
+    pub ip_rate_limit: u32,
+    /// Whether the client IP is taken from the `X-Forwarded-For` and `X-Real-IP` headers
+    pub trust_proxy_headers: bool,
+    /// How long looked up API keys are cached
+    pub key_cache_ttl: Duration,
+    /// How often the request counts of the API keys are written to the database
+    pub usage_flush_interval: Duration,
+}
+
+impl AuthConfig {
+    /// Reads the configuration from the environment
+    ///
+    /// Uses `API_REQUIRE_KEY` (default false), `API_KEY_RATE_LIMIT` (default 600),
+    /// `API_IP_RATE_LIMIT` (default 60), `API_TRUST_PROXY_HEADERS` (default false),
+    /// `API_KEY_CACHE_SECONDS` (default 60) and `API_KEY_USAGE_FLUSH_SECONDS` (default 30).
+    pub fn from_env() -> Self {
+        Self {
+            require_key: env_var("API_REQUIRE_KEY", false),
+            key_rate_limit: env_var("API_KEY_RATE_LIMIT", 600),
+            ip_rate_limit: env_var("API_IP_RATE_LIMIT", 60),
+            trust_proxy_headers: env_var("API_TRUST_PROXY_HEADERS", false),
+            key_cache_ttl: Duration::from_secs(env_var("API_KEY_CACHE_SECONDS", 60)),
+            usage_flush_interval: Duration::from_secs(env_var("API_KEY_USAGE_FLUSH_SECONDS", 30)),
+        }
+    }
+}
+
+/// Reads and parses an environment variable, falling back to a default if it is not set
+fn env_var<T: std::str::FromStr>(name: &str, default: T) -> T {
+    match env::var(name) {
+        Ok(value) => value
+            .parse()
+            .unwrap_or_else(|_| panic!("{} has an invalid value", name)),
+        Err(_) => default,
+    }
+}
+
+/// API key looked up by its hash, None if no active key has the hash
+#[derive(Debug)]
+struct CachedKey {
+    api_key: Option<ApiKey>,
+    cached_at: Instant,
+}
+
+/// State shared by the middleware and its maintenance task
+struct AuthState {
+    db_pool: DbPool,
+    config: AuthConfig,
+    limiter: RateLimiter,
+    keys: Mutex<HashMap<String, CachedKey>>,
+    usage: Mutex<HashMap<i32, i64>>,
+}
+
+/// Middleware authenticating API keys and enforcing rate limits
+#[derive(Clone)]
+pub struct Auth {
+    state: Arc<AuthState>,
+}
+
+impl Auth {
+    /// Creates the middleware and starts writing the key usage to the database
+    pub fn start(db_pool: DbPool, config: AuthConfig) -> Self {
+        info!(
+            "Rate limiting to {} requests per minute per API key and {} per IP{}",
+            config.key_rate_limit,
+            config.ip_rate_limit,
+            if config.require_key {
+                ", API key required"
+            } else {
+                ""
+            }
+        );
+
+        let state = Arc::new(AuthState {
+            db_pool,
+            config,
+            limiter: RateLimiter::default(),
+            keys: Mutex::new(HashMap::new()),
+            usage: Mutex::new(HashMap::new()),
+        });
+
+        let maintenance = state.clone();
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(maintenance.config.usage_flush_interval);
+            loop {
+                interval.tick().await;
+                maintenance.flush_usage().await;
+                maintenance.limiter.purge_idle();
+                maintenance.purge_keys();
+            }
+        });
+
+        Self { state }
+    }
+}
+
+impl AuthState {
+    /// Returns the cached lookup of a key hash, `None` if it has to be looked up
+    fn cached_key(&self, key_hash: &str) -> Option<Option<ApiKey>> {
+        self.keys
+            .lock()
+            .unwrap()
+            .get(key_hash)
+            .filter(|cached| cached.cached_at.elapsed() < self.config.key_cache_ttl)
+            .map(|cached| cached.api_key.clone())
+    }
+
+    /// Looks up the active API key with a key hash in the database and caches the result
+    async fn find_key(&self, key_hash: String) -> db::Result<Option<ApiKey>> {
+        let api_key = db::get_active_api_key_by_hash(&self.db_pool, &key_hash).await?;
+        self.keys.lock().unwrap().insert(
+            key_hash,
+            CachedKey {
+                api_key: api_key.clone(),
+                cached_at: Instant::now(),
+            },
+        );
+
+        Ok(api_key)
+    }
+
+    /// Drops expired API keys from the cache
+    fn purge_keys(&self) {
+        let ttl = self.config.key_cache_ttl;
+        self.keys
+            .lock()
+            .unwrap()
+            .retain(|_, cached| cached.cached_at.elapsed() < ttl);
+    }
+
+    /// Writes the buffered request counts of the API keys to the database
+    ///
+    /// Counts that could not be written are kept for the next attempt.
+    async fn flush_usage(&self) {
+        let usage = std::mem::take(&mut *self.usage.lock().unwrap());
+        if usage.is_empty() {
+            return;
+        }
+
+        let usage: Vec<(i32, i64)> = usage.into_iter().collect();
+        if let Err(e) = db::record_api_key_usage(&self.db_pool, &usage).await {
+            warn!("Failed to record API key usage: {}", e);
+            let mut buffered = self.usage.lock().unwrap();
+            for (id, requests) in usage {
+                *buffered.entry(id).or_default() += requests;
+            }
+        }
+    }
+
+    /// Returns the IP of the client that made a request
+    fn client_ip(&self, req: &Request) -> IpAddr {
+        if self.config.trust_proxy_headers {
+            let forwarded = req
+                .header("X-Forwarded-For")
+                .and_then(|value| value.split(',').next())
+                .or_else(|| req.header("X-Real-IP"))
+                .and_then(|value| value.trim().parse().ok());
+            if let Some(ip) = forwarded {
+                return ip;
+            }
+        }
+
+        req.remote_addr()
+            .as_socket_addr()
+            .map(|addr| addr.ip())
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for Auth {
+    type Output = AuthEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        AuthEndpoint {
+            inner: ep,
+            state: self.state.clone(),
+        }
+    }
+}
+
+/// Endpoint wrapped by the [`Auth`] middleware
+pub struct AuthEndpoint<E> {
+    inner: E,
+    state: Arc<AuthState>,
+}
+
+impl<E: Endpoint> Endpoint for AuthEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, mut req: Request) -> poem::Result<Self::Output> {
+        let state = &self.state;
+
+        let key_hash = req.header(API_KEY_HEADER).map(hash_api_key);
+
+        let decision = match key_hash {
+            Some(key_hash) => {
+                let found = match state.cached_key(&key_hash) {
+                    Some(cached) => Ok(cached),
+                    None => {
+                        let ip = state.client_ip(&req);
+                        let decision = state
+                            .limiter
+                            .check(RateLimitKey::Ip(ip), state.config.ip_rate_limit);
+                        if decision.retry_after.is_some() {
+                            return Ok(rate_limited_response(decision));
+                        }
+                        state.find_key(key_hash).await
+                    }
+                };
+                let api_key = match found {
+                    Ok(Some(api_key)) => api_key,
+                    Ok(None) => {
+                        return Ok(error_response(StatusCode::UNAUTHORIZED, "Invalid API key"));
+                    }
+                    Err(e) => {
+                        warn!("Failed to look up API key: {}", e);
+                        return Ok(error_response(
+                            StatusCode::INTERNAL_SERVER_ERROR,
+                            "Database error",
+                        ));
+                    }
+                };
+
+                let limit = api_key
+                    .rate_limit
+                    .and_then(|limit| u32::try_from(limit).ok())
+                    .unwrap_or(state.config.key_rate_limit);
+                let decision = state.limiter.check(RateLimitKey::ApiKey(api_key.id), limit);
+                if decision.retry_after.is_none() {
+                    *state.usage.lock().unwrap().entry(api_key.id).or_default() += 1;
+                }
+                req.extensions_mut().insert(api_key);
+                decision
+            }
+            None if state.config.require_key => {
+                return Ok(error_response(StatusCode::UNAUTHORIZED, "API key required"));
+            }
+            None => {
+                let ip = state.client_ip(&req);
+                state
+                    .limiter
+                    .check(RateLimitKey::Ip(ip), state.config.ip_rate_limit)
+            }
+        };
+
+        if decision.retry_after.is_some() {
+            return Ok(rate_limited_response(decision));
+        }
+
+        let mut resp = match self.inner.call(req).await {
+            Ok(resp) => resp.into_response(),
+            Err(e) => e.into_response(),
+        };
+
+        set_rate_limit_headers(&mut resp, decision);
+        Ok(resp)
+    }
+}
+
+/// Creates the response of a request rejected by the rate limit
+fn rate_limited_response(decision: RateLimitDecision) -> Response {
+    let mut resp = error_response(StatusCode::TOO_MANY_REQUESTS, "Rate limit exceeded");
+    if let Some(retry_after) = decision.retry_after {
+        resp.headers_mut().insert(
+            "Retry-After",
+            (retry_after.as_secs() + 1).to_string().parse().unwrap(),
+        );
+    }
+    set_rate_limit_headers(&mut resp, decision);
+    resp
+}
+
+/// Creates a plain text error response
+fn error_response(status: StatusCode, message: &'static str) -> Response {
+    Response::builder().status(status).body(message)
+}
+
+/// Tells the client its rate limit and how many requests it has left
+fn set_rate_limit_headers(resp: &mut Response, decision: RateLimitDecision) {
+    let headers = resp.headers_mut();
+    headers.insert("X-RateLimit-Limit", decision.limit.into());
+    headers.insert("X-RateLimit-Remaining", decision.remaining.into());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use poem::endpoint::make_sync;
+
+    #[test]
+    fn api_keys_are_stored_as_their_sha256() {
+        // SHA-256 of "abc"
+        assert_eq!(
+            hash_api_key("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn generated_keys_are_prefixed_and_identified_by_their_start() {
+        let key = generate_api_key();
+
+        assert!(key.starts_with(API_KEY_PREFIX));
+        assert_eq!(key.len(), API_KEY_PREFIX.len() + API_KEY_LENGTH);
+        assert_ne!(key, generate_api_key());
+        assert_eq!(api_key_prefix(&key), key[..API_KEY_PREFIX_LENGTH]);
+    }
+
+    /// Middleware with an IP limit of one request a minute, None without DATABASE_URL
+    async fn auth() -> Option<Auth> {
+        dotenv::dotenv().ok();
+        if env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return None;
+        }
+        let state = AuthState {
+            db_pool: db::establish_connection().await.unwrap(),
+            config: AuthConfig {
+                require_key: false,
+                key_rate_limit: 600,
+                ip_rate_limit: 1,
+                trust_proxy_headers: false,
+                key_cache_ttl: Duration::from_secs(60),
+                usage_flush_interval: Duration::from_secs(30),
+            },
+            limiter: RateLimiter::default(),
+            keys: Mutex::new(HashMap::new()),
+            usage: Mutex::new(HashMap::new()),
+        };
+        Some(Auth {
+            state: Arc::new(state),
+        })
+    }
+
+    async fn status(endpoint: &impl Endpoint<Output = Response>, key: Option<&str>) -> StatusCode {
+        let mut req = Request::builder();
+        if let Some(key) = key {
+            req = req.header(API_KEY_HEADER, key);
+        }
+        endpoint.call(req.finish()).await.unwrap().status()
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn unknown_keys_are_counted_against_the_ip_before_the_lookup() {
+        let Some(auth) = auth().await else {
+            return;
+        };
+        let endpoint = auth.transform(make_sync(|_| "ok"));
+        let unknown = generate_api_key();
+
+        let first = status(&endpoint, Some(&unknown)).await;
+        let cached = status(&endpoint, Some(&unknown)).await;
+        let other = generate_api_key();
+        let limited = status(&endpoint, Some(&other)).await;
+
+        // The first lookup used the IP's only request and cached the key as unknown
+        assert_eq!(first, StatusCode::UNAUTHORIZED);
+        assert_eq!(cached, StatusCode::UNAUTHORIZED);
+        // A key that is not cached is rejected by the IP limit without a lookup
+        assert_eq!(limited, StatusCode::TOO_MANY_REQUESTS);
+        assert!(auth.state.cached_key(&hash_api_key(&unknown)).is_some());
+        assert!(auth.state.cached_key(&hash_api_key(&other)).is_none());
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn anonymous_requests_are_limited_per_ip() {
+        let Some(auth) = auth().await else {
+            return;
+        };
+        let endpoint = auth.transform(make_sync(|_| "ok"));
+
+        assert_eq!(status(&endpoint, None).await, StatusCode::OK);
+        assert_eq!(status(&endpoint, None).await, StatusCode::TOO_MANY_REQUESTS);
+    }
+}
@@ -0,0 +1,187 @@
+//! In-memory token bucket rate limiter
+//!
+//! Every client gets a bucket that holds up to one minute worth of requests and refills
+//! continuously, so a client may burst up to its limit and is then throttled to its
+//! average rate.
+
+use std::collections::HashMap;
+use std::net::IpAddr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Client a rate limit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RateLimitKey {
+    /// Requests made with an API key
+    ApiKey(i32),
+    /// Requests made without an API key
+    Ip(IpAddr),
+}
+
+/// Outcome of a rate limit check
+#[derive(Debug, Clone, Copy)]
+pub struct RateLimitDecision {
+    /// Requests per minute the client may make
+    pub limit: u32,
+    /// Requests the client may still make right now
+    pub remaining: u32,
+    /// Time until the next request is allowed, if this one was rejected
+    pub retry_after: Option<Duration>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// Token buckets of all clients
+#[derive(Debug, Default)]
+pub struct RateLimiter {
+    buckets: Mutex<HashMap<RateLimitKey, Bucket>>,
+}
+
+impl RateLimiter {
+    /// Takes a token from the bucket of a client
+    ///
+    /// # Arguments
+    /// * `key` - The client making the request
+    /// * `limit` - Requests per minute the client may make
+    pub fn check(&self, key: RateLimitKey, limit: u32) -> RateLimitDecision {
+        let now = Instant::now();
+        let capacity = f64::from(limit.max(1));
+        let per_second = capacity / 60.0;
+
+        let mut buckets = self.buckets.lock().unwrap();
+        let bucket = buckets.entry(key).or_insert(Bucket {
+            tokens: capacity,
+            updated_at: now,
+        });
+
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_second).min(capacity);
+        bucket.updated_at = now;
+
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            RateLimitDecision {
+                limit,
+                remaining: bucket.tokens as u32,
+                retry_after: None,
+            }
+        } else {
+            RateLimitDecision {
+                limit,
+                remaining: 0,
+                retry_after: Some(Duration::from_secs_f64((1.0 - bucket.tokens) / per_second)),
+            }
+        }
+    }
+
+    /// Drops the buckets of clients that have not made a request for a minute
+    ///
+    /// Their buckets are full again, so they are recreated as they were.
+    pub fn purge_idle(&self) {
+        let now = Instant::now();
+        self.buckets
+            .lock()
+            .unwrap()
+            .retain(|_, bucket| now.duration_since(bucket.updated_at) < Duration::from_secs(60));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+
+    const IP: RateLimitKey = RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::LOCALHOST));
+
+    #[test]
+    fn bucket_allows_a_burst_up_to_the_limit() {
+        let limiter = RateLimiter::default();
+
+        let remaining: Vec<u32> = (0..3)
+            .map(|_| limiter.check(IP, 3))
+            .inspect(|decision| assert_eq!(decision.retry_after, None))
+            .map(|decision| decision.remaining)
+            .collect();
+        let rejected = limiter.check(IP, 3);
+
+        assert_eq!(remaining, [2, 1, 0]);
+        assert_eq!(rejected.remaining, 0);
+        // 3 requests per minute refill one token every 20 seconds
+        let retry_after = rejected.retry_after.unwrap();
+        assert!(
+            retry_after > Duration::from_secs(19) && retry_after <= Duration::from_secs(20),
+            "{retry_after:?}"
+        );
+    }
+
+    #[test]
+    fn buckets_refill_over_time() {
+        let limiter = RateLimiter::default();
+        limiter.check(IP, 60);
+        // Pretend the last request was two seconds ago, enough for two tokens at 60 a minute
+        limiter.buckets.lock().unwrap().get_mut(&IP).unwrap().tokens = 0.0;
+        limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .get_mut(&IP)
+            .unwrap()
+            .updated_at -= Duration::from_secs(2);
+
+        assert_eq!(limiter.check(IP, 60).retry_after, None);
+        assert_eq!(limiter.check(IP, 60).retry_after, None);
+        assert!(limiter.check(IP, 60).retry_after.is_some());
+    }
+
+    #[test]
+    fn clients_have_separate_buckets() {
+        let limiter = RateLimiter::default();
+
+        assert!(limiter.check(IP, 1).retry_after.is_none());
+        assert!(limiter.check(IP, 1).retry_after.is_some());
+        assert!(
+            limiter
+                .check(RateLimitKey::ApiKey(1), 1)
+                .retry_after
+                .is_none()
+        );
+        assert!(
+            limiter
+                .check(RateLimitKey::Ip(IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1))), 1)
+                .retry_after
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn zero_limit_still_allows_one_request_a_minute() {
+        let limiter = RateLimiter::default();
+
+        assert!(limiter.check(IP, 0).retry_after.is_none());
+        assert!(limiter.check(IP, 0).retry_after.is_some());
+    }
+
+    #[test]
+    fn idle_buckets_are_purged() {
+        let limiter = RateLimiter::default();
+        limiter.check(IP, 1);
+        limiter.check(RateLimitKey::ApiKey(1), 1);
+        limiter
+            .buckets
+            .lock()
+            .unwrap()
+            .get_mut(&IP)
+            .unwrap()
+            .updated_at -= Duration::from_secs(61);
+
+        limiter.purge_idle();
+
+        let buckets = limiter.buckets.lock().unwrap();
+        assert!(!buckets.contains_key(&IP));
+        assert!(buckets.contains_key(&RateLimitKey::ApiKey(1)));
+    }
+}
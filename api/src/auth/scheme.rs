@@ -0,0 +1,94 @@
+//! OpenAPI security schemes of the API keys
+//!
+//! The keys are validated by the [`Auth`](super::Auth) middleware before a request reaches
+//! an endpoint, so the schemes only pick up the key it resolved.
+
+use std::fmt;
+
+use db::{ApiKey, ApiKeyScope};
+use poem::Request;
+use poem_openapi::SecurityScheme;
+
+/// API key sent in the `X-API-Key` header
+#[derive(SecurityScheme)]
+#[oai(
+    rename = "ApiKey",
+    ty = "api_key",
+    key_name = "X-API-Key",
+    key_in = "header",
+    checker = "resolved_api_key"
+)]
+pub struct ApiKeyAuth(pub ApiKey);
+
+impl ApiKeyAuth {
+    /// Whether the key may use endpoints requiring a scope, admin keys may use all of them
+    pub fn has_scope(&self, scope: ApiKeyScope) -> bool {
+        self.0.scope == scope || self.0.scope == ApiKeyScope::Admin
+    }
+}
+
+/// Returns the API key the middleware resolved for a request
+async fn resolved_api_key(req: &Request, _: poem_openapi::auth::ApiKey) -> Option<ApiKey> {
+    req.extensions().get::<ApiKey>().cloned()
+}
+
+/// Access to the public endpoints, with an API key for a higher rate limit or anonymous
+#[derive(SecurityScheme)]
+pub enum ApiAccess {
+    /// Request made with an API key
+    Key(ApiKeyAuth),
+    /// Request made without an API key, rate limited per client IP
+    #[oai(fallback)]
+    Anonymous,
+}
+
+impl fmt::Display for ApiAccess {
+    /// Names the client for logs, by the name and prefix of its key
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiAccess::Key(ApiKeyAuth(api_key)) => {
+                write!(f, "API key {} ({})", api_key.name, api_key.key_prefix)
+            }
+            ApiAccess::Anonymous => write!(f, "anonymous client"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn api_key(scope: ApiKeyScope) -> ApiKey {
+        ApiKey {
+            id: 1,
+            name: "bot".to_string(),
+            key_prefix: "rh_abcdefg".to_string(),
+            scope,
+            rate_limit: None,
+            request_count: 0,
+            last_used_at: None,
+            revoked_at: None,
+            created_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn admin_keys_have_every_scope() {
+        let read = ApiKeyAuth(api_key(ApiKeyScope::Read));
+        let admin = ApiKeyAuth(api_key(ApiKeyScope::Admin));
+
+        assert!(read.has_scope(ApiKeyScope::Read));
+        assert!(!read.has_scope(ApiKeyScope::Admin));
+        assert!(admin.has_scope(ApiKeyScope::Read));
+        assert!(admin.has_scope(ApiKeyScope::Admin));
+    }
+
+    #[test]
+    fn access_names_the_key_without_revealing_it() {
+        let key = ApiAccess::Key(ApiKeyAuth(api_key(ApiKeyScope::Read)));
+
+        assert_eq!(key.to_string(), "API key bot (rh_abcdefg)");
+        assert_eq!(ApiAccess::Anonymous.to_string(), "anonymous client");
+    }
+}
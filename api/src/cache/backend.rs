@@ -0,0 +1,120 @@
+//! Storage backends of the response cache
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use bytes::Bytes;
+use futures_util::future::{BoxFuture, FutureExt, ready};
+use poem::http::HeaderMap;
+
+/// Data a cached response depends on, used to invalidate it when the data changes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheTag {
+    /// Responses listing tokens
+    Tokens,
+    /// Responses listing players with their trading activity
+    Players,
+    /// Responses aggregating exchanges, such as market statistics and leaderboards
+    Markets,
+}
+
+/// Response stored in the cache
+#[derive(Debug, Clone)]
+pub struct CachedResponse {
+    /// Headers of the response
+    pub headers: HeaderMap,
+    /// Body of the response
+    pub body: Bytes,
+    /// Entity tag of the body
+    pub etag: String,
+    /// Time the response expires at
+    pub expires_at: Instant,
+}
+
+/// Storage of cached responses
+///
+/// Implementations must not return expired responses.
+pub trait CacheBackend: Send + Sync + 'static {
+    /// Returns the response cached under a key
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<CachedResponse>>;
+
+    /// Caches a response under a key, replacing any response cached under it
+    fn insert(&self, key: String, response: CachedResponse, tag: CacheTag) -> BoxFuture<'_, ()>;
+
+    /// Removes every response with a tag
+    fn invalidate(&self, tag: CacheTag) -> BoxFuture<'_, ()>;
+
+    /// Removes every response
+    fn clear(&self) -> BoxFuture<'_, ()>;
+}
+
+/// In-process cache backend holding up to a maximum number of responses
+#[derive(Debug)]
+pub struct MemoryBackend {
+    /// Maximum number of cached responses
+    max_entries: usize,
+    /// Cached responses with their tags, by key
+    entries: Mutex<HashMap<String, (CachedResponse, CacheTag)>>,
+}
+
+impl MemoryBackend {
+    /// Creates an empty cache holding up to `max_entries` responses
+    pub fn new(max_entries: usize) -> Self {
+        Self {
+            max_entries,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+}
+
+impl CacheBackend for MemoryBackend {
+    fn get<'a>(&'a self, key: &'a str) -> BoxFuture<'a, Option<CachedResponse>> {
+        let response = self
+            .entries
+            .lock()
+            .unwrap()
+            .get(key)
+            .filter(|(response, _)| response.expires_at > Instant::now())
+            .map(|(response, _)| response.clone());
+        ready(response).boxed()
+    }
+
+    fn insert(&self, key: String, response: CachedResponse, tag: CacheTag) -> BoxFuture<'_, ()> {
+        let mut entries = self.entries.lock().unwrap();
+
+        // Make room by dropping expired responses, then the one expiring first
+        if entries.len() >= self.max_entries && !entries.contains_key(&key) {
+            let now = Instant::now();
+            entries.retain(|_, (response, _)| response.expires_at > now);
+
+            if entries.len() >= self.max_entries {
+                let first = entries
+                    .iter()
+                    .min_by_key(|(_, (response, _))| response.expires_at)
+                    .map(|(key, _)| key.clone());
+                if let Some(first) = first {
+                    entries.remove(&first);
+                }
+            }
+        }
+
+        if self.max_entries > 0 {
+            entries.insert(key, (response, tag));
+        }
+        ready(()).boxed()
+    }
+
+    fn invalidate(&self, tag: CacheTag) -> BoxFuture<'_, ()> {
+        self.entries
+            .lock()
+            .unwrap()
+            .retain(|_, (_, entry_tag)| *entry_tag != tag);
+        ready(()).boxed()
+    }
+
+    fn clear(&self) -> BoxFuture<'_, ()> {
+        self.entries.lock().unwrap().clear();
+        ready(()).boxed()
+    }
+}
@@ -0,0 +1,420 @@
+//! Response cache of the hot read endpoints
+//!
+//! Successful GET responses of the endpoints listed in [`CACHE_RULES`] are cached per path
+//! and query for a fixed time, and dropped early when the processor inserts data they
+//! depend on: new exchanges invalidate player and market responses, new tokens the token
+//! list, and changed usernames, factions or guilds of players the player responses and the
+//! leaderboard. Changes are received via Postgres LISTEN/NOTIFY; whenever the listener loses
+//! its connection the cache is cleared, as changes may have been missed. Exchanges rewritten
+//! or deleted during reprocessing invalidate the same responses as new ones.
+//!
+//! Cached responses carry an `ETag`, so clients sending `If-None-Match` get a `304 Not
+//! Modified` without a body, and a `Cache-Control` header allowing clients to reuse them
+//! for a short time.
+
+use std::env;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use db::{ChangeListener, DatabaseChange, DbPool};
+use log::{info, warn};
+use poem::http::{HeaderValue, Method, StatusCode, header};
+use poem::{Body, Endpoint, IntoResponse, Middleware, Request, Response};
+use sha2::{Digest, Sha256};
+
+mod backend;
+
+pub use backend::{CacheBackend, CacheTag, CachedResponse, MemoryBackend};
+
+/// Delay before listening again after the listener failed
+const RECONNECT_DELAY: Duration = Duration::from_secs(5);
+
+/// Caching of the responses of an endpoint
+#[derive(Debug)]
+struct CacheRule {
+    /// Path of the endpoint
+    path: &'static str,
+    /// Time responses are cached for on the server
+    ttl: Duration,
+    /// Maximum time clients may reuse a response without revalidating it
+    max_age: Duration,
+    /// Data the responses depend on
+    tag: CacheTag,
+}
+
+/// Endpoints whose responses are cached
+const CACHE_RULES: &[CacheRule] = &[
+    CacheRule {
+        path: "/staratlas/tokens",
+        ttl: Duration::from_secs(300),
+        max_age: Duration::from_secs(60),
+        tag: CacheTag::Tokens,
+    },
+    CacheRule {
+        path: "/staratlas/player",
+        ttl: Duration::from_secs(60),
+        max_age: Duration::from_secs(10),
+        tag: CacheTag::Players,
+    },
+    CacheRule {
+        path: "/staratlas/markets",
+        ttl: Duration::from_secs(30),
+        max_age: Duration::from_secs(10),
+        tag: CacheTag::Markets,
+    },
+    CacheRule {
+        path: "/staratlas/markets/movers",
+        ttl: Duration::from_secs(30),
+        max_age: Duration::from_secs(10),
+        tag: CacheTag::Markets,
+    },
+    CacheRule {
+        path: "/staratlas/markets/most-traded",
+        ttl: Duration::from_secs(30),
+        max_age: Duration::from_secs(10),
+        tag: CacheTag::Markets,
+    },
+    CacheRule {
+        path: "/staratlas/leaderboard",
+        ttl: Duration::from_secs(60),
+        max_age: Duration::from_secs(10),
+        tag: CacheTag::Markets,
+    },
+];
+
+/// Middleware caching the responses of the hot read endpoints
+#[derive(Clone)]
+pub struct ResponseCache {
+    /// Storage of the cached responses
+    backend: Arc<dyn CacheBackend>,
+}
+
+impl ResponseCache {
+    /// Creates the cache configured by the environment and starts invalidating it
+    ///
+    /// `API_CACHE` selects the backend, `memory` (default) or `off`, and
+    /// `API_CACHE_MAX_ENTRIES` (default 1024) bounds the number of cached responses.
+    pub fn from_env(db_pool: DbPool) -> Self {
+        let max_entries = env::var("API_CACHE_MAX_ENTRIES")
+            .unwrap_or_else(|_| "1024".to_string())
+            .parse::<usize>()
+            .expect("API_CACHE_MAX_ENTRIES must be a number");
+
+        let max_entries = match env::var("API_CACHE").as_deref() {
+            Ok("off") => 0,
+            Ok("memory") | Err(_) => max_entries,
+            Ok(backend) => panic!("API_CACHE must be memory or off, not {}", backend),
+        };
+        info!("Caching up to {} responses in memory", max_entries);
+
+        Self::start(db_pool, Arc::new(MemoryBackend::new(max_entries)))
+    }
+
+    /// Creates the cache with a backend and starts invalidating it
+    pub fn start(db_pool: DbPool, backend: Arc<dyn CacheBackend>) -> Self {
+        tokio::spawn(Self::invalidate(db_pool, backend.clone()));
+        Self { backend }
+    }
+
+    /// Invalidates the cached responses whenever the data they depend on changes
+    async fn invalidate(db_pool: DbPool, backend: Arc<dyn CacheBackend>) {
+        loop {
+            let mut listener = match ChangeListener::connect(&db_pool).await {
+                Ok(listener) => listener,
+                Err(e) => {
+                    warn!("Failed to listen for cache invalidations: {}", e);
+                    backend.clear().await;
+                    tokio::time::sleep(RECONNECT_DELAY).await;
+                    continue;
+                }
+            };
+            // Changes made before the subscription may not be reflected yet
+            backend.clear().await;
+            info!("Listening for cache invalidations");
+
+            loop {
+                match listener.recv().await {
+                    Ok(Some(
+                        DatabaseChange::NewExchange(_) | DatabaseChange::ExchangesRewritten(_),
+                    )) => {
+                        backend.invalidate(CacheTag::Players).await;
+                        backend.invalidate(CacheTag::Markets).await;
+                    }
+                    Ok(Some(DatabaseChange::NewToken(_))) => {
+                        backend.invalidate(CacheTag::Tokens).await;
+                    }
+                    // Leaderboards grouped by faction or guild are cached with the markets
+                    Ok(Some(DatabaseChange::PlayersUpdated(_))) => {
+                        backend.invalidate(CacheTag::Players).await;
+                        backend.invalidate(CacheTag::Markets).await;
+                    }
+                    // The connection was lost and is re-established by the next receive
+                    Ok(None) => {
+                        warn!("Cache invalidation listener lost its connection");
+                        backend.clear().await;
+                    }
+                    Err(e) => {
+                        warn!("Failed to receive cache invalidation: {}", e);
+                        break;
+                    }
+                }
+            }
+
+            backend.clear().await;
+            tokio::time::sleep(RECONNECT_DELAY).await;
+        }
+    }
+}
+
+impl<E: Endpoint> Middleware<E> for ResponseCache {
+    type Output = ResponseCacheEndpoint<E>;
+
+    fn transform(&self, ep: E) -> Self::Output {
+        ResponseCacheEndpoint {
+            inner: ep,
+            backend: self.backend.clone(),
+        }
+    }
+}
+
+/// Endpoint wrapped by the [`ResponseCache`] middleware
+pub struct ResponseCacheEndpoint<E> {
+    inner: E,
+    backend: Arc<dyn CacheBackend>,
+}
+
+impl<E: Endpoint> Endpoint for ResponseCacheEndpoint<E> {
+    type Output = Response;
+
+    async fn call(&self, req: Request) -> poem::Result<Self::Output> {
+        let rule = CACHE_RULES
+            .iter()
+            .find(|rule| rule.path == req.uri().path());
+        let rule = match rule {
+            Some(rule) if req.method() == Method::GET => rule,
+            _ => return self.inner.call(req).await.map(IntoResponse::into_response),
+        };
+
+        let key = req
+            .uri()
+            .path_and_query()
+            .map(|path_and_query| path_and_query.as_str().to_string())
+            .unwrap_or_default();
+        let if_none_match = req.header(header::IF_NONE_MATCH).map(str::to_string);
+
+        if let Some(cached) = self.backend.get(&key).await {
+            return Ok(respond(&cached, if_none_match.as_deref(), rule, "HIT"));
+        }
+
+        let resp = self.inner.call(req).await?.into_response();
+        if resp.status() != StatusCode::OK {
+            return Ok(resp);
+        }
+
+        let (parts, body) = resp.into_parts();
+        let body = body.into_bytes().await?;
+        let cached = CachedResponse {
+            headers: parts.headers,
+            etag: etag(&body),
+            body,
+            expires_at: Instant::now() + rule.ttl,
+        };
+        self.backend.insert(key, cached.clone(), rule.tag).await;
+
+        Ok(respond(&cached, if_none_match.as_deref(), rule, "MISS"))
+    }
+}
+
+/// Returns the strong entity tag of a body
+fn etag(body: &[u8]) -> String {
+    format!("\"{}\"", &hex::encode(Sha256::digest(body))[..32])
+}
+
+/// Returns true if an `If-None-Match` header matches an entity tag
+fn etag_matches(if_none_match: &str, etag: &str) -> bool {
+    if_none_match.split(',').map(str::trim).any(|candidate| {
+        candidate == "*" || candidate.strip_prefix("W/").unwrap_or(candidate) == etag
+    })
+}
+
+/// Builds the response to a request from a cached response
+///
+/// Responds with `304 Not Modified` if the client already has the response.
+fn respond(
+    cached: &CachedResponse,
+    if_none_match: Option<&str>,
+    rule: &CacheRule,
+    status: &'static str,
+) -> Response {
+    let max_age = cached
+        .expires_at
+        .saturating_duration_since(Instant::now())
+        .min(rule.max_age);
+
+    let mut resp = if if_none_match.is_some_and(|value| etag_matches(value, &cached.etag)) {
+        Response::builder()
+            .status(StatusCode::NOT_MODIFIED)
+            .finish()
+    } else {
+        let mut resp = Response::builder().body(Body::from_bytes(cached.body.clone()));
+        resp.headers_mut().extend(cached.headers.clone());
+        resp
+    };
+
+    let headers = resp.headers_mut();
+    if let Ok(etag) = HeaderValue::from_str(&cached.etag) {
+        headers.insert(header::ETAG, etag);
+    }
+    headers.insert(
+        header::CACHE_CONTROL,
+        HeaderValue::from_str(&format!("public, max-age={}", max_age.as_secs())).unwrap(),
+    );
+    headers.insert("X-Cache", HeaderValue::from_static(status));
+    resp
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use poem::endpoint::make_sync;
+
+    /// Wraps an endpoint counting its calls in the cache, without invalidation
+    fn cached(calls: Arc<AtomicUsize>) -> ResponseCacheEndpoint<impl Endpoint> {
+        ResponseCacheEndpoint {
+            inner: make_sync(move |_| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                "tokens"
+            }),
+            backend: Arc::new(MemoryBackend::new(16)),
+        }
+    }
+
+    #[test]
+    fn etag_is_a_quoted_hash_of_the_body() {
+        let tag = etag(b"tokens");
+
+        assert_eq!(tag.len(), 34);
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+        assert_eq!(tag, etag(b"tokens"));
+        assert_ne!(tag, etag(b"players"));
+    }
+
+    #[test]
+    fn etag_matches_any_listed_tag() {
+        assert!(etag_matches("\"a\"", "\"a\""));
+        assert!(etag_matches("\"b\", \"a\"", "\"a\""));
+        assert!(etag_matches("\"b\",\"a\"", "\"a\""));
+        assert!(!etag_matches("\"b\", \"c\"", "\"a\""));
+        assert!(!etag_matches("a", "\"a\""));
+    }
+
+    #[test]
+    fn etag_matches_wildcards_and_weak_tags() {
+        assert!(etag_matches("*", "\"a\""));
+        assert!(etag_matches("W/\"a\"", "\"a\""));
+        assert!(etag_matches("\"b\", W/\"a\"", "\"a\""));
+        assert!(!etag_matches("W/\"b\"", "\"a\""));
+    }
+
+    #[tokio::test]
+    async fn get_responses_of_listed_endpoints_are_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let endpoint = cached(calls.clone());
+
+        let first = endpoint
+            .call(Request::builder().uri_str("/staratlas/tokens").finish())
+            .await
+            .unwrap();
+        let second = endpoint
+            .call(Request::builder().uri_str("/staratlas/tokens").finish())
+            .await
+            .unwrap();
+
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        assert_eq!(first.headers()["X-Cache"], "MISS");
+        assert_eq!(second.headers()["X-Cache"], "HIT");
+        assert_eq!(
+            first.headers()[header::ETAG],
+            second.headers()[header::ETAG]
+        );
+        assert_eq!(second.into_body().into_string().await.unwrap(), "tokens");
+    }
+
+    #[tokio::test]
+    async fn matching_if_none_match_is_not_modified() {
+        let endpoint = cached(Arc::new(AtomicUsize::new(0)));
+        let tag = etag(b"tokens");
+
+        let resp = endpoint
+            .call(
+                Request::builder()
+                    .uri_str("/staratlas/tokens")
+                    .header(header::IF_NONE_MATCH, format!("\"other\", W/{}", tag))
+                    .finish(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(resp.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(resp.headers()[header::ETAG], tag.as_str());
+        assert!(resp.into_body().into_bytes().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn other_methods_and_paths_are_not_cached() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let endpoint = cached(calls.clone());
+
+        for _ in 0..2 {
+            let post = endpoint
+                .call(
+                    Request::builder()
+                        .method(Method::POST)
+                        .uri_str("/staratlas/tokens")
+                        .finish(),
+                )
+                .await
+                .unwrap();
+            let head = endpoint
+                .call(
+                    Request::builder()
+                        .method(Method::HEAD)
+                        .uri_str("/staratlas/tokens")
+                        .finish(),
+                )
+                .await
+                .unwrap();
+            let unlisted = endpoint
+                .call(Request::builder().uri_str("/staratlas/exchanges").finish())
+                .await
+                .unwrap();
+            let nested = endpoint
+                .call(Request::builder().uri_str("/staratlas/tokens/1").finish())
+                .await
+                .unwrap();
+
+            assert!(post.headers().get("X-Cache").is_none());
+            assert!(head.headers().get("X-Cache").is_none());
+            assert!(unlisted.headers().get("X-Cache").is_none());
+            assert!(nested.headers().get("X-Cache").is_none());
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 8);
+    }
+
+    #[tokio::test]
+    async fn queries_are_cached_separately() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let endpoint = cached(calls.clone());
+
+        for uri in ["/staratlas/player?wallet=a", "/staratlas/player?wallet=b"] {
+            endpoint
+                .call(Request::builder().uri_str(uri).finish())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,153 @@
+//! Dataloaders batching the lookups of nested GraphQL fields
+//!
+//! Every loader collects the keys requested while resolving one level of a query and
+//! fetches them with a single database query.
+
+use std::collections::HashMap;
+
+use async_graphql::{Error, dataloader::Loader};
+use db::{DbPool, Exchange, Player, PlayerVolume, Signature, Token};
+
+use super::db_error;
+
+/// Loads players by ID
+pub struct PlayerLoader {
+    /// Database connection pool
+    pub db_pool: DbPool,
+}
+
+impl Loader<i32> for PlayerLoader {
+    type Value = Player;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, Player>, Error> {
+        let players = db::get_players_by_ids(&self.db_pool, ids)
+            .await
+            .map_err(db_error)?;
+        Ok(players
+            .into_iter()
+            .map(|player| (player.id, player))
+            .collect())
+    }
+}
+
+/// Loads tokens by ID
+pub struct TokenLoader {
+    /// Database connection pool
+    pub db_pool: DbPool,
+}
+
+impl Loader<i32> for TokenLoader {
+    type Value = Token;
+    type Error = Error;
+
+    async fn load(&self, ids: &[i32]) -> Result<HashMap<i32, Token>, Error> {
+        let tokens = db::get_tokens_by_ids(&self.db_pool, ids)
+            .await
+            .map_err(db_error)?;
+        Ok(tokens.into_iter().map(|token| (token.id, token)).collect())
+    }
+}
+
+/// Loads the traded volume per currency of players by player ID
+pub struct PlayerVolumesLoader {
+    /// Database connection pool
+    pub db_pool: DbPool,
+}
+
+impl Loader<i32> for PlayerVolumesLoader {
+    type Value = Vec<PlayerVolume>;
+    type Error = Error;
+
+    async fn load(&self, player_ids: &[i32]) -> Result<HashMap<i32, Vec<PlayerVolume>>, Error> {
+        let volumes = db::get_player_volumes_by_player_ids(&self.db_pool, player_ids)
+            .await
+            .map_err(db_error)?;
+
+        let mut volumes_by_player: HashMap<i32, Vec<PlayerVolume>> = player_ids
+            .iter()
+            .map(|player_id| (*player_id, Vec::new()))
+            .collect();
+        for volume in volumes {
+            volumes_by_player
+                .entry(volume.player_id)
+                .or_default()
+                .push(volume);
+        }
+        Ok(volumes_by_player)
+    }
+}
+
+/// Key of the newest exchanges of a player
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct PlayerExchangesKey {
+    /// ID of the player
+    pub player_id: i32,
+    /// Maximum number of exchanges to load
+    pub limit: i32,
+}
+
+/// Loads the newest exchanges of players, as buyer or seller
+pub struct PlayerExchangesLoader {
+    /// Database connection pool
+    pub db_pool: DbPool,
+}
+
+impl Loader<PlayerExchangesKey> for PlayerExchangesLoader {
+    type Value = Vec<Exchange>;
+    type Error = Error;
+
+    async fn load(
+        &self,
+        keys: &[PlayerExchangesKey],
+    ) -> Result<HashMap<PlayerExchangesKey, Vec<Exchange>>, Error> {
+        // Players requested with the same limit are loaded together
+        let mut player_ids_by_limit: HashMap<i32, Vec<i32>> = HashMap::new();
+        for key in keys {
+            player_ids_by_limit
+                .entry(key.limit)
+                .or_default()
+                .push(key.player_id);
+        }
+
+        let mut exchanges_by_key: HashMap<PlayerExchangesKey, Vec<Exchange>> =
+            keys.iter().map(|key| (*key, Vec::new())).collect();
+        for (limit, player_ids) in player_ids_by_limit {
+            let exchanges = db::get_exchanges_by_player_ids(&self.db_pool, &player_ids, limit)
+                .await
+                .map_err(db_error)?;
+            for player_exchange in exchanges {
+                let key = PlayerExchangesKey {
+                    player_id: player_exchange.player_id,
+                    limit,
+                };
+                exchanges_by_key
+                    .entry(key)
+                    .or_default()
+                    .push(player_exchange.exchange);
+            }
+        }
+        Ok(exchanges_by_key)
+    }
+}
+
+/// Loads indexed transaction signatures by value
+pub struct SignatureLoader {
+    /// Database connection pool
+    pub db_pool: DbPool,
+}
+
+impl Loader<String> for SignatureLoader {
+    type Value = Signature;
+    type Error = Error;
+
+    async fn load(&self, signatures: &[String]) -> Result<HashMap<String, Signature>, Error> {
+        let signature_records = db::get_signatures_by_values(&self.db_pool, signatures)
+            .await
+            .map_err(db_error)?;
+        Ok(signature_records
+            .into_iter()
+            .map(|signature| (signature.signature.clone(), signature))
+            .collect())
+    }
+}
@@ -0,0 +1,167 @@
+//! GraphQL schema over players, tokens, exchanges, indexers and signatures
+//!
+//! This module provides the graphql [GET, POST] endpoint as defined in the guidelines. Nested
+//! fields are resolved through dataloaders, so every level of a query costs one database
+//! query no matter how many parent objects it has. Queries are rejected before execution if
+//! their depth or complexity exceeds the configured limits; list fields count as `limit`
+//! times the complexity of their items.
+
+use std::env;
+
+use async_graphql::http::GraphiQLSource;
+use async_graphql::{
+    EmptyMutation, EmptySubscription, Error, Schema, SchemaBuilder, dataloader::DataLoader,
+};
+use db::DbPool;
+use log::warn;
+use poem::{IntoResponse, handler, web::Html};
+
+mod loaders;
+mod query;
+mod types;
+
+use loaders::{
+    PlayerExchangesLoader, PlayerLoader, PlayerVolumesLoader, SignatureLoader, TokenLoader,
+};
+use query::QueryRoot;
+
+/// Path the GraphQL endpoint is served at
+pub const GRAPHQL_PATH: &str = "/graphql";
+
+/// GraphQL schema of the API
+pub type ApiSchema = Schema<QueryRoot, EmptyMutation, EmptySubscription>;
+
+/// Builds the GraphQL schema with its dataloaders and query limits
+///
+/// The limits are read from `GRAPHQL_MAX_DEPTH` (default 10) and `GRAPHQL_MAX_COMPLEXITY`
+/// (default 5000).
+pub fn build_schema(db_pool: DbPool) -> ApiSchema {
+    let max_depth = env::var("GRAPHQL_MAX_DEPTH")
+        .unwrap_or_else(|_| "10".to_string())
+        .parse::<usize>()
+        .expect("GRAPHQL_MAX_DEPTH must be a number");
+    let max_complexity = env::var("GRAPHQL_MAX_COMPLEXITY")
+        .unwrap_or_else(|_| "5000".to_string())
+        .parse::<usize>()
+        .expect("GRAPHQL_MAX_COMPLEXITY must be a number");
+
+    schema_builder(max_depth, max_complexity)
+        .data(DataLoader::new(
+            PlayerLoader {
+                db_pool: db_pool.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            TokenLoader {
+                db_pool: db_pool.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            PlayerVolumesLoader {
+                db_pool: db_pool.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            PlayerExchangesLoader {
+                db_pool: db_pool.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(DataLoader::new(
+            SignatureLoader {
+                db_pool: db_pool.clone(),
+            },
+            tokio::spawn,
+        ))
+        .data(db_pool)
+        .finish()
+}
+
+/// Starts the schema with its query limits
+fn schema_builder(
+    max_depth: usize,
+    max_complexity: usize,
+) -> SchemaBuilder<QueryRoot, EmptyMutation, EmptySubscription> {
+    Schema::build(QueryRoot, EmptyMutation, EmptySubscription)
+        .limit_depth(max_depth)
+        .limit_complexity(max_complexity)
+}
+
+/// Serves the GraphiQL IDE for the GraphQL endpoint
+#[handler]
+pub fn graphiql() -> impl IntoResponse {
+    Html(GraphiQLSource::build().endpoint(GRAPHQL_PATH).finish())
+}
+
+/// Converts a database error into a GraphQL error without exposing its details
+fn db_error(e: db::DbError) -> Error {
+    warn!("GraphQL query failed: {}", e);
+    Error::new("Database error")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Schema with the default limits and no data, queries must be rejected before any
+    /// resolver runs
+    fn schema() -> ApiSchema {
+        schema_builder(10, 5000).finish()
+    }
+
+    async fn errors(query: &str) -> Vec<String> {
+        schema()
+            .execute(query)
+            .await
+            .errors
+            .into_iter()
+            .map(|error| error.message)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn limits_outside_their_range_are_rejected() {
+        for (query, error) in [
+            (
+                "{ players(limit: 501) { id } }",
+                "the value is 501, must be less than or equal to 500",
+            ),
+            (
+                "{ exchanges(limit: 501) { id } }",
+                "the value is 501, must be less than or equal to 500",
+            ),
+            (
+                "{ players(limit: 0) { id } }",
+                "the value is 0, must be greater than or equal to 1",
+            ),
+        ] {
+            assert_eq!(
+                errors(query).await,
+                [format!("Failed to parse \"Int\": {error}")],
+                "{query}"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn nested_lists_count_their_limits_towards_the_complexity() {
+        // 500 players with 100 trades each are far above the complexity limit
+        let errors = errors("{ players(limit: 500) { trades(limit: 100) { id } } }").await;
+
+        assert_eq!(errors, ["Query is too complex."]);
+    }
+
+    #[tokio::test]
+    async fn queries_deeper_than_the_limit_are_rejected() {
+        let errors = errors(
+            "{ exchange(id: 1) { buyer { trades(limit: 1) { seller { trades(limit: 1) { buyer { \
+             trades(limit: 1) { seller { trades(limit: 1) { buyer { id } } } } } } } } } } }",
+        )
+        .await;
+
+        assert_eq!(errors, ["Query is nested too deep."]);
+    }
+}
@@ -0,0 +1,189 @@
+//! Root query type of the GraphQL schema
+
+use std::str::FromStr;
+
+use async_graphql::{Context, Error, InputObject, Object, Result};
+use chrono::{DateTime, Utc};
+use db::{DbPool, ExchangeCursor, ExchangeFilter};
+
+use super::db_error;
+use super::types::{
+    ExchangeObject, IndexerObject, PlayerObject, ProgramSignatureObject, SignatureObject,
+    TokenObject, load_program_signatures,
+};
+
+/// Conditions the exchanges must match, all optional and combined with AND
+#[derive(Debug, Default, InputObject)]
+#[graphql(name = "ExchangeFilter")]
+pub struct ExchangeFilterInput {
+    /// Wallet address of the buyer
+    buyer: Option<String>,
+    /// Wallet address of the seller
+    seller: Option<String>,
+    /// Wallet address of either the buyer or the seller
+    wallet: Option<String>,
+    /// Mint address of the asset
+    asset: Option<String>,
+    /// Mint address of the pair
+    pair: Option<String>,
+    /// Side of the exchange (buy/sell)
+    side: Option<String>,
+    /// Earliest block time
+    from_timestamp: Option<DateTime<Utc>>,
+    /// Latest block time
+    to_timestamp: Option<DateTime<Utc>>,
+    /// Lowest slot
+    from_slot: Option<i32>,
+    /// Highest slot
+    to_slot: Option<i32>,
+    /// Lowest price
+    min_price: Option<f64>,
+    /// Highest price
+    max_price: Option<f64>,
+    /// Lowest volume
+    min_volume: Option<f64>,
+    /// Highest volume
+    max_volume: Option<f64>,
+}
+
+/// Root of every GraphQL query
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Player with the given wallet address
+    async fn player(&self, ctx: &Context<'_>, wallet: String) -> Result<Option<PlayerObject>> {
+        let player = db::get_player_by_wallet_address(ctx.data_unchecked::<DbPool>(), &wallet)
+            .await
+            .map_err(db_error)?;
+        Ok(player.map(PlayerObject))
+    }
+
+    /// Players ordered by ID
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn players(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 500))] limit: i32,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+    ) -> Result<Vec<PlayerObject>> {
+        let players = db::get_players(ctx.data_unchecked::<DbPool>(), limit, offset)
+            .await
+            .map_err(db_error)?;
+        Ok(players.into_iter().map(PlayerObject).collect())
+    }
+
+    /// Token with the given mint address
+    async fn token(&self, ctx: &Context<'_>, mint: String) -> Result<Option<TokenObject>> {
+        let token = db::get_token_by_mint(ctx.data_unchecked::<DbPool>(), &mint)
+            .await
+            .map_err(db_error)?;
+        Ok(token.map(TokenObject::from))
+    }
+
+    /// All tokens
+    async fn tokens(&self, ctx: &Context<'_>) -> Result<Vec<TokenObject>> {
+        let tokens = db::get_all_tokens(ctx.data_unchecked::<DbPool>())
+            .await
+            .map_err(db_error)?;
+        Ok(tokens.into_iter().map(TokenObject::from).collect())
+    }
+
+    /// Exchange with the given ID
+    async fn exchange(&self, ctx: &Context<'_>, id: i32) -> Result<Option<ExchangeObject>> {
+        let exchange = db::get_exchange_by_id(ctx.data_unchecked::<DbPool>(), id)
+            .await
+            .map_err(db_error)?;
+        Ok(exchange.map(ExchangeObject))
+    }
+
+    /// Exchanges matching the filter, newest first
+    ///
+    /// To page through the results, pass the `slot:signature:index` of the last exchange of a
+    /// page as `after`.
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn exchanges(
+        &self,
+        ctx: &Context<'_>,
+        filter: Option<ExchangeFilterInput>,
+        #[graphql(desc = "Cursor of the last exchange of the previous page")] after: Option<String>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 500))] limit: i32,
+    ) -> Result<Vec<ExchangeObject>> {
+        let filter = filter.unwrap_or_default();
+        let after = after
+            .as_deref()
+            .map(ExchangeCursor::from_str)
+            .transpose()
+            .map_err(Error::new)?;
+
+        let filter = ExchangeFilter {
+            buyer: filter.buyer,
+            seller: filter.seller,
+            wallet: filter.wallet,
+            asset_mint: filter.asset,
+            pair_mint: filter.pair,
+            side: filter.side,
+            from_timestamp: filter.from_timestamp,
+            to_timestamp: filter.to_timestamp,
+            from_slot: filter.from_slot,
+            to_slot: filter.to_slot,
+            min_price: filter.min_price,
+            max_price: filter.max_price,
+            min_volume: filter.min_volume,
+            max_volume: filter.max_volume,
+            after,
+            ..Default::default()
+        };
+
+        let exchanges =
+            db::get_exchanges_filtered(ctx.data_unchecked::<DbPool>(), &filter, limit, 0)
+                .await
+                .map_err(db_error)?;
+        Ok(exchanges.into_iter().map(ExchangeObject::from).collect())
+    }
+
+    /// All indexers
+    async fn indexers(&self, ctx: &Context<'_>) -> Result<Vec<IndexerObject>> {
+        let indexers = db::get_all_indexers(ctx.data_unchecked::<DbPool>())
+            .await
+            .map_err(db_error)?;
+        Ok(indexers.into_iter().map(IndexerObject).collect())
+    }
+
+    /// Indexer with the given name
+    async fn indexer(&self, ctx: &Context<'_>, name: String) -> Result<Option<IndexerObject>> {
+        let indexers = db::get_all_indexers(ctx.data_unchecked::<DbPool>())
+            .await
+            .map_err(db_error)?;
+        Ok(indexers
+            .into_iter()
+            .find(|indexer| indexer.name == name)
+            .map(IndexerObject))
+    }
+
+    /// Indexed transaction with the given signature
+    async fn signature(
+        &self,
+        ctx: &Context<'_>,
+        signature: String,
+    ) -> Result<Option<SignatureObject>> {
+        let signature = db::get_signature_by_value(ctx.data_unchecked::<DbPool>(), &signature)
+            .await
+            .map_err(db_error)?;
+        Ok(signature.map(SignatureObject::from))
+    }
+
+    /// Newest signatures of a program
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn program_signatures(
+        &self,
+        ctx: &Context<'_>,
+        program_id: String,
+        #[graphql(desc = "Only return processed (true) or unprocessed (false) signatures")]
+        processed: Option<bool>,
+        #[graphql(default = 100, validator(minimum = 1, maximum = 500))] limit: i32,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+    ) -> Result<Vec<ProgramSignatureObject>> {
+        load_program_signatures(ctx, &program_id, processed, limit, offset).await
+    }
+}
@@ -0,0 +1,438 @@
+//! GraphQL object types wrapping the database models
+
+use async_graphql::{ComplexObject, Context, Object, Result, SimpleObject, dataloader::DataLoader};
+use chrono::{DateTime, Utc};
+use db::{Exchange, Indexer, Player, PlayerVolume, ProgramSignature, Signature, Token};
+
+use super::db_error;
+use super::loaders::{
+    PlayerExchangesKey, PlayerExchangesLoader, PlayerLoader, PlayerVolumesLoader, SignatureLoader,
+    TokenLoader,
+};
+
+/// A Star Atlas player, identified by their wallet
+pub struct PlayerObject(pub Player);
+
+#[Object(name = "Player")]
+impl PlayerObject {
+    /// ID of the player
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    /// Wallet address of the player
+    async fn wallet_address(&self) -> &str {
+        &self.0.wallet_address
+    }
+
+    /// Username of the player (if available)
+    async fn username(&self) -> Option<&str> {
+        self.0.username.as_deref()
+    }
+
+    /// Faction of the player's profile (MUD, ONI or USTUR, if known)
+    async fn faction(&self) -> Option<&str> {
+        self.0.faction.as_deref()
+    }
+
+    /// Guild the player is a member of (if known)
+    async fn guild(&self) -> Option<&str> {
+        self.0.guild.as_deref()
+    }
+
+    /// Block time of the first exchange of the player
+    async fn first_seen(&self) -> DateTime<Utc> {
+        self.0.first_seen
+    }
+
+    /// Block time of the last exchange of the player
+    async fn last_active(&self) -> DateTime<Utc> {
+        self.0.last_active
+    }
+
+    /// Number of exchanges in which the player was the buyer
+    async fn buy_count(&self) -> i32 {
+        self.0.buy_count
+    }
+
+    /// Number of exchanges in which the player was the seller
+    async fn sell_count(&self) -> i32 {
+        self.0.sell_count
+    }
+
+    /// Number of distinct assets the player traded
+    async fn assets_traded(&self) -> i32 {
+        self.0.assets_traded
+    }
+
+    /// Traded volume of the player per currency
+    async fn volumes(&self, ctx: &Context<'_>) -> Result<Vec<PlayerVolumeObject>> {
+        let volumes = ctx
+            .data_unchecked::<DataLoader<PlayerVolumesLoader>>()
+            .load_one(self.0.id)
+            .await?
+            .unwrap_or_default();
+        Ok(volumes.into_iter().map(PlayerVolumeObject).collect())
+    }
+
+    /// Newest exchanges in which the player was the buyer or the seller
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn trades(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(default = 20, validator(minimum = 1, maximum = 100))] limit: i32,
+    ) -> Result<Vec<ExchangeObject>> {
+        let key = PlayerExchangesKey {
+            player_id: self.0.id,
+            limit,
+        };
+        let exchanges = ctx
+            .data_unchecked::<DataLoader<PlayerExchangesLoader>>()
+            .load_one(key)
+            .await?
+            .unwrap_or_default();
+        Ok(exchanges.into_iter().map(ExchangeObject).collect())
+    }
+}
+
+/// Traded volume of a player in one currency
+pub struct PlayerVolumeObject(pub PlayerVolume);
+
+#[Object(name = "PlayerVolume")]
+impl PlayerVolumeObject {
+    /// Mint address of the currency
+    async fn currency(&self) -> &str {
+        &self.0.currency_mint
+    }
+
+    /// Symbol of the currency (if available)
+    async fn currency_symbol(&self) -> Option<&str> {
+        self.0.currency_symbol.as_deref()
+    }
+
+    /// Volume the player bought for
+    async fn buy_volume(&self) -> f64 {
+        self.0.buy_volume
+    }
+
+    /// Volume the player sold for
+    async fn sell_volume(&self) -> f64 {
+        self.0.sell_volume
+    }
+}
+
+/// A token traded on the Galactic Marketplace
+#[derive(SimpleObject)]
+#[graphql(name = "Token")]
+pub struct TokenObject {
+    /// ID of the token
+    id: i32,
+    /// Mint address of the token
+    mint: String,
+    /// Name of the token (if available)
+    name: Option<String>,
+    /// Symbol of the token (if available)
+    symbol: Option<String>,
+    /// Type of the token (if available)
+    token_type: Option<String>,
+}
+
+impl From<Token> for TokenObject {
+    fn from(token: Token) -> Self {
+        Self {
+            id: token.id,
+            mint: token.mint,
+            name: token.name,
+            symbol: token.symbol,
+            token_type: token.token_type,
+        }
+    }
+}
+
+/// An exchange on the Galactic Marketplace
+pub struct ExchangeObject(pub Exchange);
+
+#[Object(name = "Exchange")]
+impl ExchangeObject {
+    /// ID of the exchange
+    async fn id(&self) -> i32 {
+        self.0.id
+    }
+
+    /// Slot of the transaction
+    async fn slot(&self) -> i32 {
+        self.0.slot
+    }
+
+    /// Signature of the transaction
+    async fn signature(&self) -> &str {
+        &self.0.signature
+    }
+
+    /// Index of the instruction within the transaction
+    async fn index(&self) -> i32 {
+        self.0.index
+    }
+
+    /// Block time of the transaction
+    async fn timestamp(&self) -> DateTime<Utc> {
+        self.0.timestamp
+    }
+
+    /// Side of the exchange (BUY or SELL)
+    async fn side(&self) -> &str {
+        &self.0.side
+    }
+
+    /// Price per unit
+    async fn price(&self) -> f64 {
+        self.0.price
+    }
+
+    /// Number of units exchanged
+    async fn size(&self) -> i32 {
+        self.0.size
+    }
+
+    /// Total volume of the exchange
+    async fn volume(&self) -> f64 {
+        self.0.volume
+    }
+
+    /// Marketplace fee of the exchange
+    async fn fee(&self) -> f64 {
+        self.0.fee
+    }
+
+    /// Buddy fee of the exchange
+    async fn buddy(&self) -> f64 {
+        self.0.buddy
+    }
+
+    /// Player who bought
+    async fn buyer(&self, ctx: &Context<'_>) -> Result<Option<PlayerObject>> {
+        load_player(ctx, self.0.buyer).await
+    }
+
+    /// Player who sold
+    async fn seller(&self, ctx: &Context<'_>) -> Result<Option<PlayerObject>> {
+        load_player(ctx, self.0.seller).await
+    }
+
+    /// Token that was traded
+    async fn asset(&self, ctx: &Context<'_>) -> Result<Option<TokenObject>> {
+        load_token(ctx, self.0.asset).await
+    }
+
+    /// Token that was paid with
+    async fn pair(&self, ctx: &Context<'_>) -> Result<Option<TokenObject>> {
+        load_token(ctx, self.0.pair).await
+    }
+
+    /// Indexed transaction of the exchange (if available)
+    async fn transaction(&self, ctx: &Context<'_>) -> Result<Option<SignatureObject>> {
+        load_signature(ctx, &self.0.signature).await
+    }
+}
+
+/// An indexer walking the signatures of a program
+pub struct IndexerObject(pub Indexer);
+
+#[Object(name = "Indexer")]
+impl IndexerObject {
+    /// Name of the indexer
+    async fn name(&self) -> &str {
+        &self.0.name
+    }
+
+    /// Direction the indexer walks in (UP or DOWN)
+    async fn direction(&self) -> String {
+        self.0.direction.to_string()
+    }
+
+    /// ID of the indexed program
+    async fn program_id(&self) -> &str {
+        &self.0.program_id
+    }
+
+    /// Last signature the indexer fetched (if any)
+    async fn signature(&self) -> Option<&str> {
+        self.0.signature.as_deref()
+    }
+
+    /// Slot of the last signature (if any)
+    async fn block(&self) -> Option<i64> {
+        self.0.block
+    }
+
+    /// Block time of the last signature (if any)
+    async fn timestamp(&self) -> Option<DateTime<Utc>> {
+        self.0.timestamp
+    }
+
+    /// Whether the indexer reached the end of its direction
+    async fn finished(&self) -> Option<bool> {
+        self.0.finished
+    }
+
+    /// Number of signatures fetched per request
+    async fn fetch_limit(&self) -> i32 {
+        self.0.fetch_limit
+    }
+
+    /// Newest signatures of the indexed program
+    #[graphql(complexity = "limit as usize * child_complexity")]
+    async fn signatures(
+        &self,
+        ctx: &Context<'_>,
+        #[graphql(desc = "Only return processed (true) or unprocessed (false) signatures")]
+        processed: Option<bool>,
+        #[graphql(default = 20, validator(minimum = 1, maximum = 100))] limit: i32,
+        #[graphql(default = 0, validator(minimum = 0))] offset: i32,
+    ) -> Result<Vec<ProgramSignatureObject>> {
+        load_program_signatures(ctx, &self.0.program_id, processed, limit, offset).await
+    }
+}
+
+/// A signature of a program and its processing state
+pub struct ProgramSignatureObject(pub ProgramSignature);
+
+#[Object(name = "ProgramSignature")]
+impl ProgramSignatureObject {
+    /// ID of the program
+    async fn program_id(&self) -> &str {
+        &self.0.program_id
+    }
+
+    /// Signature of the transaction
+    async fn signature(&self) -> &str {
+        &self.0.signature
+    }
+
+    /// Slot of the transaction
+    async fn slot(&self) -> i64 {
+        self.0.slot
+    }
+
+    /// Whether the processor has processed the transaction
+    async fn processed(&self) -> bool {
+        self.0.processed
+    }
+
+    /// Indexed transaction of the signature (if available)
+    async fn transaction(&self, ctx: &Context<'_>) -> Result<Option<SignatureObject>> {
+        load_signature(ctx, &self.0.signature).await
+    }
+}
+
+/// An indexed transaction signature
+#[derive(SimpleObject)]
+#[graphql(name = "Signature", complex)]
+pub struct SignatureObject {
+    /// Signature of the transaction
+    signature: String,
+    /// Slot of the transaction
+    slot: i64,
+    /// Block time of the transaction
+    timestamp: DateTime<Utc>,
+}
+
+#[ComplexObject]
+impl SignatureObject {
+    /// Exchanges the processor derived from the transaction
+    async fn exchanges(&self, ctx: &Context<'_>) -> Result<Vec<ExchangeObject>> {
+        let db_pool = ctx.data_unchecked::<db::DbPool>();
+        let filter = db::ExchangeFilter {
+            signature: Some(self.signature.clone()),
+            ..Default::default()
+        };
+        let exchanges = db::get_exchanges_filtered(db_pool, &filter, 100, 0)
+            .await
+            .map_err(db_error)?;
+        Ok(exchanges.into_iter().map(ExchangeObject::from).collect())
+    }
+}
+
+impl From<Signature> for SignatureObject {
+    fn from(signature: Signature) -> Self {
+        Self {
+            signature: signature.signature,
+            slot: signature.slot,
+            timestamp: signature.timestamp,
+        }
+    }
+}
+
+impl From<db::ExchangeDetailed> for ExchangeObject {
+    fn from(exchange: db::ExchangeDetailed) -> Self {
+        Self(Exchange {
+            id: exchange.id,
+            slot: exchange.slot,
+            signature: exchange.signature,
+            index: exchange.index,
+            timestamp: exchange.timestamp,
+            side: exchange.side,
+            buyer: exchange.buyer_id,
+            seller: exchange.seller_id,
+            asset: exchange.asset_id,
+            pair: exchange.pair_id,
+            price: exchange.price,
+            size: exchange.size,
+            volume: exchange.volume,
+            fee: exchange.fee,
+            buddy: exchange.buddy,
+            processor_version: exchange.processor_version,
+        })
+    }
+}
+
+/// Loads a player through the batching loader
+async fn load_player(ctx: &Context<'_>, id: i32) -> Result<Option<PlayerObject>> {
+    let player = ctx
+        .data_unchecked::<DataLoader<PlayerLoader>>()
+        .load_one(id)
+        .await?;
+    Ok(player.map(PlayerObject))
+}
+
+/// Loads a token through the batching loader
+async fn load_token(ctx: &Context<'_>, id: i32) -> Result<Option<TokenObject>> {
+    let token = ctx
+        .data_unchecked::<DataLoader<TokenLoader>>()
+        .load_one(id)
+        .await?;
+    Ok(token.map(TokenObject::from))
+}
+
+/// Loads an indexed signature through the batching loader
+async fn load_signature(ctx: &Context<'_>, signature: &str) -> Result<Option<SignatureObject>> {
+    let signature = ctx
+        .data_unchecked::<DataLoader<SignatureLoader>>()
+        .load_one(signature.to_string())
+        .await?;
+    Ok(signature.map(SignatureObject::from))
+}
+
+/// Loads a page of the signatures of a program, newest first
+pub async fn load_program_signatures(
+    ctx: &Context<'_>,
+    program_id: &str,
+    processed: Option<bool>,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<ProgramSignatureObject>> {
+    let db_pool = ctx.data_unchecked::<db::DbPool>();
+    let program_signatures = db::get_program_signatures_page(
+        db_pool,
+        &program_id.to_string(),
+        processed,
+        limit as i64,
+        offset as i64,
+    )
+    .await
+    .map_err(db_error)?;
+    Ok(program_signatures
+        .into_iter()
+        .map(ProgramSignatureObject)
+        .collect())
+}
@@ -7,16 +7,28 @@ use std::env;
 use std::net::SocketAddr;
 
 use anyhow::Result;
-use db::establish_connection;
+use async_graphql_poem::GraphQL;
+use db::{ApiKeyScope, NewApiKey, establish_connection};
 use dotenv::dotenv;
 use log::{info, warn};
-use poem::{EndpointExt, Route, Server, listener::TcpListener, middleware::Cors};
-use poem_openapi::OpenApiService;
+use poem::{EndpointExt, Route, Server, get, listener::TcpListener, middleware::Cors};
+use poem_openapi::{ExtraHeader, OpenApiService};
 
 mod api;
+mod auth;
+mod cache;
+#[allow(dead_code)]
 mod error;
+mod graphql;
+mod portfolio;
 
-use api::{IndexerApi, StarAtlasApi};
+use api::{
+    AlertsApi, ApiKeysApi, CraftingApi, ExchangeFeed, ExportApi, FeedApi, IndexerApi,
+    LeaderboardApi, MarketApi, ProgramsApi, ScoreApi, StarAtlasApi, exchanges_ws,
+};
+use auth::{Auth, AuthConfig, api_key_prefix, hash_api_key};
+use cache::ResponseCache;
+use graphql::{GRAPHQL_PATH, build_schema, graphiql};
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -43,31 +55,114 @@ async fn main() -> Result<()> {
     // Establish database connection
     let db_pool = establish_connection().await?;
 
+    // Provision the admin API key from the configuration
+    if let Ok(admin_key) = env::var("API_ADMIN_KEY") {
+        let new_api_key = NewApiKey {
+            name: "API_ADMIN_KEY".to_string(),
+            key_prefix: api_key_prefix(&admin_key),
+            key_hash: hash_api_key(&admin_key),
+            scope: ApiKeyScope::Admin,
+            rate_limit: None,
+        };
+        let api_key = db::ensure_api_key(&db_pool, &new_api_key).await?;
+        info!("Admin API key provisioned with ID {}", api_key.id);
+    }
+
+    // Start authenticating API keys
+    let auth = Auth::start(db_pool.clone(), AuthConfig::from_env());
+
+    // Start invalidating the response cache
+    let cache = ResponseCache::from_env(db_pool.clone());
+
+    // Start the live exchange feed
+    let exchange_feed = ExchangeFeed::start(db_pool.clone());
+
     // Create API instances
+    let alerts_api = AlertsApi::new(db_pool.clone(), alerts::target::allow_private_targets());
+
+    let api_keys_api = ApiKeysApi::new(db_pool.clone());
+
+    let crafting_api = CraftingApi::new(db_pool.clone());
+
+    // Exports hold a connection for the whole download, so they get their own pool
+    let export_limit = env::var("API_EXPORT_MAX_CONCURRENT")
+        .unwrap_or_else(|_| "2".to_string())
+        .parse::<u32>()
+        .expect("API_EXPORT_MAX_CONCURRENT must be a number")
+        .max(1);
+    let export_pool = db::establish_connection_with_max_connections(export_limit).await?;
+    let export_api = ExportApi::new(export_pool, export_limit as usize);
+
+    let feed_api = FeedApi::new(exchange_feed.clone());
+
     let indexer_api = IndexerApi::new(db_pool.clone());
 
-    let staratlas_api = StarAtlasApi::new(db_pool);
+    let leaderboard_api = LeaderboardApi::new(db_pool.clone());
+
+    let market_api = MarketApi::new(db_pool.clone());
+
+    let programs_api = ProgramsApi::new(db_pool.clone());
+
+    let score_api = ScoreApi::new(db_pool.clone());
+
+    let staratlas_api = StarAtlasApi::new(db_pool.clone());
+
+    // Create GraphQL schema
+    let graphql_schema = build_schema(db_pool);
 
     // Create OpenAPI service
     let api_service = OpenApiService::new(
-        (indexer_api, staratlas_api),
+        (
+            alerts_api,
+            api_keys_api,
+            crafting_api,
+            export_api,
+            feed_api,
+            indexer_api,
+            leaderboard_api,
+            market_api,
+            programs_api,
+            score_api,
+            staratlas_api,
+        ),
         "Rogue Data Hub API",
         env!("CARGO_PKG_VERSION"),
     )
-    .server(format!("http://{}:{}", host, port));
+    .server(format!("http://{}:{}", host, port))
+    .extra_response_header::<u32, _>(
+        ExtraHeader::new("X-RateLimit-Limit")
+            .description("Requests per minute the client may make"),
+    )
+    .extra_response_header::<u32, _>(
+        ExtraHeader::new("X-RateLimit-Remaining")
+            .description("Requests the client may still make right now"),
+    );
 
     // Get the OpenAPI specification
     let spec = api_service.spec();
 
     // Create the API routes
     let ui = api_service.swagger_ui();
-    let spec_json = api_service.spec_endpoint();
+    let _spec_json = api_service.spec_endpoint();
+
+    // Set up the routes, the documentation is served without authentication
+    let api_routes = Route::new()
+        .at(
+            "/staratlas/exchanges/ws",
+            get(exchanges_ws).data(exchange_feed),
+        )
+        .at(
+            GRAPHQL_PATH,
+            get(graphiql).post(GraphQL::new(graphql_schema)),
+        )
+        .nest("/", api_service)
+        .with(cache)
+        .with(auth);
 
-    // Set up the routes
     let app = Route::new()
-        .nest("/", api_service)
         .nest("/doc", ui)
         .at("/spec", poem::endpoint::make_sync(move |_| spec.clone()))
+        .nest("/", api_routes)
         .with(Cors::new());
 
     // Start the server
@@ -0,0 +1,265 @@
+//! Portfolio and PnL calculation for a player's trades
+//!
+//! Trades are replayed in the order they happened. Every buy opens a lot at its cost per
+//! unit, every sell closes the oldest open lots of the same market first (FIFO) and
+//! realizes the difference between the proceeds and the cost of the closed lots.
+//!
+//! The buyer of an exchange pays the full price, including the marketplace fee and the
+//! buddy fee, while the seller receives the volume without the marketplace fee.
+
+use std::collections::{BTreeMap, VecDeque};
+
+use db::PlayerTrade;
+
+/// Summary of a player's trading in one market (asset and pair)
+#[derive(Debug, Clone)]
+pub struct MarketSummary {
+    /// Asset mint address
+    pub asset_mint: String,
+    /// Asset symbol (if available)
+    pub asset_symbol: Option<String>,
+    /// Pair mint address
+    pub pair_mint: String,
+    /// Pair symbol (if available)
+    pub pair_symbol: Option<String>,
+    /// Units bought
+    pub bought: i64,
+    /// Units sold
+    pub sold: i64,
+    /// Amount of the pair currency spent on buys, including fees
+    pub spent: f64,
+    /// Amount of the pair currency received from sells, after fees
+    pub received: f64,
+    /// Marketplace fees paid
+    pub fees_paid: f64,
+    /// Buddy fees paid
+    pub buddy_paid: f64,
+    /// Realized profit or loss of the sold units that were bought in this market
+    pub realized_pnl: f64,
+    /// Sold units without a matching buy in this market, which have no cost basis
+    pub unmatched_sold: i64,
+    /// Cost basis of the units still held
+    pub open_cost: f64,
+    /// Open lots as `(units, cost per unit)`, oldest first
+    lots: VecDeque<(i64, f64)>,
+}
+
+impl MarketSummary {
+    fn new(trade: &PlayerTrade) -> Self {
+        Self {
+            asset_mint: trade.asset_mint.clone(),
+            asset_symbol: trade.asset_symbol.clone(),
+            pair_mint: trade.pair_mint.clone(),
+            pair_symbol: trade.pair_symbol.clone(),
+            bought: 0,
+            sold: 0,
+            spent: 0.0,
+            received: 0.0,
+            fees_paid: 0.0,
+            buddy_paid: 0.0,
+            realized_pnl: 0.0,
+            unmatched_sold: 0,
+            open_cost: 0.0,
+            lots: VecDeque::new(),
+        }
+    }
+
+    /// Average cost per unit bought
+    pub fn average_buy_price(&self) -> Option<f64> {
+        (self.bought > 0).then(|| self.spent / self.bought as f64)
+    }
+
+    /// Units bought in this market that are still held
+    pub fn open_units(&self) -> i64 {
+        self.lots.iter().map(|(units, _)| units).sum()
+    }
+
+    /// Average proceeds per unit sold
+    pub fn average_sell_price(&self) -> Option<f64> {
+        (self.sold > 0).then(|| self.received / self.sold as f64)
+    }
+
+    fn buy(&mut self, trade: &PlayerTrade) {
+        let size = i64::from(trade.size);
+        let cost = trade.price * trade.size as f64;
+
+        self.bought += size;
+        self.spent += cost;
+        self.fees_paid += trade.fee;
+        self.buddy_paid += trade.buddy;
+
+        if size > 0 {
+            self.lots.push_back((size, cost / size as f64));
+            self.open_cost += cost;
+        }
+    }
+
+    fn sell(&mut self, trade: &PlayerTrade) {
+        let size = i64::from(trade.size);
+        let proceeds = trade.volume - trade.fee;
+
+        self.sold += size;
+        self.received += proceeds;
+
+        if size <= 0 {
+            return;
+        }
+
+        let proceeds_per_unit = proceeds / size as f64;
+        let mut remaining = size;
+        while remaining > 0 {
+            let Some(lot) = self.lots.front_mut() else {
+                self.unmatched_sold += remaining;
+                break;
+            };
+
+            let matched = remaining.min(lot.0);
+            self.realized_pnl += (proceeds_per_unit - lot.1) * matched as f64;
+            self.open_cost -= lot.1 * matched as f64;
+            lot.0 -= matched;
+            remaining -= matched;
+
+            if lot.0 == 0 {
+                self.lots.pop_front();
+            }
+        }
+    }
+}
+
+/// Replays the trades of a player and summarizes them per market
+///
+/// # Arguments
+/// * `trades` - The player's trades, oldest first
+///
+/// # Returns
+/// One summary per market, ordered by asset and pair mint
+pub fn summarize_markets(trades: &[PlayerTrade]) -> Vec<MarketSummary> {
+    let mut markets: BTreeMap<(&str, &str), MarketSummary> = BTreeMap::new();
+
+    for trade in trades {
+        let market = markets
+            .entry((&trade.asset_mint, &trade.pair_mint))
+            .or_insert_with(|| MarketSummary::new(trade));
+
+        if trade.role == "BUY" {
+            market.buy(trade);
+        } else {
+            market.sell(trade);
+        }
+    }
+
+    markets.into_values().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    const ATLAS: &str = "ATLASXmbPQxBUYbxPsV97usA3fPQYEqzQBUHgiFCUsXx";
+    const USDC: &str = "EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v";
+    const SHIP: &str = "Fw8PqtznYtg4swMk7Yjj89Tsj23u5CJLfW5Bk8ro4G1s";
+
+    fn trade(role: &str, pair: &str, price: f64, size: i32, fee: f64, buddy: f64) -> PlayerTrade {
+        PlayerTrade {
+            id: 0,
+            slot: 0,
+            signature: String::new(),
+            index: 0,
+            timestamp: Utc.timestamp_opt(0, 0).unwrap(),
+            side: role.to_string(),
+            role: role.to_string(),
+            counterparty: String::new(),
+            asset_id: 1,
+            asset_mint: SHIP.to_string(),
+            asset_symbol: None,
+            pair_id: 2,
+            pair_mint: pair.to_string(),
+            pair_symbol: None,
+            price,
+            size,
+            volume: price * size as f64 - buddy,
+            fee,
+            buddy,
+        }
+    }
+
+    #[test]
+    fn sell_consumes_oldest_lots_first() {
+        let trades = [
+            trade("BUY", ATLAS, 2.0, 10, 0.0, 0.0),
+            trade("BUY", ATLAS, 4.0, 5, 0.0, 0.0),
+            trade("SELL", ATLAS, 5.0, 12, 0.0, 0.0),
+        ];
+
+        let markets = summarize_markets(&trades);
+        assert_eq!(markets.len(), 1);
+        let market = &markets[0];
+
+        // 10 units bought at 2 and 2 of the units bought at 4 are sold at 5
+        assert_eq!(market.realized_pnl, 10.0 * 3.0 + 2.0 * 1.0);
+        assert_eq!(market.open_units(), 3);
+        assert_eq!(market.open_cost, 12.0);
+        assert_eq!(market.unmatched_sold, 0);
+        assert_eq!(market.average_buy_price(), Some(40.0 / 15.0));
+        assert_eq!(market.average_sell_price(), Some(5.0));
+    }
+
+    #[test]
+    fn selling_more_than_bought_leaves_units_unmatched() {
+        let trades = [
+            trade("BUY", ATLAS, 1.0, 2, 0.0, 0.0),
+            trade("SELL", ATLAS, 2.0, 5, 0.0, 0.0),
+        ];
+
+        let market = &summarize_markets(&trades)[0];
+
+        assert_eq!(market.realized_pnl, 2.0);
+        assert_eq!(market.unmatched_sold, 3);
+        assert_eq!(market.open_units(), 0);
+        assert_eq!(market.open_cost, 0.0);
+        assert_eq!(market.sold, 5);
+    }
+
+    #[test]
+    fn buyer_pays_fees_and_seller_receives_volume_after_fee() {
+        let trades = [
+            trade("BUY", ATLAS, 10.0, 4, 0.5, 0.25),
+            trade("SELL", ATLAS, 12.0, 4, 1.0, 0.0),
+        ];
+
+        let market = &summarize_markets(&trades)[0];
+
+        // The buy price includes the marketplace and buddy fee
+        assert_eq!(market.spent, 40.0);
+        assert_eq!(market.fees_paid, 0.5);
+        assert_eq!(market.buddy_paid, 0.25);
+        // The seller's marketplace fee is taken from the proceeds, not counted as paid
+        assert_eq!(market.received, 48.0 - 1.0);
+        assert_eq!(market.realized_pnl, 47.0 - 40.0);
+    }
+
+    #[test]
+    fn markets_are_kept_apart_per_pair() {
+        let trades = [
+            trade("BUY", ATLAS, 1.0, 3, 0.0, 0.0),
+            trade("BUY", USDC, 2.0, 1, 0.0, 0.0),
+            trade("SELL", USDC, 3.0, 2, 0.0, 0.0),
+        ];
+
+        let markets = summarize_markets(&trades);
+        assert_eq!(markets.len(), 2);
+
+        // Ordered by asset and pair mint
+        let (atlas, usdc) = (&markets[0], &markets[1]);
+        assert_eq!(atlas.pair_mint, ATLAS);
+        assert_eq!(usdc.pair_mint, USDC);
+
+        // Units bought for ATLAS do not cover the USDC sell
+        assert_eq!(atlas.open_units(), 3);
+        assert_eq!(atlas.realized_pnl, 0.0);
+        assert_eq!(usdc.realized_pnl, 1.0);
+        assert_eq!(usdc.unmatched_sold, 1);
+        assert_eq!(usdc.open_units(), 0);
+    }
+}
@@ -0,0 +1,7 @@
+ALTER TABLE market.exchanges
+    ADD COLUMN IF NOT EXISTS processor_version INTEGER NOT NULL DEFAULT 0;
+
+CREATE INDEX IF NOT EXISTS idx_marketplace_exchanges_processor_version ON market.exchanges (processor_version);
+
+CREATE INDEX IF NOT EXISTS idx_signatures_slot ON indexer.signatures (slot);
+CREATE INDEX IF NOT EXISTS idx_signatures_timestamp ON indexer.signatures (timestamp);
@@ -0,0 +1,7 @@
+ALTER TABLE indexer.program_signatures
+    ADD COLUMN IF NOT EXISTS claimed_by VARCHAR(64),
+    ADD COLUMN IF NOT EXISTS claimed_until TIMESTAMPTZ;
+
+CREATE INDEX IF NOT EXISTS idx_program_signatures_unprocessed
+    ON indexer.program_signatures (program_id, claimed_until)
+    WHERE processed = false;
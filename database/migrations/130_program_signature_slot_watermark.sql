@@ -0,0 +1,28 @@
+ALTER TABLE indexer.program_signatures
+    ADD COLUMN IF NOT EXISTS slot BIGINT;
+
+UPDATE indexer.program_signatures ps
+SET slot = s.slot
+FROM indexer.signatures s
+WHERE ps.signature = s.signature
+  AND ps.slot IS NULL;
+
+ALTER TABLE indexer.program_signatures
+    ALTER COLUMN slot SET NOT NULL;
+
+DROP INDEX IF EXISTS indexer.idx_program_signatures_unprocessed;
+
+CREATE INDEX IF NOT EXISTS idx_program_signatures_unprocessed
+    ON indexer.program_signatures (program_id, slot, signature)
+    WHERE processed = false;
+
+CREATE INDEX IF NOT EXISTS idx_program_signatures_program_slot
+    ON indexer.program_signatures (program_id, slot);
+
+
+CREATE TABLE IF NOT EXISTS indexer.program_watermarks (
+    program_id VARCHAR(50) NOT NULL PRIMARY KEY REFERENCES indexer.programs(program_id) ON DELETE CASCADE,
+    slot BIGINT,
+    timestamp TIMESTAMPTZ,
+    updated_at TIMESTAMPTZ NOT NULL
+);
@@ -0,0 +1,28 @@
+-- Merge tokens created more than once for the same mint into the one with the lowest id, so
+-- the unique index on the mint in 140 can be created. Numbered before 140 on purpose: sqlx
+-- applies it first on databases that have not run 140 yet, and on the others the unique index
+-- already rules out duplicates and this is a no-op.
+WITH duplicates AS (
+    SELECT id, MIN(id) OVER (PARTITION BY mint) AS keep_id
+    FROM staratlas.tokens
+)
+UPDATE market.exchanges e
+SET asset = d.keep_id
+FROM duplicates d
+WHERE e.asset = d.id
+  AND d.id <> d.keep_id;
+
+WITH duplicates AS (
+    SELECT id, MIN(id) OVER (PARTITION BY mint) AS keep_id
+    FROM staratlas.tokens
+)
+UPDATE market.exchanges e
+SET pair = d.keep_id
+FROM duplicates d
+WHERE e.pair = d.id
+  AND d.id <> d.keep_id;
+
+DELETE FROM staratlas.tokens t
+USING staratlas.tokens keep
+WHERE keep.mint = t.mint
+  AND keep.id < t.id;
@@ -0,0 +1,70 @@
+CREATE UNIQUE INDEX IF NOT EXISTS idx_tokens_mint ON staratlas.tokens (mint);
+
+ALTER TABLE staratlas.players
+    ADD COLUMN IF NOT EXISTS buy_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS sell_count INTEGER NOT NULL DEFAULT 0,
+    ADD COLUMN IF NOT EXISTS assets_traded INTEGER NOT NULL DEFAULT 0;
+
+
+CREATE TABLE IF NOT EXISTS staratlas.player_volumes (
+    player_id   INTEGER REFERENCES staratlas.players (id) ON DELETE CASCADE NOT NULL,
+    currency_id INTEGER REFERENCES staratlas.tokens (id) ON DELETE CASCADE  NOT NULL,
+    buy_volume  DOUBLE PRECISION NOT NULL DEFAULT 0,
+    sell_volume DOUBLE PRECISION NOT NULL DEFAULT 0,
+    PRIMARY KEY (player_id, currency_id)
+);
+
+
+CREATE TABLE IF NOT EXISTS staratlas.player_assets (
+    player_id   INTEGER REFERENCES staratlas.players (id) ON DELETE CASCADE NOT NULL,
+    asset_id    INTEGER REFERENCES staratlas.tokens (id) ON DELETE CASCADE  NOT NULL,
+    trade_count INTEGER NOT NULL,
+    PRIMARY KEY (player_id, asset_id)
+);
+
+
+-- Backfill the stats from the exchanges processed so far
+WITH trades AS (
+    SELECT buyer AS player_id, 'BUY' AS role, asset, pair, volume, timestamp FROM market.exchanges
+    UNION ALL
+    SELECT seller AS player_id, 'SELL' AS role, asset, pair, volume, timestamp FROM market.exchanges
+)
+INSERT INTO staratlas.player_assets (player_id, asset_id, trade_count)
+SELECT player_id, asset, COUNT(*)
+FROM trades
+GROUP BY player_id, asset
+ON CONFLICT DO NOTHING;
+
+WITH trades AS (
+    SELECT buyer AS player_id, volume AS buy_volume, 0 AS sell_volume, pair FROM market.exchanges
+    UNION ALL
+    SELECT seller AS player_id, 0 AS buy_volume, volume AS sell_volume, pair FROM market.exchanges
+)
+INSERT INTO staratlas.player_volumes (player_id, currency_id, buy_volume, sell_volume)
+SELECT player_id, pair, SUM(buy_volume), SUM(sell_volume)
+FROM trades
+GROUP BY player_id, pair
+ON CONFLICT DO NOTHING;
+
+WITH trades AS (
+    SELECT buyer AS player_id, 1 AS buys, 0 AS sells, timestamp FROM market.exchanges
+    UNION ALL
+    SELECT seller AS player_id, 0 AS buys, 1 AS sells, timestamp FROM market.exchanges
+),
+stats AS (
+    SELECT player_id,
+           SUM(buys)      AS buy_count,
+           SUM(sells)     AS sell_count,
+           MIN(timestamp) AS first_seen,
+           MAX(timestamp) AS last_active
+    FROM trades
+    GROUP BY player_id
+)
+UPDATE staratlas.players p
+SET buy_count     = stats.buy_count,
+    sell_count    = stats.sell_count,
+    first_seen    = stats.first_seen,
+    last_active   = stats.last_active,
+    assets_traded = (SELECT COUNT(*) FROM staratlas.player_assets pa WHERE pa.player_id = p.id)
+FROM stats
+WHERE p.id = stats.player_id;
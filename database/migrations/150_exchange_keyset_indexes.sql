@@ -0,0 +1,5 @@
+
+CREATE INDEX IF NOT EXISTS idx_marketplace_exchanges_keyset ON market.exchanges (slot, signature, index);
+CREATE INDEX IF NOT EXISTS idx_marketplace_exchanges_buyer_keyset ON market.exchanges (buyer, slot, signature, index);
+CREATE INDEX IF NOT EXISTS idx_marketplace_exchanges_seller_keyset ON market.exchanges (seller, slot, signature, index);
+CREATE INDEX IF NOT EXISTS idx_marketplace_exchanges_asset_pair_keyset ON market.exchanges (asset, pair, slot, signature, index);
@@ -0,0 +1,72 @@
+
+CREATE TABLE IF NOT EXISTS market.markets (
+    asset          INTEGER REFERENCES staratlas.tokens (id) ON DELETE CASCADE NOT NULL,
+    pair           INTEGER REFERENCES staratlas.tokens (id) ON DELETE CASCADE NOT NULL,
+    last_price     DOUBLE PRECISION,
+    last_timestamp TIMESTAMPTZ,
+    last_slot      INTEGER,
+    last_signature VARCHAR(88),
+    last_index     INTEGER,
+    PRIMARY KEY (asset, pair)
+);
+
+
+CREATE TABLE IF NOT EXISTS market.market_stats_hourly (
+    asset       INTEGER          NOT NULL,
+    pair        INTEGER          NOT NULL,
+    bucket      TIMESTAMPTZ      NOT NULL,
+    trade_count INTEGER          NOT NULL,
+    size        BIGINT           NOT NULL,
+    volume      DOUBLE PRECISION NOT NULL,
+    price_size  DOUBLE PRECISION NOT NULL,
+    high        DOUBLE PRECISION NOT NULL,
+    low         DOUBLE PRECISION NOT NULL,
+    open_price  DOUBLE PRECISION NOT NULL,
+    close_price DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (asset, pair, bucket),
+    FOREIGN KEY (asset, pair) REFERENCES market.markets (asset, pair) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_market_stats_hourly_bucket ON market.market_stats_hourly (bucket);
+
+
+CREATE TABLE IF NOT EXISTS market.market_traders_hourly (
+    asset     INTEGER     NOT NULL,
+    pair      INTEGER     NOT NULL,
+    bucket    TIMESTAMPTZ NOT NULL,
+    player_id INTEGER REFERENCES staratlas.players (id) ON DELETE CASCADE NOT NULL,
+    PRIMARY KEY (asset, pair, bucket, player_id),
+    FOREIGN KEY (asset, pair) REFERENCES market.markets (asset, pair) ON DELETE CASCADE
+);
+
+
+-- Backfill the aggregates from the exchanges processed so far
+INSERT INTO market.markets (asset, pair, last_price, last_timestamp, last_slot, last_signature, last_index)
+SELECT DISTINCT ON (asset, pair) asset, pair, price, timestamp, slot, signature, index
+FROM market.exchanges
+ORDER BY asset, pair, slot DESC, signature DESC, index DESC
+ON CONFLICT DO NOTHING;
+
+INSERT INTO market.market_stats_hourly (
+    asset, pair, bucket, trade_count, size, volume, price_size, high, low, open_price, close_price
+)
+SELECT asset,
+       pair,
+       date_trunc('hour', timestamp, 'UTC'),
+       COUNT(*),
+       SUM(size),
+       SUM(volume),
+       SUM(price * size),
+       MAX(price),
+       MIN(price),
+       (ARRAY_AGG(price ORDER BY slot, signature, index))[1],
+       (ARRAY_AGG(price ORDER BY slot DESC, signature DESC, index DESC))[1]
+FROM market.exchanges
+GROUP BY asset, pair, date_trunc('hour', timestamp, 'UTC')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO market.market_traders_hourly (asset, pair, bucket, player_id)
+SELECT asset, pair, date_trunc('hour', timestamp, 'UTC'), buyer FROM market.exchanges
+UNION
+SELECT asset, pair, date_trunc('hour', timestamp, 'UTC'), seller FROM market.exchanges
+ON CONFLICT DO NOTHING;
@@ -0,0 +1,72 @@
+CREATE SCHEMA alerts;
+
+
+CREATE TYPE alert_condition_kind AS ENUM ('price_below', 'price_above', 'wallet_traded', 'volume_above');
+
+CREATE TYPE alert_delivery_status AS ENUM ('pending', 'delivered', 'failed');
+
+
+CREATE TABLE IF NOT EXISTS alerts.webhooks (
+    id          SERIAL PRIMARY KEY,
+    url         TEXT        NOT NULL,
+    secret      VARCHAR(64) NOT NULL,
+    description TEXT,
+    active      BOOLEAN     NOT NULL DEFAULT true,
+    created_at  TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+
+CREATE TABLE IF NOT EXISTS alerts.conditions (
+    id                SERIAL PRIMARY KEY,
+    webhook_id        INTEGER REFERENCES alerts.webhooks (id) ON DELETE CASCADE NOT NULL,
+    kind              alert_condition_kind NOT NULL,
+    asset_mint        VARCHAR(50),
+    pair_mint         VARCHAR(50),
+    wallet            VARCHAR(50),
+    threshold         DOUBLE PRECISION,
+    window_seconds    INTEGER     NOT NULL DEFAULT 3600,
+    cooldown_seconds  INTEGER     NOT NULL DEFAULT 0,
+    last_triggered_at TIMESTAMPTZ,
+    created_at        TIMESTAMPTZ NOT NULL DEFAULT NOW()
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_conditions_webhook_id ON alerts.conditions (webhook_id);
+
+
+-- Exchanges waiting to be evaluated by the dispatcher, filled when an exchange is inserted
+CREATE TABLE IF NOT EXISTS alerts.exchange_queue (
+    exchange_id   INTEGER PRIMARY KEY REFERENCES market.exchanges (id) ON DELETE CASCADE,
+    claimed_until TIMESTAMPTZ
+);
+
+
+CREATE TABLE IF NOT EXISTS alerts.deliveries (
+    id               SERIAL PRIMARY KEY,
+    webhook_id       INTEGER REFERENCES alerts.webhooks (id) ON DELETE CASCADE   NOT NULL,
+    condition_id     INTEGER REFERENCES alerts.conditions (id) ON DELETE CASCADE NOT NULL,
+    exchange_id      INTEGER REFERENCES market.exchanges (id) ON DELETE CASCADE  NOT NULL,
+    payload          TEXT                   NOT NULL,
+    status           alert_delivery_status NOT NULL DEFAULT 'pending',
+    attempts         INTEGER                NOT NULL DEFAULT 0,
+    next_attempt_at  TIMESTAMPTZ            NOT NULL DEFAULT NOW(),
+    last_status_code INTEGER,
+    last_error       TEXT,
+    created_at       TIMESTAMPTZ            NOT NULL DEFAULT NOW(),
+    delivered_at     TIMESTAMPTZ,
+    CONSTRAINT unique_condition_exchange UNIQUE (condition_id, exchange_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_deliveries_webhook_id ON alerts.deliveries (webhook_id, id);
+CREATE INDEX IF NOT EXISTS idx_alert_deliveries_due ON alerts.deliveries (next_attempt_at) WHERE status = 'pending';
+
+
+CREATE TABLE IF NOT EXISTS alerts.delivery_attempts (
+    id           SERIAL PRIMARY KEY,
+    delivery_id  INTEGER REFERENCES alerts.deliveries (id) ON DELETE CASCADE NOT NULL,
+    attempted_at TIMESTAMPTZ NOT NULL DEFAULT NOW(),
+    status_code  INTEGER,
+    error        TEXT,
+    duration_ms  INTEGER     NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_alert_delivery_attempts_delivery_id ON alerts.delivery_attempts (delivery_id);
@@ -0,0 +1,29 @@
+CREATE SCHEMA auth;
+
+
+CREATE TYPE api_key_scope AS ENUM ('read', 'admin');
+
+
+-- Only a SHA-256 hash of each key is stored, the key itself is shown once when it is created
+CREATE TABLE IF NOT EXISTS auth.api_keys (
+    id            SERIAL PRIMARY KEY,
+    name          VARCHAR(100)  NOT NULL,
+    key_prefix    VARCHAR(16)   NOT NULL,
+    key_hash      CHAR(64)      NOT NULL,
+    scope         api_key_scope NOT NULL DEFAULT 'read',
+    rate_limit    INTEGER,
+    request_count BIGINT        NOT NULL DEFAULT 0,
+    last_used_at  TIMESTAMPTZ,
+    revoked_at    TIMESTAMPTZ,
+    created_at    TIMESTAMPTZ   NOT NULL DEFAULT NOW(),
+    CONSTRAINT unique_api_key_hash UNIQUE (key_hash)
+);
+
+
+-- Requests per key and day
+CREATE TABLE IF NOT EXISTS auth.api_key_usage (
+    api_key_id INTEGER REFERENCES auth.api_keys (id) ON DELETE CASCADE NOT NULL,
+    day        DATE    NOT NULL,
+    requests   BIGINT  NOT NULL DEFAULT 0,
+    PRIMARY KEY (api_key_id, day)
+);
@@ -0,0 +1,60 @@
+-- Snapshots of the marketplace program accounts, replaced as a whole by every snapshot.
+-- Amounts are raw u64 token amounts; snapshot_slot is the slot the snapshot was taken at.
+
+CREATE TABLE IF NOT EXISTS market.order_accounts (
+    address                            VARCHAR(50) PRIMARY KEY,
+    order_initializer                  VARCHAR(50) NOT NULL,
+    currency_mint                      VARCHAR(50) NOT NULL,
+    asset_mint                         VARCHAR(50) NOT NULL,
+    initializer_currency_token_account VARCHAR(50) NOT NULL,
+    initializer_asset_token_account    VARCHAR(50) NOT NULL,
+    side                               VARCHAR(4)  NOT NULL,
+    price                              BIGINT      NOT NULL,
+    origination_qty                    BIGINT      NOT NULL,
+    remaining_qty                      BIGINT      NOT NULL,
+    created_at                         TIMESTAMPTZ NOT NULL,
+    snapshot_slot                      BIGINT      NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_order_accounts_asset_currency ON market.order_accounts (asset_mint, currency_mint);
+CREATE INDEX IF NOT EXISTS idx_order_accounts_initializer ON market.order_accounts (order_initializer);
+
+
+CREATE TABLE IF NOT EXISTS market.registered_currencies (
+    address                    VARCHAR(50) PRIMARY KEY,
+    token_mint                 VARCHAR(50) NOT NULL,
+    sa_currency_vault          VARCHAR(50) NOT NULL,
+    royalty                    BIGINT      NOT NULL,
+    royalty_tier_stake_amounts BIGINT[]    NOT NULL,
+    royalty_tier_discounts     BIGINT[]    NOT NULL,
+    snapshot_slot              BIGINT      NOT NULL
+);
+
+
+CREATE TABLE IF NOT EXISTS market.fee_reductions (
+    address       VARCHAR(50) PRIMARY KEY,
+    account       VARCHAR(50) NOT NULL,
+    discount      BIGINT      NOT NULL,
+    snapshot_slot BIGINT      NOT NULL
+);
+
+
+CREATE TABLE IF NOT EXISTS market.market_vars (
+    address                 VARCHAR(50) PRIMARY KEY,
+    update_authority_master VARCHAR(50) NOT NULL,
+    snapshot_slot           BIGINT      NOT NULL
+);
+
+
+CREATE TABLE IF NOT EXISTS market.atlas_rates (
+    address       VARCHAR(50) PRIMARY KEY,
+    atlas_rate    BIGINT      NOT NULL,
+    snapshot_slot BIGINT      NOT NULL
+);
+
+
+CREATE TABLE IF NOT EXISTS market.open_orders_counters (
+    address          VARCHAR(50) PRIMARY KEY,
+    open_order_count BIGINT      NOT NULL,
+    snapshot_slot    BIGINT      NOT NULL
+);
@@ -0,0 +1,41 @@
+-- Program invocations and emitted events parsed from the log messages of processed transactions.
+-- Rows are replaced per signature whenever a transaction is (re)processed.
+
+CREATE SCHEMA IF NOT EXISTS logs;
+
+
+CREATE TABLE IF NOT EXISTS logs.invocations (
+    signature              VARCHAR(88) NOT NULL,
+    position               INTEGER     NOT NULL,
+    slot                   BIGINT      NOT NULL,
+    timestamp              TIMESTAMPTZ NOT NULL,
+    instruction_index      INTEGER     NOT NULL,
+    depth                  SMALLINT    NOT NULL,
+    program_id             VARCHAR(50) NOT NULL,
+    instruction_name       VARCHAR(100),
+    compute_units_consumed BIGINT,
+    compute_units_limit    BIGINT,
+    error                  TEXT,
+    logs                   TEXT        NOT NULL,
+    PRIMARY KEY (signature, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_invocations_program_instruction_timestamp
+    ON logs.invocations (program_id, instruction_name, timestamp);
+
+
+CREATE TABLE IF NOT EXISTS logs.events (
+    signature   VARCHAR(88)  NOT NULL,
+    position    INTEGER      NOT NULL,
+    event_index INTEGER      NOT NULL,
+    slot        BIGINT       NOT NULL,
+    timestamp   TIMESTAMPTZ  NOT NULL,
+    program_id  VARCHAR(50)  NOT NULL,
+    name        VARCHAR(100),
+    data        JSONB,
+    raw         BYTEA        NOT NULL,
+    PRIMARY KEY (signature, position, event_index),
+    FOREIGN KEY (signature, position) REFERENCES logs.invocations (signature, position) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_events_program_name_timestamp ON logs.events (program_id, name, timestamp);
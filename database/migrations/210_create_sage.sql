@@ -0,0 +1,67 @@
+-- SAGE fleet activity: fleet creation, movement, mining, cargo and starbase interactions.
+-- Activities are replaced per signature whenever a transaction is (re)processed.
+
+CREATE SCHEMA IF NOT EXISTS sage;
+
+
+INSERT INTO indexer.programs (program_id)
+VALUES ('SAGE2HAwep459SNq61LHvjxPk4pLPEJLoMETef7f7EE')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO indexer.indexer (name, direction, program_id, finished, fetch_limit)
+VALUES ('sage_up', 'UP', 'SAGE2HAwep459SNq61LHvjxPk4pLPEJLoMETef7f7EE', false, 100),
+       ('sage_down', 'DOWN', 'SAGE2HAwep459SNq61LHvjxPk4pLPEJLoMETef7f7EE', false, 100)
+ON CONFLICT DO NOTHING;
+
+
+CREATE TABLE IF NOT EXISTS sage.activities (
+    signature         VARCHAR(88) NOT NULL,
+    instruction_index INTEGER     NOT NULL,
+    slot              BIGINT      NOT NULL,
+    timestamp         TIMESTAMPTZ NOT NULL,
+    kind              VARCHAR(32) NOT NULL,
+    instruction       VARCHAR(64) NOT NULL,
+    fleet             VARCHAR(50),
+    player_profile    VARCHAR(50),
+    starbase          VARCHAR(50),
+    sector_x          BIGINT,
+    sector_y          BIGINT,
+    resource_mint     VARCHAR(50),
+    amount            BIGINT,
+    args              JSONB       NOT NULL,
+    PRIMARY KEY (signature, instruction_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_activities_fleet_timestamp ON sage.activities (fleet, timestamp);
+CREATE INDEX IF NOT EXISTS idx_activities_player_profile_timestamp ON sage.activities (player_profile, timestamp);
+CREATE INDEX IF NOT EXISTS idx_activities_kind_timestamp ON sage.activities (kind, timestamp);
+
+
+-- Token movements of an activity, e.g. mined resources minted into the fleet's cargo
+CREATE TABLE IF NOT EXISTS sage.activity_transfers (
+    signature         VARCHAR(88) NOT NULL,
+    instruction_index INTEGER     NOT NULL,
+    transfer_index    INTEGER     NOT NULL,
+    kind              VARCHAR(16) NOT NULL,
+    mint              VARCHAR(50),
+    source            VARCHAR(50),
+    destination       VARCHAR(50),
+    amount            BIGINT      NOT NULL,
+    PRIMARY KEY (signature, instruction_index, transfer_index),
+    FOREIGN KEY (signature, instruction_index) REFERENCES sage.activities (signature, instruction_index) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_activity_transfers_mint ON sage.activity_transfers (mint);
+
+
+-- Fleets as created, kept when the creating activity is reprocessed
+CREATE TABLE IF NOT EXISTS sage.fleets (
+    address           VARCHAR(50) NOT NULL PRIMARY KEY,
+    player_profile    VARCHAR(50),
+    label             VARCHAR(64),
+    created_signature VARCHAR(88) NOT NULL,
+    created_at        TIMESTAMPTZ NOT NULL,
+    disbanded_at      TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS idx_fleets_player_profile ON sage.fleets (player_profile);
@@ -0,0 +1,95 @@
+-- Crafting: recipes from snapshots of the crafting program accounts, and the steps of crafting
+-- processes from processed transactions. Process events are replaced per signature whenever a
+-- transaction is (re)processed. Amounts are raw u64 token amounts.
+
+CREATE SCHEMA IF NOT EXISTS crafting;
+
+
+INSERT INTO indexer.programs (program_id)
+VALUES ('CRAFT2RPXPJWCEix4WpJST3E7NLf79GTqZUL75wngXo5')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO indexer.indexer (name, direction, program_id, finished, fetch_limit)
+VALUES ('crafting_up', 'UP', 'CRAFT2RPXPJWCEix4WpJST3E7NLf79GTqZUL75wngXo5', false, 100),
+       ('crafting_down', 'DOWN', 'CRAFT2RPXPJWCEix4WpJST3E7NLf79GTqZUL75wngXo5', false, 100)
+ON CONFLICT DO NOTHING;
+
+
+CREATE TABLE IF NOT EXISTS crafting.recipes (
+    address       VARCHAR(50) PRIMARY KEY,
+    domain        VARCHAR(50),
+    category      VARCHAR(50),
+    duration      BIGINT,
+    status        VARCHAR(32),
+    fee_amount    BIGINT,
+    usage_count   BIGINT,
+    data          JSONB       NOT NULL,
+    snapshot_slot BIGINT      NOT NULL
+);
+
+
+CREATE TABLE IF NOT EXISTS crafting.recipe_items (
+    recipe   VARCHAR(50) NOT NULL REFERENCES crafting.recipes (address) ON DELETE CASCADE,
+    position SMALLINT    NOT NULL,
+    -- consumable, non_consumable or output
+    role     VARCHAR(16) NOT NULL,
+    mint     VARCHAR(50) NOT NULL,
+    amount   BIGINT      NOT NULL,
+    PRIMARY KEY (recipe, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_recipe_items_mint_role ON crafting.recipe_items (mint, role);
+
+
+-- Crafting program instructions, usually invoked by SAGE which adds the player and starbase
+CREATE TABLE IF NOT EXISTS crafting.process_events (
+    signature         VARCHAR(88) NOT NULL,
+    instruction_index INTEGER     NOT NULL,
+    -- 0 for a transaction instruction, otherwise 1 + the index among its inner instructions
+    position          INTEGER     NOT NULL,
+    slot              BIGINT      NOT NULL,
+    timestamp         TIMESTAMPTZ NOT NULL,
+    kind              VARCHAR(32) NOT NULL,
+    crafting_process  VARCHAR(50),
+    recipe            VARCHAR(50),
+    player_profile    VARCHAR(50),
+    starbase          VARCHAR(50),
+    quantity          BIGINT,
+    mint              VARCHAR(50),
+    amount            BIGINT,
+    args              JSONB       NOT NULL,
+    PRIMARY KEY (signature, instruction_index, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_process_events_crafting_process ON crafting.process_events (crafting_process);
+CREATE INDEX IF NOT EXISTS idx_process_events_player_profile_timestamp ON crafting.process_events (player_profile, timestamp);
+CREATE INDEX IF NOT EXISTS idx_process_events_kind_mint_timestamp ON crafting.process_events (kind, mint, timestamp);
+
+
+CREATE OR REPLACE VIEW crafting.processes AS
+SELECT crafting_process                                                          AS address,
+       (ARRAY_AGG(recipe ORDER BY timestamp) FILTER (WHERE recipe IS NOT NULL))[1] AS recipe,
+       (ARRAY_AGG(player_profile ORDER BY timestamp)
+        FILTER (WHERE player_profile IS NOT NULL))[1]                           AS player_profile,
+       (ARRAY_AGG(starbase ORDER BY timestamp) FILTER (WHERE starbase IS NOT NULL))[1] AS starbase,
+       MAX(quantity)                                                             AS quantity,
+       MIN(timestamp) FILTER (WHERE kind = 'created')                           AS created_at,
+       MIN(timestamp) FILTER (WHERE kind = 'started')                           AS started_at,
+       MAX(timestamp) FILTER (WHERE kind = 'output_claimed')                    AS completed_at,
+       MAX(timestamp) FILTER (WHERE kind = 'cancelled')                         AS cancelled_at
+FROM crafting.process_events
+WHERE crafting_process IS NOT NULL
+GROUP BY crafting_process;
+
+
+-- Consumed inputs and produced outputs per player, starbase and mint
+CREATE OR REPLACE VIEW crafting.item_flows AS
+SELECT player_profile,
+       starbase,
+       mint,
+       COALESCE(SUM(amount) FILTER (WHERE kind = 'input_consumed'), 0)::BIGINT AS consumed,
+       COALESCE(SUM(amount) FILTER (WHERE kind = 'output_claimed'), 0)::BIGINT AS produced
+FROM crafting.process_events
+WHERE kind IN ('input_consumed', 'output_claimed')
+  AND mint IS NOT NULL
+GROUP BY player_profile, starbase, mint;
@@ -0,0 +1,31 @@
+-- Player profiles: snapshots of the Player Profile program accounts. A profile's auth keys are
+-- the wallets owning it, its username comes from its PlayerName account. The usernames are
+-- copied to staratlas.players by wallet whenever a snapshot is stored.
+
+CREATE TABLE IF NOT EXISTS staratlas.profiles (
+    address        VARCHAR(50) PRIMARY KEY,
+    auth_key_count SMALLINT    NOT NULL,
+    key_threshold  SMALLINT    NOT NULL,
+    created_at     TIMESTAMPTZ,
+    name           VARCHAR(255),
+    snapshot_slot  BIGINT      NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_profiles_name ON staratlas.profiles (name);
+
+
+CREATE TABLE IF NOT EXISTS staratlas.profile_keys (
+    profile     VARCHAR(50) NOT NULL REFERENCES staratlas.profiles (address) ON DELETE CASCADE,
+    position    SMALLINT    NOT NULL,
+    key         VARCHAR(50) NOT NULL,
+    -- Program the key may be used with
+    scope       VARCHAR(50) NOT NULL,
+    -- Unix timestamp, negative if the key never expires
+    expire_time BIGINT      NOT NULL,
+    permissions BYTEA       NOT NULL,
+    -- Auth keys are the wallets owning the profile
+    is_auth     BOOLEAN     NOT NULL,
+    PRIMARY KEY (profile, position)
+);
+
+CREATE INDEX IF NOT EXISTS idx_profile_keys_key ON staratlas.profile_keys (key);
@@ -0,0 +1,41 @@
+-- Faction and guild attribution of players. Profile factions come from snapshots of the Profile
+-- Faction program, guild members from the configured guilds (DAO realms or imported lists).
+-- Both are copied to staratlas.players by wallet, like the usernames, so exchanges and
+-- leaderboards can be grouped by them.
+
+CREATE TABLE IF NOT EXISTS staratlas.profile_factions (
+    profile       VARCHAR(50) PRIMARY KEY,
+    -- MUD, ONI, USTUR or UNALIGNED
+    faction       VARCHAR(16) NOT NULL,
+    snapshot_slot BIGINT      NOT NULL
+);
+
+
+CREATE TABLE IF NOT EXISTS staratlas.guilds (
+    id         SERIAL PRIMARY KEY,
+    name       VARCHAR(100) NOT NULL UNIQUE,
+    tag        VARCHAR(16),
+    -- realm (members are the token owner records of a DAO realm) or list (imported wallets)
+    source     VARCHAR(16)  NOT NULL,
+    realm      VARCHAR(50),
+    -- Order in the configuration, players in several guilds get the first one
+    position   INTEGER      NOT NULL DEFAULT 0,
+    updated_at TIMESTAMPTZ  NOT NULL DEFAULT NOW()
+);
+
+
+CREATE TABLE IF NOT EXISTS staratlas.guild_members (
+    guild_id       INTEGER     NOT NULL REFERENCES staratlas.guilds (id) ON DELETE CASCADE,
+    wallet_address VARCHAR(50) NOT NULL,
+    PRIMARY KEY (guild_id, wallet_address)
+);
+
+CREATE INDEX IF NOT EXISTS idx_guild_members_wallet_address ON staratlas.guild_members (wallet_address);
+
+
+ALTER TABLE staratlas.players
+    ADD COLUMN IF NOT EXISTS faction VARCHAR(16),
+    ADD COLUMN IF NOT EXISTS guild   VARCHAR(100);
+
+CREATE INDEX IF NOT EXISTS idx_players_faction ON staratlas.players (faction);
+CREATE INDEX IF NOT EXISTS idx_players_guild ON staratlas.players (guild);
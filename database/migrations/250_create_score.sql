@@ -0,0 +1,38 @@
+-- SCORE fleet staking: ship stakes and withdrawals, resupplies and ATLAS reward claims per
+-- player wallet and ship mint. Events are replaced per signature whenever a transaction is
+-- (re)processed. Amounts are raw u64 token amounts.
+
+CREATE SCHEMA IF NOT EXISTS score;
+
+
+INSERT INTO indexer.programs (program_id)
+VALUES ('FLEET1qqzpexyaDpqb2DGsSzE2sDCizewCg9WjrA6DU')
+ON CONFLICT DO NOTHING;
+
+INSERT INTO indexer.indexer (name, direction, program_id, finished, fetch_limit)
+VALUES ('score_up', 'UP', 'FLEET1qqzpexyaDpqb2DGsSzE2sDCizewCg9WjrA6DU', false, 100),
+       ('score_down', 'DOWN', 'FLEET1qqzpexyaDpqb2DGsSzE2sDCizewCg9WjrA6DU', false, 100)
+ON CONFLICT DO NOTHING;
+
+
+CREATE TABLE IF NOT EXISTS score.events (
+    signature         VARCHAR(88) NOT NULL,
+    instruction_index INTEGER     NOT NULL,
+    slot              BIGINT      NOT NULL,
+    timestamp         TIMESTAMPTZ NOT NULL,
+    -- stake, unstake, resupply, resupply_withdrawn or claim
+    kind              VARCHAR(32) NOT NULL,
+    instruction       VARCHAR(64) NOT NULL,
+    player            VARCHAR(50),
+    ship_mint         VARCHAR(50),
+    -- food, fuel, ammo or toolkit for resupplies
+    resource          VARCHAR(16),
+    -- Mint of the moved tokens: the ship, the resource or ATLAS
+    mint              VARCHAR(50),
+    amount            BIGINT,
+    args              JSONB       NOT NULL,
+    PRIMARY KEY (signature, instruction_index)
+);
+
+CREATE INDEX IF NOT EXISTS idx_score_events_ship_mint_kind_timestamp ON score.events (ship_mint, kind, timestamp);
+CREATE INDEX IF NOT EXISTS idx_score_events_player_timestamp ON score.events (player, timestamp);
@@ -0,0 +1,8 @@
+ALTER TABLE indexer.program_signatures
+    ADD COLUMN IF NOT EXISTS transaction_index INTEGER;
+
+DROP INDEX IF EXISTS indexer.idx_program_signatures_unprocessed;
+
+CREATE INDEX IF NOT EXISTS idx_program_signatures_unprocessed
+    ON indexer.program_signatures (program_id, slot, transaction_index, signature)
+    WHERE processed = false;
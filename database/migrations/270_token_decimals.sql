@@ -0,0 +1,4 @@
+-- Decimals of a token, set by the marketplace processor from the checked transfers of its
+-- exchanges. NULL until an exchange of the token is (re)processed.
+ALTER TABLE staratlas.tokens
+    ADD COLUMN IF NOT EXISTS decimals SMALLINT;
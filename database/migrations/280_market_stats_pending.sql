@@ -0,0 +1,8 @@
+-- Hourly market aggregates touched by exchange writes, refreshed per processed batch by
+-- refresh_pending_market_stats instead of within each write
+CREATE TABLE IF NOT EXISTS market.market_stats_pending (
+    asset  INTEGER REFERENCES staratlas.tokens (id) ON DELETE CASCADE NOT NULL,
+    pair   INTEGER REFERENCES staratlas.tokens (id) ON DELETE CASCADE NOT NULL,
+    bucket TIMESTAMPTZ NOT NULL,
+    PRIMARY KEY (asset, pair, bucket)
+);
@@ -11,6 +11,8 @@ pub type DbPool = PgPool;
 
 /// Establishes a connection to the database using the DATABASE_URL environment variable
 ///
+/// The pool size defaults to 5 connections and can be raised with DATABASE_MAX_CONNECTIONS.
+///
 /// # Returns
 /// A connection pool that can be used for database operations
 ///
@@ -18,18 +20,40 @@ pub type DbPool = PgPool;
 /// Returns an error if the connection cannot be established or if the DATABASE_URL
 /// environment variable is not set or is invalid
 pub async fn establish_connection() -> Result<DbPool> {
+    let max_connections = env::var("DATABASE_MAX_CONNECTIONS")
+        .ok()
+        .and_then(|value| value.parse::<u32>().ok())
+        .unwrap_or(5);
+
+    establish_connection_with_max_connections(max_connections).await
+}
+
+/// Establishes a connection pool of a given size using the DATABASE_URL environment variable
+///
+/// Used for separate pools that long-running work, such as exports, must not take from the
+/// main pool.
+///
+/// # Arguments
+/// * `max_connections` - The maximum number of connections of the pool
+///
+/// # Returns
+/// A connection pool that can be used for database operations
+///
+/// # Errors
+/// Returns an error if the connection cannot be established or if the DATABASE_URL
+/// environment variable is not set or is invalid
+pub async fn establish_connection_with_max_connections(max_connections: u32) -> Result<DbPool> {
     // Load environment variables from .env file if it exists
     dotenv::dotenv().ok();
 
     // Get the database URL from the environment
-    let database_url = env::var("DATABASE_URL").map_err(|e| {
+    let database_url = env::var("DATABASE_URL").inspect_err(|_e| {
         log::error!("DATABASE_URL environment variable not set");
-        e
     })?;
 
     // Create a connection pool with reasonable defaults
     let pool = PgPoolOptions::new()
-        .max_connections(5)
+        .max_connections(max_connections)
         .acquire_timeout(Duration::from_secs(3))
         .connect(&database_url)
         .await
@@ -0,0 +1,96 @@
+//! Exports of large result sets via server-side cursors
+
+use sqlx::{Postgres, QueryBuilder, Transaction};
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{ExchangeDetailed, ExchangeFilter};
+use crate::queries::{EXCHANGES_DETAILED, push_exchange_filter};
+
+/// Name of the cursor an export reads from, unique per transaction
+const EXPORT_CURSOR: &str = "exchange_export";
+
+/// Export of every exchange matching a filter, read in batches from a server-side cursor
+///
+/// Only one batch is held in memory at a time, so exports of the full history do not
+/// depend on its size. The cursor lives in a read-only transaction that holds one pool
+/// connection until the export is dropped.
+pub struct ExchangeExport {
+    /// Transaction the cursor was declared in
+    transaction: Transaction<'static, Postgres>,
+    /// Number of exchanges fetched per batch
+    batch_size: i64,
+    /// Whether the cursor is exhausted
+    finished: bool,
+}
+
+impl ExchangeExport {
+    /// Declares a cursor over the exchanges matching a filter, oldest first
+    ///
+    /// The cursor ordering is ascending, so `filter.after` is ignored.
+    ///
+    /// # Arguments
+    /// * `pool` - The database connection pool
+    /// * `filter` - The conditions the exchanges must match
+    /// * `batch_size` - The number of exchanges to fetch per batch
+    ///
+    /// # Returns
+    /// An export positioned before the first exchange
+    ///
+    /// # Errors
+    /// Returns an error if the transaction cannot be started or the cursor not declared
+    pub async fn open(pool: &DbPool, filter: &ExchangeFilter, batch_size: i64) -> Result<Self> {
+        let mut transaction = pool.begin().await.map_err(DbError::SqlxError)?;
+        sqlx::query("SET TRANSACTION READ ONLY")
+            .execute(&mut *transaction)
+            .await
+            .map_err(DbError::SqlxError)?;
+
+        let filter = ExchangeFilter {
+            after: None,
+            ..filter.clone()
+        };
+        let mut query = QueryBuilder::<Postgres>::new(format!(
+            "DECLARE {EXPORT_CURSOR} NO SCROLL CURSOR FOR {EXCHANGES_DETAILED}"
+        ));
+        push_exchange_filter(&mut query, &filter);
+        query.push(" ORDER BY e.slot, e.signature, e.index");
+
+        query
+            .build()
+            .execute(&mut *transaction)
+            .await
+            .map_err(DbError::SqlxError)?;
+
+        Ok(Self {
+            transaction,
+            batch_size: batch_size.max(1),
+            finished: false,
+        })
+    }
+
+    /// Fetches the next batch of exchanges
+    ///
+    /// # Returns
+    /// The next exchanges in order, or an empty vector once all have been fetched
+    ///
+    /// # Errors
+    /// Returns an error if the fetch fails
+    pub async fn next_batch(&mut self) -> Result<Vec<ExchangeDetailed>> {
+        if self.finished {
+            return Ok(Vec::new());
+        }
+
+        let exchanges = sqlx::query_as::<_, ExchangeDetailed>(&format!(
+            "FETCH FORWARD {} FROM {EXPORT_CURSOR}",
+            self.batch_size
+        ))
+        .fetch_all(&mut *self.transaction)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        self.finished = (exchanges.len() as i64) < self.batch_size;
+
+        Ok(exchanges)
+    }
+}
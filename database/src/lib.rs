@@ -5,12 +5,22 @@
 
 mod connection;
 mod error;
+mod export;
+mod listener;
 mod models;
 pub mod queries;
 mod types;
 
-pub use connection::{DbPool, establish_connection};
+pub use connection::{
+    DbPool, establish_connection, establish_connection_with_max_connections,
+    establish_connection_with_url,
+};
 pub use error::{DbError, Result};
+pub use export::ExchangeExport;
+pub use listener::{
+    ChangeListener, DatabaseChange, EXCHANGES_REWRITTEN_CHANNEL, ExchangeListener,
+    NEW_EXCHANGE_CHANNEL, NEW_TOKEN_CHANNEL, PLAYERS_UPDATED_CHANNEL,
+};
 pub use types::*;
 
 pub use models::*;
@@ -0,0 +1,155 @@
+//! Notifications of database changes via Postgres LISTEN/NOTIFY
+
+use sqlx::postgres::PgListener;
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+
+/// Channel notified with the ID of every newly inserted exchange
+///
+/// Notifications are sent when the inserting transaction commits. Rewrites of an existing
+/// exchange during reprocessing are announced on `EXCHANGES_REWRITTEN_CHANNEL` instead.
+pub const NEW_EXCHANGE_CHANNEL: &str = "market_new_exchange";
+
+/// Channel notified with the ID of every newly created token
+///
+/// Notifications are sent when the inserting transaction commits.
+pub const NEW_TOKEN_CHANNEL: &str = "staratlas_new_token";
+
+/// Channel notified with the number of players whose username, faction or guild changed
+///
+/// Notifications are sent when the updating transaction commits.
+pub const PLAYERS_UPDATED_CHANNEL: &str = "staratlas_players_updated";
+
+/// Channel notified with the number of existing exchanges rewritten or deleted during
+/// reprocessing
+///
+/// Notifications are sent when the rewriting transaction commits.
+pub const EXCHANGES_REWRITTEN_CHANNEL: &str = "market_exchanges_rewritten";
+
+/// Listener for newly inserted exchanges
+pub struct ExchangeListener {
+    /// Listener connection subscribed to the new exchange channel
+    listener: PgListener,
+}
+
+impl ExchangeListener {
+    /// Opens a dedicated connection and subscribes to the new exchange channel
+    ///
+    /// # Arguments
+    /// * `pool` - The database connection pool to take the connection options from
+    ///
+    /// # Returns
+    /// A listener receiving the IDs of new exchanges
+    ///
+    /// # Errors
+    /// Returns an error if the connection cannot be established or the subscription fails
+    pub async fn connect(pool: &DbPool) -> Result<Self> {
+        let mut listener = PgListener::connect_with(pool)
+            .await
+            .map_err(DbError::SqlxError)?;
+        listener
+            .listen(NEW_EXCHANGE_CHANNEL)
+            .await
+            .map_err(DbError::SqlxError)?;
+
+        Ok(Self { listener })
+    }
+
+    /// Waits for the next new exchange
+    ///
+    /// If the connection is lost it is re-established and the subscription renewed;
+    /// exchanges inserted in the meantime are not received.
+    ///
+    /// # Returns
+    /// The ID of the new exchange (references market.exchanges)
+    ///
+    /// # Errors
+    /// Returns an error if the connection fails or the notification payload is not an ID
+    pub async fn recv(&mut self) -> Result<i32> {
+        let notification = self.listener.recv().await.map_err(DbError::SqlxError)?;
+
+        notification
+            .payload()
+            .parse()
+            .map_err(|_| DbError::Other(format!("invalid exchange ID: {}", notification.payload())))
+    }
+}
+
+/// Change announced on one of the notification channels
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DatabaseChange {
+    /// An exchange was inserted (references market.exchanges)
+    NewExchange(i32),
+    /// A token was created (references staratlas.tokens)
+    NewToken(i32),
+    /// The username, faction or guild of a number of players changed
+    PlayersUpdated(i32),
+    /// A number of existing exchanges were rewritten or deleted
+    ExchangesRewritten(i32),
+}
+
+/// Listener for newly inserted and rewritten exchanges, newly created tokens and updated
+/// players
+pub struct ChangeListener {
+    /// Listener connection subscribed to the change channels
+    listener: PgListener,
+}
+
+impl ChangeListener {
+    /// Opens a dedicated connection and subscribes to the change channels
+    ///
+    /// # Arguments
+    /// * `pool` - The database connection pool to take the connection options from
+    ///
+    /// # Returns
+    /// A listener receiving the changes
+    ///
+    /// # Errors
+    /// Returns an error if the connection cannot be established or the subscription fails
+    pub async fn connect(pool: &DbPool) -> Result<Self> {
+        let mut listener = PgListener::connect_with(pool)
+            .await
+            .map_err(DbError::SqlxError)?;
+        listener
+            .listen_all([
+                NEW_EXCHANGE_CHANNEL,
+                NEW_TOKEN_CHANNEL,
+                PLAYERS_UPDATED_CHANNEL,
+                EXCHANGES_REWRITTEN_CHANNEL,
+            ])
+            .await
+            .map_err(DbError::SqlxError)?;
+
+        Ok(Self { listener })
+    }
+
+    /// Waits for the next change
+    ///
+    /// If the connection is lost, `None` is returned, as changes made until the connection is
+    /// re-established are not received. The next call reconnects and renews the
+    /// subscriptions.
+    ///
+    /// # Returns
+    /// The change announced by the notification, or None if the connection was lost
+    ///
+    /// # Errors
+    /// Returns an error if reconnecting fails or the notification payload is not a number
+    pub async fn recv(&mut self) -> Result<Option<DatabaseChange>> {
+        let Some(notification) = self.listener.try_recv().await.map_err(DbError::SqlxError)? else {
+            return Ok(None);
+        };
+
+        let id = notification
+            .payload()
+            .parse()
+            .map_err(|_| DbError::Other(format!("invalid ID: {}", notification.payload())))?;
+
+        match notification.channel() {
+            NEW_TOKEN_CHANNEL => Ok(Some(DatabaseChange::NewToken(id))),
+            PLAYERS_UPDATED_CHANNEL => Ok(Some(DatabaseChange::PlayersUpdated(id))),
+            EXCHANGES_REWRITTEN_CHANNEL => Ok(Some(DatabaseChange::ExchangesRewritten(id))),
+            _ => Ok(Some(DatabaseChange::NewExchange(id))),
+        }
+    }
+}
@@ -0,0 +1,178 @@
+//! Models for the alerts schema
+
+use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::types::{AlertConditionKind, DeliveryStatus};
+
+/// Represents a webhook in the alerts.webhooks table
+#[derive(Debug, FromRow, Clone)]
+pub struct Webhook {
+    /// Unique identifier for the webhook
+    pub id: i32,
+
+    /// URL the alerts are posted to
+    pub url: String,
+
+    /// Secret the payloads are signed with
+    pub secret: String,
+
+    /// Description of the webhook (if available)
+    pub description: Option<String>,
+
+    /// Whether alerts are delivered to the webhook
+    pub active: bool,
+
+    /// Creation timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for creating a new webhook
+#[derive(Debug)]
+pub struct NewWebhook {
+    /// URL the alerts are posted to
+    pub url: String,
+
+    /// Secret the payloads are signed with
+    pub secret: String,
+
+    /// Description of the webhook (if available)
+    pub description: Option<String>,
+}
+
+/// Represents a condition in the alerts.conditions table
+#[derive(Debug, FromRow, Clone)]
+pub struct AlertCondition {
+    /// Unique identifier for the condition
+    pub id: i32,
+
+    /// Webhook ID (references alerts.webhooks)
+    pub webhook_id: i32,
+
+    /// Kind of the condition
+    pub kind: AlertConditionKind,
+
+    /// Mint address of the asset token the exchanges must trade (if set)
+    pub asset_mint: Option<String>,
+
+    /// Mint address of the pair token the exchanges must trade (if set)
+    pub pair_mint: Option<String>,
+
+    /// Wallet address that must be the buyer or the seller (if set)
+    pub wallet: Option<String>,
+
+    /// Price or volume threshold of the condition
+    pub threshold: Option<f64>,
+
+    /// Length of the volume window in seconds
+    pub window_seconds: i32,
+
+    /// Minimum time between two alerts of the condition in seconds
+    pub cooldown_seconds: i32,
+
+    /// Block time of the exchange that last triggered the condition
+    pub last_triggered_at: Option<DateTime<Utc>>,
+
+    /// Creation timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for creating a new condition
+#[derive(Debug)]
+pub struct NewAlertCondition {
+    /// Kind of the condition
+    pub kind: AlertConditionKind,
+
+    /// Mint address of the asset token the exchanges must trade (if set)
+    pub asset_mint: Option<String>,
+
+    /// Mint address of the pair token the exchanges must trade (if set)
+    pub pair_mint: Option<String>,
+
+    /// Wallet address that must be the buyer or the seller (if set)
+    pub wallet: Option<String>,
+
+    /// Price or volume threshold of the condition
+    pub threshold: Option<f64>,
+
+    /// Length of the volume window in seconds
+    pub window_seconds: i32,
+
+    /// Minimum time between two alerts of the condition in seconds
+    pub cooldown_seconds: i32,
+}
+
+/// Represents a delivery in the alerts.deliveries table
+#[derive(Debug, FromRow, Clone)]
+pub struct AlertDelivery {
+    /// Unique identifier for the delivery
+    pub id: i32,
+
+    /// Webhook ID (references alerts.webhooks)
+    pub webhook_id: i32,
+
+    /// Condition ID (references alerts.conditions)
+    pub condition_id: i32,
+
+    /// Exchange ID (references market.exchanges)
+    pub exchange_id: i32,
+
+    /// JSON body posted to the webhook
+    pub payload: String,
+
+    /// Status of the delivery
+    pub status: DeliveryStatus,
+
+    /// Number of attempts made
+    pub attempts: i32,
+
+    /// Earliest time of the next attempt
+    pub next_attempt_at: DateTime<Utc>,
+
+    /// HTTP status code of the last attempt (if a response was received)
+    pub last_status_code: Option<i32>,
+
+    /// Error of the last attempt (if it failed)
+    pub last_error: Option<String>,
+
+    /// Creation timestamp
+    pub created_at: DateTime<Utc>,
+
+    /// Time the webhook accepted the delivery
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A delivery claimed for sending, with the webhook it is sent to
+#[derive(Debug, FromRow, Clone)]
+pub struct DueAlertDelivery {
+    /// Delivery ID (references alerts.deliveries)
+    pub id: i32,
+
+    /// Number of attempts made before this one
+    pub attempts: i32,
+
+    /// JSON body posted to the webhook
+    pub payload: String,
+
+    /// URL the delivery is posted to
+    pub url: String,
+
+    /// Secret the payload is signed with
+    pub secret: String,
+}
+
+/// Parameters for recording a delivery attempt
+#[derive(Debug)]
+pub struct NewDeliveryAttempt {
+    /// Delivery ID (references alerts.deliveries)
+    pub delivery_id: i32,
+
+    /// HTTP status code of the response (if one was received)
+    pub status_code: Option<i32>,
+
+    /// Error of the attempt (if it failed)
+    pub error: Option<String>,
+
+    /// Duration of the attempt in milliseconds
+    pub duration_ms: i32,
+}
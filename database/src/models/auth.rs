@@ -0,0 +1,56 @@
+//! Models for the auth schema
+
+use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
+
+use crate::types::ApiKeyScope;
+
+/// Represents an API key in the auth.api_keys table
+#[derive(Debug, FromRow, Clone)]
+pub struct ApiKey {
+    /// Unique identifier for the API key
+    pub id: i32,
+
+    /// Name describing the owner or purpose of the key
+    pub name: String,
+
+    /// First characters of the key, to recognise it without storing it
+    pub key_prefix: String,
+
+    /// Scope of the key
+    pub scope: ApiKeyScope,
+
+    /// Requests per minute the key may make (server default if not set)
+    pub rate_limit: Option<i32>,
+
+    /// Number of requests made with the key
+    pub request_count: i64,
+
+    /// Timestamp of the last request made with the key (if any)
+    pub last_used_at: Option<DateTime<Utc>>,
+
+    /// Timestamp the key was revoked at (if revoked)
+    pub revoked_at: Option<DateTime<Utc>>,
+
+    /// Creation timestamp
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for creating a new API key
+#[derive(Debug)]
+pub struct NewApiKey {
+    /// Name describing the owner or purpose of the key
+    pub name: String,
+
+    /// First characters of the key
+    pub key_prefix: String,
+
+    /// Hex encoded SHA-256 hash of the key
+    pub key_hash: String,
+
+    /// Scope of the key
+    pub scope: ApiKeyScope,
+
+    /// Requests per minute the key may make (server default if not set)
+    pub rate_limit: Option<i32>,
+}
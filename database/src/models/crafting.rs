@@ -0,0 +1,140 @@
+//! Models for the crafting recipes and processes
+
+use serde_json::Value;
+use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Parameters for storing a recipe account
+#[derive(Debug, Clone)]
+pub struct NewRecipe {
+    /// Address of the recipe account
+    pub address: String,
+
+    /// Crafting domain the recipe belongs to
+    pub domain: Option<String>,
+
+    /// Recipe category
+    pub category: Option<String>,
+
+    /// Crafting duration per unit, in seconds
+    pub duration: Option<i64>,
+
+    /// Recipe status, only active recipes can be crafted
+    pub status: Option<String>,
+
+    /// Fee charged per crafting process
+    pub fee_amount: Option<i64>,
+
+    /// Number of times the recipe was used
+    pub usage_count: Option<i64>,
+
+    /// The decoded recipe account
+    pub data: Value,
+
+    /// Inputs and outputs of the recipe
+    pub items: Vec<NewRecipeItem>,
+}
+
+/// Parameters for storing an input or output of a recipe
+#[derive(Debug, Clone)]
+pub struct NewRecipeItem {
+    /// `consumable`, `non_consumable` or `output`
+    pub role: String,
+
+    /// Mint of the item
+    pub mint: String,
+
+    /// Amount per crafted unit
+    pub amount: i64,
+}
+
+/// All recipe accounts of the crafting program at a slot
+#[derive(Debug, Clone, Default)]
+pub struct CraftingSnapshot {
+    /// Slot the accounts were read at
+    pub slot: i64,
+
+    /// Recipe accounts
+    pub recipes: Vec<NewRecipe>,
+}
+
+/// Parameters for storing a step of a crafting process
+#[derive(Debug, Clone)]
+pub struct NewCraftingEvent {
+    /// Index of the transaction instruction
+    pub instruction_index: i32,
+
+    /// 0 for a transaction instruction, otherwise 1 + the index among its inner instructions
+    pub position: i32,
+
+    /// Kind of step, e.g. `started` or `output_claimed`
+    pub kind: String,
+
+    /// Address of the crafting process
+    pub crafting_process: Option<String>,
+
+    /// Address of the recipe being crafted
+    pub recipe: Option<String>,
+
+    /// Address of the crafting player's profile (if crafted through SAGE)
+    pub player_profile: Option<String>,
+
+    /// Address of the starbase crafted at (if crafted through SAGE)
+    pub starbase: Option<String>,
+
+    /// Number of units crafted by the process
+    pub quantity: Option<i64>,
+
+    /// Mint of the moved ingredient or output
+    pub mint: Option<String>,
+
+    /// Amount of the moved ingredient or output
+    pub amount: Option<i64>,
+
+    /// Decoded instruction arguments
+    pub args: Value,
+}
+
+/// Crafting steps of one transaction
+#[derive(Debug, Clone)]
+pub struct CraftingTransaction {
+    /// Transaction signature
+    pub signature: String,
+
+    /// Slot of the transaction
+    pub slot: i64,
+
+    /// Block time of the transaction
+    pub timestamp: DateTime<Utc>,
+
+    /// Steps in instruction order
+    pub events: Vec<NewCraftingEvent>,
+}
+
+/// Production cost of a recipe output compared to its market price
+#[derive(Debug, FromRow, Clone)]
+pub struct RecipeCost {
+    /// Address of the recipe
+    pub recipe: String,
+
+    /// Mint of the produced item
+    pub output_mint: String,
+
+    /// Units produced per crafted unit
+    pub output_amount: i64,
+
+    /// Number of consumed inputs
+    pub inputs: i64,
+
+    /// Number of consumed inputs with a market price
+    pub priced_inputs: i64,
+
+    /// Market value of the consumed inputs per crafted unit, if all inputs have a price
+    pub input_cost: Option<f64>,
+
+    /// Production cost per produced unit, if all inputs have a price
+    pub unit_cost: Option<f64>,
+
+    /// Market price of the produced item
+    pub market_price: Option<f64>,
+}
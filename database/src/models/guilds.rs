@@ -0,0 +1,20 @@
+//! Models for the guilds
+
+/// Parameters for storing a guild with its members
+#[derive(Debug, Clone)]
+pub struct NewGuild {
+    /// Name of the guild
+    pub name: String,
+
+    /// Short tag of the guild
+    pub tag: Option<String>,
+
+    /// `realm` if the members are taken from a DAO realm, `list` if imported
+    pub source: String,
+
+    /// Address of the DAO realm (for realm guilds)
+    pub realm: Option<String>,
+
+    /// Wallet addresses of the members
+    pub members: Vec<String>,
+}
@@ -1,8 +1,8 @@
 //! Models for the indexer table
 
 use crate::types::{Direction, PublicKeyType, SignatureType};
-use sqlx::types::chrono::{DateTime, Utc};
 use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
 
 /// Represents an indexer record in the indexer.indexer table
 #[derive(Debug, FromRow, Clone)]
@@ -0,0 +1,105 @@
+//! Models for the program invocations and events parsed from transaction logs
+
+use serde_json::Value;
+use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Parameters for storing a program invocation
+#[derive(Debug, Clone)]
+pub struct NewProgramInvocation {
+    /// Position of the invocation in the transaction, in the order invocations started
+    pub position: i32,
+
+    /// Index of the transaction instruction the invocation belongs to
+    pub instruction_index: i32,
+
+    /// 1 for transaction instructions, higher for cross-program invocations
+    pub depth: i16,
+
+    /// Address of the invoked program
+    pub program_id: String,
+
+    /// Name from the Anchor `Instruction: <Name>` log (if logged)
+    pub instruction_name: Option<String>,
+
+    /// Compute units consumed, including the invocations it made
+    pub compute_units_consumed: Option<i64>,
+
+    /// Compute units that were available to the invocation
+    pub compute_units_limit: Option<i64>,
+
+    /// Reason the invocation failed (if it failed)
+    pub error: Option<String>,
+
+    /// `Program log:` messages, one per line
+    pub logs: String,
+}
+
+/// Parameters for storing an event emitted by a program invocation
+#[derive(Debug, Clone)]
+pub struct NewProgramEvent {
+    /// Position of the emitting invocation in the transaction
+    pub position: i32,
+
+    /// Index of the event within the invocation
+    pub event_index: i32,
+
+    /// Address of the emitting program
+    pub program_id: String,
+
+    /// Event name (if an IDL of the program knows the event)
+    pub name: Option<String>,
+
+    /// Decoded event fields (if an IDL of the program knows the event)
+    pub data: Option<Value>,
+
+    /// Raw event payload, including the discriminator
+    pub raw: Vec<u8>,
+}
+
+/// Invocations and events of one transaction
+#[derive(Debug, Clone)]
+pub struct TransactionLogs {
+    /// Transaction signature
+    pub signature: String,
+
+    /// Slot of the transaction
+    pub slot: i64,
+
+    /// Block time of the transaction
+    pub timestamp: DateTime<Utc>,
+
+    /// Program invocations
+    pub invocations: Vec<NewProgramInvocation>,
+
+    /// Emitted events
+    pub events: Vec<NewProgramEvent>,
+}
+
+/// Compute unit usage of an instruction type
+#[derive(Debug, FromRow, Clone)]
+pub struct ComputeUnitStats {
+    /// Address of the program
+    pub program_id: String,
+
+    /// Instruction name (if logged)
+    pub instruction_name: Option<String>,
+
+    /// Number of invocations
+    pub invocations: i64,
+
+    /// Number of failed invocations
+    pub failed: i64,
+
+    /// Average compute units consumed
+    pub avg_units: f64,
+
+    /// Median compute units consumed
+    pub p50_units: f64,
+
+    /// 95th percentile of the compute units consumed
+    pub p95_units: f64,
+
+    /// Most compute units consumed
+    pub max_units: i64,
+}
@@ -0,0 +1,130 @@
+//! Models for the snapshots of the marketplace program accounts in the market schema
+
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Parameters for storing an order account
+#[derive(Debug, Clone)]
+pub struct NewOrderAccount {
+    /// Address of the order account
+    pub address: String,
+
+    /// Wallet address of the player that placed the order
+    pub order_initializer: String,
+
+    /// Mint address of the currency the order is priced in
+    pub currency_mint: String,
+
+    /// Mint address of the asset the order trades
+    pub asset_mint: String,
+
+    /// Token account the currency of the order is paid from or to
+    pub initializer_currency_token_account: String,
+
+    /// Token account the asset of the order is paid from or to
+    pub initializer_asset_token_account: String,
+
+    /// Side of the order (buy/sell)
+    pub side: String,
+
+    /// Price per asset in raw currency units
+    pub price: i64,
+
+    /// Quantity the order was placed with
+    pub origination_qty: i64,
+
+    /// Quantity still open
+    pub remaining_qty: i64,
+
+    /// Time the order was placed
+    pub created_at: DateTime<Utc>,
+}
+
+/// Parameters for storing a registered currency
+#[derive(Debug, Clone)]
+pub struct NewRegisteredCurrency {
+    /// Address of the registered currency account
+    pub address: String,
+
+    /// Mint address of the currency
+    pub token_mint: String,
+
+    /// Vault the fees of the currency are paid to
+    pub sa_currency_vault: String,
+
+    /// Fee rate (1_000_000 = 100%)
+    pub royalty: i64,
+
+    /// Staked amounts of the royalty tiers
+    pub royalty_tier_stake_amounts: Vec<i64>,
+
+    /// Fee discounts of the royalty tiers (1_000_000 = 100%)
+    pub royalty_tier_discounts: Vec<i64>,
+}
+
+/// Parameters for storing a fee reduction
+#[derive(Debug, Clone)]
+pub struct NewFeeReduction {
+    /// Address of the fee reduction account
+    pub address: String,
+
+    /// Account the fee reduction applies to
+    pub account: String,
+
+    /// Fee discount (1_000_000 = 100%)
+    pub discount: i64,
+}
+
+/// Parameters for storing the market vars
+#[derive(Debug, Clone)]
+pub struct NewMarketVars {
+    /// Address of the market vars account
+    pub address: String,
+
+    /// Authority allowed to update the marketplace
+    pub update_authority_master: String,
+}
+
+/// Parameters for storing an ATLAS rate
+#[derive(Debug, Clone)]
+pub struct NewAtlasRate {
+    /// Address of the ATLAS rate account
+    pub address: String,
+
+    /// ATLAS price divided by 100, in USDC units per ATLAS unit
+    pub atlas_rate: i64,
+}
+
+/// Parameters for storing an open orders counter
+#[derive(Debug, Clone)]
+pub struct NewOpenOrdersCounter {
+    /// Address of the open orders counter account
+    pub address: String,
+
+    /// Number of open orders counted
+    pub open_order_count: i64,
+}
+
+/// All marketplace program accounts at one slot
+#[derive(Debug, Clone, Default)]
+pub struct MarketplaceSnapshot {
+    /// Slot the snapshot was taken at
+    pub slot: i64,
+
+    /// Open orders
+    pub orders: Vec<NewOrderAccount>,
+
+    /// Registered currencies
+    pub currencies: Vec<NewRegisteredCurrency>,
+
+    /// Fee reductions
+    pub fee_reductions: Vec<NewFeeReduction>,
+
+    /// Market vars
+    pub market_vars: Vec<NewMarketVars>,
+
+    /// ATLAS rates
+    pub atlas_rates: Vec<NewAtlasRate>,
+
+    /// Open orders counters
+    pub open_orders_counters: Vec<NewOpenOrdersCounter>,
+}
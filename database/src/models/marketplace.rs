@@ -2,6 +2,8 @@
 
 use sqlx::FromRow;
 use sqlx::types::chrono::{DateTime, Utc};
+use std::fmt;
+use std::str::FromStr;
 
 /// Represents an exchange record in the market.exchanges table
 #[derive(Debug, FromRow, Clone)]
@@ -50,6 +52,91 @@ pub struct Exchange {
 
     /// Buddy fee of the exchange
     pub buddy: f64,
+
+    /// Version of the processor that wrote the exchange
+    pub processor_version: i32,
+}
+
+/// Represents an exchange joined with its buyer, seller, asset and pair
+#[derive(Debug, FromRow, Clone)]
+pub struct ExchangeDetailed {
+    /// Unique identifier for the exchange
+    pub id: i32,
+
+    /// Block number of the exchange
+    pub slot: i32,
+
+    /// Transaction signature
+    pub signature: String,
+
+    /// Index within the transaction
+    pub index: i32,
+
+    /// Timestamp of the exchange
+    pub timestamp: DateTime<Utc>,
+
+    /// Side of the exchange (buy/sell)
+    pub side: String,
+
+    /// Buyer ID (references staratlas.players)
+    pub buyer_id: i32,
+
+    /// Wallet address of the buyer
+    pub buyer_wallet: String,
+
+    /// Username of the buyer (if available)
+    pub buyer_username: Option<String>,
+
+    /// Seller ID (references staratlas.players)
+    pub seller_id: i32,
+
+    /// Wallet address of the seller
+    pub seller_wallet: String,
+
+    /// Username of the seller (if available)
+    pub seller_username: Option<String>,
+
+    /// Asset ID (references staratlas.tokens)
+    pub asset_id: i32,
+
+    /// Mint address of the asset token
+    pub asset_mint: String,
+
+    /// Symbol of the asset token (if available)
+    pub asset_symbol: Option<String>,
+
+    /// Name of the asset token (if available)
+    pub asset_name: Option<String>,
+
+    /// Pair ID (references staratlas.tokens)
+    pub pair_id: i32,
+
+    /// Mint address of the pair token
+    pub pair_mint: String,
+
+    /// Symbol of the pair token (if available)
+    pub pair_symbol: Option<String>,
+
+    /// Name of the pair token (if available)
+    pub pair_name: Option<String>,
+
+    /// Price of the exchange
+    pub price: f64,
+
+    /// Size of the exchange
+    pub size: i32,
+
+    /// Volume of the exchange
+    pub volume: f64,
+
+    /// Fee of the exchange
+    pub fee: f64,
+
+    /// Buddy fee of the exchange
+    pub buddy: f64,
+
+    /// Version of the processor that wrote the exchange
+    pub processor_version: i32,
 }
 
 /// Parameters for creating a new exchange
@@ -96,6 +183,9 @@ pub struct NewExchange {
 
     /// Buddy fee of the exchange
     pub buddy: f64,
+
+    /// Version of the processor that wrote the exchange
+    pub processor_version: i32,
 }
 
 /// Parameters for creating a new exchange with its dependent entities
@@ -128,6 +218,12 @@ pub struct ExchangeWithDependencies {
     /// Mint address of the pair token
     pub pair_mint: String,
 
+    /// Decimals of the asset token, if a checked transfer gave them
+    pub asset_decimals: Option<i16>,
+
+    /// Decimals of the pair token, if a checked transfer gave them
+    pub pair_decimals: Option<i16>,
+
     /// Price of the exchange
     pub price: f64,
 
@@ -142,4 +238,424 @@ pub struct ExchangeWithDependencies {
 
     /// Buddy fee of the exchange
     pub buddy: f64,
+
+    /// Version of the processor that wrote the exchange
+    pub processor_version: i32,
+}
+
+/// Exchanges of one marketplace transaction
+#[derive(Debug)]
+pub struct MarketplaceTransaction {
+    /// Transaction signature
+    pub signature: String,
+
+    /// Exchanges in instruction order
+    pub exchanges: Vec<ExchangeWithDependencies>,
+}
+
+/// A player's side of an exchange, joined with the counterparty and tokens
+///
+/// An exchange in which the player is both buyer and seller yields one trade per side.
+#[derive(Debug, FromRow, Clone)]
+pub struct PlayerTrade {
+    /// Exchange ID (references market.exchanges)
+    pub id: i32,
+
+    /// Block number of the exchange
+    pub slot: i32,
+
+    /// Transaction signature
+    pub signature: String,
+
+    /// Index within the transaction
+    pub index: i32,
+
+    /// Timestamp of the exchange
+    pub timestamp: DateTime<Utc>,
+
+    /// Side of the exchange (buy/sell)
+    pub side: String,
+
+    /// Role of the player in the exchange (BUY when the player was the buyer, SELL otherwise)
+    pub role: String,
+
+    /// Wallet address of the other party
+    pub counterparty: String,
+
+    /// Asset ID (references staratlas.tokens)
+    pub asset_id: i32,
+
+    /// Mint address of the asset token
+    pub asset_mint: String,
+
+    /// Symbol of the asset token (if available)
+    pub asset_symbol: Option<String>,
+
+    /// Pair ID (references staratlas.tokens)
+    pub pair_id: i32,
+
+    /// Mint address of the pair token
+    pub pair_mint: String,
+
+    /// Symbol of the pair token (if available)
+    pub pair_symbol: Option<String>,
+
+    /// Price of the exchange
+    pub price: f64,
+
+    /// Size of the exchange
+    pub size: i32,
+
+    /// Volume of the exchange
+    pub volume: f64,
+
+    /// Fee of the exchange
+    pub fee: f64,
+
+    /// Buddy fee of the exchange
+    pub buddy: f64,
+}
+
+/// An exchange in which a player was the buyer or the seller, tagged with the player
+#[derive(Debug, FromRow, Clone)]
+pub struct PlayerExchange {
+    /// ID of the player the exchange was loaded for
+    pub player_id: i32,
+
+    /// The exchange
+    #[sqlx(flatten)]
+    pub exchange: Exchange,
+}
+
+/// Statistics of a market (asset and pair) read from the hourly market aggregates
+#[derive(Debug, FromRow, Clone)]
+pub struct MarketStats {
+    /// Asset ID (references staratlas.tokens)
+    pub asset_id: i32,
+
+    /// Mint address of the asset token
+    pub asset_mint: String,
+
+    /// Symbol of the asset token (if available)
+    pub asset_symbol: Option<String>,
+
+    /// Name of the asset token (if available)
+    pub asset_name: Option<String>,
+
+    /// Pair ID (references staratlas.tokens)
+    pub pair_id: i32,
+
+    /// Mint address of the pair token
+    pub pair_mint: String,
+
+    /// Symbol of the pair token (if available)
+    pub pair_symbol: Option<String>,
+
+    /// Price of the last exchange
+    pub last_price: Option<f64>,
+
+    /// Timestamp of the last exchange
+    pub last_timestamp: Option<DateTime<Utc>>,
+
+    /// Number of exchanges in the last 24 hours
+    pub trade_count_24h: i64,
+
+    /// Volume of the last 24 hours
+    pub volume_24h: f64,
+
+    /// Volume weighted average price of the last 24 hours
+    pub vwap_24h: Option<f64>,
+
+    /// Highest price of the last 24 hours
+    pub high_24h: Option<f64>,
+
+    /// Lowest price of the last 24 hours
+    pub low_24h: Option<f64>,
+
+    /// Price change of the last 24 hours in percent
+    pub price_change_24h: Option<f64>,
+
+    /// Number of distinct buyers and sellers in the last 24 hours
+    pub unique_traders_24h: i64,
+
+    /// Number of exchanges in the last 7 days
+    pub trade_count_7d: i64,
+
+    /// Volume of the last 7 days
+    pub volume_7d: f64,
+
+    /// Volume weighted average price of the last 7 days
+    pub vwap_7d: Option<f64>,
+}
+
+/// Position of an exchange in the `(slot, signature, index)` ordering used for pagination
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExchangeCursor {
+    /// Block number of the exchange
+    pub slot: i32,
+
+    /// Transaction signature
+    pub signature: String,
+
+    /// Index within the transaction
+    pub index: i32,
+}
+
+impl fmt::Display for ExchangeCursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}:{}", self.slot, self.signature, self.index)
+    }
+}
+
+impl FromStr for ExchangeCursor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.split(':');
+        match (parts.next(), parts.next(), parts.next(), parts.next()) {
+            (Some(slot), Some(signature), Some(index), None) if !signature.is_empty() => Ok(Self {
+                slot: slot
+                    .parse()
+                    .map_err(|_| format!("invalid cursor slot: {slot}"))?,
+                signature: signature.to_string(),
+                index: index
+                    .parse()
+                    .map_err(|_| format!("invalid cursor index: {index}"))?,
+            }),
+            _ => Err(format!("invalid cursor: {s}")),
+        }
+    }
+}
+
+/// Filter for querying exchanges
+///
+/// All set fields are combined with AND. Ranges are inclusive.
+#[derive(Debug, Clone, Default)]
+pub struct ExchangeFilter {
+    /// Wallet address of the buyer
+    pub buyer: Option<String>,
+
+    /// Wallet address of the seller
+    pub seller: Option<String>,
+
+    /// Wallet address of either the buyer or the seller
+    pub wallet: Option<String>,
+
+    /// Buyer ID (references staratlas.players)
+    pub buyer_id: Option<i32>,
+
+    /// Seller ID (references staratlas.players)
+    pub seller_id: Option<i32>,
+
+    /// Asset ID (references staratlas.tokens)
+    pub asset_id: Option<i32>,
+
+    /// Mint address of the asset token
+    pub asset_mint: Option<String>,
+
+    /// Mint address of the pair token
+    pub pair_mint: Option<String>,
+
+    /// Side of the exchange (buy/sell)
+    pub side: Option<String>,
+
+    /// Transaction signature
+    pub signature: Option<String>,
+
+    /// Earliest timestamp of the exchange
+    pub from_timestamp: Option<DateTime<Utc>>,
+
+    /// Latest timestamp of the exchange
+    pub to_timestamp: Option<DateTime<Utc>>,
+
+    /// Lowest block number of the exchange
+    pub from_slot: Option<i32>,
+
+    /// Highest block number of the exchange
+    pub to_slot: Option<i32>,
+
+    /// Minimum price of the exchange
+    pub min_price: Option<f64>,
+
+    /// Maximum price of the exchange
+    pub max_price: Option<f64>,
+
+    /// Minimum volume of the exchange
+    pub min_volume: Option<f64>,
+
+    /// Maximum volume of the exchange
+    pub max_volume: Option<f64>,
+
+    /// Only return exchanges ordered after this cursor (i.e. older than it)
+    pub after: Option<ExchangeCursor>,
+}
+
+/// Value players are ranked by on the leaderboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMetric {
+    /// Traded volume
+    Volume,
+    /// Number of exchanges
+    Trades,
+    /// Marketplace fees of the exchanges
+    Fees,
+    /// Buddy fees of the exchanges
+    Buddy,
+}
+
+/// Side of the exchanges counted for a player on the leaderboard
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LeaderboardMode {
+    /// Only exchanges in which the player was the buyer
+    Buyer,
+    /// Only exchanges in which the player was the seller
+    Seller,
+    /// Exchanges in which the player was the buyer or the seller
+    Combined,
+}
+
+/// Parameters for ranking players on the leaderboard
+#[derive(Debug, Clone)]
+pub struct LeaderboardFilter {
+    /// Value to rank by
+    pub metric: LeaderboardMetric,
+
+    /// Side of the exchanges to count
+    pub mode: LeaderboardMode,
+
+    /// Mint address of the pair token the exchanges were paid in
+    pub currency_mint: Option<String>,
+
+    /// Earliest timestamp of the exchanges
+    pub from_timestamp: Option<DateTime<Utc>>,
+
+    /// Latest timestamp of the exchanges
+    pub to_timestamp: Option<DateTime<Utc>>,
+}
+
+/// Player attribute exchanges and leaderboards can be grouped by
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PlayerGroup {
+    /// Faction of the player's profile
+    Faction,
+    /// Guild the player is a member of
+    Guild,
+}
+
+/// Represents a faction's or guild's position on the leaderboard
+#[derive(Debug, FromRow, Clone)]
+pub struct GroupLeaderboardEntry {
+    /// Rank of the group, shared by groups with the same value
+    pub rank: i64,
+
+    /// Faction or guild, None for the players without one
+    pub group: Option<String>,
+
+    /// Number of players of the group with exchanges counted
+    pub players: i64,
+
+    /// Number of exchanges counted
+    pub trade_count: i64,
+
+    /// Volume of the exchanges counted
+    pub volume: f64,
+
+    /// Marketplace fees of the exchanges counted
+    pub fees: f64,
+
+    /// Buddy fees of the exchanges counted
+    pub buddy: f64,
+}
+
+/// Exchanges between the players of two factions or guilds in one currency
+#[derive(Debug, FromRow, Clone)]
+pub struct ExchangeFlow {
+    /// Faction or guild of the buyers, None for the players without one
+    pub buyer_group: Option<String>,
+
+    /// Faction or guild of the sellers, None for the players without one
+    pub seller_group: Option<String>,
+
+    /// Mint address of the pair token the exchanges were paid in
+    pub pair_mint: String,
+
+    /// Symbol of the pair token
+    pub pair_symbol: Option<String>,
+
+    /// Number of exchanges
+    pub trade_count: i64,
+
+    /// Number of asset units exchanged
+    pub size: i64,
+
+    /// Volume of the exchanges
+    pub volume: f64,
+}
+
+/// Represents a player's position on the leaderboard
+#[derive(Debug, FromRow, Clone)]
+pub struct LeaderboardEntry {
+    /// Rank of the player, shared by players with the same value
+    pub rank: i64,
+
+    /// Player ID (references staratlas.players)
+    pub player_id: i32,
+
+    /// Wallet address of the player
+    pub wallet_address: String,
+
+    /// Username of the player (if available)
+    pub username: Option<String>,
+
+    /// Number of exchanges counted
+    pub trade_count: i64,
+
+    /// Volume of the exchanges counted
+    pub volume: f64,
+
+    /// Marketplace fees of the exchanges counted
+    pub fees: f64,
+
+    /// Buddy fees of the exchanges counted
+    pub buddy: f64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exchange_cursor_round_trips_through_its_string() {
+        let cursor = ExchangeCursor {
+            slot: 250_000_000,
+            signature: "5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW".to_string(),
+            index: 2,
+        };
+
+        let encoded = cursor.to_string();
+
+        assert_eq!(
+            encoded,
+            "250000000:5VERv8NMvzbJMEkV8xnrLkEaWRtSz9CosKDYjCJjBRnbJLgp8uirBgmQpjKhoR4tjF3ZpRzrFmBV6UjKdiSZkQUW:2"
+        );
+        assert_eq!(encoded.parse::<ExchangeCursor>(), Ok(cursor));
+    }
+
+    #[test]
+    fn exchange_cursor_rejects_malformed_strings() {
+        for (input, error) in [
+            ("", "invalid cursor: "),
+            ("1:sig", "invalid cursor: 1:sig"),
+            ("1:sig:0:extra", "invalid cursor: 1:sig:0:extra"),
+            ("1::0", "invalid cursor: 1::0"),
+            ("slot:sig:0", "invalid cursor slot: slot"),
+            ("1:sig:-", "invalid cursor index: -"),
+        ] {
+            assert_eq!(
+                input.parse::<ExchangeCursor>(),
+                Err(error.to_string()),
+                "{input}"
+            );
+        }
+    }
 }
@@ -1,13 +1,48 @@
 //! Database models
 
+mod alerts;
+mod auth;
+mod crafting;
+mod guilds;
 mod indexer;
+mod logs;
+mod market_accounts;
 mod marketplace;
+mod player_profiles;
+mod sage;
+mod score;
 mod signature;
 mod staratlas;
 
+pub use alerts::{
+    AlertCondition, AlertDelivery, DueAlertDelivery, NewAlertCondition, NewDeliveryAttempt,
+    NewWebhook, Webhook,
+};
+pub use auth::{ApiKey, NewApiKey};
+pub use crafting::{
+    CraftingSnapshot, CraftingTransaction, NewCraftingEvent, NewRecipe, NewRecipeItem, RecipeCost,
+};
+pub use guilds::NewGuild;
 pub use indexer::{Indexer, NewIndexer, UpdateIndexer};
-pub use marketplace::{Exchange, ExchangeWithDependencies, NewExchange};
+pub use logs::{ComputeUnitStats, NewProgramEvent, NewProgramInvocation, TransactionLogs};
+pub use market_accounts::{
+    MarketplaceSnapshot, NewAtlasRate, NewFeeReduction, NewMarketVars, NewOpenOrdersCounter,
+    NewOrderAccount, NewRegisteredCurrency,
+};
+pub use marketplace::{
+    Exchange, ExchangeCursor, ExchangeDetailed, ExchangeFilter, ExchangeFlow,
+    ExchangeWithDependencies, GroupLeaderboardEntry, LeaderboardEntry, LeaderboardFilter,
+    LeaderboardMetric, LeaderboardMode, MarketStats, MarketplaceTransaction, NewExchange,
+    PlayerExchange, PlayerGroup, PlayerTrade,
+};
+pub use player_profiles::{
+    FactionSnapshot, NewProfile, NewProfileFaction, NewProfileKey, Profile, ProfileKey,
+    ProfileSnapshot,
+};
+pub use sage::{NewSageActivity, NewSageTransfer, SageTransaction};
+pub use score::{NewScoreEvent, ScoreTransaction, StakingPosition, StakingRoi};
 pub use signature::{
-    NewProgram, NewProgramSignature, NewSignature, Program, ProgramSignature, Signature,
+    NewProgram, NewProgramSignature, NewSignature, Program, ProgramSignature, ProgramWatermark,
+    Signature,
 };
-pub use staratlas::{NewPlayer, NewToken, Player, Token};
+pub use staratlas::{NewPlayer, NewToken, Player, PlayerVolume, Token};
@@ -0,0 +1,113 @@
+//! Models for the player profiles
+
+use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Parameters for storing a profile account
+#[derive(Debug, Clone)]
+pub struct NewProfile {
+    /// Address of the profile account
+    pub address: String,
+
+    /// Number of leading keys that are auth keys
+    pub auth_key_count: i16,
+
+    /// Number of auth keys required to change the profile
+    pub key_threshold: i16,
+
+    /// When the profile was created
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// Username from the profile's player name account
+    pub name: Option<String>,
+
+    /// Keys of the profile, auth keys first
+    pub keys: Vec<NewProfileKey>,
+}
+
+/// Parameters for storing a key of a profile
+#[derive(Debug, Clone)]
+pub struct NewProfileKey {
+    /// The key (a wallet for auth keys)
+    pub key: String,
+
+    /// Program the key may be used with
+    pub scope: String,
+
+    /// Unix timestamp the key expires at, negative if it never does
+    pub expire_time: i64,
+
+    /// Program specific permission flags
+    pub permissions: Vec<u8>,
+
+    /// Whether the key is an auth key, owning the profile
+    pub is_auth: bool,
+}
+
+/// All profile accounts of the Player Profile program at a slot
+#[derive(Debug, Clone, Default)]
+pub struct ProfileSnapshot {
+    /// Slot the accounts were read at
+    pub slot: i64,
+
+    /// Profile accounts
+    pub profiles: Vec<NewProfile>,
+}
+
+/// Represents a profile record in the staratlas.profiles table
+#[derive(Debug, FromRow, Clone)]
+pub struct Profile {
+    /// Address of the profile account
+    pub address: String,
+
+    /// Number of leading keys that are auth keys
+    pub auth_key_count: i16,
+
+    /// Number of auth keys required to change the profile
+    pub key_threshold: i16,
+
+    /// When the profile was created
+    pub created_at: Option<DateTime<Utc>>,
+
+    /// Username of the profile
+    pub name: Option<String>,
+}
+
+/// Represents a key record in the staratlas.profile_keys table
+#[derive(Debug, FromRow, Clone)]
+pub struct ProfileKey {
+    /// Address of the profile
+    pub profile: String,
+
+    /// The key (a wallet for auth keys)
+    pub key: String,
+
+    /// Program the key may be used with
+    pub scope: String,
+
+    /// Unix timestamp the key expires at, negative if it never does
+    pub expire_time: i64,
+
+    /// Whether the key is an auth key, owning the profile
+    pub is_auth: bool,
+}
+
+/// Parameters for storing the faction of a profile
+#[derive(Debug, Clone)]
+pub struct NewProfileFaction {
+    /// Address of the profile
+    pub profile: String,
+
+    /// MUD, ONI, USTUR or UNALIGNED
+    pub faction: String,
+}
+
+/// All profile faction accounts of the Profile Faction program at a slot
+#[derive(Debug, Clone, Default)]
+pub struct FactionSnapshot {
+    /// Slot the accounts were read at
+    pub slot: i64,
+
+    /// Factions of the profiles
+    pub factions: Vec<NewProfileFaction>,
+}
@@ -0,0 +1,79 @@
+//! Models for the SAGE fleet activity
+
+use serde_json::Value;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Parameters for storing a SAGE instruction as fleet activity
+#[derive(Debug, Clone)]
+pub struct NewSageActivity {
+    /// Index of the instruction in the transaction
+    pub instruction_index: i32,
+
+    /// Kind of activity, e.g. `warp` or `mining_stop`
+    pub kind: String,
+
+    /// Name of the SAGE instruction
+    pub instruction: String,
+
+    /// Address of the fleet (if the instruction acts on one)
+    pub fleet: Option<String>,
+
+    /// Address of the player profile owning the fleet
+    pub player_profile: Option<String>,
+
+    /// Address of the starbase (if the instruction involves one)
+    pub starbase: Option<String>,
+
+    /// Destination sector of a movement
+    pub sector: Option<(i64, i64)>,
+
+    /// Mint of the moved cargo or mined resource
+    pub resource_mint: Option<String>,
+
+    /// Amount given in the instruction arguments, e.g. the deposited cargo
+    pub amount: Option<i64>,
+
+    /// Name of the created fleet
+    pub label: Option<String>,
+
+    /// Decoded instruction arguments
+    pub args: Value,
+
+    /// Token movements made by the instruction
+    pub transfers: Vec<NewSageTransfer>,
+}
+
+/// Parameters for storing a token movement of a SAGE activity
+#[derive(Debug, Clone)]
+pub struct NewSageTransfer {
+    /// `transfer`, `mint` or `burn`
+    pub kind: String,
+
+    /// Mint of the token (not logged by unchecked transfers)
+    pub mint: Option<String>,
+
+    /// Token account the tokens were taken from (none for mints)
+    pub source: Option<String>,
+
+    /// Token account the tokens were moved to (none for burns)
+    pub destination: Option<String>,
+
+    /// Amount in base units
+    pub amount: i64,
+}
+
+/// SAGE activities of one transaction
+#[derive(Debug, Clone)]
+pub struct SageTransaction {
+    /// Transaction signature
+    pub signature: String,
+
+    /// Slot of the transaction
+    pub slot: i64,
+
+    /// Block time of the transaction
+    pub timestamp: DateTime<Utc>,
+
+    /// Activities in instruction order
+    pub activities: Vec<NewSageActivity>,
+}
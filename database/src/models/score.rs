@@ -0,0 +1,108 @@
+//! Models for the SCORE fleet staking
+
+use serde_json::Value;
+use sqlx::FromRow;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Parameters for storing a SCORE instruction as staking event
+#[derive(Debug, Clone)]
+pub struct NewScoreEvent {
+    /// Index of the instruction in the transaction
+    pub instruction_index: i32,
+
+    /// Kind of event, e.g. `stake` or `claim`
+    pub kind: String,
+
+    /// Name of the SCORE instruction
+    pub instruction: String,
+
+    /// Wallet of the staking player
+    pub player: Option<String>,
+
+    /// Mint of the staked ship
+    pub ship_mint: Option<String>,
+
+    /// Resupplied resource: `food`, `fuel`, `ammo` or `toolkit`
+    pub resource: Option<String>,
+
+    /// Mint of the moved tokens: the ship, the resource or ATLAS
+    pub mint: Option<String>,
+
+    /// Moved amount in base units
+    pub amount: Option<i64>,
+
+    /// Decoded instruction arguments
+    pub args: Value,
+}
+
+/// SCORE events of one transaction
+#[derive(Debug, Clone)]
+pub struct ScoreTransaction {
+    /// Transaction signature
+    pub signature: String,
+
+    /// Slot of the transaction
+    pub slot: i64,
+
+    /// Block time of the transaction
+    pub timestamp: DateTime<Utc>,
+
+    /// Events in instruction order
+    pub events: Vec<NewScoreEvent>,
+}
+
+/// Staking return of a ship over a period
+#[derive(Debug, FromRow, Clone)]
+pub struct StakingRoi {
+    /// Mint of the ship
+    pub ship_mint: String,
+
+    /// Ships staked at the end of the period, in base units of the ship mint
+    pub ships_staked: i64,
+
+    /// Players with ships staked at the end of the period
+    pub stakers: i64,
+
+    /// ATLAS claimed in the period
+    pub rewards: f64,
+
+    /// Market value in ATLAS of the resources supplied in the period, if all have a price
+    pub resupply_cost: Option<f64>,
+
+    /// Average market price of the ship in ATLAS
+    pub ship_price: Option<f64>,
+
+    /// Rewards minus resupply cost relative to the market value of the staked ships
+    pub roi: Option<f64>,
+}
+
+/// Staking position of a player in one ship
+#[derive(Debug, FromRow, Clone)]
+pub struct StakingPosition {
+    /// Mint of the ship
+    pub ship_mint: String,
+
+    /// Ships staked since the last withdrawal
+    pub ships_staked: i64,
+
+    /// Time of the first stake since the last withdrawal
+    pub staked_at: Option<DateTime<Utc>>,
+
+    /// ATLAS claimed in total
+    pub rewards: f64,
+
+    /// Time of the last claim
+    pub last_claim_at: Option<DateTime<Utc>>,
+
+    /// Food supplied minus food withdrawn
+    pub food: i64,
+
+    /// Fuel supplied minus fuel withdrawn
+    pub fuel: i64,
+
+    /// Ammo supplied minus ammo withdrawn
+    pub ammo: i64,
+
+    /// Toolkits supplied
+    pub toolkit: i64,
+}
@@ -39,8 +39,21 @@ pub struct ProgramSignature {
     /// The signature itself
     pub signature: SignatureType,
 
+    /// The slot number of the signature
+    pub slot: i64,
+
+    /// Position of the transaction in its block, `None` for signatures indexed before it was
+    /// recorded
+    pub transaction_index: Option<i32>,
+
     /// Whether the signature has been processed
     pub processed: bool,
+
+    /// Worker currently holding a claim on the signature
+    pub claimed_by: Option<String>,
+
+    /// When the claim of the worker expires
+    pub claimed_until: Option<DateTime<Utc>>,
 }
 
 /// Parameters for creating a new program signature record in the indexer.program_signatures table
@@ -52,6 +65,12 @@ pub struct NewProgramSignature {
     /// The signature itself
     pub signature: SignatureType,
 
+    /// The slot number of the signature
+    pub slot: i64,
+
+    /// Position of the transaction in its block
+    pub transaction_index: Option<i32>,
+
     /// Whether the signature has been processed
     pub processed: bool,
 }
@@ -69,3 +88,19 @@ pub struct NewProgram {
     /// Program ID
     pub program_id: PublicKeyType,
 }
+
+/// Represents a processed-slot watermark in the indexer.program_watermarks table
+#[derive(Debug, FromRow, Clone)]
+pub struct ProgramWatermark {
+    /// Program ID
+    pub program_id: PublicKeyType,
+
+    /// Highest slot up to which all indexed signatures are processed
+    pub slot: Option<i64>,
+
+    /// Block time of the watermark slot
+    pub timestamp: Option<DateTime<Utc>>,
+
+    /// When the watermark was last recalculated
+    pub updated_at: DateTime<Utc>,
+}
@@ -55,6 +55,21 @@ pub struct Player {
 
     /// When the player was last active
     pub last_active: DateTime<Utc>,
+
+    /// Number of exchanges in which the player was the buyer
+    pub buy_count: i32,
+
+    /// Number of exchanges in which the player was the seller
+    pub sell_count: i32,
+
+    /// Number of distinct assets the player traded
+    pub assets_traded: i32,
+
+    /// Faction of the player's profile (MUD, ONI or USTUR, if known)
+    pub faction: Option<String>,
+
+    /// Guild the player is a member of (if known)
+    pub guild: Option<String>,
 }
 
 /// Parameters for creating a new player
@@ -72,3 +87,25 @@ pub struct NewPlayer {
     /// When the player was last active
     pub last_active: DateTime<Utc>,
 }
+
+/// Represents the traded volume of a player in one currency (staratlas.player_volumes)
+#[derive(Debug, FromRow, Clone)]
+pub struct PlayerVolume {
+    /// Player ID (references staratlas.players)
+    pub player_id: i32,
+
+    /// Currency ID (references staratlas.tokens)
+    pub currency_id: i32,
+
+    /// Mint address of the currency
+    pub currency_mint: String,
+
+    /// Symbol of the currency
+    pub currency_symbol: Option<String>,
+
+    /// Volume the player bought for
+    pub buy_volume: f64,
+
+    /// Volume the player sold for
+    pub sell_volume: f64,
+}
@@ -0,0 +1,501 @@
+//! Database queries for the alerts schema
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{
+    AlertCondition, AlertDelivery, DueAlertDelivery, ExchangeDetailed, NewAlertCondition,
+    NewDeliveryAttempt, NewWebhook, Webhook,
+};
+use crate::queries::marketplace::EXCHANGES_DETAILED;
+use crate::types::DeliveryStatus;
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Creates a new webhook with its conditions
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `new_webhook` - The webhook to create
+/// * `new_conditions` - The conditions that trigger the webhook
+///
+/// # Returns
+/// The created webhook and conditions with their assigned IDs
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn create_webhook(
+    pool: &DbPool,
+    new_webhook: &NewWebhook,
+    new_conditions: &[NewAlertCondition],
+) -> Result<(Webhook, Vec<AlertCondition>)> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    let webhook = sqlx::query_as::<_, Webhook>(
+        r#"
+        INSERT INTO alerts.webhooks (url, secret, description)
+        VALUES ($1, $2, $3)
+        RETURNING id, url, secret, description, active, created_at
+        "#,
+    )
+    .bind(&new_webhook.url)
+    .bind(&new_webhook.secret)
+    .bind(&new_webhook.description)
+    .fetch_one(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    let mut conditions = Vec::with_capacity(new_conditions.len());
+    for new_condition in new_conditions {
+        let condition = sqlx::query_as::<_, AlertCondition>(
+            r#"
+            INSERT INTO alerts.conditions (
+                webhook_id, kind, asset_mint, pair_mint, wallet, threshold, window_seconds, cooldown_seconds
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING id, webhook_id, kind, asset_mint, pair_mint, wallet, threshold, window_seconds, cooldown_seconds, last_triggered_at, created_at
+            "#,
+        )
+        .bind(webhook.id)
+        .bind(new_condition.kind)
+        .bind(&new_condition.asset_mint)
+        .bind(&new_condition.pair_mint)
+        .bind(&new_condition.wallet)
+        .bind(new_condition.threshold)
+        .bind(new_condition.window_seconds)
+        .bind(new_condition.cooldown_seconds)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        conditions.push(condition);
+    }
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok((webhook, conditions))
+}
+
+/// Retrieves a webhook by its ID
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `id` - The ID of the webhook to retrieve
+///
+/// # Returns
+/// The webhook with the specified ID, or None if no such webhook exists
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_webhook_by_id(pool: &DbPool, id: i32) -> Result<Option<Webhook>> {
+    let webhook = sqlx::query_as::<_, Webhook>(
+        r#"
+        SELECT id, url, secret, description, active, created_at
+        FROM alerts.webhooks
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(webhook)
+}
+
+/// Deletes a webhook with its conditions and deliveries
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `id` - The ID of the webhook to delete
+///
+/// # Returns
+/// True if the webhook existed
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn delete_webhook(pool: &DbPool, id: i32) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM alerts.webhooks
+        WHERE id = $1
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Retrieves the conditions of a webhook
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `webhook_id` - The ID of the webhook
+///
+/// # Returns
+/// A vector of the conditions of the webhook
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_alert_conditions_by_webhook_id(
+    pool: &DbPool,
+    webhook_id: i32,
+) -> Result<Vec<AlertCondition>> {
+    let conditions = sqlx::query_as::<_, AlertCondition>(
+        r#"
+        SELECT id, webhook_id, kind, asset_mint, pair_mint, wallet, threshold, window_seconds, cooldown_seconds, last_triggered_at, created_at
+        FROM alerts.conditions
+        WHERE webhook_id = $1
+        ORDER BY id
+        "#,
+    )
+    .bind(webhook_id)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(conditions)
+}
+
+/// Retrieves the deliveries of a webhook, newest first
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `webhook_id` - The ID of the webhook
+/// * `limit` - Maximum number of deliveries to return
+/// * `offset` - Number of deliveries to skip
+///
+/// # Returns
+/// A vector of the deliveries of the webhook
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_alert_deliveries_by_webhook_id(
+    pool: &DbPool,
+    webhook_id: i32,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<AlertDelivery>> {
+    let deliveries = sqlx::query_as::<_, AlertDelivery>(
+        r#"
+        SELECT id, webhook_id, condition_id, exchange_id, payload, status, attempts, next_attempt_at, last_status_code, last_error, created_at, delivered_at
+        FROM alerts.deliveries
+        WHERE webhook_id = $1
+        ORDER BY id DESC
+        LIMIT $2 OFFSET $3
+        "#,
+    )
+    .bind(webhook_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(deliveries)
+}
+
+/// Claims a batch of queued exchanges for evaluation
+///
+/// Claimed exchanges are leased until `lease_seconds` have passed, so exchanges of a
+/// crashed dispatcher are evaluated again. Evaluated exchanges must be removed with
+/// `complete_queued_exchanges`.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `limit` - The maximum number of exchanges to claim
+/// * `lease_seconds` - How long the claim is held before others may take over
+///
+/// # Returns
+/// A vector of the claimed exchanges with their players and tokens resolved, oldest first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn claim_queued_exchanges(
+    pool: &DbPool,
+    limit: i64,
+    lease_seconds: i64,
+) -> Result<Vec<ExchangeDetailed>> {
+    let exchanges = sqlx::query_as::<_, ExchangeDetailed>(&format!(
+        r#"
+        WITH claimable AS (
+            SELECT exchange_id
+            FROM alerts.exchange_queue
+            WHERE claimed_until IS NULL OR claimed_until < NOW()
+            ORDER BY exchange_id
+            LIMIT $1
+            FOR UPDATE SKIP LOCKED
+        ),
+        claimed AS (
+            UPDATE alerts.exchange_queue q
+            SET claimed_until = NOW() + $2 * INTERVAL '1 second'
+            FROM claimable
+            WHERE q.exchange_id = claimable.exchange_id
+            RETURNING q.exchange_id
+        )
+        {EXCHANGES_DETAILED}
+        WHERE e.id IN (SELECT exchange_id FROM claimed)
+        ORDER BY e.id
+        "#
+    ))
+    .bind(limit)
+    .bind(lease_seconds)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Removes evaluated exchanges from the queue
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `exchange_ids` - The IDs of the evaluated exchanges
+///
+/// # Returns
+/// The number of exchanges removed
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn complete_queued_exchanges(pool: &DbPool, exchange_ids: &[i32]) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        DELETE FROM alerts.exchange_queue
+        WHERE exchange_id = ANY($1)
+        "#,
+    )
+    .bind(exchange_ids)
+    .execute(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(result.rows_affected())
+}
+
+/// Retrieves the conditions of active webhooks that an exchange triggers
+///
+/// Conditions still cooling down from an earlier exchange are skipped. The volume window
+/// of `volume_above` conditions ends at the block time of the exchange.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `exchange` - The exchange to evaluate
+///
+/// # Returns
+/// A vector of the triggered conditions
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_triggered_alert_conditions(
+    pool: &DbPool,
+    exchange: &ExchangeDetailed,
+) -> Result<Vec<AlertCondition>> {
+    let conditions = sqlx::query_as::<_, AlertCondition>(
+        r#"
+        SELECT c.id, c.webhook_id, c.kind, c.asset_mint, c.pair_mint, c.wallet, c.threshold,
+               c.window_seconds, c.cooldown_seconds, c.last_triggered_at, c.created_at
+        FROM alerts.conditions c
+        JOIN alerts.webhooks w ON w.id = c.webhook_id
+        WHERE w.active
+          AND (c.asset_mint IS NULL OR c.asset_mint = $1)
+          AND (c.pair_mint IS NULL OR c.pair_mint = $2)
+          AND (c.wallet IS NULL OR c.wallet = $3 OR c.wallet = $4)
+          AND (c.last_triggered_at IS NULL OR c.cooldown_seconds = 0
+               OR $5 >= c.last_triggered_at + c.cooldown_seconds * INTERVAL '1 second')
+          AND CASE c.kind
+                  WHEN 'price_below' THEN $6 < c.threshold
+                  WHEN 'price_above' THEN $6 > c.threshold
+                  WHEN 'wallet_traded' THEN c.wallet IS NOT NULL
+                  WHEN 'volume_above' THEN (
+                      SELECT COALESCE(SUM(x.volume), 0)
+                      FROM market.exchanges x
+                      WHERE x.asset = $7
+                        AND (c.pair_mint IS NULL OR x.pair = $8)
+                        AND x.timestamp > $5 - c.window_seconds * INTERVAL '1 second'
+                        AND x.timestamp <= $5
+                  ) > c.threshold
+              END
+        ORDER BY c.id
+        "#,
+    )
+    .bind(&exchange.asset_mint)
+    .bind(&exchange.pair_mint)
+    .bind(&exchange.buyer_wallet)
+    .bind(&exchange.seller_wallet)
+    .bind(exchange.timestamp)
+    .bind(exchange.price)
+    .bind(exchange.asset_id)
+    .bind(exchange.pair_id)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(conditions)
+}
+
+/// Creates a pending delivery of an alert and starts the cooldown of its condition
+///
+/// Every condition alerts at most once per exchange, so evaluating an exchange again
+/// does not create a second delivery.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `condition` - The triggered condition
+/// * `exchange` - The exchange that triggered it
+/// * `payload` - The JSON body to post to the webhook
+///
+/// # Returns
+/// The created delivery, or None if the exchange already triggered the condition
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn create_alert_delivery(
+    pool: &DbPool,
+    condition: &AlertCondition,
+    exchange: &ExchangeDetailed,
+    payload: &str,
+) -> Result<Option<AlertDelivery>> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    let delivery = sqlx::query_as::<_, AlertDelivery>(
+        r#"
+        INSERT INTO alerts.deliveries (webhook_id, condition_id, exchange_id, payload)
+        VALUES ($1, $2, $3, $4)
+        ON CONFLICT ON CONSTRAINT unique_condition_exchange DO NOTHING
+        RETURNING id, webhook_id, condition_id, exchange_id, payload, status, attempts, next_attempt_at, last_status_code, last_error, created_at, delivered_at
+        "#,
+    )
+    .bind(condition.webhook_id)
+    .bind(condition.id)
+    .bind(exchange.id)
+    .bind(payload)
+    .fetch_optional(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    if delivery.is_some() {
+        sqlx::query(
+            r#"
+            UPDATE alerts.conditions
+            SET last_triggered_at = GREATEST(last_triggered_at, $2)
+            WHERE id = $1
+            "#,
+        )
+        .bind(condition.id)
+        .bind(exchange.timestamp)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(delivery)
+}
+
+/// Claims a batch of pending deliveries whose next attempt is due
+///
+/// The next attempt of every claimed delivery is pushed back by `lease_seconds`, so a
+/// crashed dispatcher's deliveries are retried once the lease expired.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `limit` - The maximum number of deliveries to claim
+/// * `lease_seconds` - How long the claim is held before others may take over
+///
+/// # Returns
+/// A vector of the claimed deliveries with their webhook URL and secret
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn claim_due_alert_deliveries(
+    pool: &DbPool,
+    limit: i64,
+    lease_seconds: i64,
+) -> Result<Vec<DueAlertDelivery>> {
+    let deliveries = sqlx::query_as::<_, DueAlertDelivery>(
+        r#"
+        WITH due AS (
+            SELECT d.id, w.url, w.secret
+            FROM alerts.deliveries d
+            JOIN alerts.webhooks w ON w.id = d.webhook_id
+            WHERE d.status = 'pending' AND d.next_attempt_at <= NOW() AND w.active
+            ORDER BY d.next_attempt_at
+            LIMIT $1
+            FOR UPDATE OF d SKIP LOCKED
+        )
+        UPDATE alerts.deliveries d
+        SET next_attempt_at = NOW() + $2 * INTERVAL '1 second'
+        FROM due
+        WHERE d.id = due.id
+        RETURNING d.id, d.attempts, d.payload, due.url, due.secret
+        "#,
+    )
+    .bind(limit)
+    .bind(lease_seconds)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(deliveries)
+}
+
+/// Records an attempt to deliver an alert and updates the delivery with its outcome
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `attempt` - The attempt to record
+/// * `status` - The status of the delivery after the attempt
+/// * `next_attempt_at` - The time of the next attempt if the delivery stays pending
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn record_alert_delivery_attempt(
+    pool: &DbPool,
+    attempt: &NewDeliveryAttempt,
+    status: DeliveryStatus,
+    next_attempt_at: DateTime<Utc>,
+) -> Result<()> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO alerts.delivery_attempts (delivery_id, status_code, error, duration_ms)
+        VALUES ($1, $2, $3, $4)
+        "#,
+    )
+    .bind(attempt.delivery_id)
+    .bind(attempt.status_code)
+    .bind(&attempt.error)
+    .bind(attempt.duration_ms)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        UPDATE alerts.deliveries
+        SET status = $2,
+            attempts = attempts + 1,
+            next_attempt_at = $3,
+            last_status_code = $4,
+            last_error = $5,
+            delivered_at = CASE WHEN $2 = 'delivered'::alert_delivery_status THEN NOW() END
+        WHERE id = $1
+        "#,
+    )
+    .bind(attempt.delivery_id)
+    .bind(status)
+    .bind(next_attempt_at)
+    .bind(attempt.status_code)
+    .bind(&attempt.error)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
@@ -0,0 +1,211 @@
+//! Database queries for the auth schema
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{ApiKey, NewApiKey};
+
+const API_KEY_COLUMNS: &str =
+    "id, name, key_prefix, scope, rate_limit, request_count, last_used_at, revoked_at, created_at";
+
+/// Creates a new API key
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `new_api_key` - The API key to create
+///
+/// # Returns
+/// The created API key with its assigned ID
+///
+/// # Errors
+/// Returns an error if the query fails, e.g. if a key with the same hash exists
+pub async fn create_api_key(pool: &DbPool, new_api_key: &NewApiKey) -> Result<ApiKey> {
+    let api_key = sqlx::query_as::<_, ApiKey>(&format!(
+        r#"
+        INSERT INTO auth.api_keys (name, key_prefix, key_hash, scope, rate_limit)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING {API_KEY_COLUMNS}
+        "#
+    ))
+    .bind(&new_api_key.name)
+    .bind(&new_api_key.key_prefix)
+    .bind(&new_api_key.key_hash)
+    .bind(new_api_key.scope)
+    .bind(new_api_key.rate_limit)
+    .fetch_one(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(api_key)
+}
+
+/// Creates an API key, or restores the scope of an existing key with the same hash
+///
+/// A revoked key with the same hash is reinstated. This is used to provision a key
+/// from the configuration on every start.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `new_api_key` - The API key to create
+///
+/// # Returns
+/// The created or updated API key
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn ensure_api_key(pool: &DbPool, new_api_key: &NewApiKey) -> Result<ApiKey> {
+    let api_key = sqlx::query_as::<_, ApiKey>(&format!(
+        r#"
+        INSERT INTO auth.api_keys (name, key_prefix, key_hash, scope, rate_limit)
+        VALUES ($1, $2, $3, $4, $5)
+        ON CONFLICT ON CONSTRAINT unique_api_key_hash DO UPDATE SET
+            scope = EXCLUDED.scope,
+            revoked_at = NULL
+        RETURNING {API_KEY_COLUMNS}
+        "#
+    ))
+    .bind(&new_api_key.name)
+    .bind(&new_api_key.key_prefix)
+    .bind(&new_api_key.key_hash)
+    .bind(new_api_key.scope)
+    .bind(new_api_key.rate_limit)
+    .fetch_one(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(api_key)
+}
+
+/// Retrieves all API keys, including revoked ones
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+///
+/// # Returns
+/// A vector of all API keys, newest first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_api_keys(pool: &DbPool) -> Result<Vec<ApiKey>> {
+    let api_keys = sqlx::query_as::<_, ApiKey>(&format!(
+        r#"
+        SELECT {API_KEY_COLUMNS}
+        FROM auth.api_keys
+        ORDER BY id DESC
+        "#
+    ))
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(api_keys)
+}
+
+/// Retrieves an active API key by the hash of the key
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `key_hash` - The hex encoded SHA-256 hash of the key
+///
+/// # Returns
+/// The API key, or None if no such key exists or it was revoked
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_active_api_key_by_hash(pool: &DbPool, key_hash: &str) -> Result<Option<ApiKey>> {
+    let api_key = sqlx::query_as::<_, ApiKey>(&format!(
+        r#"
+        SELECT {API_KEY_COLUMNS}
+        FROM auth.api_keys
+        WHERE key_hash = $1 AND revoked_at IS NULL
+        "#
+    ))
+    .bind(key_hash)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(api_key)
+}
+
+/// Revokes an API key
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `id` - The ID of the API key to revoke
+///
+/// # Returns
+/// True if an active key with the ID existed
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn revoke_api_key(pool: &DbPool, id: i32) -> Result<bool> {
+    let result = sqlx::query(
+        r#"
+        UPDATE auth.api_keys
+        SET revoked_at = NOW()
+        WHERE id = $1 AND revoked_at IS NULL
+        "#,
+    )
+    .bind(id)
+    .execute(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(result.rows_affected() > 0)
+}
+
+/// Adds requests to the usage counters of API keys
+///
+/// Updates the total request count and last use of each key, and the requests of the
+/// current day.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `usage` - Pairs of API key ID and number of requests made since the last call
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn record_api_key_usage(pool: &DbPool, usage: &[(i32, i64)]) -> Result<()> {
+    if usage.is_empty() {
+        return Ok(());
+    }
+
+    let (ids, requests): (Vec<i32>, Vec<i64>) = usage.iter().copied().unzip();
+
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        UPDATE auth.api_keys k
+        SET request_count = k.request_count + u.requests,
+            last_used_at = NOW()
+        FROM UNNEST($1::INTEGER[], $2::BIGINT[]) AS u (api_key_id, requests)
+        WHERE k.id = u.api_key_id
+        "#,
+    )
+    .bind(&ids)
+    .bind(&requests)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO auth.api_key_usage (api_key_id, day, requests)
+        SELECT u.api_key_id, CURRENT_DATE, u.requests
+        FROM UNNEST($1::INTEGER[], $2::BIGINT[]) AS u (api_key_id, requests)
+        WHERE EXISTS (SELECT 1 FROM auth.api_keys k WHERE k.id = u.api_key_id)
+        ON CONFLICT (api_key_id, day) DO UPDATE SET
+            requests = auth.api_key_usage.requests + EXCLUDED.requests
+        "#,
+    )
+    .bind(&ids)
+    .bind(&requests)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
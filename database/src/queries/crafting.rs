@@ -0,0 +1,263 @@
+//! Database queries for the crafting recipes and processes
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{CraftingSnapshot, CraftingTransaction, RecipeCost};
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Replaces the stored recipes with a snapshot
+///
+/// Recipes of the snapshot are inserted or updated with their items, and stored recipes
+/// missing from it are deleted. Snapshots are applied one at a time and a snapshot older than
+/// the stored one is ignored.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `snapshot` - The recipe accounts of the crafting program at one slot
+///
+/// # Returns
+/// False if the snapshot was ignored because a newer one is stored
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_crafting_snapshot(pool: &DbPool, snapshot: &CraftingSnapshot) -> Result<bool> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("LOCK TABLE crafting.recipes IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let stored_slot: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(snapshot_slot) FROM crafting.recipes")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(DbError::SqlxError)?;
+    if stored_slot.is_some_and(|stored_slot| stored_slot > snapshot.slot) {
+        return Ok(false);
+    }
+
+    let recipes = &snapshot.recipes;
+    sqlx::query(
+        r#"
+        INSERT INTO crafting.recipes (
+            address, domain, category, duration, status, fee_amount, usage_count, data, snapshot_slot
+        )
+        SELECT r.*, $9
+        FROM UNNEST(
+            $1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[], $4::BIGINT[], $5::VARCHAR[], $6::BIGINT[],
+            $7::BIGINT[], $8::JSONB[]
+        ) AS r
+        ON CONFLICT (address) DO UPDATE SET
+            domain = EXCLUDED.domain,
+            category = EXCLUDED.category,
+            duration = EXCLUDED.duration,
+            status = EXCLUDED.status,
+            fee_amount = EXCLUDED.fee_amount,
+            usage_count = EXCLUDED.usage_count,
+            data = EXCLUDED.data,
+            snapshot_slot = EXCLUDED.snapshot_slot
+        "#,
+    )
+    .bind(recipes.iter().map(|r| r.address.clone()).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.domain.clone()).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.category.clone()).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.duration).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.status.clone()).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.fee_amount).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.usage_count).collect::<Vec<_>>())
+    .bind(recipes.iter().map(|r| r.data.clone()).collect::<Vec<_>>())
+    .bind(snapshot.slot)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query("DELETE FROM crafting.recipes WHERE snapshot_slot <> $1")
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    // Items are rewritten, a recipe's items can change with its counts
+    sqlx::query("DELETE FROM crafting.recipe_items")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let items: Vec<_> = recipes
+        .iter()
+        .flat_map(|recipe| {
+            recipe
+                .items
+                .iter()
+                .enumerate()
+                .map(move |(position, item)| (&recipe.address, position as i16, item))
+        })
+        .collect();
+    sqlx::query(
+        r#"
+        INSERT INTO crafting.recipe_items (recipe, position, role, mint, amount)
+        SELECT *
+        FROM UNNEST($1::VARCHAR[], $2::SMALLINT[], $3::VARCHAR[], $4::VARCHAR[], $5::BIGINT[])
+        "#,
+    )
+    .bind(
+        items
+            .iter()
+            .map(|(recipe, _, _)| recipe.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        items
+            .iter()
+            .map(|(_, position, _)| *position)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        items
+            .iter()
+            .map(|(_, _, i)| i.role.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        items
+            .iter()
+            .map(|(_, _, i)| i.mint.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(items.iter().map(|(_, _, i)| i.amount).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(true)
+}
+
+/// Replaces the stored crafting process steps of a transaction
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `transaction` - The crafting steps of the transaction
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_crafting_events(
+    pool: &DbPool,
+    transaction: &CraftingTransaction,
+) -> Result<()> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("DELETE FROM crafting.process_events WHERE signature = $1")
+        .bind(&transaction.signature)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let events = &transaction.events;
+    sqlx::query(
+        r#"
+        INSERT INTO crafting.process_events (
+            signature, slot, timestamp, instruction_index, position, kind, crafting_process, recipe,
+            player_profile, starbase, quantity, mint, amount, args
+        )
+        SELECT $1, $2, $3, e.*
+        FROM UNNEST(
+            $4::INTEGER[], $5::INTEGER[], $6::VARCHAR[], $7::VARCHAR[], $8::VARCHAR[], $9::VARCHAR[],
+            $10::VARCHAR[], $11::BIGINT[], $12::VARCHAR[], $13::BIGINT[], $14::JSONB[]
+        ) AS e
+        "#,
+    )
+    .bind(&transaction.signature)
+    .bind(transaction.slot)
+    .bind(transaction.timestamp)
+    .bind(events.iter().map(|e| e.instruction_index).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.position).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.kind.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.crafting_process.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.recipe.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.player_profile.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.starbase.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.quantity).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.mint.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.amount).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.args.clone()).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Retrieves the production cost of recipe outputs next to their market price
+///
+/// Items are priced at their volume weighted average price in the given currency. The cost
+/// of a recipe is the market value of its consumable inputs; non-consumable inputs and the
+/// recipe fee are not included. Amounts are compared in base units, as Star Atlas items have
+/// no decimals.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `currency_mint` - Mint of the currency prices are taken in
+/// * `output_mint` - Only include recipes producing this item
+/// * `since` - Only use exchanges since this time for the prices
+///
+/// # Returns
+/// A vector of recipe costs, ordered by output mint
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_recipe_costs(
+    pool: &DbPool,
+    currency_mint: &str,
+    output_mint: Option<&str>,
+    since: DateTime<Utc>,
+) -> Result<Vec<RecipeCost>> {
+    let costs = sqlx::query_as::<_, RecipeCost>(
+        r#"
+        WITH prices AS (
+            SELECT asset.mint, SUM(e.price * e.size) / NULLIF(SUM(e.size), 0) AS price
+            FROM market.exchanges e
+            JOIN staratlas.tokens asset ON asset.id = e.asset
+            JOIN staratlas.tokens pair ON pair.id = e.pair
+            WHERE pair.mint = $1 AND e.timestamp >= $3
+            GROUP BY asset.mint
+        ),
+        inputs AS (
+            SELECT i.recipe,
+                   COUNT(*) AS inputs,
+                   COUNT(p.price) AS priced_inputs,
+                   SUM(i.amount * p.price) AS input_cost
+            FROM crafting.recipe_items i
+            LEFT JOIN prices p ON p.mint = i.mint
+            WHERE i.role = 'consumable'
+            GROUP BY i.recipe
+        )
+        SELECT o.recipe,
+               o.mint AS output_mint,
+               o.amount AS output_amount,
+               COALESCE(i.inputs, 0) AS inputs,
+               COALESCE(i.priced_inputs, 0) AS priced_inputs,
+               CASE WHEN i.inputs = i.priced_inputs THEN i.input_cost END AS input_cost,
+               CASE WHEN i.inputs = i.priced_inputs THEN i.input_cost / NULLIF(o.amount, 0) END AS unit_cost,
+               p.price AS market_price
+        FROM crafting.recipe_items o
+        LEFT JOIN inputs i ON i.recipe = o.recipe
+        LEFT JOIN prices p ON p.mint = o.mint
+        WHERE o.role = 'output'
+          AND ($2::VARCHAR IS NULL OR o.mint = $2)
+        ORDER BY o.mint, o.recipe
+        "#,
+    )
+    .bind(currency_mint)
+    .bind(output_mint)
+    .bind(since)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(costs)
+}
@@ -0,0 +1,109 @@
+//! Database queries for the guilds
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::NewGuild;
+use crate::queries::staratlas::notify_players_updated;
+
+/// Replaces the stored guilds and their members and updates the player guilds
+///
+/// The given guilds are the complete configuration, so stored guilds missing from it are
+/// deleted. Players get the guild they are a member of, the first configured one if they are
+/// in several.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `guilds` - The guilds with their members, in configuration order
+///
+/// # Returns
+/// The number of players whose guild changed
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_guilds(pool: &DbPool, guilds: &[NewGuild]) -> Result<u64> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("LOCK TABLE staratlas.guilds IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    sqlx::query("DELETE FROM staratlas.guilds WHERE NOT (name = ANY($1))")
+        .bind(guilds.iter().map(|g| g.name.clone()).collect::<Vec<_>>())
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    for (position, guild) in guilds.iter().enumerate() {
+        let guild_id: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO staratlas.guilds (name, tag, source, realm, position, updated_at)
+            VALUES ($1, $2, $3, $4, $5, NOW())
+            ON CONFLICT (name) DO UPDATE SET
+                tag = EXCLUDED.tag,
+                source = EXCLUDED.source,
+                realm = EXCLUDED.realm,
+                position = EXCLUDED.position,
+                updated_at = EXCLUDED.updated_at
+            RETURNING id
+            "#,
+        )
+        .bind(&guild.name)
+        .bind(&guild.tag)
+        .bind(&guild.source)
+        .bind(&guild.realm)
+        .bind(position as i32)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        sqlx::query("DELETE FROM staratlas.guild_members WHERE guild_id = $1")
+            .bind(guild_id)
+            .execute(&mut *tx)
+            .await
+            .map_err(DbError::SqlxError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO staratlas.guild_members (guild_id, wallet_address)
+            SELECT DISTINCT $1, m
+            FROM UNNEST($2::VARCHAR[]) AS m
+            "#,
+        )
+        .bind(guild_id)
+        .bind(&guild.members)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    let updated = sqlx::query(
+        r#"
+        UPDATE staratlas.players p
+        SET guild = n.guild
+        FROM (
+            SELECT pl.id, (
+                SELECT g.name
+                FROM staratlas.guild_members m
+                JOIN staratlas.guilds g ON g.id = m.guild_id
+                WHERE m.wallet_address = pl.wallet_address
+                ORDER BY g.position
+                LIMIT 1
+            ) AS guild
+            FROM staratlas.players pl
+        ) n
+        WHERE p.id = n.id
+          AND p.guild IS DISTINCT FROM n.guild
+        "#,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?
+    .rows_affected();
+
+    notify_players_updated(&mut tx, updated).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(updated)
+}
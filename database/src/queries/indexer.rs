@@ -20,10 +20,10 @@ use crate::types::PublicKeyType;
 pub async fn get_all_indexers(pool: &DbPool) -> Result<Vec<Indexer>> {
     let indexers = sqlx::query_as::<_, Indexer>(
         r#"
-        SELECT id, name, direction, program_id, signature, block, timestamp,
+        SELECT name, direction, program_id, signature, block, timestamp,
             finished, fetch_limit
         FROM indexer.indexer
-        ORDER BY id
+        ORDER BY name
         "#,
     )
     .fetch_all(pool)
@@ -50,11 +50,11 @@ pub async fn get_indexers_by_program_id(
 ) -> Result<Vec<Indexer>> {
     let indexers = sqlx::query_as::<_, Indexer>(
         r#"
-        SELECT id, name, direction, program_id, signature, signature,
-               timestamp , finished, fetch_limit
+        SELECT name, direction, program_id, signature, block,
+               timestamp, finished, fetch_limit
         FROM indexer.indexer
         WHERE program_id = $1
-        ORDER BY id
+        ORDER BY name
         "#,
     )
     .bind(program_id.clone())
@@ -120,11 +120,11 @@ pub async fn create_indexer(pool: &DbPool, new_indexer: &NewIndexer) -> Result<I
         "#,
     )
     .bind(&new_indexer.name)
-    .bind(&new_indexer.direction)
+    .bind(new_indexer.direction)
     .bind(&new_indexer.program_id)
     .bind(&new_indexer.signature)
-    .bind(&new_indexer.block)
-    .bind(&new_indexer.timestamp)
+    .bind(new_indexer.block)
+    .bind(new_indexer.timestamp)
     .bind(new_indexer.finished)
     .bind(new_indexer.fetch_limit)
     .fetch_one(pool)
@@ -166,10 +166,10 @@ pub async fn update_indexer(
                    finished, fetch_limit
         "#,
     )
-    .bind(&update.direction)
+    .bind(update.direction)
     .bind(&update.signature)
-    .bind(&update.block)
-    .bind(&update.timestamp)
+    .bind(update.block)
+    .bind(update.timestamp)
     .bind(update.finished)
     .bind(name)
     .fetch_one(pool)
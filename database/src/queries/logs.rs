@@ -0,0 +1,127 @@
+//! Database queries for the program invocations and events parsed from transaction logs
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{ComputeUnitStats, TransactionLogs};
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Replaces the stored invocations and events of a transaction
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `logs` - The invocations and events parsed from the transaction's logs
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_transaction_logs(pool: &DbPool, logs: &TransactionLogs) -> Result<()> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    // Events are deleted with their invocations
+    sqlx::query("DELETE FROM logs.invocations WHERE signature = $1")
+        .bind(&logs.signature)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let invocations = &logs.invocations;
+    sqlx::query(
+        r#"
+        INSERT INTO logs.invocations (
+            signature, slot, timestamp, position, instruction_index, depth, program_id, instruction_name,
+            compute_units_consumed, compute_units_limit, error, logs
+        )
+        SELECT $1, $2, $3, i.*
+        FROM UNNEST(
+            $4::INTEGER[], $5::INTEGER[], $6::SMALLINT[], $7::VARCHAR[], $8::VARCHAR[],
+            $9::BIGINT[], $10::BIGINT[], $11::TEXT[], $12::TEXT[]
+        ) AS i
+        "#,
+    )
+    .bind(&logs.signature)
+    .bind(logs.slot)
+    .bind(logs.timestamp)
+    .bind(invocations.iter().map(|i| i.position).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.instruction_index).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.depth).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.program_id.clone()).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.instruction_name.clone()).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.compute_units_consumed).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.compute_units_limit).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.error.clone()).collect::<Vec<_>>())
+    .bind(invocations.iter().map(|i| i.logs.clone()).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    let events = &logs.events;
+    sqlx::query(
+        r#"
+        INSERT INTO logs.events (signature, slot, timestamp, position, event_index, program_id, name, data, raw)
+        SELECT $1, $2, $3, e.*
+        FROM UNNEST($4::INTEGER[], $5::INTEGER[], $6::VARCHAR[], $7::VARCHAR[], $8::JSONB[], $9::BYTEA[]) AS e
+        "#,
+    )
+    .bind(&logs.signature)
+    .bind(logs.slot)
+    .bind(logs.timestamp)
+    .bind(events.iter().map(|e| e.position).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.event_index).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.program_id.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.name.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.data.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.raw.clone()).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Retrieves the compute unit usage per instruction type
+///
+/// Only transaction instructions are counted, their usage includes the cross-program
+/// invocations they make.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - Only count instructions of this program
+/// * `since` - Only count instructions of transactions since this time
+///
+/// # Returns
+/// A vector of compute unit statistics, most invoked instruction types first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_compute_unit_stats(
+    pool: &DbPool,
+    program_id: Option<&str>,
+    since: DateTime<Utc>,
+) -> Result<Vec<ComputeUnitStats>> {
+    let stats = sqlx::query_as::<_, ComputeUnitStats>(
+        r#"
+        SELECT program_id, instruction_name,
+               COUNT(*) AS invocations,
+               COUNT(*) FILTER (WHERE error IS NOT NULL) AS failed,
+               AVG(compute_units_consumed)::DOUBLE PRECISION AS avg_units,
+               PERCENTILE_CONT(0.5) WITHIN GROUP (ORDER BY compute_units_consumed) AS p50_units,
+               PERCENTILE_CONT(0.95) WITHIN GROUP (ORDER BY compute_units_consumed) AS p95_units,
+               MAX(compute_units_consumed) AS max_units
+        FROM logs.invocations
+        WHERE depth = 1
+          AND compute_units_consumed IS NOT NULL
+          AND timestamp >= $1
+          AND ($2::VARCHAR IS NULL OR program_id = $2)
+        GROUP BY program_id, instruction_name
+        ORDER BY invocations DESC, program_id, instruction_name
+        "#,
+    )
+    .bind(since)
+    .bind(program_id)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(stats)
+}
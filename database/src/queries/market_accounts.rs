@@ -0,0 +1,215 @@
+//! Database queries for the snapshots of the marketplace program accounts
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::MarketplaceSnapshot;
+
+/// Replaces the stored marketplace accounts with a snapshot
+///
+/// Accounts of the snapshot are inserted or updated, and stored accounts missing from it,
+/// such as filled or cancelled orders, are deleted. Snapshots are applied one at a time and
+/// a snapshot older than the stored one is ignored.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `snapshot` - The accounts of the marketplace program at one slot
+///
+/// # Returns
+/// False if the snapshot was ignored because a newer one is stored
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_marketplace_snapshot(
+    pool: &DbPool,
+    snapshot: &MarketplaceSnapshot,
+) -> Result<bool> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("LOCK TABLE market.order_accounts IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let stored_slot: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(snapshot_slot) FROM market.order_accounts")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(DbError::SqlxError)?;
+    if stored_slot.is_some_and(|stored_slot| stored_slot > snapshot.slot) {
+        return Ok(false);
+    }
+
+    let orders = &snapshot.orders;
+    sqlx::query(
+        r#"
+        INSERT INTO market.order_accounts (
+            address, order_initializer, currency_mint, asset_mint, initializer_currency_token_account,
+            initializer_asset_token_account, side, price, origination_qty, remaining_qty, created_at, snapshot_slot
+        )
+        SELECT o.*, $12
+        FROM UNNEST(
+            $1::VARCHAR[], $2::VARCHAR[], $3::VARCHAR[], $4::VARCHAR[], $5::VARCHAR[],
+            $6::VARCHAR[], $7::VARCHAR[], $8::BIGINT[], $9::BIGINT[], $10::BIGINT[], $11::TIMESTAMPTZ[]
+        ) AS o
+        ON CONFLICT (address) DO UPDATE SET
+            order_initializer = EXCLUDED.order_initializer,
+            currency_mint = EXCLUDED.currency_mint,
+            asset_mint = EXCLUDED.asset_mint,
+            initializer_currency_token_account = EXCLUDED.initializer_currency_token_account,
+            initializer_asset_token_account = EXCLUDED.initializer_asset_token_account,
+            side = EXCLUDED.side,
+            price = EXCLUDED.price,
+            origination_qty = EXCLUDED.origination_qty,
+            remaining_qty = EXCLUDED.remaining_qty,
+            created_at = EXCLUDED.created_at,
+            snapshot_slot = EXCLUDED.snapshot_slot
+        "#,
+    )
+    .bind(orders.iter().map(|o| o.address.clone()).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.order_initializer.clone()).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.currency_mint.clone()).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.asset_mint.clone()).collect::<Vec<_>>())
+    .bind(
+        orders
+            .iter()
+            .map(|o| o.initializer_currency_token_account.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        orders
+            .iter()
+            .map(|o| o.initializer_asset_token_account.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(orders.iter().map(|o| o.side.clone()).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.price).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.origination_qty).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.remaining_qty).collect::<Vec<_>>())
+    .bind(orders.iter().map(|o| o.created_at).collect::<Vec<_>>())
+    .bind(snapshot.slot)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    for currency in &snapshot.currencies {
+        sqlx::query(
+            r#"
+            INSERT INTO market.registered_currencies (
+                address, token_mint, sa_currency_vault, royalty, royalty_tier_stake_amounts, royalty_tier_discounts, snapshot_slot
+            )
+            VALUES ($1, $2, $3, $4, $5, $6, $7)
+            ON CONFLICT (address) DO UPDATE SET
+                token_mint = EXCLUDED.token_mint,
+                sa_currency_vault = EXCLUDED.sa_currency_vault,
+                royalty = EXCLUDED.royalty,
+                royalty_tier_stake_amounts = EXCLUDED.royalty_tier_stake_amounts,
+                royalty_tier_discounts = EXCLUDED.royalty_tier_discounts,
+                snapshot_slot = EXCLUDED.snapshot_slot
+            "#,
+        )
+        .bind(&currency.address)
+        .bind(&currency.token_mint)
+        .bind(&currency.sa_currency_vault)
+        .bind(currency.royalty)
+        .bind(&currency.royalty_tier_stake_amounts)
+        .bind(&currency.royalty_tier_discounts)
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    for fee_reduction in &snapshot.fee_reductions {
+        sqlx::query(
+            r#"
+            INSERT INTO market.fee_reductions (address, account, discount, snapshot_slot)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (address) DO UPDATE SET
+                account = EXCLUDED.account,
+                discount = EXCLUDED.discount,
+                snapshot_slot = EXCLUDED.snapshot_slot
+            "#,
+        )
+        .bind(&fee_reduction.address)
+        .bind(&fee_reduction.account)
+        .bind(fee_reduction.discount)
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    for market_vars in &snapshot.market_vars {
+        sqlx::query(
+            r#"
+            INSERT INTO market.market_vars (address, update_authority_master, snapshot_slot)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (address) DO UPDATE SET
+                update_authority_master = EXCLUDED.update_authority_master,
+                snapshot_slot = EXCLUDED.snapshot_slot
+            "#,
+        )
+        .bind(&market_vars.address)
+        .bind(&market_vars.update_authority_master)
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    for atlas_rate in &snapshot.atlas_rates {
+        sqlx::query(
+            r#"
+            INSERT INTO market.atlas_rates (address, atlas_rate, snapshot_slot)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (address) DO UPDATE SET
+                atlas_rate = EXCLUDED.atlas_rate,
+                snapshot_slot = EXCLUDED.snapshot_slot
+            "#,
+        )
+        .bind(&atlas_rate.address)
+        .bind(atlas_rate.atlas_rate)
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    for counter in &snapshot.open_orders_counters {
+        sqlx::query(
+            r#"
+            INSERT INTO market.open_orders_counters (address, open_order_count, snapshot_slot)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (address) DO UPDATE SET
+                open_order_count = EXCLUDED.open_order_count,
+                snapshot_slot = EXCLUDED.snapshot_slot
+            "#,
+        )
+        .bind(&counter.address)
+        .bind(counter.open_order_count)
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    // Accounts missing from the snapshot were closed
+    for table in [
+        "market.order_accounts",
+        "market.registered_currencies",
+        "market.fee_reductions",
+        "market.market_vars",
+        "market.atlas_rates",
+        "market.open_orders_counters",
+    ] {
+        sqlx::query(&format!("DELETE FROM {table} WHERE snapshot_slot <> $1"))
+            .bind(snapshot.slot)
+            .execute(&mut *tx)
+            .await
+            .map_err(DbError::SqlxError)?;
+    }
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(true)
+}
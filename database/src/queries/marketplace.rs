@@ -2,11 +2,16 @@
 
 use crate::connection::DbPool;
 use crate::error::{DbError, Result};
+use crate::listener::{EXCHANGES_REWRITTEN_CHANNEL, NEW_EXCHANGE_CHANNEL};
 use crate::models::{
-    Exchange, ExchangeWithDependencies, NewExchange, NewPlayer, NewToken, Player, Token,
+    Exchange, ExchangeDetailed, ExchangeFilter, ExchangeFlow, ExchangeWithDependencies,
+    GroupLeaderboardEntry, LeaderboardEntry, LeaderboardFilter, LeaderboardMetric, LeaderboardMode,
+    MarketStats, MarketplaceTransaction, NewExchange, Player, PlayerExchange, PlayerGroup,
+    PlayerTrade, Token,
 };
 use crate::queries::staratlas;
 use sqlx::types::chrono::{DateTime, Utc};
+use sqlx::{PgConnection, Postgres, QueryBuilder};
 
 /// Retrieves all exchanges from the database
 ///
@@ -21,7 +26,7 @@ use sqlx::types::chrono::{DateTime, Utc};
 pub async fn get_exchanges(pool: &DbPool, limit: i32, offset: i32) -> Result<Vec<Exchange>> {
     let exchanges = sqlx::query_as::<_, Exchange>(
         r#"
-        SELECT id, slot, signature, index, timestamp , side, buyer, seller, asset, pair, price, size, volume, fee, buddy
+        SELECT id, slot, signature, index, timestamp , side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
         FROM market.exchanges
         ORDER BY slot DESC
             LIMIT $1 OFFSET $2
@@ -50,7 +55,7 @@ pub async fn get_exchanges(pool: &DbPool, limit: i32, offset: i32) -> Result<Vec
 pub async fn get_exchange_by_id(pool: &DbPool, id: i32) -> Result<Option<Exchange>> {
     let exchange = sqlx::query_as::<_, Exchange>(
         r#"
-        SELECT id, slot, signature, index, timestamp , side, buyer, seller, asset, pair, price, size, volume, fee, buddy
+        SELECT id, slot, signature, index, timestamp , side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
         FROM market.exchanges
         WHERE id = $1
         "#,
@@ -83,7 +88,7 @@ pub async fn get_exchanges_by_buyer_id(
 ) -> Result<Vec<Exchange>> {
     let exchanges = sqlx::query_as::<_, Exchange>(
         r#"
-        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy
+        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
         FROM market.exchanges
         WHERE buyer = $1
         ORDER BY slot DESC
@@ -119,7 +124,7 @@ pub async fn get_exchanges_by_seller_id(
 ) -> Result<Vec<Exchange>> {
     let exchanges = sqlx::query_as::<_, Exchange>(
         r#"
-        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy
+        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
         FROM market.exchanges
         WHERE seller = $1
         ORDER BY slot DESC
@@ -155,7 +160,7 @@ pub async fn get_exchanges_by_asset_id(
 ) -> Result<Vec<Exchange>> {
     let exchanges = sqlx::query_as::<_, Exchange>(
         r#"
-        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy
+        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
         FROM market.exchanges
         WHERE asset = $1
         ORDER BY slot DESC
@@ -172,47 +177,745 @@ pub async fn get_exchanges_by_asset_id(
     Ok(exchanges)
 }
 
-/// Creates a new exchange in the database
+/// Exchanges joined with the buyer and seller players and the asset and pair tokens
+pub(crate) const EXCHANGES_DETAILED: &str = r#"
+    SELECT e.id, e.slot, e.signature, e.index, e.timestamp, e.side,
+           b.id AS buyer_id, b.wallet_address AS buyer_wallet, b.username AS buyer_username,
+           s.id AS seller_id, s.wallet_address AS seller_wallet, s.username AS seller_username,
+           a.id AS asset_id, a.mint AS asset_mint, a.symbol AS asset_symbol, a.name AS asset_name,
+           p.id AS pair_id, p.mint AS pair_mint, p.symbol AS pair_symbol, p.name AS pair_name,
+           e.price, e.size, e.volume, e.fee, e.buddy, e.processor_version
+    FROM market.exchanges e
+    JOIN staratlas.players b ON b.id = e.buyer
+    JOIN staratlas.players s ON s.id = e.seller
+    JOIN staratlas.tokens a ON a.id = e.asset
+    JOIN staratlas.tokens p ON p.id = e.pair
+"#;
+
+/// Retrieves exchanges with their players and tokens resolved
 ///
 /// # Arguments
 /// * `pool` - The database connection pool
-/// * `new_exchange` - The exchange to create
+/// * `limit` - Maximum number of exchanges to return
+/// * `offset` - Number of exchanges to skip
 ///
 /// # Returns
-/// The created exchange with its assigned ID
+/// A vector of detailed exchanges, newest first
 ///
 /// # Errors
 /// Returns an error if the query fails
-pub async fn create_exchange(pool: &DbPool, new_exchange: &NewExchange) -> Result<Exchange> {
-    let exchange = sqlx::query_as::<_, Exchange>(
+pub async fn get_exchanges_detailed(
+    pool: &DbPool,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<ExchangeDetailed>> {
+    let exchanges = sqlx::query_as::<_, ExchangeDetailed>(&format!(
+        "{EXCHANGES_DETAILED} ORDER BY e.slot DESC LIMIT $1 OFFSET $2"
+    ))
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Retrieves an exchange by its ID with its players and tokens resolved
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `id` - The ID of the exchange to retrieve
+///
+/// # Returns
+/// The detailed exchange with the specified ID, or None if no such exchange exists
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchange_detailed_by_id(
+    pool: &DbPool,
+    id: i32,
+) -> Result<Option<ExchangeDetailed>> {
+    let exchange =
+        sqlx::query_as::<_, ExchangeDetailed>(&format!("{EXCHANGES_DETAILED} WHERE e.id = $1"))
+            .bind(id)
+            .fetch_optional(pool)
+            .await
+            .map_err(DbError::SqlxError)?;
+
+    Ok(exchange)
+}
+
+/// Retrieves exchanges by buyer ID with their players and tokens resolved
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `buyer_id` - The ID of the buyer to search for
+/// * `limit` - Maximum number of exchanges to return
+/// * `offset` - Number of exchanges to skip
+///
+/// # Returns
+/// A vector of detailed exchanges with the specified buyer ID, newest first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchanges_detailed_by_buyer_id(
+    pool: &DbPool,
+    buyer_id: i32,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<ExchangeDetailed>> {
+    let exchanges = sqlx::query_as::<_, ExchangeDetailed>(&format!(
+        "{EXCHANGES_DETAILED} WHERE e.buyer = $1 ORDER BY e.slot DESC LIMIT $2 OFFSET $3"
+    ))
+    .bind(buyer_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Retrieves exchanges by seller ID with their players and tokens resolved
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `seller_id` - The ID of the seller to search for
+/// * `limit` - Maximum number of exchanges to return
+/// * `offset` - Number of exchanges to skip
+///
+/// # Returns
+/// A vector of detailed exchanges with the specified seller ID, newest first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchanges_detailed_by_seller_id(
+    pool: &DbPool,
+    seller_id: i32,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<ExchangeDetailed>> {
+    let exchanges = sqlx::query_as::<_, ExchangeDetailed>(&format!(
+        "{EXCHANGES_DETAILED} WHERE e.seller = $1 ORDER BY e.slot DESC LIMIT $2 OFFSET $3"
+    ))
+    .bind(seller_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Retrieves exchanges by asset ID with their players and tokens resolved
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `asset_id` - The ID of the asset to search for
+/// * `limit` - Maximum number of exchanges to return
+/// * `offset` - Number of exchanges to skip
+///
+/// # Returns
+/// A vector of detailed exchanges with the specified asset ID, newest first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchanges_detailed_by_asset_id(
+    pool: &DbPool,
+    asset_id: i32,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<ExchangeDetailed>> {
+    let exchanges = sqlx::query_as::<_, ExchangeDetailed>(&format!(
+        "{EXCHANGES_DETAILED} WHERE e.asset = $1 ORDER BY e.slot DESC LIMIT $2 OFFSET $3"
+    ))
+    .bind(asset_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Appends the WHERE clause of an exchange filter to a query over `EXCHANGES_DETAILED`
+pub(crate) fn push_exchange_filter<'a>(
+    query: &mut QueryBuilder<'a, Postgres>,
+    filter: &'a ExchangeFilter,
+) {
+    query.push(" WHERE TRUE");
+
+    if let Some(buyer) = &filter.buyer {
+        query
+            .push(" AND e.buyer IN (SELECT id FROM staratlas.players WHERE wallet_address = ")
+            .push_bind(buyer)
+            .push(")");
+    }
+    if let Some(seller) = &filter.seller {
+        query
+            .push(" AND e.seller IN (SELECT id FROM staratlas.players WHERE wallet_address = ")
+            .push_bind(seller)
+            .push(")");
+    }
+    if let Some(wallet) = &filter.wallet {
+        query
+            .push(" AND (e.buyer IN (SELECT id FROM staratlas.players WHERE wallet_address = ")
+            .push_bind(wallet)
+            .push(") OR e.seller IN (SELECT id FROM staratlas.players WHERE wallet_address = ")
+            .push_bind(wallet)
+            .push("))");
+    }
+    if let Some(buyer_id) = filter.buyer_id {
+        query.push(" AND e.buyer = ").push_bind(buyer_id);
+    }
+    if let Some(seller_id) = filter.seller_id {
+        query.push(" AND e.seller = ").push_bind(seller_id);
+    }
+    if let Some(asset_id) = filter.asset_id {
+        query.push(" AND e.asset = ").push_bind(asset_id);
+    }
+    if let Some(asset_mint) = &filter.asset_mint {
+        query
+            .push(" AND e.asset IN (SELECT id FROM staratlas.tokens WHERE mint = ")
+            .push_bind(asset_mint)
+            .push(")");
+    }
+    if let Some(pair_mint) = &filter.pair_mint {
+        query
+            .push(" AND e.pair IN (SELECT id FROM staratlas.tokens WHERE mint = ")
+            .push_bind(pair_mint)
+            .push(")");
+    }
+    if let Some(side) = &filter.side {
+        query.push(" AND e.side = ").push_bind(side.to_uppercase());
+    }
+    if let Some(signature) = &filter.signature {
+        query.push(" AND e.signature = ").push_bind(signature);
+    }
+    if let Some(from_timestamp) = filter.from_timestamp {
+        query.push(" AND e.timestamp >= ").push_bind(from_timestamp);
+    }
+    if let Some(to_timestamp) = filter.to_timestamp {
+        query.push(" AND e.timestamp <= ").push_bind(to_timestamp);
+    }
+    if let Some(from_slot) = filter.from_slot {
+        query.push(" AND e.slot >= ").push_bind(from_slot);
+    }
+    if let Some(to_slot) = filter.to_slot {
+        query.push(" AND e.slot <= ").push_bind(to_slot);
+    }
+    if let Some(min_price) = filter.min_price {
+        query.push(" AND e.price >= ").push_bind(min_price);
+    }
+    if let Some(max_price) = filter.max_price {
+        query.push(" AND e.price <= ").push_bind(max_price);
+    }
+    if let Some(min_volume) = filter.min_volume {
+        query.push(" AND e.volume >= ").push_bind(min_volume);
+    }
+    if let Some(max_volume) = filter.max_volume {
+        query.push(" AND e.volume <= ").push_bind(max_volume);
+    }
+    if let Some(after) = &filter.after {
+        query
+            .push(" AND (e.slot, e.signature, e.index) < (")
+            .push_bind(after.slot)
+            .push(", ")
+            .push_bind(&after.signature)
+            .push(", ")
+            .push_bind(after.index)
+            .push(")");
+    }
+}
+
+/// Retrieves exchanges matching a filter with their players and tokens resolved
+///
+/// Exchanges are ordered newest first by `(slot, signature, index)`. To page through the
+/// results, pass the cursor of the last returned exchange as `filter.after` of the next call;
+/// unlike an offset, this stays fast at any depth and is not shifted by new exchanges.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `filter` - The conditions the exchanges must match
+/// * `limit` - Maximum number of exchanges to return
+/// * `offset` - Number of exchanges to skip, ignored when `filter.after` is set
+///
+/// # Returns
+/// A vector of detailed exchanges matching the filter
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchanges_filtered(
+    pool: &DbPool,
+    filter: &ExchangeFilter,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<ExchangeDetailed>> {
+    let mut query = QueryBuilder::<Postgres>::new(EXCHANGES_DETAILED);
+    push_exchange_filter(&mut query, filter);
+
+    query
+        .push(" ORDER BY e.slot DESC, e.signature DESC, e.index DESC LIMIT ")
+        .push_bind(limit);
+    // The cursor already positions the page, skipping rows past it would drop exchanges
+    if filter.after.is_none() {
+        query.push(" OFFSET ").push_bind(offset);
+    }
+
+    let exchanges = query
+        .build_query_as::<ExchangeDetailed>()
+        .fetch_all(pool)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Retrieves the statistics of every market that has traded
+///
+/// Statistics are read from the hourly market aggregates, so the 24 hour and 7 day windows
+/// start at the beginning of the hour 23 hours and 6 days 23 hours before the current one.
+/// The 24 hour price change compares the last price with the close of the last hour before
+/// the window, or with the open of the first hour in it if the market is newer.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+///
+/// # Returns
+/// A vector of market statistics
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_market_stats(pool: &DbPool) -> Result<Vec<MarketStats>> {
+    let stats = sqlx::query_as::<_, MarketStats>(
         r#"
-        INSERT INTO market.exchanges (
-            slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy
-        )
-        VALUES (
-            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14
+        WITH bounds AS (
+            SELECT date_trunc('hour', NOW(), 'UTC') - INTERVAL '23 hours' AS day_start,
+                   date_trunc('hour', NOW(), 'UTC') - INTERVAL '167 hours' AS week_start
         )
-        RETURNING id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy
+        SELECT a.id AS asset_id, a.mint AS asset_mint, a.symbol AS asset_symbol, a.name AS asset_name,
+               p.id AS pair_id, p.mint AS pair_mint, p.symbol AS pair_symbol,
+               m.last_price, m.last_timestamp,
+               COALESCE(d.trade_count, 0) AS trade_count_24h,
+               COALESCE(d.volume, 0) AS volume_24h,
+               d.price_size / NULLIF(d.size, 0) AS vwap_24h,
+               d.high AS high_24h,
+               d.low AS low_24h,
+               (m.last_price - r.price) / NULLIF(r.price, 0) * 100 AS price_change_24h,
+               COALESCE(t.traders, 0) AS unique_traders_24h,
+               COALESCE(w.trade_count, 0) AS trade_count_7d,
+               COALESCE(w.volume, 0) AS volume_7d,
+               w.price_size / NULLIF(w.size, 0) AS vwap_7d
+        FROM market.markets m
+        CROSS JOIN bounds
+        JOIN staratlas.tokens a ON a.id = m.asset
+        JOIN staratlas.tokens p ON p.id = m.pair
+        LEFT JOIN LATERAL (
+            SELECT SUM(h.trade_count)::BIGINT AS trade_count, SUM(h.size)::BIGINT AS size,
+                   SUM(h.volume) AS volume, SUM(h.price_size) AS price_size,
+                   MAX(h.high) AS high, MIN(h.low) AS low
+            FROM market.market_stats_hourly h
+            WHERE h.asset = m.asset AND h.pair = m.pair AND h.bucket >= bounds.day_start
+        ) d ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT SUM(h.trade_count)::BIGINT AS trade_count, SUM(h.size)::BIGINT AS size,
+                   SUM(h.volume) AS volume, SUM(h.price_size) AS price_size
+            FROM market.market_stats_hourly h
+            WHERE h.asset = m.asset AND h.pair = m.pair AND h.bucket >= bounds.week_start
+        ) w ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT COUNT(DISTINCT th.player_id) AS traders
+            FROM market.market_traders_hourly th
+            WHERE th.asset = m.asset AND th.pair = m.pair AND th.bucket >= bounds.day_start
+        ) t ON TRUE
+        LEFT JOIN LATERAL (
+            SELECT COALESCE(
+                (SELECT h.close_price FROM market.market_stats_hourly h
+                 WHERE h.asset = m.asset AND h.pair = m.pair AND h.bucket < bounds.day_start
+                 ORDER BY h.bucket DESC LIMIT 1),
+                (SELECT h.open_price FROM market.market_stats_hourly h
+                 WHERE h.asset = m.asset AND h.pair = m.pair AND h.bucket >= bounds.day_start
+                 ORDER BY h.bucket LIMIT 1)
+            ) AS price
+        ) r ON TRUE
+        WHERE m.last_price IS NOT NULL
+        ORDER BY a.mint, p.mint
         "#,
     )
-        .bind(new_exchange.slot)
-        .bind(&new_exchange.signature.as_str())
-        .bind(new_exchange.index)
-        .bind(new_exchange.timestamp)
-        .bind(&new_exchange.side.as_str())
-        .bind(new_exchange.buyer)
-        .bind(new_exchange.seller)
-        .bind(new_exchange.asset)
-        .bind(new_exchange.pair)
-        .bind(new_exchange.price)
-        .bind(new_exchange.size)
-        .bind(new_exchange.volume)
-        .bind(new_exchange.fee)
-        .bind(new_exchange.buddy)
-        .fetch_one(pool)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(stats)
+}
+
+/// Column of the leaderboard totals a metric ranks by
+fn leaderboard_metric(metric: LeaderboardMetric) -> &'static str {
+    match metric {
+        LeaderboardMetric::Volume => "volume",
+        LeaderboardMetric::Trades => "trade_count",
+        LeaderboardMetric::Fees => "fees",
+        LeaderboardMetric::Buddy => "buddy",
+    }
+}
+
+/// Starts a leaderboard query with the `trades` CTE, one row per exchange and counted side
+/// with the `player_id`, `volume`, `fee` and `buddy`
+fn leaderboard_trades(filter: &LeaderboardFilter) -> QueryBuilder<'_, Postgres> {
+    let sides: &[&str] = match filter.mode {
+        LeaderboardMode::Buyer => &["buyer"],
+        LeaderboardMode::Seller => &["seller"],
+        LeaderboardMode::Combined => &["buyer", "seller"],
+    };
+
+    let mut query = QueryBuilder::<Postgres>::new("WITH trades AS (");
+    for (i, side) in sides.iter().enumerate() {
+        if i > 0 {
+            query.push(" UNION ALL ");
+        }
+        query.push(format!(
+            "SELECT e.{side} AS player_id, e.volume, e.fee, e.buddy FROM market.exchanges e WHERE TRUE"
+        ));
+        if let Some(currency_mint) = &filter.currency_mint {
+            query
+                .push(" AND e.pair IN (SELECT id FROM staratlas.tokens WHERE mint = ")
+                .push_bind(currency_mint)
+                .push(")");
+        }
+        if let Some(from_timestamp) = filter.from_timestamp {
+            query.push(" AND e.timestamp >= ").push_bind(from_timestamp);
+        }
+        if let Some(to_timestamp) = filter.to_timestamp {
+            query.push(" AND e.timestamp <= ").push_bind(to_timestamp);
+        }
+    }
+    query.push(")");
+
+    query
+}
+
+/// Ranks players by a metric over their exchanges
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `filter` - The metric, side, currency and time range to rank by
+/// * `limit` - Maximum number of players to return
+/// * `offset` - Number of players to skip
+///
+/// # Returns
+/// A vector of leaderboard entries, best first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_leaderboard(
+    pool: &DbPool,
+    filter: &LeaderboardFilter,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<LeaderboardEntry>> {
+    let metric = leaderboard_metric(filter.metric);
+
+    let mut query = leaderboard_trades(filter);
+    query.push(format!(
+        r#",
+        totals AS (
+            SELECT player_id, COUNT(*) AS trade_count, SUM(volume) AS volume, SUM(fee) AS fees, SUM(buddy) AS buddy
+            FROM trades
+            GROUP BY player_id
+        )
+        SELECT RANK() OVER (ORDER BY t.{metric} DESC) AS rank,
+               p.id AS player_id, p.wallet_address, p.username,
+               t.trade_count, t.volume, t.fees, t.buddy
+        FROM totals t
+        JOIN staratlas.players p ON p.id = t.player_id
+        ORDER BY t.{metric} DESC, p.id
+        LIMIT "#
+    ));
+    query.push_bind(limit).push(" OFFSET ").push_bind(offset);
+
+    let entries = query
+        .build_query_as::<LeaderboardEntry>()
+        .fetch_all(pool)
         .await
         .map_err(DbError::SqlxError)?;
 
+    Ok(entries)
+}
+
+/// Ranks the factions or guilds of the players by a metric over their exchanges
+///
+/// Players without a faction or guild are ranked together as one group. In combined mode an
+/// exchange between two players of the same group counts once per side, like an exchange of
+/// a player with itself on the player leaderboard.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `filter` - The metric, side, currency and time range to rank by
+/// * `group` - The player attribute to group by
+/// * `limit` - Maximum number of groups to return
+/// * `offset` - Number of groups to skip
+///
+/// # Returns
+/// A vector of group leaderboard entries, best first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_group_leaderboard(
+    pool: &DbPool,
+    filter: &LeaderboardFilter,
+    group: PlayerGroup,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<GroupLeaderboardEntry>> {
+    let metric = leaderboard_metric(filter.metric);
+    let column = player_group_column(group);
+
+    let mut query = leaderboard_trades(filter);
+    query.push(format!(
+        r#",
+        totals AS (
+            SELECT p.{column} AS "group", COUNT(DISTINCT t.player_id) AS players, COUNT(*) AS trade_count,
+                   SUM(t.volume) AS volume, SUM(t.fee) AS fees, SUM(t.buddy) AS buddy
+            FROM trades t
+            JOIN staratlas.players p ON p.id = t.player_id
+            GROUP BY p.{column}
+        )
+        SELECT RANK() OVER (ORDER BY t.{metric} DESC) AS rank,
+               t."group", t.players, t.trade_count, t.volume, t.fees, t.buddy
+        FROM totals t
+        ORDER BY t.{metric} DESC, t."group" NULLS LAST
+        LIMIT "#
+    ));
+    query.push_bind(limit).push(" OFFSET ").push_bind(offset);
+
+    let entries = query
+        .build_query_as::<GroupLeaderboardEntry>()
+        .fetch_all(pool)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(entries)
+}
+
+/// Column of `staratlas.players` holding a player group
+fn player_group_column(group: PlayerGroup) -> &'static str {
+    match group {
+        PlayerGroup::Faction => "faction",
+        PlayerGroup::Guild => "guild",
+    }
+}
+
+/// Sums the exchanges matching a filter per faction or guild of the buyer and the seller
+///
+/// Volumes are only comparable within one currency, so the flows are also split by pair.
+/// Players without a faction or guild are summed together.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `filter` - The conditions the exchanges must match
+/// * `group` - The player attribute to group by
+///
+/// # Returns
+/// A vector of flows, ordered by pair and largest volume first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchange_flows(
+    pool: &DbPool,
+    filter: &ExchangeFilter,
+    group: PlayerGroup,
+) -> Result<Vec<ExchangeFlow>> {
+    let column = player_group_column(group);
+
+    let mut query = QueryBuilder::<Postgres>::new(format!(
+        r#"
+        SELECT b.{column} AS buyer_group, s.{column} AS seller_group, p.mint AS pair_mint,
+               p.symbol AS pair_symbol, COUNT(*) AS trade_count, SUM(e.size)::BIGINT AS size,
+               SUM(e.volume) AS volume
+        FROM market.exchanges e
+        JOIN staratlas.players b ON b.id = e.buyer
+        JOIN staratlas.players s ON s.id = e.seller
+        JOIN staratlas.tokens p ON p.id = e.pair
+        "#
+    ));
+    push_exchange_filter(&mut query, filter);
+    query.push(format!(
+        " GROUP BY b.{column}, s.{column}, p.mint, p.symbol ORDER BY p.mint, volume DESC"
+    ));
+
+    let flows = query
+        .build_query_as::<ExchangeFlow>()
+        .fetch_all(pool)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(flows)
+}
+
+/// Both sides of every exchange the player took part in, joined with the counterparty
+/// wallet and the asset and pair tokens
+const PLAYER_TRADES: &str = r#"
+    SELECT e.id, e.slot, e.signature, e.index, e.timestamp, e.side, 'BUY' AS role,
+           cp.wallet_address AS counterparty,
+           a.id AS asset_id, a.mint AS asset_mint, a.symbol AS asset_symbol,
+           p.id AS pair_id, p.mint AS pair_mint, p.symbol AS pair_symbol,
+           e.price, e.size, e.volume, e.fee, e.buddy
+    FROM market.exchanges e
+    JOIN staratlas.players cp ON cp.id = e.seller
+    JOIN staratlas.tokens a ON a.id = e.asset
+    JOIN staratlas.tokens p ON p.id = e.pair
+    WHERE e.buyer = $1
+    UNION ALL
+    SELECT e.id, e.slot, e.signature, e.index, e.timestamp, e.side, 'SELL' AS role,
+           cp.wallet_address AS counterparty,
+           a.id AS asset_id, a.mint AS asset_mint, a.symbol AS asset_symbol,
+           p.id AS pair_id, p.mint AS pair_mint, p.symbol AS pair_symbol,
+           e.price, e.size, e.volume, e.fee, e.buddy
+    FROM market.exchanges e
+    JOIN staratlas.players cp ON cp.id = e.buyer
+    JOIN staratlas.tokens a ON a.id = e.asset
+    JOIN staratlas.tokens p ON p.id = e.pair
+    WHERE e.seller = $1
+"#;
+
+/// Retrieves the trades of a player, newest first
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `player_id` - The ID of the player
+/// * `limit` - Maximum number of trades to return
+/// * `offset` - Number of trades to skip
+///
+/// # Returns
+/// A vector of trades in which the player was the buyer or the seller
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_trades_by_player_id(
+    pool: &DbPool,
+    player_id: i32,
+    limit: i32,
+    offset: i32,
+) -> Result<Vec<PlayerTrade>> {
+    let trades = sqlx::query_as::<_, PlayerTrade>(&format!(
+        "{PLAYER_TRADES} ORDER BY slot DESC, signature DESC, index DESC, role DESC LIMIT $2 OFFSET $3"
+    ))
+    .bind(player_id)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(trades)
+}
+
+/// Retrieves the newest exchanges of each of the given players
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `player_ids` - The IDs of the players
+/// * `limit` - Maximum number of exchanges to return per player
+///
+/// # Returns
+/// A vector of exchanges in which the players were the buyer or the seller, tagged with the
+/// player and ordered newest first per player
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_exchanges_by_player_ids(
+    pool: &DbPool,
+    player_ids: &[i32],
+    limit: i32,
+) -> Result<Vec<PlayerExchange>> {
+    let exchanges = sqlx::query_as::<_, PlayerExchange>(
+        r#"
+        SELECT p.id AS player_id, e.*
+        FROM UNNEST($1::INTEGER[]) AS p(id)
+        CROSS JOIN LATERAL (
+            SELECT *
+            FROM (
+                (SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
+                 FROM market.exchanges
+                 WHERE buyer = p.id
+                 ORDER BY slot DESC, signature DESC, index DESC
+                 LIMIT $2)
+                UNION
+                (SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
+                 FROM market.exchanges
+                 WHERE seller = p.id
+                 ORDER BY slot DESC, signature DESC, index DESC
+                 LIMIT $2)
+            ) player_exchanges
+            ORDER BY slot DESC, signature DESC, index DESC
+            LIMIT $2
+        ) e
+        ORDER BY p.id, e.slot DESC, e.signature DESC, e.index DESC
+        "#,
+    )
+    .bind(player_ids)
+    .bind(limit)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Retrieves all trades of a player in the order they happened
+///
+/// Within an exchange the player traded with themselves, the buy is returned before the sell.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `player_id` - The ID of the player
+///
+/// # Returns
+/// A vector of all trades in which the player was the buyer or the seller
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_all_trades_by_player_id(
+    pool: &DbPool,
+    player_id: i32,
+) -> Result<Vec<PlayerTrade>> {
+    let trades = sqlx::query_as::<_, PlayerTrade>(&format!(
+        "{PLAYER_TRADES} ORDER BY slot, signature, index, role"
+    ))
+    .bind(player_id)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(trades)
+}
+
+/// Creates a new exchange in the database
+///
+/// Exchanges are keyed by `(signature, index)`. If an exchange for the same instruction
+/// already exists it is overwritten, so processing a signature twice is idempotent.
+/// The activity stats of the buyer and seller are updated in the same transaction.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `new_exchange` - The exchange to create
+///
+/// # Returns
+/// The created or updated exchange with its assigned ID
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn create_exchange(pool: &DbPool, new_exchange: &NewExchange) -> Result<Exchange> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    let exchange = upsert_exchange_with_stats(&mut tx, new_exchange).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
     Ok(exchange)
 }
 
@@ -220,7 +923,7 @@ pub async fn create_exchange(pool: &DbPool, new_exchange: &NewExchange) -> Resul
 ///
 /// This function ensures that the buyer, seller, asset, and pair entities exist
 /// in their respective tables before creating the exchange. If they don't exist,
-/// they will be created.
+/// they will be created. Everything is written in a single transaction.
 ///
 /// # Arguments
 /// * `pool` - The database connection pool
@@ -235,31 +938,149 @@ pub async fn create_exchange_with_dependencies(
     pool: &DbPool,
     exchange_data: &ExchangeWithDependencies,
 ) -> Result<Exchange> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    let new_exchange = resolve_exchange_dependencies(&mut tx, exchange_data).await?;
+    let exchange = upsert_exchange_with_stats(&mut tx, &new_exchange).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(exchange)
+}
+
+/// Replaces the stored exchanges of a marketplace transaction
+///
+/// The exchanges are upserted by `(signature, index)` with their dependent entities like
+/// [`create_exchange_with_dependencies`], and exchanges of the signature that the transaction no
+/// longer produces (e.g. after a decoder fix) are deleted, all in one transaction. The player
+/// stats follow the rewritten and deleted rows, the market aggregates they touch are refreshed
+/// by the next [`refresh_pending_market_stats`]. Rewritten and deleted exchanges are announced
+/// on `EXCHANGES_REWRITTEN_CHANNEL` when the transaction commits.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `transaction` - The exchanges of the transaction
+///
+/// # Returns
+/// The stored exchanges of the transaction
+///
+/// # Errors
+/// Returns an error if any of the database operations fail
+pub async fn replace_exchanges(
+    pool: &DbPool,
+    transaction: &MarketplaceTransaction,
+) -> Result<Vec<Exchange>> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    let mut new_exchanges = Vec::with_capacity(transaction.exchanges.len());
+    for exchange_data in &transaction.exchanges {
+        new_exchanges.push(resolve_exchange_dependencies(&mut tx, exchange_data).await?);
+    }
+
+    let previous = sqlx::query_as::<_, Exchange>(
+        r#"
+        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
+        FROM market.exchanges
+        WHERE signature = $1
+        ORDER BY index
+        FOR UPDATE
+        "#,
+    )
+    .bind(&transaction.signature)
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    // Lock every affected player and market up front, in a fixed order, so concurrent workers
+    // can't deadlock
+    let player_ids: Vec<i32> = previous
+        .iter()
+        .map(|exchange| (exchange.buyer, exchange.seller))
+        .chain(
+            new_exchanges
+                .iter()
+                .map(|exchange| (exchange.buyer, exchange.seller)),
+        )
+        .flat_map(|(buyer, seller)| [buyer, seller])
+        .collect();
+    staratlas::lock_players(&mut tx, &player_ids).await?;
+
+    for exchange in &previous {
+        staratlas::apply_exchange_to_player_stats(&mut tx, exchange, -1).await?;
+    }
+
+    let indexes: Vec<i32> = new_exchanges
+        .iter()
+        .map(|exchange| exchange.index)
+        .collect();
+    sqlx::query(
+        r#"
+        DELETE FROM market.exchanges
+        WHERE signature = $1 AND NOT (index = ANY($2))
+        "#,
+    )
+    .bind(&transaction.signature)
+    .bind(&indexes)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    let mut exchanges = Vec::with_capacity(new_exchanges.len());
+    for new_exchange in &new_exchanges {
+        let exchange = write_exchange(&mut tx, new_exchange).await?;
+        staratlas::apply_exchange_to_player_stats(&mut tx, &exchange, 1).await?;
+        if !previous
+            .iter()
+            .any(|previous| previous.index == exchange.index)
+        {
+            queue_new_exchange(&mut tx, exchange.id).await?;
+        }
+        exchanges.push(exchange);
+    }
+
+    let touched: Vec<&Exchange> = previous.iter().chain(&exchanges).collect();
+    mark_market_stats_pending(&mut tx, &touched).await?;
+
+    // Every previous exchange was either rewritten or deleted
+    notify_exchanges_rewritten(&mut tx, previous.len()).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(exchanges)
+}
+
+/// Helper function to get or create the players and tokens of an exchange and record the
+/// token decimals
+async fn resolve_exchange_dependencies(
+    conn: &mut PgConnection,
+    exchange_data: &ExchangeWithDependencies,
+) -> Result<NewExchange> {
     // Get or create buyer
     let buyer = get_or_create_player(
-        pool,
-        exchange_data.buyer_wallet.clone(),
+        &mut *conn,
+        &exchange_data.buyer_wallet,
         exchange_data.timestamp,
     )
     .await?;
 
     // Get or create seller
     let seller = get_or_create_player(
-        pool,
-        exchange_data.seller_wallet.clone(),
+        &mut *conn,
+        &exchange_data.seller_wallet,
         exchange_data.timestamp,
     )
     .await?;
 
     // Get or create asset token
-    let asset =
-        get_or_create_token(pool, exchange_data.asset_mint.clone(), None, None, None).await?;
+    let asset = get_or_create_token(&mut *conn, &exchange_data.asset_mint).await?;
 
     // Get or create pair token
-    let pair = get_or_create_token(pool, exchange_data.pair_mint.clone(), None, None, None).await?;
+    let pair = get_or_create_token(&mut *conn, &exchange_data.pair_mint).await?;
 
-    // Create the exchange
-    let new_exchange = NewExchange {
+    set_token_decimals(&mut *conn, asset.id, exchange_data.asset_decimals).await?;
+    set_token_decimals(&mut *conn, pair.id, exchange_data.pair_decimals).await?;
+
+    Ok(NewExchange {
         slot: exchange_data.slot,
         signature: exchange_data.signature.clone(),
         index: exchange_data.index,
@@ -274,65 +1095,818 @@ pub async fn create_exchange_with_dependencies(
         volume: exchange_data.volume,
         fee: exchange_data.fee,
         buddy: exchange_data.buddy,
-    };
+        processor_version: exchange_data.processor_version,
+    })
+}
+
+/// Helper function to upsert an exchange and move the player stats from the previous
+/// version of the row (if any) to the new one, marking the affected market aggregates pending
+///
+/// New exchanges are queued for the alert dispatcher and announced on `NEW_EXCHANGE_CHANNEL`,
+/// rewritten ones on `EXCHANGES_REWRITTEN_CHANNEL`, when the transaction commits.
+async fn upsert_exchange_with_stats(
+    conn: &mut PgConnection,
+    new_exchange: &NewExchange,
+) -> Result<Exchange> {
+    let previous = sqlx::query_as::<_, Exchange>(
+        r#"
+        SELECT id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
+        FROM market.exchanges
+        WHERE signature = $1 AND index = $2
+        FOR UPDATE
+        "#,
+    )
+    .bind(new_exchange.signature.as_str())
+    .bind(new_exchange.index)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    // Lock every affected player up front, in a fixed order, so concurrent workers can't deadlock
+    let mut player_ids = vec![new_exchange.buyer, new_exchange.seller];
+    if let Some(previous) = &previous {
+        player_ids.extend([previous.buyer, previous.seller]);
+    }
+    staratlas::lock_players(conn, &player_ids).await?;
+
+    if let Some(previous) = &previous {
+        staratlas::apply_exchange_to_player_stats(conn, previous, -1).await?;
+    }
+
+    let exchange = write_exchange(conn, new_exchange).await?;
+
+    staratlas::apply_exchange_to_player_stats(conn, &exchange, 1).await?;
+
+    if previous.is_none() {
+        queue_new_exchange(conn, exchange.id).await?;
+    } else {
+        notify_exchanges_rewritten(conn, 1).await?;
+    }
+
+    let touched: Vec<&Exchange> = std::iter::once(&exchange).chain(&previous).collect();
+    mark_market_stats_pending(conn, &touched).await?;
+
+    Ok(exchange)
+}
+
+/// Helper function to insert an exchange or overwrite the one of the same `(signature, index)`
+async fn write_exchange(conn: &mut PgConnection, new_exchange: &NewExchange) -> Result<Exchange> {
+    sqlx::query_as::<_, Exchange>(
+        r#"
+        INSERT INTO market.exchanges (
+            slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
+        )
+        VALUES (
+            $1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $15
+        )
+        ON CONFLICT ON CONSTRAINT unique_txhash_index DO UPDATE SET
+            slot = EXCLUDED.slot,
+            timestamp = EXCLUDED.timestamp,
+            side = EXCLUDED.side,
+            buyer = EXCLUDED.buyer,
+            seller = EXCLUDED.seller,
+            asset = EXCLUDED.asset,
+            pair = EXCLUDED.pair,
+            price = EXCLUDED.price,
+            size = EXCLUDED.size,
+            volume = EXCLUDED.volume,
+            fee = EXCLUDED.fee,
+            buddy = EXCLUDED.buddy,
+            processor_version = EXCLUDED.processor_version
+        RETURNING id, slot, signature, index, timestamp, side, buyer, seller, asset, pair, price, size, volume, fee, buddy, processor_version
+        "#,
+    )
+    .bind(new_exchange.slot)
+    .bind(new_exchange.signature.as_str())
+    .bind(new_exchange.index)
+    .bind(new_exchange.timestamp)
+    .bind(new_exchange.side.as_str())
+    .bind(new_exchange.buyer)
+    .bind(new_exchange.seller)
+    .bind(new_exchange.asset)
+    .bind(new_exchange.pair)
+    .bind(new_exchange.price)
+    .bind(new_exchange.size)
+    .bind(new_exchange.volume)
+    .bind(new_exchange.fee)
+    .bind(new_exchange.buddy)
+    .bind(new_exchange.processor_version)
+    .fetch_one(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)
+}
+
+/// Helper function to queue a new exchange for the alert dispatcher and announce it on
+/// `NEW_EXCHANGE_CHANNEL` when the transaction commits
+async fn queue_new_exchange(conn: &mut PgConnection, exchange_id: i32) -> Result<()> {
+    sqlx::query(
+        r#"
+        INSERT INTO alerts.exchange_queue (exchange_id)
+        VALUES ($1)
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(exchange_id)
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(NEW_EXCHANGE_CHANNEL)
+        .bind(exchange_id.to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Helper function to announce rewritten or deleted exchanges on `EXCHANGES_REWRITTEN_CHANNEL`
+///
+/// Nothing is announced if no exchange was rewritten. The notification is sent when the
+/// transaction of the connection commits.
+async fn notify_exchanges_rewritten(conn: &mut PgConnection, rewritten: usize) -> Result<()> {
+    if rewritten == 0 {
+        return Ok(());
+    }
+
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(EXCHANGES_REWRITTEN_CHANNEL)
+        .bind(rewritten.min(i32::MAX as usize).to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Helper function to lock the aggregate rows of the given markets, creating them if needed
+///
+/// Markets are locked in a fixed order, so concurrent refreshes can't deadlock.
+async fn lock_markets(conn: &mut PgConnection, markets: &[(i32, i32)]) -> Result<()> {
+    let mut markets = markets.to_vec();
+    markets.sort_unstable();
+    markets.dedup();
+
+    for (asset, pair) in markets {
+        sqlx::query(
+            r#"
+            INSERT INTO market.markets (asset, pair)
+            VALUES ($1, $2)
+            ON CONFLICT DO NOTHING
+            "#,
+        )
+        .bind(asset)
+        .bind(pair)
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        sqlx::query(
+            r#"
+            SELECT 1
+            FROM market.markets
+            WHERE asset = $1 AND pair = $2
+            FOR UPDATE
+            "#,
+        )
+        .bind(asset)
+        .bind(pair)
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+    }
+
+    Ok(())
+}
+
+/// Helper function to mark the hourly aggregates of the markets of the given exchanges for
+/// [`refresh_pending_market_stats`]
+async fn mark_market_stats_pending(conn: &mut PgConnection, exchanges: &[&Exchange]) -> Result<()> {
+    let assets: Vec<i32> = exchanges.iter().map(|exchange| exchange.asset).collect();
+    let pairs: Vec<i32> = exchanges.iter().map(|exchange| exchange.pair).collect();
+    let timestamps: Vec<DateTime<Utc>> = exchanges
+        .iter()
+        .map(|exchange| exchange.timestamp)
+        .collect();
+
+    sqlx::query(
+        r#"
+        INSERT INTO market.market_stats_pending (asset, pair, bucket)
+        SELECT DISTINCT asset, pair, date_trunc('hour', timestamp, 'UTC')
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::TIMESTAMPTZ[]) AS touched (asset, pair, timestamp)
+        ON CONFLICT DO NOTHING
+        "#,
+    )
+    .bind(&assets)
+    .bind(&pairs)
+    .bind(&timestamps)
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Recomputes the hourly aggregates marked pending by exchange writes, and the last trade of
+/// their markets, from the exchanges
+///
+/// Writes only mark the hours they touch, so the aggregates of a whole batch of transactions
+/// are refreshed with a fixed number of queries. Concurrent callers claim disjoint pending
+/// hours and lock the markets in a fixed order.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+///
+/// # Returns
+/// The number of hourly aggregates refreshed
+///
+/// # Errors
+/// Returns an error if any of the database operations fail
+pub async fn refresh_pending_market_stats(pool: &DbPool) -> Result<u64> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    let pending: Vec<(i32, i32, DateTime<Utc>)> = sqlx::query_as(
+        r#"
+        DELETE FROM market.market_stats_pending
+        WHERE (asset, pair, bucket) IN (
+            SELECT asset, pair, bucket
+            FROM market.market_stats_pending
+            FOR UPDATE SKIP LOCKED
+        )
+        RETURNING asset, pair, bucket
+        "#,
+    )
+    .fetch_all(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    if pending.is_empty() {
+        tx.commit().await.map_err(DbError::SqlxError)?;
+        return Ok(0);
+    }
+
+    let markets: Vec<(i32, i32)> = pending
+        .iter()
+        .map(|&(asset, pair, _)| (asset, pair))
+        .collect();
+    lock_markets(&mut tx, &markets).await?;
+
+    let assets: Vec<i32> = pending.iter().map(|&(asset, _, _)| asset).collect();
+    let pairs: Vec<i32> = pending.iter().map(|&(_, pair, _)| pair).collect();
+    let buckets: Vec<DateTime<Utc>> = pending.iter().map(|&(_, _, bucket)| bucket).collect();
+
+    sqlx::query(
+        r#"
+        UPDATE market.markets m
+        SET (last_price, last_timestamp, last_slot, last_signature, last_index) = (
+            SELECT price, timestamp, slot, signature, index
+            FROM market.exchanges e
+            WHERE e.asset = m.asset AND e.pair = m.pair
+            ORDER BY slot DESC, signature DESC, index DESC
+            LIMIT 1
+        )
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[]) AS pending (asset, pair)
+        WHERE m.asset = pending.asset AND m.pair = pending.pair
+        "#,
+    )
+    .bind(&assets)
+    .bind(&pairs)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM market.market_stats_hourly h
+        USING UNNEST($1::INTEGER[], $2::INTEGER[], $3::TIMESTAMPTZ[]) AS pending (asset, pair, bucket)
+        WHERE h.asset = pending.asset AND h.pair = pending.pair AND h.bucket = pending.bucket
+        "#,
+    )
+    .bind(&assets)
+    .bind(&pairs)
+    .bind(&buckets)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        INSERT INTO market.market_stats_hourly (
+            asset, pair, bucket, trade_count, size, volume, price_size, high, low, open_price, close_price
+        )
+        SELECT pending.asset,
+               pending.pair,
+               pending.bucket,
+               COUNT(*),
+               SUM(e.size),
+               SUM(e.volume),
+               SUM(e.price * e.size),
+               MAX(e.price),
+               MIN(e.price),
+               (ARRAY_AGG(e.price ORDER BY e.slot, e.signature, e.index))[1],
+               (ARRAY_AGG(e.price ORDER BY e.slot DESC, e.signature DESC, e.index DESC))[1]
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::TIMESTAMPTZ[]) AS pending (asset, pair, bucket)
+        JOIN market.exchanges e
+          ON e.asset = pending.asset AND e.pair = pending.pair
+         AND e.timestamp >= pending.bucket
+         AND e.timestamp < pending.bucket + INTERVAL '1 hour'
+        GROUP BY pending.asset, pending.pair, pending.bucket
+        "#,
+    )
+    .bind(&assets)
+    .bind(&pairs)
+    .bind(&buckets)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query(
+        r#"
+        DELETE FROM market.market_traders_hourly th
+        USING UNNEST($1::INTEGER[], $2::INTEGER[], $3::TIMESTAMPTZ[]) AS pending (asset, pair, bucket)
+        WHERE th.asset = pending.asset AND th.pair = pending.pair AND th.bucket = pending.bucket
+        "#,
+    )
+    .bind(&assets)
+    .bind(&pairs)
+    .bind(&buckets)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
 
-    create_exchange(pool, &new_exchange).await
+    sqlx::query(
+        r#"
+        INSERT INTO market.market_traders_hourly (asset, pair, bucket, player_id)
+        SELECT DISTINCT pending.asset, pending.pair, pending.bucket, traders.player_id
+        FROM UNNEST($1::INTEGER[], $2::INTEGER[], $3::TIMESTAMPTZ[]) AS pending (asset, pair, bucket)
+        JOIN market.exchanges e
+          ON e.asset = pending.asset AND e.pair = pending.pair
+         AND e.timestamp >= pending.bucket
+         AND e.timestamp < pending.bucket + INTERVAL '1 hour',
+             LATERAL (VALUES (e.buyer), (e.seller)) AS traders (player_id)
+        "#,
+    )
+    .bind(&assets)
+    .bind(&pairs)
+    .bind(&buckets)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(pending.len() as u64)
 }
 
 /// Helper function to get a player by wallet address or create a new one if it doesn't exist
+///
+/// Concurrent workers may create the same player, so the insert tolerates conflicts.
+/// The player row is not locked here; activity timestamps are updated with the stats.
 async fn get_or_create_player(
-    pool: &DbPool,
-    wallet_address: String,
+    conn: &mut PgConnection,
+    wallet_address: &str,
     timestamp: DateTime<Utc>,
 ) -> Result<Player> {
+    let select_player = r#"
+        SELECT id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+               faction, guild
+        FROM staratlas.players
+        WHERE wallet_address = $1
+        "#;
+
     // Try to get the player by wallet address
-    if let Some(player) = staratlas::get_player_by_wallet_address(pool, &wallet_address).await? {
+    if let Some(player) = sqlx::query_as::<_, Player>(select_player)
+        .bind(wallet_address)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?
+    {
         return Ok(player);
     }
 
-    // Player doesn't exist, create a new one
-    let new_player = NewPlayer {
-        wallet_address,
-        username: None,
-        first_seen: timestamp,
-        last_active: timestamp,
+    // Player doesn't exist, create a new one attributed like the stored players
+    let player = sqlx::query_as::<_, Player>(
+        r#"
+        INSERT INTO staratlas.players (
+            wallet_address, username, faction, guild, first_seen, last_active
+        )
+        VALUES (
+            $1,
+            (
+                SELECT LEFT(p.name, 50)
+                FROM staratlas.profile_keys k
+                JOIN staratlas.profiles p ON p.address = k.profile
+                WHERE k.key = $1 AND k.is_auth AND p.name IS NOT NULL
+                ORDER BY p.created_at DESC NULLS LAST
+                LIMIT 1
+            ),
+            (
+                SELECT f.faction
+                FROM staratlas.profile_keys k
+                JOIN staratlas.profiles p ON p.address = k.profile
+                JOIN staratlas.profile_factions f ON f.profile = k.profile
+                WHERE k.key = $1 AND k.is_auth
+                ORDER BY p.created_at DESC NULLS LAST
+                LIMIT 1
+            ),
+            (
+                SELECT g.name
+                FROM staratlas.guild_members m
+                JOIN staratlas.guilds g ON g.id = m.guild_id
+                WHERE m.wallet_address = $1
+                ORDER BY g.position
+                LIMIT 1
+            ),
+            $2, $2
+        )
+        ON CONFLICT (wallet_address) DO NOTHING
+        RETURNING id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+                  faction, guild
+        "#,
+    )
+    .bind(wallet_address)
+    .bind(timestamp)
+    .fetch_optional(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    match player {
+        Some(player) => Ok(player),
+        // Another worker created the player in the meantime
+        None => sqlx::query_as::<_, Player>(select_player)
+            .bind(wallet_address)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(DbError::SqlxError),
+    }
+}
+
+/// Helper function to record the decimals of a token, leaving them as they are if unknown
+async fn set_token_decimals(
+    conn: &mut PgConnection,
+    token_id: i32,
+    decimals: Option<i16>,
+) -> Result<()> {
+    let Some(decimals) = decimals else {
+        return Ok(());
     };
 
-    staratlas::create_player(pool, &new_player).await
+    sqlx::query(
+        r#"
+        UPDATE staratlas.tokens
+        SET decimals = $2
+        WHERE id = $1 AND decimals IS DISTINCT FROM $2
+        "#,
+    )
+    .bind(token_id)
+    .bind(decimals)
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(())
 }
 
 /// Helper function to get a token by mint address or create a new one if it doesn't exist
-async fn get_or_create_token(
-    pool: &DbPool,
-    mint: String,
-    name: Option<String>,
-    symbol: Option<String>,
-    token_type: Option<String>,
-) -> Result<Token> {
-    // Try to find the token by mint address
-    let tokens = sqlx::query_as::<_, Token>(
-        r#"
+///
+/// New tokens are announced on `NEW_TOKEN_CHANNEL` when the transaction commits.
+async fn get_or_create_token(conn: &mut PgConnection, mint: &str) -> Result<Token> {
+    let select_token = r#"
         SELECT id, mint, name, symbol, token_type
         FROM staratlas.tokens
         WHERE mint = $1
+        "#;
+
+    // Try to find the token by mint address
+    if let Some(token) = sqlx::query_as::<_, Token>(select_token)
+        .bind(mint)
+        .fetch_optional(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?
+    {
+        return Ok(token);
+    }
+
+    // Token doesn't exist, create a new one
+    let token = sqlx::query_as::<_, Token>(
+        r#"
+        INSERT INTO staratlas.tokens (
+            mint, name, symbol, token_type
+        )
+        VALUES (
+            $1, NULL, NULL, NULL
+        )
+        ON CONFLICT (mint) DO NOTHING
+        RETURNING id, mint, name, symbol, token_type
         "#,
     )
-    .bind(&mint.as_str())
-    .fetch_all(pool)
+    .bind(mint)
+    .fetch_optional(&mut *conn)
     .await
     .map_err(DbError::SqlxError)?;
 
-    if let Some(token) = tokens.into_iter().next() {
-        return Ok(token);
+    match token {
+        Some(token) => {
+            staratlas::notify_new_token(conn, token.id).await?;
+            Ok(token)
+        }
+        // Another worker created the token in the meantime
+        None => sqlx::query_as::<_, Token>(select_token)
+            .bind(mint)
+            .fetch_one(&mut *conn)
+            .await
+            .map_err(DbError::SqlxError),
     }
+}
 
-    // Token doesn't exist, create a new one
-    let new_token = NewToken {
-        mint,
-        name,
-        symbol,
-        token_type,
-    };
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ExchangeCursor;
+
+    /// Fixture rows with mints, wallets and signatures unique to the run
+    struct Fixture {
+        pool: DbPool,
+        prefix: String,
+    }
+
+    impl Fixture {
+        fn name(&self, name: &str) -> String {
+            format!("{}{}", self.prefix, name)
+        }
+
+        fn exchange(&self, index: i32) -> ExchangeWithDependencies {
+            ExchangeWithDependencies {
+                slot: 1,
+                signature: self.name("sig"),
+                index,
+                timestamp: Utc::now(),
+                side: "BUY".to_string(),
+                buyer_wallet: self.name("buyer"),
+                seller_wallet: self.name("seller"),
+                asset_mint: self.name("asset"),
+                pair_mint: self.name("atlas"),
+                asset_decimals: Some(0),
+                pair_decimals: Some(8),
+                price: 10.0,
+                size: 1,
+                volume: 10.0,
+                fee: 0.0,
+                buddy: 0.0,
+                processor_version: 1,
+            }
+        }
+
+        fn transaction(&self, exchanges: i32) -> MarketplaceTransaction {
+            MarketplaceTransaction {
+                signature: self.name("sig"),
+                exchanges: (0..exchanges).map(|index| self.exchange(index)).collect(),
+            }
+        }
+
+        async fn clean_up(&self) {
+            for query in [
+                "DELETE FROM market.exchanges WHERE signature LIKE $1 || '%'",
+                "DELETE FROM staratlas.tokens WHERE mint LIKE $1 || '%'",
+                "DELETE FROM staratlas.players WHERE wallet_address LIKE $1 || '%'",
+            ] {
+                sqlx::query(query)
+                    .bind(&self.prefix)
+                    .execute(&self.pool)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn reprocessing_with_fewer_exchanges_deletes_the_rest() {
+        dotenv::dotenv().ok();
+        if std::env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return;
+        }
+        let fixture = Fixture {
+            pool: crate::establish_connection().await.unwrap(),
+            prefix: format!("replace{}", Utc::now().timestamp_micros()),
+        };
+
+        let first = replace_exchanges(&fixture.pool, &fixture.transaction(2)).await;
+        let second = replace_exchanges(&fixture.pool, &fixture.transaction(1)).await;
+        let stored: Vec<i32> = sqlx::query_scalar(
+            "SELECT index FROM market.exchanges WHERE signature = $1 ORDER BY index",
+        )
+        .bind(fixture.name("sig"))
+        .fetch_all(&fixture.pool)
+        .await
+        .unwrap();
+        let buy_count: i32 =
+            sqlx::query_scalar("SELECT buy_count FROM staratlas.players WHERE wallet_address = $1")
+                .bind(fixture.name("buyer"))
+                .fetch_one(&fixture.pool)
+                .await
+                .unwrap();
+        fixture.clean_up().await;
+
+        assert_eq!(first.unwrap().len(), 2);
+        assert_eq!(second.unwrap().len(), 1);
+        assert_eq!(stored, [0]);
+        // The deleted exchange no longer counts for the buyer
+        assert_eq!(buy_count, 1);
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn rewriting_an_exchange_moves_it_between_player_stats() {
+        dotenv::dotenv().ok();
+        if std::env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return;
+        }
+        let fixture = Fixture {
+            pool: crate::establish_connection().await.unwrap(),
+            prefix: format!("rewrite{}", Utc::now().timestamp_micros()),
+        };
+
+        let first = replace_exchanges(&fixture.pool, &fixture.transaction(1)).await;
+        let mut rewritten = fixture.transaction(1);
+        rewritten.exchanges[0].buyer_wallet = fixture.name("other");
+        let second = replace_exchanges(&fixture.pool, &rewritten).await;
+        let stats: Vec<(String, i32, i32, f64, i32)> = sqlx::query_as(
+            r#"
+            SELECT p.wallet_address, p.buy_count, p.sell_count,
+                   v.buy_volume + v.sell_volume, COALESCE(a.trade_count, 0)
+            FROM staratlas.players p
+            JOIN staratlas.player_volumes v ON v.player_id = p.id
+            LEFT JOIN staratlas.player_assets a ON a.player_id = p.id
+            WHERE p.wallet_address LIKE $1 || '%'
+            ORDER BY p.wallet_address
+            "#,
+        )
+        .bind(&fixture.prefix)
+        .fetch_all(&fixture.pool)
+        .await
+        .unwrap();
+        fixture.clean_up().await;
+
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        assert_eq!(
+            stats,
+            [
+                (fixture.name("buyer"), 0, 0, 0.0, 0),
+                (fixture.name("other"), 1, 0, 10.0, 1),
+                (fixture.name("seller"), 0, 1, 10.0, 1),
+            ]
+        );
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn rewriting_exchanges_is_announced() {
+        dotenv::dotenv().ok();
+        if std::env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return;
+        }
+        let fixture = Fixture {
+            pool: crate::establish_connection().await.unwrap(),
+            prefix: format!("announce{}", Utc::now().timestamp_micros()),
+        };
+        let mut listener = crate::ChangeListener::connect(&fixture.pool).await.unwrap();
+
+        let first = replace_exchanges(&fixture.pool, &fixture.transaction(2)).await;
+        let second = replace_exchanges(&fixture.pool, &fixture.transaction(1)).await;
+        // Other tests may insert exchanges concurrently
+        let rewritten = tokio::time::timeout(std::time::Duration::from_secs(5), async {
+            loop {
+                if let Ok(Some(crate::DatabaseChange::ExchangesRewritten(count))) =
+                    listener.recv().await
+                {
+                    return count;
+                }
+            }
+        })
+        .await;
+        fixture.clean_up().await;
+
+        assert!(first.is_ok());
+        assert!(second.is_ok());
+        // One exchange was rewritten and one deleted
+        assert_eq!(rewritten, Ok(2));
+    }
 
-    staratlas::create_token(pool, &new_token).await
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn cursor_pages_ignore_the_offset() {
+        dotenv::dotenv().ok();
+        if std::env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return;
+        }
+        let fixture = Fixture {
+            pool: crate::establish_connection().await.unwrap(),
+            prefix: format!("cursor{}", Utc::now().timestamp_micros()),
+        };
+
+        let written = replace_exchanges(&fixture.pool, &fixture.transaction(3)).await;
+        let filter = ExchangeFilter {
+            signature: Some(fixture.name("sig")),
+            after: Some(ExchangeCursor {
+                slot: 1,
+                signature: fixture.name("sig"),
+                index: 2,
+            }),
+            ..Default::default()
+        };
+        let page = get_exchanges_filtered(&fixture.pool, &filter, 10, 1).await;
+        fixture.clean_up().await;
+
+        assert!(written.is_ok());
+        let indexes: Vec<i32> = page
+            .unwrap()
+            .iter()
+            .map(|exchange| exchange.index)
+            .collect();
+        assert_eq!(indexes, [1, 0]);
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn market_aggregates_follow_the_exchanges_once_refreshed() {
+        dotenv::dotenv().ok();
+        if std::env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return;
+        }
+        let fixture = Fixture {
+            pool: crate::establish_connection().await.unwrap(),
+            prefix: format!("stats{}", Utc::now().timestamp_micros()),
+        };
+        let hourly = || {
+            sqlx::query_as::<_, (i32, i64, f64, f64, f64, f64, f64, Option<f64>)>(
+                r#"
+                SELECT h.trade_count, h.size, h.volume, h.high, h.low, h.open_price, h.close_price,
+                       m.last_price
+                FROM market.market_stats_hourly h
+                JOIN market.markets m ON m.asset = h.asset AND m.pair = h.pair
+                JOIN staratlas.tokens a ON a.id = h.asset
+                WHERE a.mint = $1
+                "#,
+            )
+            .bind(fixture.name("asset"))
+            .fetch_all(&fixture.pool)
+        };
+        let traders = || {
+            sqlx::query_scalar::<_, i64>(
+                r#"
+                SELECT COUNT(*)
+                FROM market.market_traders_hourly th
+                JOIN staratlas.tokens a ON a.id = th.asset
+                WHERE a.mint = $1
+                "#,
+            )
+            .bind(fixture.name("asset"))
+            .fetch_one(&fixture.pool)
+        };
+
+        // All in one hour, so one aggregate row
+        let timestamp = Utc::now();
+        let mut transaction = fixture.transaction(3);
+        for (exchange, price) in transaction.exchanges.iter_mut().zip([12.0, 8.0, 10.0]) {
+            exchange.timestamp = timestamp;
+            exchange.price = price;
+            exchange.size = 2;
+            exchange.volume = price * 2.0;
+        }
+        transaction.exchanges[2].buyer_wallet = fixture.name("other");
+        replace_exchanges(&fixture.pool, &transaction)
+            .await
+            .unwrap();
+        let before_refresh = hourly().await.unwrap();
+        refresh_pending_market_stats(&fixture.pool).await.unwrap();
+        let refreshed = hourly().await.unwrap();
+        let refreshed_traders = traders().await.unwrap();
+
+        transaction.exchanges.truncate(1);
+        replace_exchanges(&fixture.pool, &transaction)
+            .await
+            .unwrap();
+        refresh_pending_market_stats(&fixture.pool).await.unwrap();
+        let rewritten = hourly().await.unwrap();
+        let rewritten_traders = traders().await.unwrap();
+        fixture.clean_up().await;
+
+        // Writes leave the aggregates to the refresh
+        assert!(before_refresh.is_empty());
+        assert_eq!(refreshed, [(3, 6, 60.0, 12.0, 8.0, 12.0, 10.0, Some(10.0))]);
+        assert_eq!(refreshed_traders, 3);
+        assert_eq!(
+            rewritten,
+            [(1, 2, 24.0, 12.0, 12.0, 12.0, 12.0, Some(12.0))]
+        );
+        assert_eq!(rewritten_traders, 2);
+    }
 }
@@ -1,11 +1,29 @@
 //! Database queries
 
+mod alerts;
+mod auth;
+mod crafting;
+mod guilds;
 mod indexer;
+mod logs;
+mod market_accounts;
 mod marketplace;
+mod player_profiles;
+mod sage;
+mod score;
 mod signature;
 pub mod staratlas;
 
+pub use alerts::*;
+pub use auth::*;
+pub use crafting::*;
+pub use guilds::*;
 pub use indexer::*;
+pub use logs::*;
+pub use market_accounts::*;
 pub use marketplace::*;
+pub use player_profiles::*;
+pub use sage::*;
+pub use score::*;
 pub use signature::*;
 pub use staratlas::*;
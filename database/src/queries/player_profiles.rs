@@ -0,0 +1,380 @@
+//! Database queries for the player profiles
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{FactionSnapshot, Profile, ProfileKey, ProfileSnapshot};
+use crate::queries::staratlas::notify_players_updated;
+use sqlx::PgConnection;
+
+/// Replaces the stored profiles with a snapshot and updates the player usernames
+///
+/// Profiles of the snapshot are inserted or updated with their keys, and stored profiles
+/// missing from it are deleted. Players then get the name of the profile their wallet is an
+/// auth key of, the newest named one if there are several, and their faction is updated as
+/// the keys may have changed. Snapshots are applied one at a time and a snapshot older than the
+/// stored one is ignored.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `snapshot` - The profile accounts of the Player Profile program at one slot
+///
+/// # Returns
+/// The number of players whose username changed, or None if the snapshot was ignored because
+/// a newer one is stored
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_profile_snapshot(
+    pool: &DbPool,
+    snapshot: &ProfileSnapshot,
+) -> Result<Option<u64>> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("LOCK TABLE staratlas.profiles IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let stored_slot: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(snapshot_slot) FROM staratlas.profiles")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(DbError::SqlxError)?;
+    if stored_slot.is_some_and(|stored_slot| stored_slot > snapshot.slot) {
+        return Ok(None);
+    }
+
+    let profiles = &snapshot.profiles;
+    sqlx::query(
+        r#"
+        INSERT INTO staratlas.profiles (
+            address, auth_key_count, key_threshold, created_at, name, snapshot_slot
+        )
+        SELECT p.*, $6
+        FROM UNNEST(
+            $1::VARCHAR[], $2::SMALLINT[], $3::SMALLINT[], $4::TIMESTAMPTZ[], $5::VARCHAR[]
+        ) AS p
+        ON CONFLICT (address) DO UPDATE SET
+            auth_key_count = EXCLUDED.auth_key_count,
+            key_threshold = EXCLUDED.key_threshold,
+            created_at = EXCLUDED.created_at,
+            name = EXCLUDED.name,
+            snapshot_slot = EXCLUDED.snapshot_slot
+        "#,
+    )
+    .bind(
+        profiles
+            .iter()
+            .map(|p| p.address.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        profiles
+            .iter()
+            .map(|p| p.auth_key_count)
+            .collect::<Vec<_>>(),
+    )
+    .bind(profiles.iter().map(|p| p.key_threshold).collect::<Vec<_>>())
+    .bind(profiles.iter().map(|p| p.created_at).collect::<Vec<_>>())
+    .bind(profiles.iter().map(|p| p.name.clone()).collect::<Vec<_>>())
+    .bind(snapshot.slot)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query("DELETE FROM staratlas.profiles WHERE snapshot_slot <> $1")
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    // Keys are rewritten, they are added and removed by the players
+    sqlx::query("DELETE FROM staratlas.profile_keys")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let keys: Vec<_> = profiles
+        .iter()
+        .flat_map(|profile| {
+            profile
+                .keys
+                .iter()
+                .enumerate()
+                .map(move |(position, key)| (&profile.address, position as i16, key))
+        })
+        .collect();
+    sqlx::query(
+        r#"
+        INSERT INTO staratlas.profile_keys (
+            profile, position, key, scope, expire_time, permissions, is_auth
+        )
+        SELECT *
+        FROM UNNEST(
+            $1::VARCHAR[], $2::SMALLINT[], $3::VARCHAR[], $4::VARCHAR[], $5::BIGINT[], $6::BYTEA[],
+            $7::BOOLEAN[]
+        )
+        "#,
+    )
+    .bind(
+        keys.iter()
+            .map(|(profile, _, _)| profile.to_string())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        keys.iter()
+            .map(|(_, position, _)| *position)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        keys.iter()
+            .map(|(_, _, k)| k.key.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        keys.iter()
+            .map(|(_, _, k)| k.scope.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        keys.iter()
+            .map(|(_, _, k)| k.expire_time)
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        keys.iter()
+            .map(|(_, _, k)| k.permissions.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(keys.iter().map(|(_, _, k)| k.is_auth).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    let updated = sqlx::query(
+        r#"
+        UPDATE staratlas.players p
+        SET username = n.name
+        FROM (
+            SELECT DISTINCT ON (k.key) k.key, LEFT(pr.name, 50) AS name
+            FROM staratlas.profile_keys k
+            JOIN staratlas.profiles pr ON pr.address = k.profile
+            WHERE k.is_auth AND pr.name IS NOT NULL
+            ORDER BY k.key, pr.created_at DESC NULLS LAST
+        ) n
+        WHERE p.wallet_address = n.key
+          AND p.username IS DISTINCT FROM n.name
+        "#,
+    )
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?
+    .rows_affected();
+
+    let factions_updated = update_player_factions(&mut tx).await?;
+    notify_players_updated(&mut tx, updated + factions_updated).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(Some(updated))
+}
+
+/// Replaces the stored profile factions with a snapshot and updates the player factions
+///
+/// Players get the faction of the newest profile with a faction their wallet is an auth key
+/// of. Snapshots are applied one at a time and a snapshot older than the stored one is
+/// ignored.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `snapshot` - The profile faction accounts of the Profile Faction program at one slot
+///
+/// # Returns
+/// The number of players whose faction changed, or None if the snapshot was ignored because
+/// a newer one is stored
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_faction_snapshot(
+    pool: &DbPool,
+    snapshot: &FactionSnapshot,
+) -> Result<Option<u64>> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("LOCK TABLE staratlas.profile_factions IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let stored_slot: Option<i64> =
+        sqlx::query_scalar("SELECT MAX(snapshot_slot) FROM staratlas.profile_factions")
+            .fetch_one(&mut *tx)
+            .await
+            .map_err(DbError::SqlxError)?;
+    if stored_slot.is_some_and(|stored_slot| stored_slot > snapshot.slot) {
+        return Ok(None);
+    }
+
+    let factions = &snapshot.factions;
+    sqlx::query(
+        r#"
+        INSERT INTO staratlas.profile_factions (profile, faction, snapshot_slot)
+        SELECT f.*, $3
+        FROM UNNEST($1::VARCHAR[], $2::VARCHAR[]) AS f
+        ON CONFLICT (profile) DO UPDATE SET
+            faction = EXCLUDED.faction,
+            snapshot_slot = EXCLUDED.snapshot_slot
+        "#,
+    )
+    .bind(
+        factions
+            .iter()
+            .map(|f| f.profile.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(
+        factions
+            .iter()
+            .map(|f| f.faction.clone())
+            .collect::<Vec<_>>(),
+    )
+    .bind(snapshot.slot)
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    sqlx::query("DELETE FROM staratlas.profile_factions WHERE snapshot_slot <> $1")
+        .bind(snapshot.slot)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let updated = update_player_factions(&mut tx).await?;
+    notify_players_updated(&mut tx, updated).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(Some(updated))
+}
+
+/// Sets the faction of every player to the one of the newest profile with a faction its
+/// wallet is an auth key of, returning the number of changed players
+async fn update_player_factions(conn: &mut PgConnection) -> Result<u64> {
+    let updated = sqlx::query(
+        r#"
+        UPDATE staratlas.players p
+        SET faction = n.faction
+        FROM (
+            SELECT pl.id, (
+                SELECT f.faction
+                FROM staratlas.profile_keys k
+                JOIN staratlas.profiles pr ON pr.address = k.profile
+                JOIN staratlas.profile_factions f ON f.profile = k.profile
+                WHERE k.key = pl.wallet_address AND k.is_auth
+                ORDER BY pr.created_at DESC NULLS LAST
+                LIMIT 1
+            ) AS faction
+            FROM staratlas.players pl
+        ) n
+        WHERE p.id = n.id
+          AND p.faction IS DISTINCT FROM n.faction
+        "#,
+    )
+    .execute(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?
+    .rows_affected();
+
+    Ok(updated)
+}
+
+/// Retrieves a profile by its address
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `address` - The address of the profile account
+///
+/// # Returns
+/// The profile, or None if no such profile is stored
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_profile(pool: &DbPool, address: &str) -> Result<Option<Profile>> {
+    let profile = sqlx::query_as::<_, Profile>(
+        r#"
+        SELECT address, auth_key_count, key_threshold, created_at, name
+        FROM staratlas.profiles
+        WHERE address = $1
+        "#,
+    )
+    .bind(address)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(profile)
+}
+
+/// Retrieves the keys of profiles
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `addresses` - The addresses of the profile accounts
+///
+/// # Returns
+/// A vector of the profiles' keys, ordered by profile with auth keys first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_profile_keys(pool: &DbPool, addresses: &[String]) -> Result<Vec<ProfileKey>> {
+    let keys = sqlx::query_as::<_, ProfileKey>(
+        r#"
+        SELECT profile, key, scope, expire_time, is_auth
+        FROM staratlas.profile_keys
+        WHERE profile = ANY($1)
+        ORDER BY profile, position
+        "#,
+    )
+    .bind(addresses)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(keys)
+}
+
+/// Retrieves the profiles a key (usually a wallet) belongs to
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `key` - The key to look up
+/// * `auth_only` - Only include profiles the key is an auth key of
+///
+/// # Returns
+/// A vector of profiles, newest first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_profiles_by_key(
+    pool: &DbPool,
+    key: &str,
+    auth_only: bool,
+) -> Result<Vec<Profile>> {
+    let profiles = sqlx::query_as::<_, Profile>(
+        r#"
+        SELECT DISTINCT p.address, p.auth_key_count, p.key_threshold, p.created_at, p.name
+        FROM staratlas.profile_keys k
+        JOIN staratlas.profiles p ON p.address = k.profile
+        WHERE k.key = $1
+          AND (k.is_auth OR NOT $2)
+        ORDER BY p.created_at DESC NULLS LAST, p.address
+        "#,
+    )
+    .bind(key)
+    .bind(auth_only)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(profiles)
+}
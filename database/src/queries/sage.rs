@@ -0,0 +1,135 @@
+//! Database queries for the SAGE fleet activity
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::SageTransaction;
+
+/// Replaces the stored SAGE activities of a transaction
+///
+/// Fleets created by the transaction are stored (or updated) and fleets it disbands are
+/// marked as disbanded.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `transaction` - The SAGE activities of the transaction
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_sage_activities(pool: &DbPool, transaction: &SageTransaction) -> Result<()> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    // Transfers are deleted with their activities
+    sqlx::query("DELETE FROM sage.activities WHERE signature = $1")
+        .bind(&transaction.signature)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let activities = &transaction.activities;
+    sqlx::query(
+        r#"
+        INSERT INTO sage.activities (
+            signature, slot, timestamp, instruction_index, kind, instruction, fleet, player_profile,
+            starbase, sector_x, sector_y, resource_mint, amount, args
+        )
+        SELECT $1, $2, $3, a.*
+        FROM UNNEST(
+            $4::INTEGER[], $5::VARCHAR[], $6::VARCHAR[], $7::VARCHAR[], $8::VARCHAR[], $9::VARCHAR[],
+            $10::BIGINT[], $11::BIGINT[], $12::VARCHAR[], $13::BIGINT[], $14::JSONB[]
+        ) AS a
+        "#,
+    )
+    .bind(&transaction.signature)
+    .bind(transaction.slot)
+    .bind(transaction.timestamp)
+    .bind(activities.iter().map(|a| a.instruction_index).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.kind.clone()).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.instruction.clone()).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.fleet.clone()).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.player_profile.clone()).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.starbase.clone()).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.sector.map(|(x, _)| x)).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.sector.map(|(_, y)| y)).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.resource_mint.clone()).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.amount).collect::<Vec<_>>())
+    .bind(activities.iter().map(|a| a.args.clone()).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    let transfers: Vec<_> = activities
+        .iter()
+        .flat_map(|activity| {
+            activity
+                .transfers
+                .iter()
+                .enumerate()
+                .map(move |(index, transfer)| (activity.instruction_index, index as i32, transfer))
+        })
+        .collect();
+    sqlx::query(
+        r#"
+        INSERT INTO sage.activity_transfers (
+            signature, instruction_index, transfer_index, kind, mint, source, destination, amount
+        )
+        SELECT $1, t.*
+        FROM UNNEST(
+            $2::INTEGER[], $3::INTEGER[], $4::VARCHAR[], $5::VARCHAR[], $6::VARCHAR[], $7::VARCHAR[],
+            $8::BIGINT[]
+        ) AS t
+        "#,
+    )
+    .bind(&transaction.signature)
+    .bind(transfers.iter().map(|(index, _, _)| *index).collect::<Vec<_>>())
+    .bind(transfers.iter().map(|(_, index, _)| *index).collect::<Vec<_>>())
+    .bind(transfers.iter().map(|(_, _, t)| t.kind.clone()).collect::<Vec<_>>())
+    .bind(transfers.iter().map(|(_, _, t)| t.mint.clone()).collect::<Vec<_>>())
+    .bind(transfers.iter().map(|(_, _, t)| t.source.clone()).collect::<Vec<_>>())
+    .bind(transfers.iter().map(|(_, _, t)| t.destination.clone()).collect::<Vec<_>>())
+    .bind(transfers.iter().map(|(_, _, t)| t.amount).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    for activity in activities {
+        let Some(fleet) = &activity.fleet else {
+            continue;
+        };
+        match activity.kind.as_str() {
+            "fleet_created" => {
+                sqlx::query(
+                    r#"
+                    INSERT INTO sage.fleets (address, player_profile, label, created_signature, created_at)
+                    VALUES ($1, $2, $3, $4, $5)
+                    ON CONFLICT (address) DO UPDATE
+                        SET player_profile    = EXCLUDED.player_profile,
+                            label             = EXCLUDED.label,
+                            created_signature = EXCLUDED.created_signature,
+                            created_at        = EXCLUDED.created_at
+                    "#,
+                )
+                .bind(fleet)
+                .bind(&activity.player_profile)
+                .bind(&activity.label)
+                .bind(&transaction.signature)
+                .bind(transaction.timestamp)
+                .execute(&mut *tx)
+                .await
+                .map_err(DbError::SqlxError)?;
+            }
+            "fleet_disbanded" => {
+                sqlx::query("UPDATE sage.fleets SET disbanded_at = $2 WHERE address = $1")
+                    .bind(fleet)
+                    .bind(transaction.timestamp)
+                    .execute(&mut *tx)
+                    .await
+                    .map_err(DbError::SqlxError)?;
+            }
+            _ => {}
+        }
+    }
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
@@ -0,0 +1,423 @@
+//! Database queries for the SCORE fleet staking
+
+use crate::connection::DbPool;
+use crate::error::{DbError, Result};
+use crate::models::{ScoreTransaction, StakingPosition, StakingRoi};
+use sqlx::types::chrono::{DateTime, Utc};
+
+/// Replaces the stored SCORE events of a transaction
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `transaction` - The staking events of the transaction
+///
+/// # Errors
+/// Returns an error if a query fails
+pub async fn replace_score_events(pool: &DbPool, transaction: &ScoreTransaction) -> Result<()> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
+    sqlx::query("DELETE FROM score.events WHERE signature = $1")
+        .bind(&transaction.signature)
+        .execute(&mut *tx)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    let events = &transaction.events;
+    sqlx::query(
+        r#"
+        INSERT INTO score.events (
+            signature, slot, timestamp, instruction_index, kind, instruction, player, ship_mint,
+            resource, mint, amount, args
+        )
+        SELECT $1, $2, $3, e.*
+        FROM UNNEST(
+            $4::INTEGER[], $5::VARCHAR[], $6::VARCHAR[], $7::VARCHAR[], $8::VARCHAR[], $9::VARCHAR[],
+            $10::VARCHAR[], $11::BIGINT[], $12::JSONB[]
+        ) AS e
+        "#,
+    )
+    .bind(&transaction.signature)
+    .bind(transaction.slot)
+    .bind(transaction.timestamp)
+    .bind(events.iter().map(|e| e.instruction_index).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.kind.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.instruction.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.player.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.ship_mint.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.resource.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.mint.clone()).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.amount).collect::<Vec<_>>())
+    .bind(events.iter().map(|e| e.args.clone()).collect::<Vec<_>>())
+    .execute(&mut *tx)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Retrieves the staking return per ship over a period
+///
+/// Ships count as staked from their stake until the player withdraws the ships of that mint.
+/// Claimed rewards and the resources supplied in the period (minus those withdrawn) are
+/// compared to the market value of the staked ships. Ships and resources are priced at their
+/// volume weighted average price in ATLAS over the period. Their amounts are in base units and
+/// scaled by the decimals recorded for the mint, without decimals (or a price) the resupply
+/// cost or the return stays empty.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `atlas_mint` - Mint of ATLAS, the reward and pricing currency
+/// * `atlas_decimals` - Decimals of ATLAS
+/// * `ship_mint` - Only include this ship
+/// * `since` - Start of the period
+///
+/// # Returns
+/// A vector of staking returns, highest return first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_staking_roi(
+    pool: &DbPool,
+    atlas_mint: &str,
+    atlas_decimals: u8,
+    ship_mint: Option<&str>,
+    since: DateTime<Utc>,
+) -> Result<Vec<StakingRoi>> {
+    let roi = sqlx::query_as::<_, StakingRoi>(
+        r#"
+        WITH prices AS (
+            SELECT asset.mint, asset.decimals,
+                   SUM(e.price * e.size) / NULLIF(SUM(e.size), 0) AS price
+            FROM market.exchanges e
+            JOIN staratlas.tokens asset ON asset.id = e.asset
+            JOIN staratlas.tokens pair ON pair.id = e.pair
+            WHERE pair.mint = $1 AND e.timestamp >= $4
+            GROUP BY asset.mint, asset.decimals
+        ),
+        withdrawals AS (
+            SELECT player, ship_mint, MAX(timestamp) AS timestamp
+            FROM score.events
+            WHERE kind = 'unstake'
+            GROUP BY player, ship_mint
+        ),
+        staked AS (
+            SELECT e.ship_mint, SUM(e.amount)::BIGINT AS ships, COUNT(DISTINCT e.player) AS stakers
+            FROM score.events e
+            LEFT JOIN withdrawals w ON w.player = e.player AND w.ship_mint = e.ship_mint
+            WHERE e.kind = 'stake'
+              AND (w.timestamp IS NULL OR e.timestamp > w.timestamp)
+            GROUP BY e.ship_mint
+        ),
+        period AS (
+            SELECT e.ship_mint,
+                   (SUM(e.amount) FILTER (WHERE e.kind = 'claim' AND e.mint = $1))::FLOAT8 AS rewards,
+                   SUM(CASE e.kind WHEN 'resupply' THEN e.amount ELSE -e.amount END
+                           / POWER(10, p.decimals) * p.price)
+                       FILTER (WHERE e.kind IN ('resupply', 'resupply_withdrawn')) AS resupply_value,
+                   COUNT(*) FILTER (WHERE e.kind IN ('resupply', 'resupply_withdrawn')
+                       AND (p.price IS NULL OR p.decimals IS NULL)) AS unpriced
+            FROM score.events e
+            LEFT JOIN prices p ON p.mint = e.mint
+            WHERE e.timestamp >= $4
+            GROUP BY e.ship_mint
+        ),
+        ships AS (
+            SELECT COALESCE(s.ship_mint, r.ship_mint) AS ship_mint,
+                   COALESCE(s.ships, 0) AS ships_staked,
+                   COALESCE(s.stakers, 0) AS stakers,
+                   COALESCE(r.rewards, 0) / POWER(10, $2::INTEGER) AS rewards,
+                   CASE WHEN COALESCE(r.unpriced, 0) = 0 THEN COALESCE(r.resupply_value, 0) END AS resupply_cost,
+                   p.price AS ship_price,
+                   p.decimals AS ship_decimals
+            FROM staked s
+            FULL JOIN period r ON r.ship_mint = s.ship_mint
+            LEFT JOIN prices p ON p.mint = COALESCE(s.ship_mint, r.ship_mint)
+        )
+        SELECT s.ship_mint, s.ships_staked, s.stakers, s.rewards,
+               s.resupply_cost, s.ship_price,
+               (s.rewards - s.resupply_cost)
+                   / NULLIF(s.ships_staked / POWER(10, s.ship_decimals) * s.ship_price, 0) AS roi
+        FROM ships s
+        WHERE s.ship_mint IS NOT NULL
+          AND ($3::VARCHAR IS NULL OR s.ship_mint = $3)
+        ORDER BY roi DESC NULLS LAST, s.ship_mint
+        "#,
+    )
+    .bind(atlas_mint)
+    .bind(atlas_decimals as i32)
+    .bind(ship_mint)
+    .bind(since)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(roi)
+}
+
+/// Retrieves the staking positions of a player
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `player` - Wallet of the player
+/// * `atlas_decimals` - Decimals of ATLAS, the reward currency
+///
+/// # Returns
+/// A vector of positions per ship the player ever staked, currently staked ships first
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_staking_positions(
+    pool: &DbPool,
+    player: &str,
+    atlas_decimals: u8,
+) -> Result<Vec<StakingPosition>> {
+    let positions = sqlx::query_as::<_, StakingPosition>(
+        r#"
+        WITH events AS (
+            SELECT e.*,
+                   MAX(e.timestamp) FILTER (WHERE e.kind = 'unstake')
+                       OVER (PARTITION BY e.ship_mint) AS withdrawn_at
+            FROM score.events e
+            WHERE e.player = $1 AND e.ship_mint IS NOT NULL
+        )
+        SELECT ship_mint,
+               COALESCE(SUM(amount) FILTER (WHERE kind = 'stake'
+                   AND (withdrawn_at IS NULL OR timestamp > withdrawn_at)), 0)::BIGINT AS ships_staked,
+               MIN(timestamp) FILTER (WHERE kind = 'stake'
+                   AND (withdrawn_at IS NULL OR timestamp > withdrawn_at)) AS staked_at,
+               COALESCE(SUM(amount) FILTER (WHERE kind = 'claim'), 0)::FLOAT8
+                   / POWER(10, $2::INTEGER) AS rewards,
+               MAX(timestamp) FILTER (WHERE kind = 'claim') AS last_claim_at,
+               COALESCE(SUM(CASE kind WHEN 'resupply' THEN amount ELSE -amount END)
+                   FILTER (WHERE resource = 'food'), 0)::BIGINT AS food,
+               COALESCE(SUM(CASE kind WHEN 'resupply' THEN amount ELSE -amount END)
+                   FILTER (WHERE resource = 'fuel'), 0)::BIGINT AS fuel,
+               COALESCE(SUM(CASE kind WHEN 'resupply' THEN amount ELSE -amount END)
+                   FILTER (WHERE resource = 'ammo'), 0)::BIGINT AS ammo,
+               COALESCE(SUM(amount) FILTER (WHERE resource = 'toolkit'), 0)::BIGINT AS toolkit
+        FROM events
+        GROUP BY ship_mint
+        ORDER BY ships_staked DESC, ship_mint
+        "#,
+    )
+    .bind(player)
+    .bind(atlas_decimals as i32)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(positions)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Fixture rows with mints, wallets and signatures unique to the run
+    struct Fixture {
+        pool: DbPool,
+        prefix: String,
+    }
+
+    impl Fixture {
+        fn name(&self, name: &str) -> String {
+            format!("{}{}", self.prefix, name)
+        }
+
+        async fn token(&self, name: &str, decimals: Option<i16>) -> i32 {
+            sqlx::query_scalar(
+                "INSERT INTO staratlas.tokens (mint, decimals) VALUES ($1, $2) RETURNING id",
+            )
+            .bind(self.name(name))
+            .bind(decimals)
+            .fetch_one(&self.pool)
+            .await
+            .unwrap()
+        }
+
+        async fn player(&self, name: &str) -> i32 {
+            sqlx::query_scalar(
+                r#"
+                INSERT INTO staratlas.players (wallet_address, first_seen, last_active)
+                VALUES ($1, NOW(), NOW())
+                RETURNING id
+                "#,
+            )
+            .bind(self.name(name))
+            .fetch_one(&self.pool)
+            .await
+            .unwrap()
+        }
+
+        async fn exchange(
+            &self,
+            index: i32,
+            players: (i32, i32),
+            tokens: (i32, i32),
+            price: f64,
+            size: i32,
+        ) {
+            sqlx::query(
+                r#"
+                INSERT INTO market.exchanges (
+                    slot, signature, index, timestamp, side, buyer, seller, asset, pair, price,
+                    size, volume, fee, buddy
+                )
+                VALUES (1, $1, $2, NOW() - INTERVAL '1 hour', 'BUY', $3, $4, $5, $6, $7, $8, $7 * $8, 0, 0)
+                "#,
+            )
+            .bind(self.name("exchange"))
+            .bind(index)
+            .bind(players.0)
+            .bind(players.1)
+            .bind(tokens.0)
+            .bind(tokens.1)
+            .bind(price)
+            .bind(size)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        }
+
+        #[allow(clippy::too_many_arguments)]
+        async fn event(
+            &self,
+            index: i32,
+            hours_ago: u64,
+            kind: &str,
+            player: &str,
+            ship: &str,
+            mint: &str,
+            amount: i64,
+        ) {
+            sqlx::query(
+                r#"
+                INSERT INTO score.events (
+                    signature, instruction_index, slot, timestamp, kind, instruction, player,
+                    ship_mint, mint, amount, args
+                )
+                VALUES ($1, $2, 1, $3, $4, 'fixture', $5, $6, $7, $8, '{}')
+                "#,
+            )
+            .bind(self.name("events"))
+            .bind(index)
+            .bind(Utc::now() - Duration::from_secs(3600 * hours_ago))
+            .bind(kind)
+            .bind(self.name(player))
+            .bind(self.name(ship))
+            .bind(self.name(mint))
+            .bind(amount)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        }
+
+        async fn clean_up(&self) {
+            for query in [
+                "DELETE FROM score.events WHERE signature LIKE $1 || '%'",
+                "DELETE FROM market.exchanges WHERE signature LIKE $1 || '%'",
+                "DELETE FROM staratlas.tokens WHERE mint LIKE $1 || '%'",
+                "DELETE FROM staratlas.players WHERE wallet_address LIKE $1 || '%'",
+            ] {
+                sqlx::query(query)
+                    .bind(&self.prefix)
+                    .execute(&self.pool)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn staking_roi_scales_amounts_by_token_decimals() {
+        dotenv::dotenv().ok();
+        if std::env::var("DATABASE_URL").is_err() {
+            eprintln!("DATABASE_URL not set, skipping");
+            return;
+        }
+        let fixture = Fixture {
+            pool: crate::establish_connection().await.unwrap(),
+            prefix: format!("roi{}", Utc::now().timestamp_micros()),
+        };
+
+        let atlas = fixture.token("atlas", Some(8)).await;
+        let ship = fixture.token("ship", Some(0)).await;
+        let food = fixture.token("food", Some(2)).await;
+        let undecided = fixture.token("undecided", None).await;
+        let players = (fixture.player("a").await, fixture.player("b").await);
+
+        // 100 ATLAS per ship, 0.5 ATLAS per food, 50 ATLAS per ship of unknown decimals
+        fixture.exchange(0, players, (ship, atlas), 100.0, 2).await;
+        fixture.exchange(1, players, (food, atlas), 0.5, 100).await;
+        fixture
+            .exchange(2, players, (undecided, atlas), 50.0, 1)
+            .await;
+
+        // Player a stakes 10 ships, supplies 50 food, takes 10 back and claims 1000 ATLAS
+        fixture.event(0, 5, "stake", "a", "ship", "ship", 10).await;
+        fixture
+            .event(1, 4, "resupply", "a", "ship", "food", 5000)
+            .await;
+        fixture
+            .event(2, 3, "resupply_withdrawn", "a", "ship", "food", 1000)
+            .await;
+        fixture
+            .event(3, 2, "claim", "a", "ship", "atlas", 1000 * 100_000_000)
+            .await;
+        // Player b withdrew their ships, they no longer count as staked
+        fixture.event(4, 5, "stake", "b", "ship", "ship", 3).await;
+        fixture.event(5, 4, "unstake", "b", "ship", "ship", 3).await;
+        fixture
+            .event(6, 2, "stake", "a", "undecided", "undecided", 4)
+            .await;
+        fixture
+            .event(7, 1, "claim", "a", "undecided", "atlas", 10 * 100_000_000)
+            .await;
+
+        let since = Utc::now() - Duration::from_secs(24 * 3600);
+        let atlas_mint = fixture.name("atlas");
+        let roi = get_staking_roi(&fixture.pool, &atlas_mint, 8, None, since).await;
+        let single = get_staking_roi(
+            &fixture.pool,
+            &atlas_mint,
+            8,
+            Some(&fixture.name("ship")),
+            since,
+        )
+        .await;
+        fixture.clean_up().await;
+
+        let roi = roi.unwrap();
+        let ship = roi
+            .iter()
+            .find(|roi| roi.ship_mint == fixture.name("ship"))
+            .unwrap();
+        assert_eq!((ship.ships_staked, ship.stakers), (10, 1));
+        assert_eq!(ship.rewards, 1000.0);
+        assert_eq!(ship.ship_price, Some(100.0));
+        assert_eq!(ship.resupply_cost, Some(20.0));
+        assert_eq!(ship.roi, Some((1000.0 - 20.0) / (10.0 * 100.0)));
+
+        // Without decimals the value of the staked ships is unknown
+        let undecided = roi
+            .iter()
+            .find(|roi| roi.ship_mint == fixture.name("undecided"))
+            .unwrap();
+        assert_eq!(undecided.rewards, 10.0);
+        assert_eq!(undecided.ship_price, Some(50.0));
+        assert_eq!(undecided.roi, None);
+
+        assert_eq!(
+            single
+                .unwrap()
+                .iter()
+                .map(|roi| roi.ship_mint.clone())
+                .collect::<Vec<_>>(),
+            [fixture.name("ship")]
+        );
+    }
+}
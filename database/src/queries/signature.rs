@@ -5,7 +5,8 @@ use sqlx::types::chrono::{DateTime, Utc};
 use crate::connection::DbPool;
 use crate::error::{DbError, Result};
 use crate::models::{
-    NewProgram, NewProgramSignature, NewSignature, Program, ProgramSignature, Signature,
+    NewProgram, NewProgramSignature, NewSignature, Program, ProgramSignature, ProgramWatermark,
+    Signature,
 };
 use crate::types::{PublicKeyType, SignatureType};
 
@@ -64,6 +65,36 @@ pub async fn get_signature_by_value(
     Ok(signature_record)
 }
 
+/// Retrieves the signatures with the given values
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `signatures` - The signature values to retrieve
+///
+/// # Returns
+/// A vector of the signatures that exist, in no particular order
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_signatures_by_values(
+    pool: &DbPool,
+    signatures: &[SignatureType],
+) -> Result<Vec<Signature>> {
+    let signature_records = sqlx::query_as::<_, Signature>(
+        r#"
+        SELECT signature, slot, timestamp
+        FROM indexer.signatures
+        WHERE signature = ANY($1)
+        "#,
+    )
+    .bind(signatures)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(signature_records)
+}
+
 /// Retrieves signatures by timestamp range
 ///
 /// # Arguments
@@ -192,7 +223,7 @@ pub async fn delete_signature(pool: &DbPool, signature: &SignatureType) -> Resul
 pub async fn get_all_program_signatures(pool: &DbPool) -> Result<Vec<ProgramSignature>> {
     let program_signatures = sqlx::query_as::<_, ProgramSignature>(
         r#"
-        SELECT program_id, signature, processed
+        SELECT program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
         FROM indexer.program_signatures
         "#,
     )
@@ -220,7 +251,7 @@ pub async fn get_program_signatures_by_program_id(
 ) -> Result<Vec<ProgramSignature>> {
     let program_signatures = sqlx::query_as::<_, ProgramSignature>(
         r#"
-        SELECT program_id, signature, processed
+        SELECT program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
         FROM indexer.program_signatures
         WHERE program_id = $1
         "#,
@@ -233,6 +264,47 @@ pub async fn get_program_signatures_by_program_id(
     Ok(program_signatures)
 }
 
+/// Retrieves a page of the program signatures of a program, newest first
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - The program ID to search for
+/// * `processed` - Only return processed (true) or unprocessed (false) signatures, if set
+/// * `limit` - Maximum number of program signatures to return
+/// * `offset` - Number of program signatures to skip
+///
+/// # Returns
+/// A vector of program signatures with the specified program ID
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_program_signatures_page(
+    pool: &DbPool,
+    program_id: &PublicKeyType,
+    processed: Option<bool>,
+    limit: i64,
+    offset: i64,
+) -> Result<Vec<ProgramSignature>> {
+    let program_signatures = sqlx::query_as::<_, ProgramSignature>(
+        r#"
+        SELECT program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
+        FROM indexer.program_signatures
+        WHERE program_id = $1 AND ($2::BOOLEAN IS NULL OR processed = $2)
+        ORDER BY slot DESC, signature DESC
+        LIMIT $3 OFFSET $4
+        "#,
+    )
+    .bind(program_id)
+    .bind(processed)
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(program_signatures)
+}
+
 /// Retrieves the most recent program signature for a program ID
 ///
 /// # Arguments
@@ -250,7 +322,7 @@ pub async fn get_oldest_program_signature_by_program_id(
 ) -> Result<Option<ProgramSignature>> {
     let program_signature = sqlx::query_as::<_, ProgramSignature>(
         r#"
-        SELECT ps.program_id, ps.signature, ps.processed
+        SELECT ps.program_id, ps.signature, ps.slot, ps.transaction_index, ps.processed, ps.claimed_by, ps.claimed_until
         FROM indexer.program_signatures ps
         JOIN indexer.signatures s ON ps.signature = s.signature
         WHERE program_id = $1
@@ -272,7 +344,7 @@ pub async fn get_newest_program_signature_by_program_id(
 ) -> Result<Option<ProgramSignature>> {
     let program_signature = sqlx::query_as::<_, ProgramSignature>(
         r#"
-        SELECT ps.program_id, ps.signature, ps.processed
+        SELECT ps.program_id, ps.signature, ps.slot, ps.transaction_index, ps.processed, ps.claimed_by, ps.claimed_until
         FROM indexer.program_signatures ps
         JOIN indexer.signatures s ON ps.signature = s.signature
         WHERE program_id = $1
@@ -290,6 +362,8 @@ pub async fn get_newest_program_signature_by_program_id(
 
 /// Retrieves unprocessed program signatures by program ID
 ///
+/// Program signatures are returned in block order, by slot and position in the block.
+///
 /// # Arguments
 /// * `pool` - The database connection pool
 /// * `program_id` - The program ID to search for
@@ -307,9 +381,10 @@ pub async fn get_unprocessed_program_signatures_by_program_id(
 ) -> Result<Vec<ProgramSignature>> {
     let program_signatures = sqlx::query_as::<_, ProgramSignature>(
         r#"
-        SELECT program_id, signature, processed
+        SELECT program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
         FROM indexer.program_signatures
         WHERE program_id = $1 AND processed = false
+        ORDER BY slot ASC, transaction_index ASC NULLS LAST, signature ASC
         LIMIT $2
         "#,
     )
@@ -322,6 +397,124 @@ pub async fn get_unprocessed_program_signatures_by_program_id(
     Ok(program_signatures)
 }
 
+/// Claims a batch of unprocessed program signatures for a worker
+///
+/// The oldest unclaimed signatures are claimed first and returned in block order, by slot and
+/// position in the block. Concurrent workers skip the rows another claim is locking, so each
+/// worker gets a disjoint batch and workers process in parallel; the watermark tells up to which
+/// slot the data is complete. Rows whose lease expired without being processed can be claimed
+/// again, so crashed workers' rows are retried.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - The program ID to claim signatures for
+/// * `worker_id` - The unique name of the claiming worker
+/// * `limit` - The maximum number of program signatures to claim
+/// * `lease_seconds` - How long the claim is held before others may take over
+///
+/// # Returns
+/// A vector of the program signatures claimed by the worker
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn claim_unprocessed_program_signatures(
+    pool: &DbPool,
+    program_id: &PublicKeyType,
+    worker_id: &str,
+    limit: i64,
+    lease_seconds: i64,
+) -> Result<Vec<ProgramSignature>> {
+    let mut program_signatures = sqlx::query_as::<_, ProgramSignature>(
+        r#"
+        WITH claimable AS (
+            SELECT program_id, signature
+            FROM indexer.program_signatures
+            WHERE program_id = $1
+              AND processed = false
+              AND (claimed_until IS NULL OR claimed_until < NOW())
+            ORDER BY slot ASC, transaction_index ASC NULLS LAST, signature ASC
+            LIMIT $3
+            FOR UPDATE SKIP LOCKED
+        )
+        UPDATE indexer.program_signatures ps
+        SET claimed_by = $2, claimed_until = NOW() + $4 * INTERVAL '1 second'
+        FROM claimable
+        WHERE ps.program_id = claimable.program_id AND ps.signature = claimable.signature
+        RETURNING ps.program_id, ps.signature, ps.slot, ps.transaction_index, ps.processed, ps.claimed_by, ps.claimed_until
+        "#,
+    )
+    .bind(program_id)
+    .bind(worker_id)
+    .bind(limit)
+    .bind(lease_seconds)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    // UPDATE ... RETURNING does not keep the order of the sub-select
+    program_signatures.sort_by(|a, b| {
+        (
+            a.slot,
+            a.transaction_index.is_none(),
+            a.transaction_index,
+            &a.signature,
+        )
+            .cmp(&(
+                b.slot,
+                b.transaction_index.is_none(),
+                b.transaction_index,
+                &b.signature,
+            ))
+    });
+
+    Ok(program_signatures)
+}
+
+/// Extends a worker's claims on unprocessed program signatures by a new lease
+///
+/// Claims another worker took over after the lease expired are not renewed, so the caller
+/// must stop processing the signatures when fewer claims than requested were renewed.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - The program ID of the claimed signatures
+/// * `worker_id` - The unique name of the claiming worker
+/// * `signatures` - The claimed signatures to renew
+/// * `lease_seconds` - How long the claim is held from now on
+///
+/// # Returns
+/// The number of renewed claims
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn renew_program_signature_claims(
+    pool: &DbPool,
+    program_id: &PublicKeyType,
+    worker_id: &str,
+    signatures: &[SignatureType],
+    lease_seconds: i64,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE indexer.program_signatures
+        SET claimed_until = NOW() + $4 * INTERVAL '1 second'
+        WHERE program_id = $1
+          AND claimed_by = $2
+          AND processed = false
+          AND signature = ANY($3)
+        "#,
+    )
+    .bind(program_id)
+    .bind(worker_id)
+    .bind(signatures)
+    .bind(lease_seconds)
+    .execute(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(result.rows_affected())
+}
+
 /// Creates a new program signature in the database
 ///
 /// # Arguments
@@ -340,16 +533,18 @@ pub async fn create_program_signature(
     let program_signature = sqlx::query_as::<_, ProgramSignature>(
         r#"
         INSERT INTO indexer.program_signatures (
-            program_id, signature, processed
+            program_id, signature, slot, transaction_index, processed
         )
         VALUES (
-            $1, $2, $3
+            $1, $2, $3, $4, $5
         )
-        RETURNING program_id, signature, processed
+        RETURNING program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
         "#,
     )
     .bind(&new_program_signature.program_id)
     .bind(&new_program_signature.signature)
+    .bind(new_program_signature.slot)
+    .bind(new_program_signature.transaction_index)
     .bind(new_program_signature.processed)
     .fetch_one(pool)
     .await
@@ -365,17 +560,19 @@ pub async fn create_program_signature_unchecked(
     let program_signature = sqlx::query_as::<_, ProgramSignature>(
         r#"
         INSERT INTO indexer.program_signatures (
-            program_id, signature, processed
+            program_id, signature, slot, transaction_index, processed
         )
         VALUES (
-            $1, $2, $3
+            $1, $2, $3, $4, $5
         )
         ON CONFLICT DO NOTHING
-        RETURNING program_id, signature, processed
+        RETURNING program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
         "#,
     )
     .bind(&new_program_signature.program_id)
     .bind(&new_program_signature.signature)
+    .bind(new_program_signature.slot)
+    .bind(new_program_signature.transaction_index)
     .bind(new_program_signature.processed)
     .fetch_optional(pool)
     .await
@@ -386,6 +583,8 @@ pub async fn create_program_signature_unchecked(
 
 /// Updates the processed status of a program signature
 ///
+/// Any claim on the program signature is released.
+///
 /// # Arguments
 /// * `pool` - The database connection pool
 /// * `program_id` - The program ID of the program signature to update
@@ -406,9 +605,9 @@ pub async fn update_program_signature_processed(
     let program_signature = sqlx::query_as::<_, ProgramSignature>(
         r#"
         UPDATE indexer.program_signatures
-        SET processed = $3
+        SET processed = $3, claimed_by = NULL, claimed_until = NULL
         WHERE program_id = $1 AND signature = $2
-        RETURNING program_id, signature, processed
+        RETURNING program_id, signature, slot, transaction_index, processed, claimed_by, claimed_until
         "#,
     )
     .bind(program_id)
@@ -421,6 +620,58 @@ pub async fn update_program_signature_processed(
     Ok(program_signature)
 }
 
+/// Resets the processed status of program signatures within a slot and/or time range
+///
+/// Used to reprocess already processed signatures, e.g. after a decoder fix. Range bounds
+/// are inclusive and a `None` bound is left open.
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - The program ID of the program signatures to reset
+/// * `from_slot` - The lowest slot to reset
+/// * `to_slot` - The highest slot to reset
+/// * `from_timestamp` - The earliest timestamp to reset
+/// * `to_timestamp` - The latest timestamp to reset
+///
+/// # Returns
+/// The number of program signatures that were reset
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn reset_program_signatures_processed(
+    pool: &DbPool,
+    program_id: &PublicKeyType,
+    from_slot: Option<i64>,
+    to_slot: Option<i64>,
+    from_timestamp: Option<DateTime<Utc>>,
+    to_timestamp: Option<DateTime<Utc>>,
+) -> Result<u64> {
+    let result = sqlx::query(
+        r#"
+        UPDATE indexer.program_signatures ps
+        SET processed = false, claimed_by = NULL, claimed_until = NULL
+        FROM indexer.signatures s
+        WHERE ps.signature = s.signature
+          AND ps.program_id = $1
+          AND ps.processed = true
+          AND ($2::BIGINT IS NULL OR ps.slot >= $2)
+          AND ($3::BIGINT IS NULL OR ps.slot <= $3)
+          AND ($4::TIMESTAMPTZ IS NULL OR s.timestamp >= $4)
+          AND ($5::TIMESTAMPTZ IS NULL OR s.timestamp <= $5)
+        "#,
+    )
+    .bind(program_id)
+    .bind(from_slot)
+    .bind(to_slot)
+    .bind(from_timestamp)
+    .bind(to_timestamp)
+    .execute(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(result.rows_affected())
+}
+
 /// Retrieves all programs from the database
 ///
 /// # Arguments
@@ -505,3 +756,305 @@ pub async fn create_program(pool: &DbPool, new_program: &NewProgram) -> Result<P
 
     Ok(program)
 }
+
+/// Recalculates the processed-slot watermark of a program
+///
+/// The watermark is the highest slot up to which every indexed signature of the program
+/// has been processed, i.e. data is complete up to and including that slot. While a DOWN
+/// indexer of the program has not reported `finished`, older signatures keep arriving below
+/// the slot it reached, so the watermark is held below that slot (and is `NULL` before the
+/// indexer reached any slot).
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - The program ID to update the watermark for
+///
+/// # Returns
+/// The updated watermark
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn update_program_watermark(
+    pool: &DbPool,
+    program_id: &PublicKeyType,
+) -> Result<ProgramWatermark> {
+    let watermark = sqlx::query_as::<_, ProgramWatermark>(
+        r#"
+        WITH processed AS (
+            SELECT COALESCE(
+                (SELECT MIN(slot) - 1
+                 FROM indexer.program_signatures
+                 WHERE program_id = $1 AND processed = false),
+                (SELECT MAX(slot)
+                 FROM indexer.program_signatures
+                 WHERE program_id = $1)
+            ) AS slot
+        ),
+        backfill AS (
+            SELECT COUNT(*) > 0 AS running,
+                   BOOL_OR(block IS NULL) AS unstarted,
+                   MIN(block) AS block
+            FROM indexer.indexer
+            WHERE program_id = $1
+              AND direction = 'DOWN'
+              AND finished IS NOT TRUE
+        ),
+        watermark AS (
+            SELECT CASE
+                       WHEN NOT b.running THEN p.slot
+                       WHEN b.unstarted THEN NULL
+                       ELSE LEAST(p.slot, b.block - 1)
+                   END AS slot
+            FROM processed p, backfill b
+        )
+        INSERT INTO indexer.program_watermarks (program_id, slot, timestamp, updated_at)
+        SELECT $1,
+               w.slot,
+               (SELECT s.timestamp
+                FROM indexer.signatures s
+                WHERE s.slot <= w.slot
+                ORDER BY s.slot DESC
+                LIMIT 1),
+               NOW()
+        FROM watermark w
+        ON CONFLICT (program_id) DO UPDATE SET
+            slot = EXCLUDED.slot,
+            timestamp = EXCLUDED.timestamp,
+            updated_at = EXCLUDED.updated_at
+        RETURNING program_id, slot, timestamp, updated_at
+        "#,
+    )
+    .bind(program_id)
+    .fetch_one(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(watermark)
+}
+
+/// Retrieves the processed-slot watermarks of all programs
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+///
+/// # Returns
+/// A vector of all program watermarks
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_all_program_watermarks(pool: &DbPool) -> Result<Vec<ProgramWatermark>> {
+    let watermarks = sqlx::query_as::<_, ProgramWatermark>(
+        r#"
+        SELECT program_id, slot, timestamp, updated_at
+        FROM indexer.program_watermarks
+        ORDER BY program_id
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(watermarks)
+}
+
+/// Retrieves the processed-slot watermark of a program
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `program_id` - The program ID to retrieve the watermark for
+///
+/// # Returns
+/// The watermark of the program, or None if it has not been calculated yet
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_program_watermark(
+    pool: &DbPool,
+    program_id: &PublicKeyType,
+) -> Result<Option<ProgramWatermark>> {
+    let watermark = sqlx::query_as::<_, ProgramWatermark>(
+        r#"
+        SELECT program_id, slot, timestamp, updated_at
+        FROM indexer.program_watermarks
+        WHERE program_id = $1
+        "#,
+    )
+    .bind(program_id)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(watermark)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Fixture program with signatures unique to the run
+    struct Fixture {
+        pool: DbPool,
+        prefix: String,
+    }
+
+    impl Fixture {
+        async fn new(prefix: &str) -> Option<Fixture> {
+            dotenv::dotenv().ok();
+            if std::env::var("DATABASE_URL").is_err() {
+                eprintln!("DATABASE_URL not set, skipping");
+                return None;
+            }
+            let fixture = Fixture {
+                pool: crate::establish_connection().await.unwrap(),
+                prefix: format!("{}{}", prefix, Utc::now().timestamp_micros()),
+            };
+            sqlx::query("INSERT INTO indexer.programs (program_id) VALUES ($1)")
+                .bind(&fixture.prefix)
+                .execute(&fixture.pool)
+                .await
+                .unwrap();
+            Some(fixture)
+        }
+
+        fn name(&self, name: &str) -> String {
+            format!("{}{}", self.prefix, name)
+        }
+
+        async fn signature(
+            &self,
+            name: &str,
+            slot: i64,
+            transaction_index: Option<i32>,
+            processed: bool,
+        ) {
+            sqlx::query(
+                "INSERT INTO indexer.signatures (signature, slot, timestamp) VALUES ($1, $2, NOW())",
+            )
+            .bind(self.name(name))
+            .bind(slot)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+            sqlx::query(
+                r#"
+                INSERT INTO indexer.program_signatures (
+                    program_id, signature, slot, transaction_index, processed
+                )
+                VALUES ($1, $2, $3, $4, $5)
+                "#,
+            )
+            .bind(&self.prefix)
+            .bind(self.name(name))
+            .bind(slot)
+            .bind(transaction_index)
+            .bind(processed)
+            .execute(&self.pool)
+            .await
+            .unwrap();
+        }
+
+        async fn claim(&self, worker: &str, limit: i64, lease_seconds: i64) -> Vec<String> {
+            claim_unprocessed_program_signatures(
+                &self.pool,
+                &self.prefix,
+                worker,
+                limit,
+                lease_seconds,
+            )
+            .await
+            .unwrap()
+            .into_iter()
+            .map(|program_signature| program_signature.signature)
+            .collect()
+        }
+
+        async fn clean_up(&self) {
+            for query in [
+                "DELETE FROM indexer.programs WHERE program_id = $1",
+                "DELETE FROM indexer.signatures WHERE signature LIKE $1 || '%'",
+            ] {
+                sqlx::query(query)
+                    .bind(&self.prefix)
+                    .execute(&self.pool)
+                    .await
+                    .unwrap();
+            }
+        }
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn workers_claim_disjoint_batches_in_block_order() {
+        let Some(fixture) = Fixture::new("claims").await else {
+            return;
+        };
+        fixture.signature("late", 10, Some(1), false).await;
+        fixture.signature("early", 10, Some(0), false).await;
+        fixture.signature("unindexed", 11, None, false).await;
+        fixture.signature("next", 12, Some(0), false).await;
+        fixture.signature("done", 9, Some(0), true).await;
+
+        let first = fixture.claim("a", 2, 600).await;
+        let second = fixture.claim("b", 10, 600).await;
+        let third = fixture.claim("c", 10, 600).await;
+        fixture.clean_up().await;
+
+        assert_eq!(first, [fixture.name("early"), fixture.name("late")]);
+        assert_eq!(second, [fixture.name("unindexed"), fixture.name("next")]);
+        assert!(third.is_empty());
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn expired_leases_are_taken_over_and_no_longer_renewed() {
+        let Some(fixture) = Fixture::new("leases").await else {
+            return;
+        };
+        fixture.signature("first", 10, Some(0), false).await;
+        fixture.signature("second", 11, Some(0), false).await;
+
+        // Worker a's lease has expired right away
+        let expired = fixture.claim("a", 10, -1).await;
+        let taken_over = fixture.claim("b", 10, 600).await;
+        let renew = |worker: &'static str| {
+            renew_program_signature_claims(&fixture.pool, &fixture.prefix, worker, &taken_over, 600)
+        };
+        let renewed_by_a = renew("a").await;
+        let renewed_by_b = renew("b").await;
+        fixture.clean_up().await;
+
+        assert_eq!(expired, [fixture.name("first"), fixture.name("second")]);
+        assert_eq!(taken_over, expired);
+        assert_eq!(renewed_by_a.unwrap(), 0);
+        assert_eq!(renewed_by_b.unwrap(), 2);
+    }
+
+    /// Runs against the database of DATABASE_URL, skipped without one
+    #[tokio::test]
+    async fn watermark_stops_below_the_oldest_unprocessed_signature() {
+        let Some(fixture) = Fixture::new("watermark").await else {
+            return;
+        };
+        fixture.signature("a", 10, Some(0), true).await;
+        fixture.signature("b", 11, Some(0), true).await;
+        fixture.signature("c", 12, Some(0), false).await;
+        fixture.signature("d", 13, Some(0), true).await;
+
+        let behind = update_program_watermark(&fixture.pool, &fixture.prefix).await;
+        update_program_signature_processed(
+            &fixture.pool,
+            &fixture.prefix,
+            &fixture.name("c"),
+            true,
+        )
+        .await
+        .unwrap();
+        let complete = update_program_watermark(&fixture.pool, &fixture.prefix).await;
+        let stored = get_program_watermark(&fixture.pool, &fixture.prefix).await;
+        fixture.clean_up().await;
+
+        assert_eq!(behind.unwrap().slot, Some(11));
+        assert_eq!(complete.unwrap().slot, Some(13));
+        assert_eq!(stored.unwrap().unwrap().slot, Some(13));
+    }
+}
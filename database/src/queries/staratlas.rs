@@ -2,7 +2,9 @@
 
 use crate::connection::DbPool;
 use crate::error::{DbError, Result};
-use crate::models::{NewPlayer, NewToken, Player, Token};
+use crate::listener::{NEW_TOKEN_CHANNEL, PLAYERS_UPDATED_CHANNEL};
+use crate::models::{Exchange, NewPlayer, NewToken, Player, PlayerVolume, Token};
+use sqlx::PgConnection;
 
 /// Retrieves all tokens from the database
 ///
@@ -56,8 +58,64 @@ pub async fn get_token_by_id(pool: &DbPool, id: i32) -> Result<Option<Token>> {
     Ok(token)
 }
 
+/// Retrieves a token by its mint address
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `mint` - The mint address of the token to retrieve
+///
+/// # Returns
+/// The token with the specified mint address, or None if no such token exists
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_token_by_mint(pool: &DbPool, mint: &str) -> Result<Option<Token>> {
+    let token = sqlx::query_as::<_, Token>(
+        r#"
+        SELECT id, mint, name, symbol, token_type
+        FROM staratlas.tokens
+        WHERE mint = $1
+        "#,
+    )
+    .bind(mint)
+    .fetch_optional(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(token)
+}
+
+/// Retrieves the tokens with the given IDs
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `ids` - The IDs of the tokens to retrieve
+///
+/// # Returns
+/// A vector of the tokens that exist, in no particular order
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_tokens_by_ids(pool: &DbPool, ids: &[i32]) -> Result<Vec<Token>> {
+    let tokens = sqlx::query_as::<_, Token>(
+        r#"
+        SELECT id, mint, name, symbol, token_type
+        FROM staratlas.tokens
+        WHERE id = ANY($1)
+        "#,
+    )
+    .bind(ids)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(tokens)
+}
+
 /// Creates a new token in the database
 ///
+/// The token is announced on `NEW_TOKEN_CHANNEL`.
+///
 /// # Arguments
 /// * `pool` - The database connection pool
 /// * `new_token` - The token to create
@@ -68,6 +126,8 @@ pub async fn get_token_by_id(pool: &DbPool, id: i32) -> Result<Option<Token>> {
 /// # Errors
 /// Returns an error if the query fails
 pub async fn create_token(pool: &DbPool, new_token: &NewToken) -> Result<Token> {
+    let mut tx = pool.begin().await.map_err(DbError::SqlxError)?;
+
     let token = sqlx::query_as::<_, Token>(
         r#"
         INSERT INTO staratlas.tokens (
@@ -83,13 +143,51 @@ pub async fn create_token(pool: &DbPool, new_token: &NewToken) -> Result<Token>
     .bind(&new_token.name)
     .bind(&new_token.symbol)
     .bind(&new_token.token_type)
-    .fetch_one(pool)
+    .fetch_one(&mut *tx)
     .await
     .map_err(DbError::SqlxError)?;
 
+    notify_new_token(&mut tx, token.id).await?;
+
+    tx.commit().await.map_err(DbError::SqlxError)?;
+
     Ok(token)
 }
 
+/// Helper function to announce a new token on `NEW_TOKEN_CHANNEL`
+///
+/// The notification is sent when the transaction of the connection commits.
+pub(crate) async fn notify_new_token(conn: &mut PgConnection, id: i32) -> Result<()> {
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(NEW_TOKEN_CHANNEL)
+        .bind(id.to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Helper function to announce changed usernames, factions or guilds on
+/// `PLAYERS_UPDATED_CHANNEL`
+///
+/// Nothing is announced if no player changed. The notification is sent when the transaction
+/// of the connection commits.
+pub(crate) async fn notify_players_updated(conn: &mut PgConnection, updated: u64) -> Result<()> {
+    if updated == 0 {
+        return Ok(());
+    }
+
+    sqlx::query("SELECT pg_notify($1, $2)")
+        .bind(PLAYERS_UPDATED_CHANNEL)
+        .bind(updated.min(i32::MAX as u64).to_string())
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
 /// Retrieves all players from the database
 ///
 /// # Arguments
@@ -103,7 +201,8 @@ pub async fn create_token(pool: &DbPool, new_token: &NewToken) -> Result<Token>
 pub async fn get_all_players(pool: &DbPool) -> Result<Vec<Player>> {
     let players = sqlx::query_as::<_, Player>(
         r#"
-        SELECT id, wallet_address, username, first_seen, last_active
+        SELECT id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+               faction, guild
         FROM staratlas.players
         ORDER BY id
         "#,
@@ -129,7 +228,8 @@ pub async fn get_all_players(pool: &DbPool) -> Result<Vec<Player>> {
 pub async fn get_player_by_id(pool: &DbPool, id: i32) -> Result<Option<Player>> {
     let player = sqlx::query_as::<_, Player>(
         r#"
-        SELECT id, wallet_address, username, first_seen, last_active
+        SELECT id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+               faction, guild
         FROM staratlas.players
         WHERE id = $1
         "#,
@@ -142,6 +242,65 @@ pub async fn get_player_by_id(pool: &DbPool, id: i32) -> Result<Option<Player>>
     Ok(player)
 }
 
+/// Retrieves a page of players
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `limit` - Maximum number of players to return
+/// * `offset` - Number of players to skip
+///
+/// # Returns
+/// A vector of players ordered by ID
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_players(pool: &DbPool, limit: i32, offset: i32) -> Result<Vec<Player>> {
+    let players = sqlx::query_as::<_, Player>(
+        r#"
+        SELECT id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+               faction, guild
+        FROM staratlas.players
+        ORDER BY id
+        LIMIT $1 OFFSET $2
+        "#,
+    )
+    .bind(limit)
+    .bind(offset)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(players)
+}
+
+/// Retrieves the players with the given IDs
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `ids` - The IDs of the players to retrieve
+///
+/// # Returns
+/// A vector of the players that exist, in no particular order
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_players_by_ids(pool: &DbPool, ids: &[i32]) -> Result<Vec<Player>> {
+    let players = sqlx::query_as::<_, Player>(
+        r#"
+        SELECT id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+               faction, guild
+        FROM staratlas.players
+        WHERE id = ANY($1)
+        "#,
+    )
+    .bind(ids)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(players)
+}
+
 /// Retrieves a player by their wallet address
 ///
 /// # Arguments
@@ -159,7 +318,8 @@ pub async fn get_player_by_wallet_address(
 ) -> Result<Option<Player>> {
     let player = sqlx::query_as::<_, Player>(
         r#"
-        SELECT id, wallet_address, username, first_seen, last_active
+        SELECT id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+               faction, guild
         FROM staratlas.players
         WHERE wallet_address = $1
         "#,
@@ -192,7 +352,8 @@ pub async fn create_player(pool: &DbPool, new_player: &NewPlayer) -> Result<Play
         VALUES (
             $1, $2, $3, $4
         )
-        RETURNING id, wallet_address, username, first_seen, last_active
+        RETURNING id, wallet_address, username, first_seen, last_active, buy_count, sell_count, assets_traded,
+                  faction, guild
         "#,
     )
     .bind(&new_player.wallet_address)
@@ -205,3 +366,229 @@ pub async fn create_player(pool: &DbPool, new_player: &NewPlayer) -> Result<Play
 
     Ok(player)
 }
+
+/// Retrieves the traded volume per currency of all players
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+///
+/// # Returns
+/// A vector of the volumes of all players, ordered by player
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_all_player_volumes(pool: &DbPool) -> Result<Vec<PlayerVolume>> {
+    let volumes = sqlx::query_as::<_, PlayerVolume>(
+        r#"
+        SELECT pv.player_id, pv.currency_id, t.mint AS currency_mint, t.symbol AS currency_symbol,
+               pv.buy_volume, pv.sell_volume
+        FROM staratlas.player_volumes pv
+        JOIN staratlas.tokens t ON t.id = pv.currency_id
+        ORDER BY pv.player_id, pv.currency_id
+        "#,
+    )
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(volumes)
+}
+
+/// Retrieves the traded volume per currency of a player
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `player_id` - The ID of the player
+///
+/// # Returns
+/// A vector of the volumes of the player, one per currency
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_player_volumes_by_player_id(
+    pool: &DbPool,
+    player_id: i32,
+) -> Result<Vec<PlayerVolume>> {
+    let volumes = sqlx::query_as::<_, PlayerVolume>(
+        r#"
+        SELECT pv.player_id, pv.currency_id, t.mint AS currency_mint, t.symbol AS currency_symbol,
+               pv.buy_volume, pv.sell_volume
+        FROM staratlas.player_volumes pv
+        JOIN staratlas.tokens t ON t.id = pv.currency_id
+        WHERE pv.player_id = $1
+        ORDER BY pv.currency_id
+        "#,
+    )
+    .bind(player_id)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(volumes)
+}
+
+/// Retrieves the traded volume per currency of the given players
+///
+/// # Arguments
+/// * `pool` - The database connection pool
+/// * `player_ids` - The IDs of the players
+///
+/// # Returns
+/// A vector of the volumes of the players, ordered by player and currency
+///
+/// # Errors
+/// Returns an error if the query fails
+pub async fn get_player_volumes_by_player_ids(
+    pool: &DbPool,
+    player_ids: &[i32],
+) -> Result<Vec<PlayerVolume>> {
+    let volumes = sqlx::query_as::<_, PlayerVolume>(
+        r#"
+        SELECT pv.player_id, pv.currency_id, t.mint AS currency_mint, t.symbol AS currency_symbol,
+               pv.buy_volume, pv.sell_volume
+        FROM staratlas.player_volumes pv
+        JOIN staratlas.tokens t ON t.id = pv.currency_id
+        WHERE pv.player_id = ANY($1)
+        ORDER BY pv.player_id, pv.currency_id
+        "#,
+    )
+    .bind(player_ids)
+    .fetch_all(pool)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(volumes)
+}
+
+/// Locks the rows of the given players until the end of the transaction
+///
+/// Rows are locked in ascending ID order so concurrent writers can't deadlock.
+pub(crate) async fn lock_players(conn: &mut PgConnection, player_ids: &[i32]) -> Result<()> {
+    let mut player_ids = player_ids.to_vec();
+    player_ids.sort_unstable();
+    player_ids.dedup();
+
+    sqlx::query(
+        r#"
+        SELECT id
+        FROM staratlas.players
+        WHERE id = ANY($1)
+        ORDER BY id
+        FOR UPDATE
+        "#,
+    )
+    .bind(player_ids)
+    .fetch_all(&mut *conn)
+    .await
+    .map_err(DbError::SqlxError)?;
+
+    Ok(())
+}
+
+/// Adds (`direction = 1`) or removes (`direction = -1`) an exchange from the stats of its
+/// buyer and seller
+///
+/// `first_seen` and `last_active` only ever widen, removing an exchange leaves them as they are.
+/// The caller must hold the locks of both players, see [`lock_players`].
+pub(crate) async fn apply_exchange_to_player_stats(
+    conn: &mut PgConnection,
+    exchange: &Exchange,
+    direction: i32,
+) -> Result<()> {
+    for (player_id, is_buyer) in [(exchange.buyer, true), (exchange.seller, false)] {
+        let (buys, sells) = if is_buyer {
+            (direction, 0)
+        } else {
+            (0, direction)
+        };
+        let volume = exchange.volume * direction as f64;
+        let (buy_volume, sell_volume) = if is_buyer {
+            (volume, 0.0)
+        } else {
+            (0.0, volume)
+        };
+
+        sqlx::query(
+            r#"
+            UPDATE staratlas.players
+            SET buy_count = buy_count + $2,
+                sell_count = sell_count + $3,
+                first_seen = CASE WHEN $4 > 0 THEN LEAST(first_seen, $5) ELSE first_seen END,
+                last_active = CASE WHEN $4 > 0 THEN GREATEST(last_active, $5) ELSE last_active END
+            WHERE id = $1
+            "#,
+        )
+        .bind(player_id)
+        .bind(buys)
+        .bind(sells)
+        .bind(direction)
+        .bind(exchange.timestamp)
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        sqlx::query(
+            r#"
+            INSERT INTO staratlas.player_volumes (player_id, currency_id, buy_volume, sell_volume)
+            VALUES ($1, $2, $3, $4)
+            ON CONFLICT (player_id, currency_id) DO UPDATE SET
+                buy_volume = player_volumes.buy_volume + EXCLUDED.buy_volume,
+                sell_volume = player_volumes.sell_volume + EXCLUDED.sell_volume
+            "#,
+        )
+        .bind(player_id)
+        .bind(exchange.pair)
+        .bind(buy_volume)
+        .bind(sell_volume)
+        .execute(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        // Track the distinct assets a player traded via a per-asset trade count
+        let trade_count: i32 = sqlx::query_scalar(
+            r#"
+            INSERT INTO staratlas.player_assets (player_id, asset_id, trade_count)
+            VALUES ($1, $2, $3)
+            ON CONFLICT (player_id, asset_id) DO UPDATE SET
+                trade_count = player_assets.trade_count + EXCLUDED.trade_count
+            RETURNING trade_count
+            "#,
+        )
+        .bind(player_id)
+        .bind(exchange.asset)
+        .bind(direction)
+        .fetch_one(&mut *conn)
+        .await
+        .map_err(DbError::SqlxError)?;
+
+        let assets_traded = match (direction > 0, trade_count) {
+            (true, 1) => 1,
+            (false, 0) => -1,
+            _ => 0,
+        };
+
+        if trade_count <= 0 {
+            sqlx::query(
+                "DELETE FROM staratlas.player_assets WHERE player_id = $1 AND asset_id = $2",
+            )
+            .bind(player_id)
+            .bind(exchange.asset)
+            .execute(&mut *conn)
+            .await
+            .map_err(DbError::SqlxError)?;
+        }
+
+        if assets_traded != 0 {
+            sqlx::query(
+                "UPDATE staratlas.players SET assets_traded = assets_traded + $2 WHERE id = $1",
+            )
+            .bind(player_id)
+            .bind(assets_traded)
+            .execute(&mut *conn)
+            .await
+            .map_err(DbError::SqlxError)?;
+        }
+    }
+
+    Ok(())
+}
@@ -51,3 +51,69 @@ impl From<Direction> for String {
         }
     }
 }
+
+/// Kind of condition an alert webhook is triggered by
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[sqlx(type_name = "alert_condition_kind", rename_all = "snake_case")]
+pub enum AlertConditionKind {
+    /// An exchange of the asset had a price below the threshold
+    PriceBelow,
+    /// An exchange of the asset had a price above the threshold
+    PriceAbove,
+    /// The wallet bought or sold
+    WalletTraded,
+    /// The volume of the asset within the window exceeded the threshold
+    VolumeAbove,
+}
+
+impl fmt::Display for AlertConditionKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AlertConditionKind::PriceBelow => write!(f, "price_below"),
+            AlertConditionKind::PriceAbove => write!(f, "price_above"),
+            AlertConditionKind::WalletTraded => write!(f, "wallet_traded"),
+            AlertConditionKind::VolumeAbove => write!(f, "volume_above"),
+        }
+    }
+}
+
+/// Status of an alert delivery
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[sqlx(type_name = "alert_delivery_status", rename_all = "snake_case")]
+pub enum DeliveryStatus {
+    /// Waiting for its next attempt
+    Pending,
+    /// Accepted by the webhook
+    Delivered,
+    /// Given up after the last attempt
+    Failed,
+}
+
+impl fmt::Display for DeliveryStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeliveryStatus::Pending => write!(f, "pending"),
+            DeliveryStatus::Delivered => write!(f, "delivered"),
+            DeliveryStatus::Failed => write!(f, "failed"),
+        }
+    }
+}
+
+/// Scope of an API key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Type)]
+#[sqlx(type_name = "api_key_scope", rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    /// May call the public read endpoints
+    Read,
+    /// May also manage API keys
+    Admin,
+}
+
+impl fmt::Display for ApiKeyScope {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ApiKeyScope::Read => write!(f, "read"),
+            ApiKeyScope::Admin => write!(f, "admin"),
+        }
+    }
+}
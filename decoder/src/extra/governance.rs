@@ -0,0 +1,51 @@
+//! SPL Governance, used by DAOs (e.g. Star Atlas guilds) to track their members
+//!
+//! A member is a wallet with a token owner record of the realm, holding the tokens it
+//! deposited to vote with.
+
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+/// The SPL Governance instance deployed by Solana Labs, realms may use their own deployment
+pub const ID: Pubkey = pubkey!("GovER5Lthms3bLBqWub97yVrMmEogzX7xNjdXpPPCVZ");
+
+/// Offset of the realm in a token owner record, after the account type
+pub const REALM_OFFSET: usize = 1;
+
+/// Account types of token owner records (V1 and V2)
+const TOKEN_OWNER_RECORD_TYPES: [u8; 2] = [2, 17];
+
+/// Membership of a wallet in a realm
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenOwnerRecord {
+    pub realm: Pubkey,
+    /// Community or council mint the deposit is made in
+    pub governing_token_mint: Pubkey,
+    /// The member's wallet
+    pub governing_token_owner: Pubkey,
+    pub governing_token_deposit_amount: u64,
+}
+
+/// Decodes the leading fields of a token owner record
+///
+/// Returns `None` if the account is not a token owner record.
+pub fn decode_token_owner_record(data: &[u8]) -> Option<TokenOwnerRecord> {
+    if !TOKEN_OWNER_RECORD_TYPES.contains(data.first()?) {
+        return None;
+    }
+    let pubkey = |offset: usize| {
+        data.get(offset..offset + 32)
+            .map(|key| Pubkey::new_from_array(key.try_into().unwrap()))
+    };
+
+    Some(TokenOwnerRecord {
+        realm: pubkey(REALM_OFFSET)?,
+        governing_token_mint: pubkey(REALM_OFFSET + 32)?,
+        governing_token_owner: pubkey(REALM_OFFSET + 64)?,
+        governing_token_deposit_amount: u64::from_le_bytes(
+            data.get(REALM_OFFSET + 96..REALM_OFFSET + 104)?
+                .try_into()
+                .unwrap(),
+        ),
+    })
+}
@@ -1 +1,3 @@
+pub mod governance;
+pub mod token;
 pub mod transfer_hook;
@@ -0,0 +1,271 @@
+//! SPL Token and Token-2022 instructions moving tokens
+//!
+//! Token-2022 shares the instruction layout of SPL Token and adds extensions. Mints with the
+//! transfer fee extension are moved with `TransferCheckedWithFee`, which withholds a fee in the
+//! destination account. Mints with the transfer hook extension make every checked transfer
+//! invoke the hook program, see [`transfer_hook`](super::transfer_hook).
+
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA");
+
+pub const TOKEN_2022_ID: Pubkey = pubkey!("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb");
+
+/// Instruction tag of the Token-2022 transfer fee extension
+const TRANSFER_FEE_EXTENSION: u8 = 26;
+
+/// Transfer fee extension instruction moving tokens
+const TRANSFER_CHECKED_WITH_FEE: u8 = 1;
+
+/// Whether a program is SPL Token or Token-2022
+pub fn is_token_program(program_id: &Pubkey) -> bool {
+    *program_id == ID || *program_id == TOKEN_2022_ID
+}
+
+/// Token instruction moving tokens
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenInstructionKind {
+    Transfer,
+    TransferChecked,
+    TransferCheckedWithFee,
+    MintTo,
+    MintToChecked,
+    Burn,
+    BurnChecked,
+}
+
+impl TokenInstructionKind {
+    /// Maps the instruction type of the RPC's `jsonParsed` encoding
+    pub fn from_parsed_type(name: &str) -> Option<Self> {
+        let kind = match name {
+            "transfer" => Self::Transfer,
+            "transferChecked" => Self::TransferChecked,
+            "transferCheckedWithFee" => Self::TransferCheckedWithFee,
+            "mintTo" => Self::MintTo,
+            "mintToChecked" => Self::MintToChecked,
+            "burn" => Self::Burn,
+            "burnChecked" => Self::BurnChecked,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    /// How the instruction moves tokens: `transfer`, `mint` or `burn`
+    pub fn movement(&self) -> &'static str {
+        match self {
+            Self::Transfer | Self::TransferChecked | Self::TransferCheckedWithFee => "transfer",
+            Self::MintTo | Self::MintToChecked => "mint",
+            Self::Burn | Self::BurnChecked => "burn",
+        }
+    }
+
+    pub fn is_transfer(&self) -> bool {
+        self.movement() == "transfer"
+    }
+}
+
+/// A decoded token transfer, mint or burn
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TokenInstruction {
+    pub program_id: Pubkey,
+    pub kind: TokenInstructionKind,
+    /// Mint of the token, not an account of unchecked transfers
+    pub mint: Option<Pubkey>,
+    /// Token account the tokens are taken from (none for mints)
+    pub source: Option<Pubkey>,
+    /// Token account the tokens are moved to (none for burns)
+    pub destination: Option<Pubkey>,
+    /// Amount in base units, for transfers with fee including the fee
+    pub amount: u64,
+    /// Decimals of the mint, given by checked instructions
+    pub decimals: Option<u8>,
+    /// Transfer fee withheld in the destination account
+    pub fee: Option<u64>,
+}
+
+/// Decodes a token instruction from its data and accounts
+///
+/// Returns `None` for other programs, instructions that move no tokens and malformed data.
+pub fn decode_token_instruction(
+    program_id: &Pubkey,
+    data: &[u8],
+    accounts: &[Pubkey],
+) -> Option<TokenInstruction> {
+    if !is_token_program(program_id) {
+        return None;
+    }
+    let u64_at = |offset: usize| {
+        data.get(offset..offset + 8)
+            .map(|bytes| u64::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let account = |index: usize| accounts.get(index).copied();
+
+    let (kind, mint, source, destination, decimals, fee) = match *data.first()? {
+        3 => (
+            TokenInstructionKind::Transfer,
+            None,
+            account(0),
+            account(1),
+            None,
+            None,
+        ),
+        7 => (
+            TokenInstructionKind::MintTo,
+            account(0),
+            None,
+            account(1),
+            None,
+            None,
+        ),
+        8 => (
+            TokenInstructionKind::Burn,
+            account(1),
+            account(0),
+            None,
+            None,
+            None,
+        ),
+        12 => (
+            TokenInstructionKind::TransferChecked,
+            account(1),
+            account(0),
+            account(2),
+            Some(*data.get(9)?),
+            None,
+        ),
+        14 => (
+            TokenInstructionKind::MintToChecked,
+            account(0),
+            None,
+            account(1),
+            Some(*data.get(9)?),
+            None,
+        ),
+        15 => (
+            TokenInstructionKind::BurnChecked,
+            account(1),
+            account(0),
+            None,
+            Some(*data.get(9)?),
+            None,
+        ),
+        TRANSFER_FEE_EXTENSION
+            if *program_id == TOKEN_2022_ID && data.get(1) == Some(&TRANSFER_CHECKED_WITH_FEE) =>
+        {
+            // The extension tag shifts the fields by one byte
+            return Some(TokenInstruction {
+                program_id: *program_id,
+                kind: TokenInstructionKind::TransferCheckedWithFee,
+                mint: account(1),
+                source: account(0),
+                destination: account(2),
+                amount: u64_at(2)?,
+                decimals: Some(*data.get(10)?),
+                fee: Some(u64_at(11)?),
+            });
+        }
+        _ => return None,
+    };
+
+    Some(TokenInstruction {
+        program_id: *program_id,
+        kind,
+        mint,
+        source,
+        destination,
+        amount: u64_at(1)?,
+        decimals,
+        fee,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn accounts() -> Vec<Pubkey> {
+        (0..4).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    fn transfer_checked_with_fee(amount: u64, decimals: u8, fee: u64) -> Vec<u8> {
+        let mut data = vec![TRANSFER_FEE_EXTENSION, TRANSFER_CHECKED_WITH_FEE];
+        data.extend(amount.to_le_bytes());
+        data.push(decimals);
+        data.extend(fee.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn transfer_checked_with_fee_reads_fields_after_the_extension_tag() {
+        let accounts = accounts();
+        let data = transfer_checked_with_fee(1_000_000, 6, 2_500);
+        // Amount at 2..10, decimals at 10, fee at 11..19
+        assert_eq!(data.len(), 19);
+        assert_eq!(&data[2..10], &1_000_000u64.to_le_bytes());
+        assert_eq!(data[10], 6);
+        assert_eq!(&data[11..19], &2_500u64.to_le_bytes());
+
+        let instruction = decode_token_instruction(&TOKEN_2022_ID, &data, &accounts).unwrap();
+
+        // source, mint, destination, authority
+        assert_eq!(
+            instruction,
+            TokenInstruction {
+                program_id: TOKEN_2022_ID,
+                kind: TokenInstructionKind::TransferCheckedWithFee,
+                mint: Some(accounts[1]),
+                source: Some(accounts[0]),
+                destination: Some(accounts[2]),
+                amount: 1_000_000,
+                decimals: Some(6),
+                fee: Some(2_500),
+            }
+        );
+    }
+
+    #[test]
+    fn transfer_fee_extension_is_only_decoded_for_token_2022() {
+        let data = transfer_checked_with_fee(1_000_000, 6, 2_500);
+
+        assert_eq!(decode_token_instruction(&ID, &data, &accounts()), None);
+    }
+
+    #[test]
+    fn transfer_fee_extension_skips_other_sub_instructions() {
+        let mut data = transfer_checked_with_fee(1_000_000, 6, 2_500);
+        // WithdrawWithheldTokensFromMint
+        data[1] = 2;
+
+        assert_eq!(
+            decode_token_instruction(&TOKEN_2022_ID, &data, &accounts()),
+            None
+        );
+    }
+
+    #[test]
+    fn truncated_transfer_checked_with_fee_is_not_decoded() {
+        let data = transfer_checked_with_fee(1_000_000, 6, 2_500);
+
+        assert_eq!(
+            decode_token_instruction(&TOKEN_2022_ID, &data[..18], &accounts()),
+            None
+        );
+    }
+
+    #[test]
+    fn transfer_checked_reads_decimals_after_the_amount() {
+        let accounts = accounts();
+        let mut data = vec![12];
+        data.extend(42u64.to_le_bytes());
+        data.push(8);
+
+        let instruction = decode_token_instruction(&ID, &data, &accounts).unwrap();
+
+        assert_eq!(instruction.kind, TokenInstructionKind::TransferChecked);
+        assert_eq!(instruction.amount, 42);
+        assert_eq!(instruction.decimals, Some(8));
+        assert_eq!(instruction.fee, None);
+        assert_eq!(instruction.mint, Some(accounts[1]));
+    }
+}
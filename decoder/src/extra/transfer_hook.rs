@@ -1,4 +1,47 @@
+//! Transfer hooks of Token-2022 mints with the transfer hook extension
+//!
+//! Token-2022 invokes the hook program of such a mint with the `Execute` instruction of the
+//! SPL transfer hook interface on every checked transfer. Any program can be a hook, only
+//! executions of the hooks in [`KNOWN_HOOKS`] are recognized; add the hook program of a new
+//! mint there.
+
 use solana_sdk::pubkey;
 use solana_sdk::pubkey::Pubkey;
 
+/// The transfer hook of Star Atlas mints
 pub const ID: Pubkey = pubkey!("tHookmPkFZDJGkS9us6sVsnYi2EKHCrVtw8zD6oXYPE");
+
+/// Hook programs of the mints traded on the marketplace
+pub const KNOWN_HOOKS: &[Pubkey] = &[ID];
+
+/// Discriminator of the interface's `Execute` instruction
+pub const EXECUTE_DISCRIMINATOR: [u8; 8] = [105, 37, 101, 197, 75, 251, 102, 26];
+
+/// Whether an instruction is the `Execute` instruction of a known transfer hook
+pub fn is_execute(program_id: &Pubkey, data: &[u8]) -> bool {
+    KNOWN_HOOKS.contains(program_id) && data.starts_with(&EXECUTE_DISCRIMINATOR)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn recognizes_executions_of_known_hooks() {
+        let mut data = EXECUTE_DISCRIMINATOR.to_vec();
+        data.extend(1_000u64.to_le_bytes());
+
+        assert!(is_execute(&ID, &data));
+    }
+
+    #[test]
+    fn rejects_other_programs_and_instructions() {
+        let mut data = EXECUTE_DISCRIMINATOR.to_vec();
+        data.extend(1_000u64.to_le_bytes());
+
+        // Any program could use the discriminator
+        assert!(!is_execute(&Pubkey::new_unique(), &data));
+        assert!(!is_execute(&ID, &[1, 2, 3]));
+        assert!(!is_execute(&ID, &EXECUTE_DISCRIMINATOR[..4]));
+    }
+}
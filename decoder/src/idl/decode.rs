@@ -0,0 +1,365 @@
+use crate::idl::IdlError;
+use crate::idl::types::{Idl, IdlDefinedFields, IdlType, IdlTypeDefTy};
+use serde_json::{Map, Value};
+use solana_sdk::pubkey::Pubkey;
+
+/// Nesting limit for defined types, guards against recursive definitions
+const MAX_DEPTH: usize = 64;
+
+/// Borsh reader turning IDL typed data into JSON values
+///
+/// Integers up to 64 bits become JSON numbers, wider ones decimal strings, public keys base58
+/// and `bytes` hex strings. Unit enum variants become their name, variants with fields an
+/// object keyed by the variant name.
+pub struct IdlReader<'a> {
+    idl: &'a Idl,
+    data: &'a [u8],
+}
+
+impl<'a> IdlReader<'a> {
+    pub fn new(idl: &'a Idl, data: &'a [u8]) -> Self {
+        IdlReader { idl, data }
+    }
+
+    /// Bytes not consumed yet
+    pub fn remaining(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Reads named fields into a JSON object
+    pub fn read_fields<'f>(
+        &mut self,
+        fields: impl IntoIterator<Item = (&'f str, &'f IdlType)>,
+    ) -> Result<Value, IdlError> {
+        self.read_named(fields, 0)
+    }
+
+    /// Reads a value of a defined type
+    pub fn read_defined(&mut self, name: &str) -> Result<Value, IdlError> {
+        self.read_defined_at(name, 0)
+    }
+
+    /// Reads a value of the given type
+    pub fn read(&mut self, ty: &IdlType) -> Result<Value, IdlError> {
+        self.read_at(ty, 0)
+    }
+
+    fn read_at(&mut self, ty: &IdlType, depth: usize) -> Result<Value, IdlError> {
+        Ok(match ty {
+            IdlType::Bool => match self.take::<1>()? {
+                [0] => Value::Bool(false),
+                [1] => Value::Bool(true),
+                [other] => {
+                    return Err(IdlError::InvalidData(format!("invalid bool {}", other)));
+                }
+            },
+            IdlType::U8 => u8::from_le_bytes(self.take()?).into(),
+            IdlType::I8 => i8::from_le_bytes(self.take()?).into(),
+            IdlType::U16 => u16::from_le_bytes(self.take()?).into(),
+            IdlType::I16 => i16::from_le_bytes(self.take()?).into(),
+            IdlType::U32 => u32::from_le_bytes(self.take()?).into(),
+            IdlType::I32 => i32::from_le_bytes(self.take()?).into(),
+            IdlType::F32 => f32::from_le_bytes(self.take()?).into(),
+            IdlType::U64 => u64::from_le_bytes(self.take()?).into(),
+            IdlType::I64 => i64::from_le_bytes(self.take()?).into(),
+            IdlType::F64 => f64::from_le_bytes(self.take()?).into(),
+            IdlType::U128 => u128::from_le_bytes(self.take()?).to_string().into(),
+            IdlType::I128 => i128::from_le_bytes(self.take()?).to_string().into(),
+            // No native 256 bit integers, keep the little endian bytes
+            IdlType::U256 | IdlType::I256 => hex::encode(self.take::<32>()?).into(),
+            IdlType::Bytes => {
+                let len = self.read_len()?;
+                hex::encode(self.take_slice(len)?).into()
+            }
+            IdlType::String => {
+                let len = self.read_len()?;
+                String::from_utf8(self.take_slice(len)?.to_vec())
+                    .map_err(|err| IdlError::InvalidData(err.to_string()))?
+                    .into()
+            }
+            IdlType::Pubkey => Pubkey::new_from_array(self.take()?).to_string().into(),
+            IdlType::Option(inner) => match self.take::<1>()? {
+                [0] => Value::Null,
+                [1] => self.read_at(inner, depth)?,
+                [other] => {
+                    return Err(IdlError::InvalidData(format!(
+                        "invalid option tag {}",
+                        other
+                    )));
+                }
+            },
+            IdlType::Vec(inner) => {
+                let len = self.read_len()?;
+                self.read_many(inner, len, depth)?
+            }
+            IdlType::Array(inner, len) => self.read_many(inner, *len, depth)?,
+            IdlType::Defined(name) => self.read_defined_at(name, depth + 1)?,
+        })
+    }
+
+    fn read_defined_at(&mut self, name: &str, depth: usize) -> Result<Value, IdlError> {
+        if depth > MAX_DEPTH {
+            return Err(IdlError::InvalidIdl(format!(
+                "type {} nests too deep",
+                name
+            )));
+        }
+        let type_def = self
+            .idl
+            .type_def(name)
+            .ok_or_else(|| IdlError::UnknownType(name.to_string()))?;
+        if let Some(serialization) = type_def.serialization.as_deref()
+            && serialization != "borsh"
+        {
+            return Err(IdlError::InvalidIdl(format!(
+                "type {} uses unsupported serialization {}",
+                name, serialization
+            )));
+        }
+        self.read_type_def_at(&type_def.ty, depth)
+    }
+
+    /// Reads a value of an inline type definition
+    pub fn read_type_def(&mut self, ty: &IdlTypeDefTy) -> Result<Value, IdlError> {
+        self.read_type_def_at(ty, 0)
+    }
+
+    fn read_type_def_at(&mut self, ty: &IdlTypeDefTy, depth: usize) -> Result<Value, IdlError> {
+        match ty {
+            IdlTypeDefTy::Struct { fields } => self.read_defined_fields(fields.as_ref(), depth),
+            IdlTypeDefTy::Enum { variants } => {
+                let index = self.take::<1>()?[0] as usize;
+                let variant = variants.get(index).ok_or_else(|| {
+                    IdlError::InvalidData(format!("invalid enum variant {}", index))
+                })?;
+                match &variant.fields {
+                    None => Ok(Value::String(variant.name.clone())),
+                    Some(fields) => {
+                        let value = self.read_defined_fields(Some(fields), depth)?;
+                        Ok(Value::Object(Map::from_iter([(
+                            variant.name.clone(),
+                            value,
+                        )])))
+                    }
+                }
+            }
+            IdlTypeDefTy::Type { alias } => self.read_at(alias, depth),
+        }
+    }
+
+    fn read_defined_fields(
+        &mut self,
+        fields: Option<&IdlDefinedFields>,
+        depth: usize,
+    ) -> Result<Value, IdlError> {
+        match fields {
+            None => Ok(Value::Object(Map::new())),
+            Some(IdlDefinedFields::Named(fields)) => self.read_named(
+                fields.iter().map(|field| (field.name.as_str(), &field.ty)),
+                depth,
+            ),
+            Some(IdlDefinedFields::Tuple(types)) => types
+                .iter()
+                .map(|ty| self.read_at(ty, depth))
+                .collect::<Result<_, _>>()
+                .map(Value::Array),
+        }
+    }
+
+    fn read_named<'f>(
+        &mut self,
+        fields: impl IntoIterator<Item = (&'f str, &'f IdlType)>,
+        depth: usize,
+    ) -> Result<Value, IdlError> {
+        let mut object = Map::new();
+        for (name, ty) in fields {
+            object.insert(name.to_string(), self.read_at(ty, depth)?);
+        }
+        Ok(Value::Object(object))
+    }
+
+    fn read_many(&mut self, ty: &IdlType, len: usize, depth: usize) -> Result<Value, IdlError> {
+        // Grows with the data actually read, a corrupt length must not allocate up front
+        let mut values = Vec::new();
+        for _ in 0..len {
+            values.push(self.read_at(ty, depth)?);
+        }
+        Ok(Value::Array(values))
+    }
+
+    fn read_len(&mut self) -> Result<usize, IdlError> {
+        Ok(u32::from_le_bytes(self.take()?) as usize)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], IdlError> {
+        let bytes = self.take_slice(N)?;
+        Ok(bytes.try_into().expect("slice of requested length"))
+    }
+
+    fn take_slice(&mut self, len: usize) -> Result<&'a [u8], IdlError> {
+        if self.data.len() < len {
+            return Err(IdlError::UnexpectedEnd);
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn idl() -> Idl {
+        Idl::from_json(
+            r#"{
+                "types": [
+                    {"name": "Point", "type": {"kind": "struct", "fields": [
+                        {"name": "x", "type": "i16"}, {"name": "y", "type": "i16"}
+                    ]}},
+                    {"name": "Pair", "type": {"kind": "struct", "fields": ["u8", "bool"]}},
+                    {"name": "Shape", "type": {"kind": "enum", "variants": [
+                        {"name": "Empty"},
+                        {"name": "Dot", "fields": [{"name": "at", "type": {"defined": {"name": "Point"}}}]}
+                    ]}},
+                    {"name": "Amount", "type": {"kind": "type", "alias": "u64"}},
+                    {"name": "Loop", "type": {"kind": "type", "alias": {"defined": "Loop"}}}
+                ]
+            }"#,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn reads_little_endian_integers() {
+        let idl = idl();
+        let mut data = vec![0xff, 0xfe, 0xff];
+        data.extend(300u32.to_le_bytes());
+        data.extend(u64::MAX.to_le_bytes());
+        data.extend((-5i128).to_le_bytes());
+        let mut reader = IdlReader::new(&idl, &data);
+
+        assert_eq!(reader.read(&IdlType::U8).unwrap(), json!(255));
+        assert_eq!(reader.read(&IdlType::I16).unwrap(), json!(-2));
+        assert_eq!(reader.read(&IdlType::U32).unwrap(), json!(300));
+        assert_eq!(reader.read(&IdlType::U64).unwrap(), json!(u64::MAX));
+        // Wider than a JSON number, kept as a decimal string
+        assert_eq!(reader.read(&IdlType::I128).unwrap(), json!("-5"));
+        assert!(reader.remaining().is_empty());
+    }
+
+    #[test]
+    fn reads_length_prefixed_strings_bytes_and_vecs() {
+        let idl = idl();
+        let mut data = vec![2, 0, 0, 0, b'h', b'i'];
+        data.extend([2, 0, 0, 0, 0xab, 0xcd]);
+        data.extend([3, 0, 0, 0, 1, 0, 1]);
+        let mut reader = IdlReader::new(&idl, &data);
+
+        assert_eq!(reader.read(&IdlType::String).unwrap(), json!("hi"));
+        assert_eq!(reader.read(&IdlType::Bytes).unwrap(), json!("abcd"));
+        assert_eq!(
+            reader.read(&IdlType::Vec(Box::new(IdlType::Bool))).unwrap(),
+            json!([true, false, true])
+        );
+    }
+
+    #[test]
+    fn reads_pubkeys_options_and_fixed_arrays() {
+        let idl = idl();
+        let pubkey = Pubkey::new_unique();
+        let mut data = pubkey.to_bytes().to_vec();
+        data.extend([0, 1, 7, 1, 2, 3]);
+        let mut reader = IdlReader::new(&idl, &data);
+
+        assert_eq!(
+            reader.read(&IdlType::Pubkey).unwrap(),
+            json!(pubkey.to_string())
+        );
+        let option = IdlType::Option(Box::new(IdlType::U8));
+        assert_eq!(reader.read(&option).unwrap(), Value::Null);
+        assert_eq!(reader.read(&option).unwrap(), json!(7));
+        assert_eq!(
+            reader
+                .read(&IdlType::Array(Box::new(IdlType::U8), 3))
+                .unwrap(),
+            json!([1, 2, 3])
+        );
+    }
+
+    #[test]
+    fn reads_defined_structs_enums_and_aliases() {
+        let idl = idl();
+        let mut data = vec![1, 0, 0xfe, 0xff];
+        data.extend([9, 1]);
+        data.extend([0]);
+        data.extend([1, 3, 0, 4, 0]);
+        data.extend(42u64.to_le_bytes());
+        let mut reader = IdlReader::new(&idl, &data);
+
+        assert_eq!(
+            reader.read_defined("Point").unwrap(),
+            json!({"x": 1, "y": -2})
+        );
+        assert_eq!(reader.read_defined("Pair").unwrap(), json!([9, true]));
+        assert_eq!(reader.read_defined("Shape").unwrap(), json!("Empty"));
+        assert_eq!(
+            reader.read_defined("Shape").unwrap(),
+            json!({"Dot": {"at": {"x": 3, "y": 4}}})
+        );
+        assert_eq!(reader.read_defined("Amount").unwrap(), json!(42));
+    }
+
+    #[test]
+    fn keeps_trailing_bytes_as_remaining() {
+        let idl = idl();
+        let data = [1, 0, 2, 0, 0xaa, 0xbb];
+        let mut reader = IdlReader::new(&idl, &data);
+
+        reader.read_defined("Point").unwrap();
+        assert_eq!(reader.remaining(), &[0xaa, 0xbb]);
+    }
+
+    #[test]
+    fn rejects_invalid_and_truncated_data() {
+        let idl = idl();
+
+        assert!(matches!(
+            IdlReader::new(&idl, &[2]).read(&IdlType::Bool),
+            Err(IdlError::InvalidData(_))
+        ));
+        assert!(matches!(
+            IdlReader::new(&idl, &[5]).read(&IdlType::Option(Box::new(IdlType::U8))),
+            Err(IdlError::InvalidData(_))
+        ));
+        assert!(matches!(
+            IdlReader::new(&idl, &[2]).read_defined("Shape"),
+            Err(IdlError::InvalidData(_))
+        ));
+        assert!(matches!(
+            IdlReader::new(&idl, &[1, 2, 3]).read(&IdlType::U32),
+            Err(IdlError::UnexpectedEnd)
+        ));
+        // A length beyond the data fails instead of allocating
+        assert!(matches!(
+            IdlReader::new(&idl, &[0xff, 0xff, 0xff, 0xff])
+                .read(&IdlType::Vec(Box::new(IdlType::U64))),
+            Err(IdlError::UnexpectedEnd)
+        ));
+    }
+
+    #[test]
+    fn rejects_unknown_and_recursive_types() {
+        let idl = idl();
+
+        assert!(matches!(
+            IdlReader::new(&idl, &[]).read_defined("Missing"),
+            Err(IdlError::UnknownType(_))
+        ));
+        assert!(matches!(
+            IdlReader::new(&idl, &[]).read_defined("Loop"),
+            Err(IdlError::InvalidIdl(_))
+        ));
+    }
+}
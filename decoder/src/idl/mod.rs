@@ -0,0 +1,53 @@
+//! Runtime Anchor IDL decoding
+//!
+//! The modules generated by `anchor_idl` decode a single IDL version baked in at compile time.
+//! Program upgrades change instruction and account layouts, so historical and current
+//! transactions may need different IDLs. The [`IdlRegistry`] holds several IDL versions per
+//! program, each valid for a slot range, and decodes instructions, accounts and events with the
+//! IDL that was deployed at the given slot. IDLs can be loaded from a directory at runtime.
+//!
+//! Only the marketplace and buddy link IDLs are compiled in, see [`IdlRegistry::builtin`]. The
+//! Star Atlas programs in [`crate::staratlas`] that are upgraded independently (SAGE, crafting,
+//! SCORE, Player Profile, Profile Faction) are decoded with the IDL versions the processor
+//! loads into the registry from `IDL_DIR`, see [`IdlRegistry::load_dir`]. The repository ships
+//! them with their `registry.json` manifest in `idls/`.
+
+mod decode;
+mod registry;
+mod types;
+
+pub use decode::IdlReader;
+pub use registry::{
+    DecodedIdlAccount, DecodedIdlEvent, DecodedIdlInstruction, IdlRegistry, IdlVersion,
+};
+pub use types::*;
+
+use thiserror::Error;
+
+/// Errors that can occur while loading IDLs or decoding data with them
+#[derive(Debug, Error)]
+pub enum IdlError {
+    /// Error when reading an IDL file
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// Error when an IDL or the registry manifest is not valid JSON
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// Error when an IDL can not be used for decoding
+    #[error("Invalid IDL: {0}")]
+    InvalidIdl(String),
+
+    /// Error when a type is referenced but not defined in the IDL
+    #[error("Unknown type: {0}")]
+    UnknownType(String),
+
+    /// Error when the data ends before the value is complete
+    #[error("Unexpected end of data")]
+    UnexpectedEnd,
+
+    /// Error when the data does not match the IDL
+    #[error("Invalid data: {0}")]
+    InvalidData(String),
+}
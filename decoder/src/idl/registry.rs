@@ -0,0 +1,468 @@
+use crate::idl::types::{Idl, to_snake_case};
+use crate::idl::{IdlError, IdlReader};
+use serde::Deserialize;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// File listing the IDLs of a directory with the programs and slots they apply to
+pub const MANIFEST_FILE: &str = "registry.json";
+
+/// IDL version valid from `from_slot` until `to_slot` (exclusive) or the next version
+#[derive(Debug, Clone)]
+pub struct IdlVersion {
+    pub from_slot: u64,
+    pub to_slot: Option<u64>,
+    pub idl: Arc<Idl>,
+}
+
+/// IDL versions per program, selected by slot
+#[derive(Debug, Clone, Default)]
+pub struct IdlRegistry {
+    programs: HashMap<Pubkey, Vec<IdlVersion>>,
+}
+
+/// Instruction decoded with the IDL valid at its slot
+#[derive(Debug, Clone)]
+pub struct DecodedIdlInstruction {
+    /// Program name from the IDL
+    pub program: String,
+    /// Version of the IDL used
+    pub version: String,
+    pub name: String,
+    pub args: Value,
+    /// Accounts by IDL name, accounts beyond the IDL (remaining accounts) are left out
+    pub accounts: Vec<(String, Pubkey)>,
+}
+
+/// Account decoded with the IDL valid at a slot
+#[derive(Debug, Clone)]
+pub struct DecodedIdlAccount {
+    /// Program name from the IDL
+    pub program: String,
+    /// Version of the IDL used
+    pub version: String,
+    pub name: String,
+    pub data: Value,
+    /// Bytes after the IDL layout, padding or data the program appends (e.g. item lists)
+    pub remaining: Vec<u8>,
+}
+
+/// Event decoded with the IDL valid at a slot
+#[derive(Debug, Clone)]
+pub struct DecodedIdlEvent {
+    /// Program name from the IDL
+    pub program: String,
+    /// Version of the IDL used
+    pub version: String,
+    pub name: String,
+    pub data: Value,
+}
+
+/// Entry of the directory manifest
+#[derive(Debug, Deserialize)]
+struct ManifestEntry {
+    /// IDL file, relative to the directory
+    idl: String,
+    /// Program address, defaults to the address in the IDL
+    #[serde(default)]
+    program: Option<String>,
+    /// Slot the IDL applies from, usually the slot of the program upgrade deploying it
+    #[serde(default)]
+    from_slot: u64,
+    #[serde(default)]
+    to_slot: Option<u64>,
+}
+
+impl DecodedIdlInstruction {
+    /// Looks up an account by its snake_case IDL name, legacy camelCase names match too
+    pub fn account(&self, name: &str) -> Option<&Pubkey> {
+        self.accounts
+            .iter()
+            .find(|(account, _)| account == name || to_snake_case(account) == name)
+            .map(|(_, pubkey)| pubkey)
+    }
+
+    /// Looks up an argument by its snake_case IDL name, searching into struct arguments
+    ///
+    /// Programs like SAGE wrap their arguments in a single `input` struct, so a field of it is
+    /// found as well. The first match in argument order wins.
+    pub fn arg(&self, name: &str) -> Option<&Value> {
+        fn find<'a>(value: &'a Value, name: &str) -> Option<&'a Value> {
+            let fields = value.as_object()?;
+            fields
+                .iter()
+                .find(|(field, _)| *field == name || to_snake_case(field) == name)
+                .map(|(_, value)| value)
+                .or_else(|| fields.values().find_map(|value| find(value, name)))
+        }
+
+        find(&self.args, name)
+    }
+}
+
+impl DecodedIdlAccount {
+    /// Looks up a top-level field by its snake_case IDL name, legacy camelCase names match too
+    pub fn field(&self, name: &str) -> Option<&Value> {
+        self.data
+            .as_object()?
+            .iter()
+            .find(|(field, _)| *field == name || to_snake_case(field) == name)
+            .map(|(_, value)| value)
+    }
+}
+
+impl IdlRegistry {
+    /// Creates an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Creates a registry with the IDLs compiled into the decoder, valid from genesis
+    pub fn builtin() -> Self {
+        let mut registry = Self::new();
+        for (program_id, json) in [
+            (
+                crate::staratlas::marketplace::ID,
+                include_str!("../../marketplace_0.30.0.json"),
+            ),
+            (
+                crate::staratlas::buddy::ID,
+                include_str!("../../buddylink_0.30.0.json"),
+            ),
+        ] {
+            let idl = Idl::from_json(json).expect("built-in IDLs are valid");
+            registry.insert(program_id, 0, None, idl);
+        }
+        registry
+    }
+
+    /// Adds an IDL version, replacing a version of the program starting at the same slot
+    pub fn insert(&mut self, program_id: Pubkey, from_slot: u64, to_slot: Option<u64>, idl: Idl) {
+        let versions = self.programs.entry(program_id).or_default();
+        versions.retain(|version| version.from_slot != from_slot);
+        versions.push(IdlVersion {
+            from_slot,
+            to_slot,
+            idl: Arc::new(idl),
+        });
+        versions.sort_by_key(|version| version.from_slot);
+    }
+
+    /// Loads the IDLs of a directory
+    ///
+    /// IDLs listed in the `registry.json` manifest apply to the given program and slot range,
+    /// e.g. `[{"idl": "marketplace_0.31.0.json", "from_slot": 300000000}]`. Other `*.json` files
+    /// apply to the program of their `address` from genesis. Returns the number of IDLs loaded.
+    pub fn load_dir(&mut self, dir: impl AsRef<Path>) -> Result<usize, IdlError> {
+        let dir = dir.as_ref();
+        let manifest_path = dir.join(MANIFEST_FILE);
+        let mut entries: Vec<ManifestEntry> = match fs::read_to_string(&manifest_path) {
+            Ok(manifest) => serde_json::from_str(&manifest)?,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(err) => return Err(err.into()),
+        };
+
+        let mut files = fs::read_dir(dir)?
+            .map(|entry| entry.map(|entry| entry.file_name().to_string_lossy().into_owned()))
+            .collect::<Result<Vec<_>, _>>()?;
+        files.sort();
+        for file in files {
+            if file.ends_with(".json")
+                && file != MANIFEST_FILE
+                && !entries.iter().any(|entry| entry.idl == file)
+            {
+                entries.push(ManifestEntry {
+                    idl: file,
+                    program: None,
+                    from_slot: 0,
+                    to_slot: None,
+                });
+            }
+        }
+
+        for entry in &entries {
+            let idl = Idl::from_json(&fs::read_to_string(dir.join(&entry.idl))?)?;
+            let address = entry
+                .program
+                .as_deref()
+                .or(idl.program_address())
+                .ok_or_else(|| {
+                    IdlError::InvalidIdl(format!("{} has no program address", entry.idl))
+                })?;
+            let program_id = Pubkey::from_str(address).map_err(|err| {
+                IdlError::InvalidIdl(format!("{}: invalid program address: {}", entry.idl, err))
+            })?;
+            self.insert(program_id, entry.from_slot, entry.to_slot, idl);
+        }
+
+        Ok(entries.len())
+    }
+
+    /// All IDL versions of a program, ordered by slot
+    pub fn versions(&self, program_id: &Pubkey) -> &[IdlVersion] {
+        self.programs
+            .get(program_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// The IDL valid for a program at a slot
+    pub fn idl(&self, program_id: &Pubkey, slot: u64) -> Option<&Idl> {
+        self.versions(program_id)
+            .iter()
+            .rev()
+            .find(|version| version.from_slot <= slot)
+            .filter(|version| version.to_slot.is_none_or(|to_slot| slot < to_slot))
+            .map(|version| version.idl.as_ref())
+    }
+
+    /// Decodes instruction data and accounts with the IDL valid at the slot
+    ///
+    /// Returns `Ok(None)` if no IDL is registered for the slot or the discriminator is not part
+    /// of it, and an error if the data does not match the instruction's layout.
+    pub fn decode_instruction(
+        &self,
+        program_id: &Pubkey,
+        slot: u64,
+        data: &[u8],
+        accounts: &[Pubkey],
+    ) -> Result<Option<DecodedIdlInstruction>, IdlError> {
+        let Some(idl) = self.idl(program_id, slot) else {
+            return Ok(None);
+        };
+        let Some((instruction, discriminator)) = idl
+            .instructions
+            .iter()
+            .map(|instruction| (instruction, instruction.discriminator()))
+            .find(|(_, discriminator)| data.starts_with(discriminator))
+        else {
+            return Ok(None);
+        };
+
+        let args = IdlReader::new(idl, &data[discriminator.len()..]).read_fields(
+            instruction
+                .args
+                .iter()
+                .map(|arg| (arg.name.as_str(), &arg.ty)),
+        )?;
+
+        Ok(Some(DecodedIdlInstruction {
+            program: idl.name().to_string(),
+            version: idl.version().to_string(),
+            name: instruction.name.clone(),
+            args,
+            accounts: instruction
+                .account_names()
+                .into_iter()
+                .zip(accounts.iter().copied())
+                .collect(),
+        }))
+    }
+
+    /// Decodes account data with the IDL valid at the slot
+    ///
+    /// Returns `Ok(None)` if no IDL is registered for the slot or the discriminator is not part
+    /// of it. Trailing bytes (e.g. padding of preallocated accounts) are returned as they are.
+    pub fn decode_account(
+        &self,
+        program_id: &Pubkey,
+        slot: u64,
+        data: &[u8],
+    ) -> Result<Option<DecodedIdlAccount>, IdlError> {
+        let Some(idl) = self.idl(program_id, slot) else {
+            return Ok(None);
+        };
+        let Some((account, discriminator)) = idl
+            .accounts
+            .iter()
+            .map(|account| (account, account.discriminator()))
+            .find(|(_, discriminator)| data.starts_with(discriminator))
+        else {
+            return Ok(None);
+        };
+
+        let mut reader = IdlReader::new(idl, &data[discriminator.len()..]);
+        let data = match &account.ty {
+            Some(ty) => reader.read_type_def(ty)?,
+            None => reader.read_defined(&account.name)?,
+        };
+
+        Ok(Some(DecodedIdlAccount {
+            program: idl.name().to_string(),
+            version: idl.version().to_string(),
+            name: account.name.clone(),
+            data,
+            remaining: reader.remaining().to_vec(),
+        }))
+    }
+
+    /// Decodes an event (e.g. a `Program data:` log payload) with the IDL valid at the slot
+    ///
+    /// Returns `Ok(None)` if no IDL is registered for the slot or the discriminator is not part
+    /// of it.
+    pub fn decode_event(
+        &self,
+        program_id: &Pubkey,
+        slot: u64,
+        data: &[u8],
+    ) -> Result<Option<DecodedIdlEvent>, IdlError> {
+        let Some(idl) = self.idl(program_id, slot) else {
+            return Ok(None);
+        };
+        let Some((event, discriminator)) = idl
+            .events
+            .iter()
+            .map(|event| (event, event.discriminator()))
+            .find(|(_, discriminator)| data.starts_with(discriminator))
+        else {
+            return Ok(None);
+        };
+
+        let mut reader = IdlReader::new(idl, &data[discriminator.len()..]);
+        let data = match &event.fields {
+            Some(fields) => {
+                reader.read_fields(fields.iter().map(|field| (field.name.as_str(), &field.ty)))?
+            }
+            None => reader.read_defined(&event.name)?,
+        };
+
+        Ok(Some(DecodedIdlEvent {
+            program: idl.name().to_string(),
+            version: idl.version().to_string(),
+            name: event.name.clone(),
+            data,
+        }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::sighash;
+
+    fn idl(version: &str) -> Idl {
+        Idl::from_json(&format!(
+            r#"{{"metadata": {{"name": "test", "version": "{}"}}}}"#,
+            version
+        ))
+        .unwrap()
+    }
+
+    fn version_at(registry: &IdlRegistry, program_id: &Pubkey, slot: u64) -> Option<String> {
+        registry
+            .idl(program_id, slot)
+            .map(|idl| idl.version().to_string())
+    }
+
+    #[test]
+    fn selects_the_latest_version_started_at_the_slot() {
+        let program_id = Pubkey::new_unique();
+        let mut registry = IdlRegistry::new();
+        registry.insert(program_id, 200, None, idl("2"));
+        registry.insert(program_id, 100, None, idl("1"));
+
+        assert_eq!(version_at(&registry, &program_id, 99), None);
+        assert_eq!(
+            version_at(&registry, &program_id, 100).as_deref(),
+            Some("1")
+        );
+        assert_eq!(
+            version_at(&registry, &program_id, 199).as_deref(),
+            Some("1")
+        );
+        assert_eq!(
+            version_at(&registry, &program_id, 200).as_deref(),
+            Some("2")
+        );
+        assert_eq!(version_at(&registry, &Pubkey::new_unique(), 200), None);
+    }
+
+    #[test]
+    fn leaves_a_gap_after_to_slot() {
+        let program_id = Pubkey::new_unique();
+        let mut registry = IdlRegistry::new();
+        registry.insert(program_id, 100, Some(150), idl("1"));
+        registry.insert(program_id, 200, None, idl("2"));
+
+        assert_eq!(
+            version_at(&registry, &program_id, 149).as_deref(),
+            Some("1")
+        );
+        // to_slot is exclusive, the earlier version does not fill the gap
+        assert_eq!(version_at(&registry, &program_id, 150), None);
+        assert_eq!(version_at(&registry, &program_id, 199), None);
+        assert_eq!(
+            version_at(&registry, &program_id, 200).as_deref(),
+            Some("2")
+        );
+    }
+
+    #[test]
+    fn later_version_overrides_an_overlapping_range() {
+        let program_id = Pubkey::new_unique();
+        let mut registry = IdlRegistry::new();
+        registry.insert(program_id, 100, Some(300), idl("1"));
+        registry.insert(program_id, 200, Some(250), idl("2"));
+
+        assert_eq!(
+            version_at(&registry, &program_id, 199).as_deref(),
+            Some("1")
+        );
+        assert_eq!(
+            version_at(&registry, &program_id, 200).as_deref(),
+            Some("2")
+        );
+        // Past the end of the later version nothing applies, the earlier range is not resumed
+        assert_eq!(version_at(&registry, &program_id, 260), None);
+    }
+
+    #[test]
+    fn replaces_a_version_starting_at_the_same_slot() {
+        let program_id = Pubkey::new_unique();
+        let mut registry = IdlRegistry::new();
+        registry.insert(program_id, 100, None, idl("1"));
+        registry.insert(program_id, 100, None, idl("1-fixed"));
+
+        assert_eq!(registry.versions(&program_id).len(), 1);
+        assert_eq!(
+            version_at(&registry, &program_id, 100).as_deref(),
+            Some("1-fixed")
+        );
+    }
+
+    #[test]
+    fn decodes_marketplace_pda_orders_with_the_shipped_manifest() {
+        let mut registry = IdlRegistry::builtin();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        let program_id = crate::staratlas::marketplace::ID;
+
+        for (discriminator, name) in [
+            ("12a3f263760ec756", "process_initialize_buy_pda"),
+            ("82b80db047238238", "process_initialize_sell_pda"),
+        ] {
+            let instruction = registry
+                .decode_instruction(&program_id, 0, &hex::decode(discriminator).unwrap(), &[])
+                .unwrap()
+                .unwrap();
+            assert_eq!(instruction.name, name);
+        }
+
+        // The built-in instructions are still part of the registered version
+        let exchange = sighash("global", "process_exchange");
+        assert!(
+            registry
+                .idl(&program_id, 0)
+                .unwrap()
+                .instructions
+                .iter()
+                .any(|instruction| instruction.discriminator() == exchange)
+        );
+    }
+}
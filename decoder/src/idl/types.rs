@@ -0,0 +1,334 @@
+use crate::idl::IdlError;
+use serde::Deserialize;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+
+/// Anchor IDL, in the 0.30 format or the legacy (pre 0.30) format
+#[derive(Debug, Clone, Deserialize)]
+pub struct Idl {
+    /// Program address (0.30)
+    #[serde(default)]
+    pub address: Option<String>,
+    #[serde(default)]
+    pub metadata: Option<IdlMetadata>,
+    /// Program name (legacy)
+    #[serde(default)]
+    name: Option<String>,
+    /// Program version (legacy)
+    #[serde(default)]
+    version: Option<String>,
+    #[serde(default)]
+    pub instructions: Vec<IdlInstruction>,
+    #[serde(default)]
+    pub accounts: Vec<IdlAccount>,
+    #[serde(default)]
+    pub events: Vec<IdlEvent>,
+    #[serde(default)]
+    pub types: Vec<IdlTypeDef>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlMetadata {
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub version: Option<String>,
+    /// Program address (legacy)
+    #[serde(default)]
+    pub address: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlInstruction {
+    pub name: String,
+    /// Missing in legacy IDLs, see [`IdlInstruction::discriminator`]
+    #[serde(default, rename = "discriminator")]
+    discriminator_bytes: Option<Vec<u8>>,
+    #[serde(default)]
+    pub accounts: Vec<IdlInstructionAccountItem>,
+    #[serde(default)]
+    pub args: Vec<IdlField>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum IdlInstructionAccountItem {
+    /// Nested accounts struct, its accounts are passed inline
+    Composite {
+        name: String,
+        accounts: Vec<IdlInstructionAccountItem>,
+    },
+    Single(IdlInstructionAccount),
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlInstructionAccount {
+    pub name: String,
+    #[serde(default, alias = "isMut")]
+    pub writable: bool,
+    #[serde(default, alias = "isSigner")]
+    pub signer: bool,
+    #[serde(default, alias = "isOptional")]
+    pub optional: bool,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlAccount {
+    pub name: String,
+    #[serde(default, rename = "discriminator")]
+    discriminator_bytes: Option<Vec<u8>>,
+    /// Inline type definition (legacy), 0.30 IDLs define it in `types`
+    #[serde(default, rename = "type")]
+    pub ty: Option<IdlTypeDefTy>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlEvent {
+    pub name: String,
+    #[serde(default, rename = "discriminator")]
+    discriminator_bytes: Option<Vec<u8>>,
+    /// Inline fields (legacy), 0.30 IDLs define them in `types`
+    #[serde(default)]
+    pub fields: Option<Vec<IdlField>>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub ty: IdlType,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlTypeDef {
+    pub name: String,
+    /// `borsh` unless stated otherwise, other serializations are not supported
+    #[serde(default)]
+    pub serialization: Option<String>,
+    #[serde(rename = "type")]
+    pub ty: IdlTypeDefTy,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub enum IdlTypeDefTy {
+    Struct {
+        #[serde(default)]
+        fields: Option<IdlDefinedFields>,
+    },
+    Enum {
+        variants: Vec<IdlEnumVariant>,
+    },
+    Type {
+        alias: IdlType,
+    },
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdlEnumVariant {
+    pub name: String,
+    #[serde(default)]
+    pub fields: Option<IdlDefinedFields>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(untagged)]
+pub enum IdlDefinedFields {
+    Named(Vec<IdlField>),
+    Tuple(Vec<IdlType>),
+}
+
+/// Type of a field, argument or alias
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(try_from = "Value")]
+pub enum IdlType {
+    Bool,
+    U8,
+    I8,
+    U16,
+    I16,
+    U32,
+    I32,
+    F32,
+    U64,
+    I64,
+    F64,
+    U128,
+    I128,
+    U256,
+    I256,
+    Bytes,
+    String,
+    Pubkey,
+    Option(Box<IdlType>),
+    Vec(Box<IdlType>),
+    Array(Box<IdlType>, usize),
+    Defined(String),
+}
+
+impl TryFrom<Value> for IdlType {
+    type Error = IdlError;
+
+    fn try_from(value: Value) -> Result<Self, Self::Error> {
+        let invalid = || IdlError::InvalidIdl(format!("unsupported type {}", value));
+        match &value {
+            Value::String(name) => Ok(match name.as_str() {
+                "bool" => IdlType::Bool,
+                "u8" => IdlType::U8,
+                "i8" => IdlType::I8,
+                "u16" => IdlType::U16,
+                "i16" => IdlType::I16,
+                "u32" => IdlType::U32,
+                "i32" => IdlType::I32,
+                "f32" => IdlType::F32,
+                "u64" => IdlType::U64,
+                "i64" => IdlType::I64,
+                "f64" => IdlType::F64,
+                "u128" => IdlType::U128,
+                "i128" => IdlType::I128,
+                "u256" => IdlType::U256,
+                "i256" => IdlType::I256,
+                "bytes" => IdlType::Bytes,
+                "string" => IdlType::String,
+                "pubkey" | "publicKey" => IdlType::Pubkey,
+                _ => return Err(invalid()),
+            }),
+            Value::Object(object) if object.len() == 1 => {
+                let (kind, inner) = object.iter().next().ok_or_else(invalid)?;
+                match kind.as_str() {
+                    "option" | "coption" => {
+                        Ok(IdlType::Option(Box::new(inner.clone().try_into()?)))
+                    }
+                    "vec" => Ok(IdlType::Vec(Box::new(inner.clone().try_into()?))),
+                    "array" => match inner.as_array().map(Vec::as_slice) {
+                        Some([ty, Value::Number(len)]) => Ok(IdlType::Array(
+                            Box::new(ty.clone().try_into()?),
+                            len.as_u64().ok_or_else(invalid)? as usize,
+                        )),
+                        _ => Err(invalid()),
+                    },
+                    // `{"defined": "Name"}` (legacy) or `{"defined": {"name": "Name"}}` (0.30)
+                    "defined" => match inner {
+                        Value::String(name) => Ok(IdlType::Defined(name.clone())),
+                        Value::Object(defined) => match defined.get("generics") {
+                            Some(Value::Array(generics)) if !generics.is_empty() => Err(invalid()),
+                            _ => defined
+                                .get("name")
+                                .and_then(Value::as_str)
+                                .map(|name| IdlType::Defined(name.to_string()))
+                                .ok_or_else(invalid),
+                        },
+                        _ => Err(invalid()),
+                    },
+                    _ => Err(invalid()),
+                }
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl Idl {
+    /// Parses an IDL from its JSON representation
+    pub fn from_json(json: &str) -> Result<Self, IdlError> {
+        Ok(serde_json::from_str(json)?)
+    }
+
+    /// Program name
+    pub fn name(&self) -> &str {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.name.as_deref())
+            .or(self.name.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Program version the IDL was generated for
+    pub fn version(&self) -> &str {
+        self.metadata
+            .as_ref()
+            .and_then(|metadata| metadata.version.as_deref())
+            .or(self.version.as_deref())
+            .unwrap_or_default()
+    }
+
+    /// Program address, if the IDL states one
+    pub fn program_address(&self) -> Option<&str> {
+        self.address.as_deref().or(self
+            .metadata
+            .as_ref()
+            .and_then(|metadata| metadata.address.as_deref()))
+    }
+
+    /// Finds a type definition by name
+    pub fn type_def(&self, name: &str) -> Option<&IdlTypeDef> {
+        self.types.iter().find(|ty| ty.name == name)
+    }
+}
+
+impl IdlInstruction {
+    /// The instruction discriminator, derived from the name for legacy IDLs
+    pub fn discriminator(&self) -> Vec<u8> {
+        self.discriminator_bytes
+            .clone()
+            .unwrap_or_else(|| sighash("global", &to_snake_case(&self.name)))
+    }
+
+    /// Names of the accounts in the order they are passed, nested accounts flattened
+    pub fn account_names(&self) -> Vec<String> {
+        fn flatten(items: &[IdlInstructionAccountItem], names: &mut Vec<String>) {
+            for item in items {
+                match item {
+                    IdlInstructionAccountItem::Composite { accounts, .. } => {
+                        flatten(accounts, names)
+                    }
+                    IdlInstructionAccountItem::Single(account) => names.push(account.name.clone()),
+                }
+            }
+        }
+
+        let mut names = Vec::new();
+        flatten(&self.accounts, &mut names);
+        names
+    }
+}
+
+impl IdlAccount {
+    /// The account discriminator, derived from the name for legacy IDLs
+    pub fn discriminator(&self) -> Vec<u8> {
+        self.discriminator_bytes
+            .clone()
+            .unwrap_or_else(|| sighash("account", &self.name))
+    }
+}
+
+impl IdlEvent {
+    /// The event discriminator, derived from the name for legacy IDLs
+    pub fn discriminator(&self) -> Vec<u8> {
+        self.discriminator_bytes
+            .clone()
+            .unwrap_or_else(|| sighash("event", &self.name))
+    }
+}
+
+/// Anchor's discriminator: the first 8 bytes of `sha256("<namespace>:<name>")`
+pub fn sighash(namespace: &str, name: &str) -> Vec<u8> {
+    Sha256::digest(format!("{}:{}", namespace, name))[..8].to_vec()
+}
+
+/// Converts the camelCase names of legacy IDLs to the snake_case Anchor hashes
+pub fn to_snake_case(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut snake = String::with_capacity(name.len() + 4);
+    for (index, char) in chars.iter().enumerate() {
+        if char.is_uppercase() && index > 0 {
+            let previous = chars[index - 1];
+            let next_lowercase = chars.get(index + 1).is_some_and(|next| next.is_lowercase());
+            if previous != '_' && (!previous.is_uppercase() || next_lowercase) {
+                snake.push('_');
+            }
+        }
+        snake.extend(char.to_lowercase());
+    }
+    snake
+}
@@ -1,2 +1,4 @@
 pub mod extra;
+pub mod idl;
+pub mod logs;
 pub mod staratlas;
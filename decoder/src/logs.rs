@@ -0,0 +1,255 @@
+//! Parsing of transaction log messages
+//!
+//! The runtime logs every program invocation (`Program <id> invoke [<depth>]`), its compute
+//! units and its result. Programs add `Program log:` messages, Anchor programs among them the
+//! `Instruction: <Name>` marker and error details, and emit events as base64 `Program data:`.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+/// One program invocation with the logs written while it was the innermost running program
+#[derive(Debug, Clone, Default)]
+pub struct ProgramInvocation {
+    pub program_id: String,
+    /// 1 for transaction instructions, higher for cross-program invocations
+    pub depth: u32,
+    /// Index of the transaction instruction the invocation belongs to
+    pub instruction_index: usize,
+    /// Name from the Anchor `Instruction: <Name>` log
+    pub instruction_name: Option<String>,
+    /// `Program log:` messages
+    pub logs: Vec<String>,
+    /// Decoded `Program data:` payloads, e.g. Anchor events
+    pub data: Vec<Vec<u8>>,
+    /// Compute units consumed, including the invocations it made
+    pub compute_units_consumed: Option<u64>,
+    /// Compute units that were available to the invocation
+    pub compute_units_limit: Option<u64>,
+    /// Reason of a failed invocation, `None` if it succeeded or the logs are truncated
+    pub error: Option<String>,
+    /// Set if the logs end before the invocation's result
+    pub incomplete: bool,
+}
+
+/// Invocations of a transaction in the order they started
+#[derive(Debug, Clone, Default)]
+pub struct ParsedLogs {
+    pub invocations: Vec<ProgramInvocation>,
+    /// Set if the runtime truncated the logs, later invocations are missing
+    pub truncated: bool,
+}
+
+/// Parses the log messages of a transaction
+pub fn parse_logs(messages: &[String]) -> ParsedLogs {
+    let mut parsed = ParsedLogs::default();
+    // Indexes into `invocations` of the running invocations, innermost last
+    let mut stack: Vec<usize> = Vec::new();
+    let mut instructions = 0;
+
+    for message in messages {
+        if message == "Log truncated" {
+            parsed.truncated = true;
+            break;
+        }
+
+        if let Some(rest) = message.strip_prefix("Program log: ") {
+            if let Some(invocation) = stack.last().map(|&i| &mut parsed.invocations[i]) {
+                if invocation.instruction_name.is_none()
+                    && let Some(name) = rest.strip_prefix("Instruction: ")
+                {
+                    invocation.instruction_name = Some(name.to_string());
+                }
+                invocation.logs.push(rest.to_string());
+            }
+        } else if let Some(rest) = message.strip_prefix("Program data: ") {
+            if let Some(invocation) = stack.last().map(|&i| &mut parsed.invocations[i]) {
+                // `sol_log_data` writes one base64 chunk per field, Anchor events are a single one
+                let data = rest
+                    .split(' ')
+                    .filter_map(|chunk| STANDARD.decode(chunk).ok())
+                    .flatten()
+                    .collect();
+                invocation.data.push(data);
+            }
+        } else if let Some(rest) = message.strip_prefix("Program ") {
+            let (program_id, event) = rest.split_once(' ').unwrap_or((rest, ""));
+            if let Some(depth) = event
+                .strip_prefix("invoke [")
+                .and_then(|depth| depth.strip_suffix(']'))
+                .and_then(|depth| depth.parse().ok())
+            {
+                let instruction_index = match stack.last() {
+                    Some(&parent) => parsed.invocations[parent].instruction_index,
+                    None => {
+                        instructions += 1;
+                        instructions - 1
+                    }
+                };
+                stack.push(parsed.invocations.len());
+                parsed.invocations.push(ProgramInvocation {
+                    program_id: program_id.to_string(),
+                    depth,
+                    instruction_index,
+                    incomplete: true,
+                    ..Default::default()
+                });
+            } else if let Some(units) = event
+                .strip_prefix("consumed ")
+                .and_then(|units| units.strip_suffix(" compute units"))
+            {
+                if let Some(invocation) = stack.last().map(|&i| &mut parsed.invocations[i])
+                    && invocation.program_id == program_id
+                    && let Some((consumed, limit)) = units.split_once(" of ")
+                {
+                    invocation.compute_units_consumed = consumed.parse().ok();
+                    invocation.compute_units_limit = limit.parse().ok();
+                }
+            } else if event == "success" || event.starts_with("failed: ") {
+                if let Some(i) = stack.pop() {
+                    let invocation = &mut parsed.invocations[i];
+                    invocation.incomplete = false;
+                    invocation.error = event.strip_prefix("failed: ").map(ToString::to_string);
+                }
+            } else if let Some(invocation) = stack.last().map(|&i| &mut parsed.invocations[i]) {
+                // e.g. `Program return: <id> <data>` or `Program is not deployed`
+                invocation.logs.push(message.clone());
+            }
+        } else if let Some(invocation) = stack.last().map(|&i| &mut parsed.invocations[i]) {
+            invocation.logs.push(message.clone());
+        }
+    }
+
+    parsed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixture(logs: &str) -> Vec<String> {
+        logs.lines().map(ToString::to_string).collect()
+    }
+
+    #[test]
+    fn tracks_nested_invocations_by_depth() {
+        let parsed = parse_logs(&fixture(include_str!("../fixtures/logs/exchange.log")));
+
+        let invocations: Vec<_> = parsed
+            .invocations
+            .iter()
+            .map(|invocation| {
+                (
+                    &invocation.program_id[..5],
+                    invocation.depth,
+                    invocation.instruction_index,
+                    invocation.instruction_name.as_deref(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            invocations,
+            [
+                ("Compu", 1, 0, None),
+                ("trade", 1, 1, Some("ProcessExchange")),
+                ("Token", 2, 1, Some("TransferChecked")),
+                ("Token", 2, 1, Some("TransferChecked")),
+                ("hookL", 3, 1, Some("Execute")),
+            ]
+        );
+        assert!(!parsed.truncated);
+        assert!(
+            parsed
+                .invocations
+                .iter()
+                .all(|invocation| !invocation.incomplete && invocation.error.is_none())
+        );
+    }
+
+    #[test]
+    fn attributes_compute_units_to_the_innermost_invocation() {
+        let parsed = parse_logs(&fixture(include_str!("../fixtures/logs/exchange.log")));
+
+        let units: Vec<_> = parsed
+            .invocations
+            .iter()
+            .map(|invocation| {
+                (
+                    invocation.compute_units_consumed,
+                    invocation.compute_units_limit,
+                )
+            })
+            .collect();
+        assert_eq!(
+            units,
+            [
+                (None, None),
+                (Some(41000), Some(199850)),
+                (Some(6200), Some(180000)),
+                (Some(9800), Some(167000)),
+                (Some(2100), Some(160000)),
+            ]
+        );
+    }
+
+    #[test]
+    fn keeps_data_and_return_logs_with_the_running_invocation() {
+        let parsed = parse_logs(&fixture(include_str!("../fixtures/logs/exchange.log")));
+        let exchange = &parsed.invocations[1];
+
+        // One payload per `Program data:` line, its base64 chunks concatenated
+        assert_eq!(exchange.data, [vec![1, 2, 3, 4, 5]]);
+        assert_eq!(
+            exchange.logs,
+            [
+                "Instruction: ProcessExchange",
+                "Program return: traderDnaR5w6Tcoi3NFm53i48FTDNbGjBSZwWXDRrg AQ==",
+            ]
+        );
+        assert!(parsed.invocations[3].data.is_empty());
+    }
+
+    #[test]
+    fn records_the_error_of_failed_invocations() {
+        let parsed = parse_logs(&fixture(include_str!("../fixtures/logs/failed.log")));
+
+        let errors: Vec<_> = parsed
+            .invocations
+            .iter()
+            .map(|invocation| (invocation.depth, invocation.error.as_deref()))
+            .collect();
+        assert_eq!(
+            errors,
+            [
+                (1, Some("custom program error: 0x1")),
+                (2, Some("custom program error: 0x1")),
+            ]
+        );
+        assert_eq!(
+            parsed.invocations[1].logs,
+            ["Instruction: TransferChecked", "Error: insufficient funds"]
+        );
+        assert_eq!(parsed.invocations[0].compute_units_consumed, Some(24000));
+        assert!(
+            parsed
+                .invocations
+                .iter()
+                .all(|invocation| !invocation.incomplete)
+        );
+    }
+
+    #[test]
+    fn stops_at_truncated_logs() {
+        let parsed = parse_logs(&fixture(include_str!("../fixtures/logs/truncated.log")));
+
+        assert!(parsed.truncated);
+        assert_eq!(parsed.invocations.len(), 2);
+        // Nothing after the marker is applied, both invocations stay open without a result
+        assert!(
+            parsed
+                .invocations
+                .iter()
+                .all(|invocation| invocation.incomplete && invocation.error.is_none())
+        );
+        assert_eq!(parsed.invocations[1].logs, ["Instruction: Transfer"]);
+    }
+}
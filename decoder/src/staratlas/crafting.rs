@@ -0,0 +1,229 @@
+//! Star Atlas crafting program
+//!
+//! Like SAGE, the crafting program is decoded with runtime IDLs, see [`crate::idl`]. Players
+//! craft through SAGE, which invokes the crafting program for every step of a crafting process.
+
+use crate::idl::{DecodedIdlAccount, Idl, IdlReader, to_snake_case};
+use serde_json::Value;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+pub const ID: Pubkey = pubkey!("CRAFT2RPXPJWCEix4WpJST3E7NLf79GTqZUL75wngXo5");
+
+/// IDL type of the items a recipe account appends to its layout
+const RECIPE_ITEM_TYPE: &str = "RecipeInputsOutputs";
+
+/// Step of a crafting process a crafting instruction stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CraftingEventKind {
+    Created,
+    IngredientDeposited,
+    IngredientWithdrawn,
+    Started,
+    Stopped,
+    InputConsumed,
+    OutputClaimed,
+    IngredientReturned,
+    Cancelled,
+    Closed,
+}
+
+impl CraftingEventKind {
+    /// Classifies an instruction by its IDL name, snake_case or legacy camelCase
+    ///
+    /// Returns `None` for instructions that are not part of a crafting process (e.g. recipe
+    /// administration).
+    pub fn from_instruction(name: &str) -> Option<Self> {
+        let kind = match to_snake_case(name).as_str() {
+            "create_crafting_process" => Self::Created,
+            "deposit_crafting_ingredient" => Self::IngredientDeposited,
+            "withdraw_crafting_ingredient" => Self::IngredientWithdrawn,
+            "start_crafting_process" => Self::Started,
+            "stop_crafting_process" => Self::Stopped,
+            "burn_consumable_ingredient" => Self::InputConsumed,
+            "claim_recipe_output" => Self::OutputClaimed,
+            "claim_non_consumable_ingredient" => Self::IngredientReturned,
+            "cancel_crafting_process" => Self::Cancelled,
+            "close_crafting_process" => Self::Closed,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Created => "created",
+            Self::IngredientDeposited => "ingredient_deposited",
+            Self::IngredientWithdrawn => "ingredient_withdrawn",
+            Self::Started => "started",
+            Self::Stopped => "stopped",
+            Self::InputConsumed => "input_consumed",
+            Self::OutputClaimed => "output_claimed",
+            Self::IngredientReturned => "ingredient_returned",
+            Self::Cancelled => "cancelled",
+            Self::Closed => "closed",
+        }
+    }
+}
+
+/// An input or output of a recipe
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RecipeItem {
+    pub mint: Pubkey,
+    /// Amount per crafted unit, in base units
+    pub amount: u64,
+}
+
+/// Inputs and outputs of a recipe account
+#[derive(Debug, Clone, Default)]
+pub struct RecipeItems {
+    /// Inputs burned by crafting
+    pub consumables: Vec<RecipeItem>,
+    /// Inputs returned after crafting (e.g. crew or tools)
+    pub non_consumables: Vec<RecipeItem>,
+    pub outputs: Vec<RecipeItem>,
+}
+
+/// Reads the inputs and outputs a recipe account appends to its IDL layout
+///
+/// The items follow the recipe in the order consumables, non-consumables, outputs, with the
+/// counts given in the recipe, each laid out as the `RecipeInputsOutputs` type of the IDL the
+/// account was decoded with. Returns `None` if the account is not a recipe, the IDL does not
+/// define the item type or the appended data is shorter than the counts require.
+pub fn recipe_items(idl: &Idl, account: &DecodedIdlAccount) -> Option<RecipeItems> {
+    if to_snake_case(&account.name) != "recipe" {
+        return None;
+    }
+    let count = |name: &str| {
+        account
+            .field(name)
+            .and_then(|count| count.as_u64())
+            .map(|count| count as usize)
+    };
+    let consumables = count("consumables_count")?;
+    let non_consumables = count("non_consumables_count")?;
+    let outputs = count("outputs_count")?;
+
+    let mut reader = IdlReader::new(idl, &account.remaining);
+    let mut read = |count: usize| {
+        (0..count)
+            .map(|_| {
+                let item = reader.read_defined(RECIPE_ITEM_TYPE).ok()?;
+                Some(RecipeItem {
+                    mint: item
+                        .get("mint")
+                        .and_then(Value::as_str)
+                        .and_then(|mint| Pubkey::from_str(mint).ok())?,
+                    amount: item.get("amount").and_then(Value::as_u64)?,
+                })
+            })
+            .collect::<Option<Vec<_>>>()
+    };
+
+    Some(RecipeItems {
+        consumables: read(consumables)?,
+        non_consumables: read(non_consumables)?,
+        outputs: read(outputs)?,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::{IdlRegistry, sighash};
+    use std::collections::HashSet;
+
+    fn registry() -> IdlRegistry {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        registry
+    }
+
+    /// Recipe account data: the IDL layout, the items and the padding of the preallocated account
+    fn recipe(counts: [u8; 3], items: &[(Pubkey, u64)]) -> Vec<u8> {
+        let mut data = sighash("account", "Recipe");
+        data.push(1);
+        for _ in 0..3 {
+            data.extend(Pubkey::new_unique().to_bytes());
+        }
+        data.extend(600i64.to_le_bytes());
+        data.extend(0i64.to_le_bytes());
+        data.extend([0; 32]);
+        data.push(1);
+        data.extend(10u64.to_le_bytes());
+        data.extend(Pubkey::new_unique().to_bytes());
+        data.extend(42u64.to_le_bytes());
+        data.extend(0u64.to_le_bytes());
+        data.extend(0u64.to_le_bytes());
+        data.extend(counts);
+        data.extend((items.len() as u16).to_le_bytes());
+        data.push(255);
+        for (mint, amount) in items {
+            data.extend(mint.to_bytes());
+            data.extend(amount.to_le_bytes());
+        }
+        data.extend([0; 16]);
+        data
+    }
+
+    #[test]
+    fn decodes_recipe_items_in_role_order() {
+        let registry = registry();
+        let idl = registry
+            .idl(&ID, 0)
+            .expect("crafting IDL in idls/registry.json");
+        let mints: Vec<_> = (0..4).map(|_| Pubkey::new_unique()).collect();
+        let data = recipe(
+            [2, 1, 1],
+            &[
+                (mints[0], 5),
+                (mints[1], 1_000_000),
+                (mints[2], 1),
+                (mints[3], 3),
+            ],
+        );
+
+        let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+        assert_eq!(account.field("duration"), Some(&600.into()));
+        assert_eq!(account.field("status"), Some(&"Active".into()));
+        assert_eq!(account.field("usage_count"), Some(&42.into()));
+
+        let items = recipe_items(idl, &account).unwrap();
+        let item = |mint: Pubkey, amount: u64| RecipeItem { mint, amount };
+        assert_eq!(
+            items.consumables,
+            [item(mints[0], 5), item(mints[1], 1_000_000)]
+        );
+        assert_eq!(items.non_consumables, [item(mints[2], 1)]);
+        assert_eq!(items.outputs, [item(mints[3], 3)]);
+    }
+
+    #[test]
+    fn rejects_recipes_with_fewer_items_than_counted() {
+        let registry = registry();
+        let idl = registry.idl(&ID, 0).unwrap();
+        let mut data = recipe([1, 0, 1], &[(Pubkey::new_unique(), 1)]);
+        // Drop the padding, the second item is missing
+        data.truncate(data.len() - 16);
+
+        let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+        assert!(recipe_items(idl, &account).is_none());
+    }
+
+    #[test]
+    fn shipped_idl_defines_every_process_step() {
+        let registry = registry();
+        let idl = registry.idl(&ID, 0).unwrap();
+
+        let kinds: HashSet<_> = idl
+            .instructions
+            .iter()
+            .filter_map(|instruction| CraftingEventKind::from_instruction(&instruction.name))
+            .map(|kind| kind.as_str())
+            .collect();
+        assert_eq!(kinds.len(), 10);
+    }
+}
@@ -2,3 +2,5 @@ use anchor_decoder::anchor_idl;
 
 #[anchor_idl("./marketplace_0.30.0.json")]
 pub const ID: Pubkey = crate::ID;
+
+pub mod accounts;
@@ -0,0 +1,232 @@
+//! Decoding of the marketplace program accounts
+//!
+//! Every account starts with the 8 byte Anchor discriminator of its type, which is checked
+//! before the data is decoded. Accounts may be allocated larger than their data, so
+//! trailing bytes are ignored.
+
+use borsh::{BorshDeserialize, BorshSerialize};
+use solana_sdk::pubkey::Pubkey;
+
+pub use super::{
+    AtlasRateAccount, FeeReduction, MarketVars, OpenOrdersCounter, OrderAccount, OrderSide,
+    RoyaltyTier,
+};
+
+/// Account type with an Anchor discriminator
+pub trait AccountDiscriminator: BorshDeserialize {
+    /// First 8 bytes of every account of the type
+    const DISCRIMINATOR: [u8; 8];
+
+    /// Decodes an account of the type, or returns None if it is of another type or invalid
+    fn decode_account(data: &[u8]) -> Option<Self> {
+        if data.len() < 8 || data[..8] != Self::DISCRIMINATOR {
+            return None;
+        }
+        Self::deserialize(&mut &data[8..]).ok()
+    }
+}
+
+/// Currency registered with the marketplace
+///
+/// Replaces the type generated from the IDL, which cannot decode the royalty tiers.
+#[derive(Debug, BorshSerialize, BorshDeserialize)]
+pub struct RegisteredCurrency {
+    pub token_mint: Pubkey,
+    pub sa_currency_vault: Pubkey,
+    pub royalty: u64,
+    pub bump: u8,
+    pub royalty_tiers: Vec<RoyaltyTier>,
+}
+
+impl AccountDiscriminator for AtlasRateAccount {
+    const DISCRIMINATOR: [u8; 8] = [246, 171, 232, 144, 218, 236, 33, 161];
+}
+
+impl AccountDiscriminator for FeeReduction {
+    const DISCRIMINATOR: [u8; 8] = [187, 248, 181, 2, 183, 165, 66, 175];
+}
+
+impl AccountDiscriminator for MarketVars {
+    const DISCRIMINATOR: [u8; 8] = [255, 142, 134, 25, 56, 1, 219, 124];
+}
+
+impl AccountDiscriminator for OpenOrdersCounter {
+    const DISCRIMINATOR: [u8; 8] = [245, 112, 49, 129, 46, 33, 183, 73];
+}
+
+impl AccountDiscriminator for OrderAccount {
+    const DISCRIMINATOR: [u8; 8] = [79, 67, 112, 155, 214, 14, 32, 55];
+}
+
+impl AccountDiscriminator for RegisteredCurrency {
+    const DISCRIMINATOR: [u8; 8] = [60, 114, 244, 134, 16, 166, 51, 149];
+}
+
+/// Decoded marketplace account
+#[derive(Debug)]
+pub enum MarketplaceAccount {
+    AtlasRateAccount(AtlasRateAccount),
+    FeeReduction(FeeReduction),
+    MarketVars(MarketVars),
+    OpenOrdersCounter(OpenOrdersCounter),
+    OrderAccount(OrderAccount),
+    RegisteredCurrency(RegisteredCurrency),
+}
+
+/// Decodes a marketplace account of any type
+///
+/// Returns None if the discriminator is unknown or the data does not match its type.
+pub fn decode_account(data: &[u8]) -> Option<MarketplaceAccount> {
+    if data.len() < 8 {
+        return None;
+    }
+
+    match <[u8; 8]>::try_from(&data[..8]).ok()? {
+        AtlasRateAccount::DISCRIMINATOR => {
+            AtlasRateAccount::decode_account(data).map(MarketplaceAccount::AtlasRateAccount)
+        }
+        FeeReduction::DISCRIMINATOR => {
+            FeeReduction::decode_account(data).map(MarketplaceAccount::FeeReduction)
+        }
+        MarketVars::DISCRIMINATOR => {
+            MarketVars::decode_account(data).map(MarketplaceAccount::MarketVars)
+        }
+        OpenOrdersCounter::DISCRIMINATOR => {
+            OpenOrdersCounter::decode_account(data).map(MarketplaceAccount::OpenOrdersCounter)
+        }
+        OrderAccount::DISCRIMINATOR => {
+            OrderAccount::decode_account(data).map(MarketplaceAccount::OrderAccount)
+        }
+        RegisteredCurrency::DISCRIMINATOR => {
+            RegisteredCurrency::decode_account(data).map(MarketplaceAccount::RegisteredCurrency)
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::sighash;
+
+    /// Returns the data of an account of a type, with trailing padding
+    fn account_data<T: AccountDiscriminator + BorshSerialize>(account: &T) -> Vec<u8> {
+        let mut data = T::DISCRIMINATOR.to_vec();
+        data.extend(account.try_to_vec().unwrap());
+        data.extend([0; 16]);
+        data
+    }
+
+    fn order() -> OrderAccount {
+        OrderAccount {
+            order_initializer_pubkey: Pubkey::new_unique(),
+            currency_mint: Pubkey::new_unique(),
+            asset_mint: Pubkey::new_unique(),
+            initializer_currency_token_account: Pubkey::new_unique(),
+            initializer_asset_token_account: Pubkey::new_unique(),
+            order_side: OrderSide::Sell,
+            price: 1_500_000,
+            order_origination_qty: 10,
+            order_remaining_qty: 4,
+            created_at_timestamp: 1_700_000_000,
+        }
+    }
+
+    #[test]
+    fn discriminators_are_the_anchor_account_hashes() {
+        for (name, discriminator) in [
+            ("AtlasRateAccount", AtlasRateAccount::DISCRIMINATOR),
+            ("FeeReduction", FeeReduction::DISCRIMINATOR),
+            ("MarketVars", MarketVars::DISCRIMINATOR),
+            ("OpenOrdersCounter", OpenOrdersCounter::DISCRIMINATOR),
+            ("OrderAccount", OrderAccount::DISCRIMINATOR),
+            ("RegisteredCurrency", RegisteredCurrency::DISCRIMINATOR),
+        ] {
+            assert_eq!(sighash("account", name), discriminator, "{}", name);
+        }
+    }
+
+    #[test]
+    fn decodes_an_order_ignoring_trailing_bytes() {
+        let order = order();
+
+        let decoded = OrderAccount::decode_account(&account_data(&order)).unwrap();
+
+        assert_eq!(decoded.asset_mint, order.asset_mint);
+        assert!(matches!(decoded.order_side, OrderSide::Sell));
+        assert_eq!(decoded.price, 1_500_000);
+        assert_eq!(decoded.order_remaining_qty, 4);
+        assert_eq!(decoded.created_at_timestamp, 1_700_000_000);
+    }
+
+    #[test]
+    fn decodes_registered_currencies_with_royalty_tiers() {
+        let currency = RegisteredCurrency {
+            token_mint: Pubkey::new_unique(),
+            sa_currency_vault: Pubkey::new_unique(),
+            royalty: 60_000,
+            bump: 254,
+            royalty_tiers: vec![
+                RoyaltyTier {
+                    stake_amount: 1_000,
+                    discount: 100_000,
+                },
+                RoyaltyTier {
+                    stake_amount: 10_000,
+                    discount: 250_000,
+                },
+            ],
+        };
+
+        let Some(MarketplaceAccount::RegisteredCurrency(decoded)) =
+            decode_account(&account_data(&currency))
+        else {
+            panic!("not decoded as a registered currency");
+        };
+
+        assert_eq!(decoded.token_mint, currency.token_mint);
+        assert_eq!(decoded.royalty, 60_000);
+        assert_eq!(decoded.royalty_tiers.len(), 2);
+        assert_eq!(decoded.royalty_tiers[1].discount, 250_000);
+    }
+
+    #[test]
+    fn decodes_accounts_by_their_discriminator() {
+        let counter = OpenOrdersCounter {
+            open_order_count: 3,
+            bump: 255,
+        };
+
+        assert!(matches!(
+            decode_account(&account_data(&order())),
+            Some(MarketplaceAccount::OrderAccount(_))
+        ));
+        assert!(matches!(
+            decode_account(&account_data(&counter)),
+            Some(MarketplaceAccount::OpenOrdersCounter(OpenOrdersCounter {
+                open_order_count: 3,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn rejects_other_and_truncated_accounts() {
+        let mut other = account_data(&order());
+        other[..8].copy_from_slice(&MarketVars::DISCRIMINATOR);
+        let mut unknown = account_data(&order());
+        unknown[0] ^= 1;
+        let truncated = &account_data(&order())[..40];
+
+        // The discriminator decides the type, whatever the data would decode as
+        assert!(OrderAccount::decode_account(&other).is_none());
+        assert!(matches!(
+            decode_account(&other),
+            Some(MarketplaceAccount::MarketVars(_))
+        ));
+        assert!(decode_account(&unknown).is_none());
+        assert!(decode_account(truncated).is_none());
+        assert!(decode_account(&OrderAccount::DISCRIMINATOR[..4]).is_none());
+        assert!(FeeReduction::decode_account(&account_data(&order())).is_none());
+    }
+}
@@ -1,2 +1,7 @@
 pub mod buddy;
+pub mod crafting;
 pub mod marketplace;
+pub mod player_profile;
+pub mod profile_faction;
+pub mod sage;
+pub mod score;
@@ -0,0 +1,201 @@
+//! Star Atlas Player Profile program
+//!
+//! Profiles are the identity players act with in SAGE and crafting. A profile holds a list of
+//! keys, the first of which are the auth keys (the player's wallets), and may have a
+//! `PlayerName` account with the username. Decoded with runtime IDLs, see [`crate::idl`].
+
+use crate::idl::{DecodedIdlAccount, Idl, IdlReader, to_snake_case};
+use serde_json::Value;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+pub const ID: Pubkey = pubkey!("pprofELXjL5Kck7Jn5hCpwAn7WMHoVkc3uP9wAeMhbG");
+
+/// IDL type of the keys a profile account appends to its layout
+const PROFILE_KEY_TYPE: &str = "ProfileKey";
+
+/// A key allowed to act for a profile
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileKey {
+    pub key: Pubkey,
+    /// Program the key may be used with, the profile program itself for auth keys
+    pub scope: Pubkey,
+    /// Unix timestamp the key expires at, negative if it never does
+    pub expire_time: i64,
+    pub permissions: [u8; 8],
+    /// Auth keys own the profile and can change its keys
+    pub is_auth: bool,
+}
+
+/// Reads the keys a profile account appends to its IDL layout
+///
+/// The keys fill the rest of the account, auth keys first, each laid out as the `ProfileKey`
+/// type of the IDL the account was decoded with. Returns `None` if the account is not a
+/// profile, the IDL does not define the key type or the keys do not fill the account.
+pub fn profile_keys(idl: &Idl, account: &DecodedIdlAccount) -> Option<Vec<ProfileKey>> {
+    if to_snake_case(&account.name) != "profile" {
+        return None;
+    }
+    let auth_key_count = account.field("auth_key_count")?.as_u64()? as usize;
+
+    let mut reader = IdlReader::new(idl, &account.remaining);
+    let mut keys = vec![];
+    while !reader.remaining().is_empty() {
+        let key = reader.read_defined(PROFILE_KEY_TYPE).ok()?;
+        let field = |name: &str| key.get(name);
+        let pubkey = |name: &str| Pubkey::from_str(field(name)?.as_str()?).ok();
+        let permissions = field("permissions")?
+            .as_array()?
+            .iter()
+            .map(|byte| u8::try_from(byte.as_u64()?).ok())
+            .collect::<Option<Vec<_>>>()?;
+
+        keys.push(ProfileKey {
+            key: pubkey("key")?,
+            scope: pubkey("scope")?,
+            expire_time: field("expire_time")
+                .or_else(|| field("expireTime"))
+                .and_then(Value::as_i64)?,
+            permissions: permissions.try_into().ok()?,
+            is_auth: keys.len() < auth_key_count,
+        });
+    }
+
+    Some(keys)
+}
+
+/// Reads the profile and the name of a player name account
+///
+/// The name is appended to the IDL layout as UTF-8. Returns `None` if the account is not a
+/// player name or the name is not valid UTF-8.
+pub fn player_name(account: &DecodedIdlAccount) -> Option<(Pubkey, String)> {
+    if to_snake_case(&account.name) != "player_name" {
+        return None;
+    }
+    let profile = Pubkey::from_str(account.field("profile")?.as_str()?).ok()?;
+    let name = std::str::from_utf8(&account.remaining).ok()?;
+
+    Some((profile, name.trim_end_matches('\0').to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::{IdlRegistry, sighash};
+
+    fn registry() -> IdlRegistry {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        registry
+    }
+
+    fn key(key: Pubkey, scope: Pubkey, expire_time: i64, permissions: [u8; 8]) -> Vec<u8> {
+        [
+            key.to_bytes().as_slice(),
+            &scope.to_bytes(),
+            &expire_time.to_le_bytes(),
+            &permissions,
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn reads_profile_keys_auth_keys_first() {
+        let registry = registry();
+        let idl = registry
+            .idl(&ID, 0)
+            .expect("Player Profile IDL in idls/registry.json");
+        let (wallet, hot_wallet, sage) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            crate::staratlas::sage::ID,
+        );
+        let mut data = sighash("account", "Profile");
+        data.push(1);
+        data.extend(1u16.to_le_bytes());
+        data.extend(1u16.to_le_bytes());
+        data.extend(7u64.to_le_bytes());
+        data.extend(1_700_000_000i64.to_le_bytes());
+        data.extend(key(wallet, ID, -1, [0xff; 8]));
+        data.extend(key(
+            hot_wallet,
+            sage,
+            1_800_000_000,
+            [1, 0, 0, 0, 0, 0, 0, 0],
+        ));
+
+        let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+        assert_eq!(account.field("auth_key_count"), Some(&1.into()));
+        assert_eq!(account.field("created_at"), Some(&1_700_000_000.into()));
+
+        assert_eq!(
+            profile_keys(idl, &account).unwrap(),
+            [
+                ProfileKey {
+                    key: wallet,
+                    scope: ID,
+                    expire_time: -1,
+                    permissions: [0xff; 8],
+                    is_auth: true,
+                },
+                ProfileKey {
+                    key: hot_wallet,
+                    scope: sage,
+                    expire_time: 1_800_000_000,
+                    permissions: [1, 0, 0, 0, 0, 0, 0, 0],
+                    is_auth: false,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_profiles_with_a_partial_key() {
+        let registry = registry();
+        let idl = registry.idl(&ID, 0).unwrap();
+        let mut data = sighash("account", "Profile");
+        data.extend([1, 1, 0, 1, 0]);
+        data.extend([0; 16]);
+        data.extend(key(Pubkey::new_unique(), ID, -1, [0; 8]));
+        data.extend([0; 10]);
+
+        let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+        assert!(profile_keys(idl, &account).is_none());
+    }
+
+    #[test]
+    fn reads_the_name_appended_to_a_player_name() {
+        let registry = registry();
+        let profile = Pubkey::new_unique();
+        let mut data = sighash("account", "PlayerName");
+        data.push(1);
+        data.extend(profile.to_bytes());
+        data.push(254);
+        data.extend("Zwerg 🚀".as_bytes());
+
+        let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+        assert_eq!(
+            player_name(&account),
+            Some((profile, "Zwerg 🚀".to_string()))
+        );
+
+        // Not a profile
+        assert!(profile_keys(registry.idl(&ID, 0).unwrap(), &account).is_none());
+    }
+
+    #[test]
+    fn rejects_names_that_are_not_utf8() {
+        let registry = registry();
+        let mut data = sighash("account", "PlayerName");
+        data.push(1);
+        data.extend(Pubkey::new_unique().to_bytes());
+        data.push(254);
+        data.extend([0xff, 0xfe]);
+
+        let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+        assert!(player_name(&account).is_none());
+    }
+}
@@ -0,0 +1,99 @@
+//! Star Atlas Profile Faction program
+//!
+//! A profile joins a faction by creating its faction account, the choice is final. Decoded with
+//! runtime IDLs, see [`crate::idl`].
+
+use crate::idl::{DecodedIdlAccount, to_snake_case};
+use serde_json::Value;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+pub const ID: Pubkey = pubkey!("pFACSRuobDmvfMKq1bAzwj27t6d2GJhSCHb1VcfnRmq");
+
+/// Faction of a profile
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Faction {
+    Unaligned,
+    Mud,
+    Oni,
+    Ustur,
+}
+
+impl Faction {
+    /// Reads the faction from its IDL value, an enum variant name or its index
+    pub fn from_value(value: &Value) -> Option<Self> {
+        if let Some(index) = value.as_u64() {
+            return match index {
+                0 => Some(Self::Unaligned),
+                1 => Some(Self::Mud),
+                2 => Some(Self::Oni),
+                3 => Some(Self::Ustur),
+                _ => None,
+            };
+        }
+        match value.as_str()?.to_ascii_lowercase().as_str() {
+            "unaligned" => Some(Self::Unaligned),
+            "mud" => Some(Self::Mud),
+            "oni" => Some(Self::Oni),
+            "ustur" => Some(Self::Ustur),
+            _ => None,
+        }
+    }
+
+    /// Name the faction is stored with
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Unaligned => "UNALIGNED",
+            Self::Mud => "MUD",
+            Self::Oni => "ONI",
+            Self::Ustur => "USTUR",
+        }
+    }
+}
+
+/// Reads the profile and the faction of a profile faction account
+///
+/// Returns `None` if the account is not a profile faction account or the faction is unknown.
+pub fn profile_faction(account: &DecodedIdlAccount) -> Option<(Pubkey, Faction)> {
+    if to_snake_case(&account.name) != "profile_faction_account" {
+        return None;
+    }
+    let profile = Pubkey::from_str(account.field("profile")?.as_str()?).ok()?;
+    let faction = Faction::from_value(account.field("faction")?)?;
+
+    Some((profile, faction))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::{IdlRegistry, sighash};
+
+    #[test]
+    fn reads_the_faction_of_a_profile() {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        let profile = Pubkey::new_unique();
+
+        for (index, faction) in [(1, Faction::Mud), (2, Faction::Oni), (3, Faction::Ustur)] {
+            let mut data = sighash("account", "ProfileFactionAccount");
+            data.push(0);
+            data.extend(profile.to_bytes());
+            data.extend([index, 255]);
+
+            let account = registry.decode_account(&ID, 0, &data).unwrap().unwrap();
+            assert_eq!(profile_faction(&account), Some((profile, faction)));
+        }
+    }
+
+    #[test]
+    fn reads_factions_by_name_or_index() {
+        assert_eq!(Faction::from_value(&"MUD".into()), Some(Faction::Mud));
+        assert_eq!(Faction::from_value(&"ustur".into()), Some(Faction::Ustur));
+        assert_eq!(Faction::from_value(&2.into()), Some(Faction::Oni));
+        assert_eq!(Faction::from_value(&4.into()), None);
+    }
+}
@@ -0,0 +1,110 @@
+//! Star Atlas SAGE program
+//!
+//! SAGE is upgraded frequently, its instructions are decoded with runtime IDLs, see
+//! [`crate::idl`]. This module names the instructions the processor records as fleet activity.
+
+use crate::idl::to_snake_case;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("SAGE2HAwep459SNq61LHvjxPk4pLPEJLoMETef7f7EE");
+
+/// Fleet activity a SAGE instruction stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SageActivityKind {
+    FleetCreated,
+    FleetDisbanded,
+    Warp,
+    SubwarpStart,
+    SubwarpStop,
+    MiningStart,
+    MiningStop,
+    CargoDeposit,
+    CargoWithdraw,
+    StarbaseDock,
+    StarbaseUndock,
+    StarbaseUpkeep,
+}
+
+impl SageActivityKind {
+    /// Classifies an instruction by its IDL name, snake_case or legacy camelCase
+    ///
+    /// Returns `None` for instructions that are not fleet activity (e.g. admin instructions).
+    pub fn from_instruction(name: &str) -> Option<Self> {
+        let kind = match to_snake_case(name).as_str() {
+            "create_fleet" => Self::FleetCreated,
+            "disband_fleet" => Self::FleetDisbanded,
+            "warp_to_coordinate" | "warp_lane" => Self::Warp,
+            "start_subwarp" => Self::SubwarpStart,
+            "stop_subwarp" => Self::SubwarpStop,
+            "start_mining_asteroid" => Self::MiningStart,
+            "stop_mining_asteroid" => Self::MiningStop,
+            "deposit_cargo_to_fleet" | "deposit_cargo_to_game" => Self::CargoDeposit,
+            "withdraw_cargo_from_fleet" | "withdraw_cargo_from_game" => Self::CargoWithdraw,
+            "idle_to_loading_bay" => Self::StarbaseDock,
+            "loading_bay_to_idle" => Self::StarbaseUndock,
+            "deposit_starbase_upkeep_resource" => Self::StarbaseUpkeep,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::FleetCreated => "fleet_created",
+            Self::FleetDisbanded => "fleet_disbanded",
+            Self::Warp => "warp",
+            Self::SubwarpStart => "subwarp_start",
+            Self::SubwarpStop => "subwarp_stop",
+            Self::MiningStart => "mining_start",
+            Self::MiningStop => "mining_stop",
+            Self::CargoDeposit => "cargo_deposit",
+            Self::CargoWithdraw => "cargo_withdraw",
+            Self::StarbaseDock => "starbase_dock",
+            Self::StarbaseUndock => "starbase_undock",
+            Self::StarbaseUpkeep => "starbase_upkeep",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::IdlRegistry;
+    use std::collections::HashSet;
+
+    #[test]
+    fn shipped_idl_defines_every_activity_instruction() {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        let idl = registry
+            .idl(&ID, 0)
+            .expect("SAGE IDL in idls/registry.json");
+
+        let kinds: HashSet<_> = idl
+            .instructions
+            .iter()
+            .filter_map(|instruction| SageActivityKind::from_instruction(&instruction.name))
+            .map(|kind| kind.as_str())
+            .collect();
+        assert_eq!(kinds.len(), 12);
+
+        // The processor reads the fleet owner from one of these accounts
+        for instruction in &idl.instructions {
+            let accounts: Vec<_> = instruction
+                .account_names()
+                .iter()
+                .map(|name| to_snake_case(name))
+                .collect();
+            assert!(
+                ["owning_profile", "player_profile", "profile"]
+                    .iter()
+                    .any(|name| accounts.iter().any(|account| account == name)),
+                "{} has no profile account",
+                instruction.name
+            );
+        }
+    }
+}
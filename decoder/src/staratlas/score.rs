@@ -0,0 +1,150 @@
+//! Star Atlas SCORE (fleet staking) program
+//!
+//! Players stake ships, keep them supplied with food, fuel, ammo and toolkits, and claim ATLAS
+//! rewards for the time they were supplied. Decoded with runtime IDLs, see [`crate::idl`].
+
+use crate::idl::to_snake_case;
+use solana_sdk::pubkey;
+use solana_sdk::pubkey::Pubkey;
+
+pub const ID: Pubkey = pubkey!("FLEET1qqzpexyaDpqb2DGsSzE2sDCizewCg9WjrA6DU");
+
+/// Mint of the ATLAS rewards
+pub const ATLAS_MINT: Pubkey = pubkey!("ATLASXmbPQxBUYbxPsV97usA3fPQYEqzQBUHgiFCUsXx");
+
+/// Decimals of ATLAS
+pub const ATLAS_DECIMALS: u8 = 8;
+
+/// Resource a staked ship consumes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreResource {
+    Food,
+    Fuel,
+    Ammo,
+    Toolkit,
+}
+
+impl ScoreResource {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Food => "food",
+            Self::Fuel => "fuel",
+            Self::Ammo => "ammo",
+            Self::Toolkit => "toolkit",
+        }
+    }
+
+    /// Names of the instruction account holding the resource mint
+    pub fn mint_accounts(&self) -> &'static [&'static str] {
+        match self {
+            Self::Food => &["food_mint"],
+            Self::Fuel => &["fuel_mint"],
+            Self::Ammo => &["arms_mint", "ammo_mint"],
+            Self::Toolkit => &["toolkit_mint"],
+        }
+    }
+}
+
+/// Staking step a SCORE instruction stands for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScoreEventKind {
+    Stake,
+    Unstake,
+    Resupply(ScoreResource),
+    ResupplyWithdrawn(ScoreResource),
+    Claim,
+}
+
+impl ScoreEventKind {
+    /// Classifies an instruction by its IDL name, snake_case or legacy camelCase
+    ///
+    /// Returns `None` for instructions that move no ships, resources or rewards (e.g.
+    /// settling or admin instructions).
+    pub fn from_instruction(name: &str) -> Option<Self> {
+        let kind = match to_snake_case(name).as_str() {
+            "process_initial_deposit" | "process_partial_deposit" => Self::Stake,
+            "process_withdraw_ships" => Self::Unstake,
+            "process_refeed" => Self::Resupply(ScoreResource::Food),
+            "process_refuel" => Self::Resupply(ScoreResource::Fuel),
+            "process_rearm" => Self::Resupply(ScoreResource::Ammo),
+            "process_repair" => Self::Resupply(ScoreResource::Toolkit),
+            "process_withdraw_food" => Self::ResupplyWithdrawn(ScoreResource::Food),
+            "process_withdraw_fuel" => Self::ResupplyWithdrawn(ScoreResource::Fuel),
+            "process_withdraw_arms" => Self::ResupplyWithdrawn(ScoreResource::Ammo),
+            "process_harvest" => Self::Claim,
+            _ => return None,
+        };
+        Some(kind)
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Stake => "stake",
+            Self::Unstake => "unstake",
+            Self::Resupply(_) => "resupply",
+            Self::ResupplyWithdrawn(_) => "resupply_withdrawn",
+            Self::Claim => "claim",
+        }
+    }
+
+    /// Resource moved by a resupply or its withdrawal
+    pub fn resource(&self) -> Option<ScoreResource> {
+        match self {
+            Self::Resupply(resource) | Self::ResupplyWithdrawn(resource) => Some(*resource),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::idl::IdlRegistry;
+    use std::collections::HashSet;
+
+    #[test]
+    fn shipped_idl_defines_every_staking_step() {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        let idl = registry
+            .idl(&ID, 0)
+            .expect("SCORE IDL in idls/registry.json");
+
+        let kinds: Vec<_> = idl
+            .instructions
+            .iter()
+            .filter_map(|instruction| ScoreEventKind::from_instruction(&instruction.name))
+            .collect();
+        let resources: HashSet<_> = kinds
+            .iter()
+            .filter_map(|kind| kind.resource())
+            .map(|resource| resource.as_str())
+            .collect();
+        assert_eq!(kinds.len(), 11);
+        assert_eq!(resources.len(), 4);
+
+        // Resupplies name the resource mint account
+        for instruction in &idl.instructions {
+            if let Some(resource) =
+                ScoreEventKind::from_instruction(&instruction.name).and_then(|kind| kind.resource())
+            {
+                let accounts: Vec<_> = instruction
+                    .account_names()
+                    .iter()
+                    .map(|name| to_snake_case(name))
+                    .collect();
+                assert!(
+                    resource
+                        .mint_accounts()
+                        .iter()
+                        .any(|mint| accounts.iter().any(|account| account == mint)),
+                    "{} has no {} mint account",
+                    instruction.name,
+                    resource.as_str()
+                );
+            }
+        }
+    }
+}
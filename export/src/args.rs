@@ -0,0 +1,59 @@
+use chrono::{DateTime, Utc};
+use clap::Parser;
+use export::ExportFormat;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Export Star Atlas exchanges as CSV, NDJSON or Parquet", long_about = None)]
+pub struct Args {
+    /// Format of the export (csv, ndjson or parquet), taken from the output extension if not set (csv for standard output)
+    #[arg(short, long)]
+    pub format: Option<ExportFormat>,
+
+    /// File to write to, standard output if not set
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Number of exchanges fetched and encoded at once
+    #[arg(long, default_value_t = export::DEFAULT_BATCH_SIZE)]
+    pub batch_size: i64,
+
+    /// Buyer wallet address
+    #[arg(long)]
+    pub buyer: Option<String>,
+
+    /// Seller wallet address
+    #[arg(long)]
+    pub seller: Option<String>,
+
+    /// Wallet address of either the buyer or the seller
+    #[arg(long)]
+    pub wallet: Option<String>,
+
+    /// Asset mint address
+    #[arg(long)]
+    pub asset: Option<String>,
+
+    /// Pair mint address
+    #[arg(long)]
+    pub pair: Option<String>,
+
+    /// Side (buy/sell)
+    #[arg(long)]
+    pub side: Option<String>,
+
+    /// Earliest block time (RFC 3339, inclusive)
+    #[arg(long)]
+    pub from_timestamp: Option<DateTime<Utc>>,
+
+    /// Latest block time (RFC 3339, inclusive)
+    #[arg(long)]
+    pub to_timestamp: Option<DateTime<Utc>>,
+
+    /// Lowest slot (inclusive)
+    #[arg(long)]
+    pub from_slot: Option<i32>,
+
+    /// Highest slot (inclusive)
+    #[arg(long)]
+    pub to_slot: Option<i32>,
+}
@@ -0,0 +1,498 @@
+//! Streaming exports of Star Atlas exchanges as CSV, NDJSON or Parquet
+//!
+//! Exchanges are read in batches from a server-side cursor (`db::ExchangeExport`) and
+//! encoded batch by batch, so an export of the full history never holds more than one batch
+//! in memory. Used by the export endpoint of the API and by the `export` CLI.
+
+use std::io::Write;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use arrow_array::{
+    ArrayRef, Float64Array, Int32Array, RecordBatch, StringArray, TimestampMicrosecondArray,
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::{DateTime, Utc};
+use db::{ExchangeDetailed, ExchangeExport};
+use futures_util::{Stream, stream};
+use parquet::arrow::ArrowWriter;
+use parquet::basic::Compression;
+use parquet::file::properties::WriterProperties;
+use serde::Serialize;
+use thiserror::Error;
+
+/// Default number of exchanges fetched from the cursor and encoded at once
+pub const DEFAULT_BATCH_SIZE: i64 = 10_000;
+
+/// Errors that can occur during an export
+#[derive(Debug, Error)]
+pub enum ExportError {
+    /// Error reading the exchanges
+    #[error("Database error: {0}")]
+    Db(#[from] db::DbError),
+
+    /// Error encoding CSV
+    #[error("CSV error: {0}")]
+    Csv(#[from] csv::Error),
+
+    /// Error encoding NDJSON
+    #[error("JSON error: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// Error building the Arrow batches
+    #[error("Arrow error: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+
+    /// Error encoding Parquet
+    #[error("Parquet error: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+}
+
+/// A specialized Result type for exports
+pub type Result<T> = std::result::Result<T, ExportError>;
+
+/// File format of an export
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Comma separated values with a header row
+    Csv,
+    /// One JSON object per line
+    Ndjson,
+    /// Apache Parquet with one row group per batch
+    Parquet,
+}
+
+impl ExportFormat {
+    /// MIME type of the format
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "text/csv",
+            ExportFormat::Ndjson => "application/x-ndjson",
+            ExportFormat::Parquet => "application/vnd.apache.parquet",
+        }
+    }
+
+    /// File extension of the format
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Csv => "csv",
+            ExportFormat::Ndjson => "ndjson",
+            ExportFormat::Parquet => "parquet",
+        }
+    }
+}
+
+impl FromStr for ExportFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "csv" => Ok(ExportFormat::Csv),
+            "ndjson" | "jsonl" => Ok(ExportFormat::Ndjson),
+            "parquet" => Ok(ExportFormat::Parquet),
+            _ => Err(format!("unknown export format: {}", s)),
+        }
+    }
+}
+
+/// One exported exchange, the columns of every format
+#[derive(Debug, Serialize)]
+struct ExchangeRow<'a> {
+    id: i32,
+    slot: i32,
+    signature: &'a str,
+    index: i32,
+    timestamp: DateTime<Utc>,
+    side: &'a str,
+    buyer: &'a str,
+    buyer_username: Option<&'a str>,
+    seller: &'a str,
+    seller_username: Option<&'a str>,
+    asset: &'a str,
+    asset_symbol: Option<&'a str>,
+    pair: &'a str,
+    pair_symbol: Option<&'a str>,
+    price: f64,
+    size: i32,
+    volume: f64,
+    fee: f64,
+    buddy: f64,
+}
+
+impl<'a> From<&'a ExchangeDetailed> for ExchangeRow<'a> {
+    fn from(exchange: &'a ExchangeDetailed) -> Self {
+        Self {
+            id: exchange.id,
+            slot: exchange.slot,
+            signature: &exchange.signature,
+            index: exchange.index,
+            timestamp: exchange.timestamp,
+            side: &exchange.side,
+            buyer: &exchange.buyer_wallet,
+            buyer_username: exchange.buyer_username.as_deref(),
+            seller: &exchange.seller_wallet,
+            seller_username: exchange.seller_username.as_deref(),
+            asset: &exchange.asset_mint,
+            asset_symbol: exchange.asset_symbol.as_deref(),
+            pair: &exchange.pair_mint,
+            pair_symbol: exchange.pair_symbol.as_deref(),
+            price: exchange.price,
+            size: exchange.size,
+            volume: exchange.volume,
+            fee: exchange.fee,
+            buddy: exchange.buddy,
+        }
+    }
+}
+
+/// Buffer the Parquet writer writes into, drained after every batch
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl SharedBuffer {
+    /// Takes the bytes written so far
+    fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut *self.0.lock().unwrap())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Encodes batches of exchanges into the bytes of an export
+pub struct ExportEncoder(Encoder);
+
+/// Encoder state of each format
+enum Encoder {
+    /// CSV encoder, writing the header with the first batch
+    Csv {
+        /// Whether the header was written
+        header_written: bool,
+    },
+    /// NDJSON encoder
+    Ndjson,
+    /// Parquet encoder, writing one row group per batch and the footer when finished
+    Parquet {
+        /// Arrow schema of the rows
+        schema: SchemaRef,
+        /// Writer encoding the row groups
+        writer: Box<ArrowWriter<SharedBuffer>>,
+        /// Buffer the writer writes into
+        buffer: SharedBuffer,
+    },
+}
+
+impl ExportEncoder {
+    /// Creates an encoder for the given format
+    ///
+    /// # Errors
+    /// Returns an error if the Parquet writer cannot be created
+    pub fn new(format: ExportFormat) -> Result<Self> {
+        Ok(Self(match format {
+            ExportFormat::Csv => Encoder::Csv {
+                header_written: false,
+            },
+            ExportFormat::Ndjson => Encoder::Ndjson,
+            ExportFormat::Parquet => {
+                let schema = parquet_schema();
+                let buffer = SharedBuffer::default();
+                let properties = WriterProperties::builder()
+                    .set_compression(Compression::SNAPPY)
+                    .build();
+                let writer =
+                    ArrowWriter::try_new(buffer.clone(), schema.clone(), Some(properties))?;
+                Encoder::Parquet {
+                    schema,
+                    writer: Box::new(writer),
+                    buffer,
+                }
+            }
+        }))
+    }
+
+    /// Encodes a batch of exchanges
+    ///
+    /// # Returns
+    /// The bytes to append to the export
+    ///
+    /// # Errors
+    /// Returns an error if the batch cannot be encoded
+    pub fn encode(&mut self, exchanges: &[ExchangeDetailed]) -> Result<Vec<u8>> {
+        match &mut self.0 {
+            Encoder::Csv { header_written } => {
+                let mut writer = csv::WriterBuilder::new()
+                    .has_headers(!*header_written)
+                    .from_writer(Vec::new());
+                for exchange in exchanges {
+                    writer.serialize(ExchangeRow::from(exchange))?;
+                }
+                *header_written |= !exchanges.is_empty();
+                writer
+                    .into_inner()
+                    .map_err(|e| ExportError::Csv(e.into_error().into()))
+            }
+            Encoder::Ndjson => {
+                let mut bytes = Vec::new();
+                for exchange in exchanges {
+                    serde_json::to_writer(&mut bytes, &ExchangeRow::from(exchange))?;
+                    bytes.push(b'\n');
+                }
+                Ok(bytes)
+            }
+            Encoder::Parquet {
+                schema,
+                writer,
+                buffer,
+            } => {
+                if !exchanges.is_empty() {
+                    writer.write(&record_batch(schema.clone(), exchanges)?)?;
+                    writer.flush()?;
+                }
+                Ok(buffer.take())
+            }
+        }
+    }
+
+    /// Finishes the export
+    ///
+    /// # Returns
+    /// The bytes that end the export, e.g. the Parquet footer
+    ///
+    /// # Errors
+    /// Returns an error if the export cannot be finished
+    pub fn finish(self) -> Result<Vec<u8>> {
+        match self.0 {
+            Encoder::Csv { .. } | Encoder::Ndjson => Ok(Vec::new()),
+            Encoder::Parquet { writer, buffer, .. } => {
+                writer.close()?;
+                Ok(buffer.take())
+            }
+        }
+    }
+}
+
+/// Streams an export as encoded chunks, one per batch read from the cursor
+///
+/// The stream ends after the chunk that finishes the export, or with the first error.
+pub fn export_stream(
+    export: ExchangeExport,
+    encoder: ExportEncoder,
+) -> impl Stream<Item = Result<Vec<u8>>> + Send {
+    stream::try_unfold(Some((export, encoder)), |state| async move {
+        let Some((mut export, mut encoder)) = state else {
+            return Ok(None);
+        };
+
+        let exchanges = export.next_batch().await?;
+        if exchanges.is_empty() {
+            return Ok(Some((encoder.finish()?, None)));
+        }
+
+        let chunk = encoder.encode(&exchanges)?;
+        Ok(Some((chunk, Some((export, encoder)))))
+    })
+}
+
+/// Arrow schema of the exported rows
+fn parquet_schema() -> SchemaRef {
+    Arc::new(Schema::new(vec![
+        Field::new("id", DataType::Int32, false),
+        Field::new("slot", DataType::Int32, false),
+        Field::new("signature", DataType::Utf8, false),
+        Field::new("index", DataType::Int32, false),
+        Field::new(
+            "timestamp",
+            DataType::Timestamp(TimeUnit::Microsecond, Some("UTC".into())),
+            false,
+        ),
+        Field::new("side", DataType::Utf8, false),
+        Field::new("buyer", DataType::Utf8, false),
+        Field::new("buyer_username", DataType::Utf8, true),
+        Field::new("seller", DataType::Utf8, false),
+        Field::new("seller_username", DataType::Utf8, true),
+        Field::new("asset", DataType::Utf8, false),
+        Field::new("asset_symbol", DataType::Utf8, true),
+        Field::new("pair", DataType::Utf8, false),
+        Field::new("pair_symbol", DataType::Utf8, true),
+        Field::new("price", DataType::Float64, false),
+        Field::new("size", DataType::Int32, false),
+        Field::new("volume", DataType::Float64, false),
+        Field::new("fee", DataType::Float64, false),
+        Field::new("buddy", DataType::Float64, false),
+    ]))
+}
+
+/// Builds an Arrow record batch of exchanges in the column order of `parquet_schema`
+fn record_batch(schema: SchemaRef, exchanges: &[ExchangeDetailed]) -> Result<RecordBatch> {
+    let int32 = |value: fn(&ExchangeDetailed) -> i32| -> ArrayRef {
+        Arc::new(Int32Array::from_iter_values(exchanges.iter().map(value)))
+    };
+    let float64 = |value: fn(&ExchangeDetailed) -> f64| -> ArrayRef {
+        Arc::new(Float64Array::from_iter_values(exchanges.iter().map(value)))
+    };
+    let string = |value: fn(&ExchangeDetailed) -> &str| -> ArrayRef {
+        Arc::new(StringArray::from_iter_values(exchanges.iter().map(value)))
+    };
+    let optional_string = |value: fn(&ExchangeDetailed) -> Option<&str>| -> ArrayRef {
+        Arc::new(exchanges.iter().map(value).collect::<StringArray>())
+    };
+
+    let columns = vec![
+        int32(|e| e.id),
+        int32(|e| e.slot),
+        string(|e| &e.signature),
+        int32(|e| e.index),
+        Arc::new(
+            TimestampMicrosecondArray::from_iter_values(
+                exchanges.iter().map(|e| e.timestamp.timestamp_micros()),
+            )
+            .with_timezone("UTC"),
+        ) as ArrayRef,
+        string(|e| &e.side),
+        string(|e| &e.buyer_wallet),
+        optional_string(|e| e.buyer_username.as_deref()),
+        string(|e| &e.seller_wallet),
+        optional_string(|e| e.seller_username.as_deref()),
+        string(|e| &e.asset_mint),
+        optional_string(|e| e.asset_symbol.as_deref()),
+        string(|e| &e.pair_mint),
+        optional_string(|e| e.pair_symbol.as_deref()),
+        float64(|e| e.price),
+        int32(|e| e.size),
+        float64(|e| e.volume),
+        float64(|e| e.fee),
+        float64(|e| e.buddy),
+    ];
+
+    Ok(RecordBatch::try_new(schema, columns)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn exchange(id: i32, buyer_username: Option<&str>) -> ExchangeDetailed {
+        ExchangeDetailed {
+            id,
+            slot: 250_000_000 + id,
+            signature: format!("sig{id}"),
+            index: 0,
+            timestamp: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+            side: "BUY".to_string(),
+            buyer_id: 1,
+            buyer_wallet: "buyer".to_string(),
+            buyer_username: buyer_username.map(str::to_string),
+            seller_id: 2,
+            seller_wallet: "seller".to_string(),
+            seller_username: None,
+            asset_id: 3,
+            asset_mint: "asset".to_string(),
+            asset_symbol: Some("SHIP".to_string()),
+            asset_name: None,
+            pair_id: 4,
+            pair_mint: "atlas".to_string(),
+            pair_symbol: Some("ATLAS".to_string()),
+            pair_name: None,
+            price: 1.5,
+            size: 2,
+            volume: 3.0,
+            fee: 0.25,
+            buddy: 0.0,
+            processor_version: 1,
+        }
+    }
+
+    const CSV_HEADER: &str = "id,slot,signature,index,timestamp,side,buyer,buyer_username,seller,\
+        seller_username,asset,asset_symbol,pair,pair_symbol,price,size,volume,fee,buddy\n";
+
+    #[test]
+    fn csv_writes_the_header_once_before_the_first_row() {
+        let mut encoder = ExportEncoder::new(ExportFormat::Csv).unwrap();
+
+        let empty = encoder.encode(&[]).unwrap();
+        let first = encoder.encode(&[exchange(1, None)]).unwrap();
+        let second = encoder.encode(&[exchange(2, None)]).unwrap();
+        let end = encoder.finish().unwrap();
+
+        assert!(empty.is_empty());
+        assert_eq!(
+            String::from_utf8(first).unwrap(),
+            format!(
+                "{CSV_HEADER}1,250000001,sig1,0,2023-11-14T22:13:20Z,BUY,buyer,,seller,,asset,SHIP,\
+                 atlas,ATLAS,1.5,2,3.0,0.25,0.0\n"
+            )
+        );
+        assert_eq!(
+            String::from_utf8(second).unwrap(),
+            "2,250000002,sig2,0,2023-11-14T22:13:20Z,BUY,buyer,,seller,,asset,SHIP,atlas,ATLAS,\
+             1.5,2,3.0,0.25,0.0\n"
+        );
+        assert!(end.is_empty());
+    }
+
+    #[test]
+    fn csv_quotes_fields_with_separators() {
+        let mut encoder = ExportEncoder::new(ExportFormat::Csv).unwrap();
+
+        let csv =
+            String::from_utf8(encoder.encode(&[exchange(1, Some("a,\"b\""))]).unwrap()).unwrap();
+
+        assert!(csv.contains(",buyer,\"a,\"\"b\"\"\",seller,"), "{csv}");
+    }
+
+    #[test]
+    fn ndjson_writes_one_object_per_line() {
+        let mut encoder = ExportEncoder::new(ExportFormat::Ndjson).unwrap();
+
+        let ndjson = String::from_utf8(
+            encoder
+                .encode(&[exchange(1, Some("pilot")), exchange(2, None)])
+                .unwrap(),
+        )
+        .unwrap();
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+
+        assert!(ndjson.ends_with('\n'));
+        assert_eq!(lines.len(), 2);
+        assert_eq!(lines[0]["id"], 1);
+        assert_eq!(lines[0]["buyer_username"], "pilot");
+        assert_eq!(lines[0]["timestamp"], "2023-11-14T22:13:20Z");
+        assert_eq!(lines[0]["price"], 1.5);
+        assert_eq!(lines[1]["id"], 2);
+        assert!(lines[1]["buyer_username"].is_null());
+        assert!(encoder.finish().unwrap().is_empty());
+    }
+
+    #[test]
+    fn parquet_ends_with_the_footer_when_finished() {
+        let mut encoder = ExportEncoder::new(ExportFormat::Parquet).unwrap();
+
+        let mut bytes = encoder
+            .encode(&[exchange(1, None), exchange(2, None)])
+            .unwrap();
+        bytes.extend(encoder.finish().unwrap());
+
+        assert!(bytes.starts_with(b"PAR1"));
+        assert!(bytes.ends_with(b"PAR1"));
+    }
+
+    #[test]
+    fn formats_parse_case_insensitively() {
+        assert_eq!("CSV".parse(), Ok(ExportFormat::Csv));
+        assert_eq!("jsonl".parse(), Ok(ExportFormat::Ndjson));
+        assert_eq!("Parquet".parse(), Ok(ExportFormat::Parquet));
+        assert!("xml".parse::<ExportFormat>().is_err());
+    }
+}
@@ -0,0 +1,82 @@
+use crate::args::Args;
+
+use clap::Parser;
+use db::{ExchangeExport, ExchangeFilter};
+use export::{ExportEncoder, ExportFormat};
+use std::path::Path;
+use tokio::io::{AsyncWrite, AsyncWriteExt};
+
+mod args;
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+
+    let args = Args::parse();
+
+    // Logs go to stderr, so they never mix with an export written to stdout
+    env_logger::Builder::new()
+        .filter(None, log::LevelFilter::Info)
+        .init();
+
+    let format = match (args.format, &args.output) {
+        (Some(format), _) => format,
+        (None, Some(output)) => Path::new(output)
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .unwrap_or_default()
+            .parse::<ExportFormat>()
+            .map_err(anyhow::Error::msg)?,
+        (None, None) => ExportFormat::Csv,
+    };
+
+    let filter = ExchangeFilter {
+        buyer: args.buyer,
+        seller: args.seller,
+        wallet: args.wallet,
+        asset_mint: args.asset,
+        pair_mint: args.pair,
+        side: args.side,
+        from_timestamp: args.from_timestamp,
+        to_timestamp: args.to_timestamp,
+        from_slot: args.from_slot,
+        to_slot: args.to_slot,
+        ..Default::default()
+    };
+
+    let mut output: Box<dyn AsyncWrite + Unpin> = match &args.output {
+        Some(path) => Box::new(tokio::io::BufWriter::new(
+            tokio::fs::File::create(path).await?,
+        )),
+        None => Box::new(tokio::io::BufWriter::new(tokio::io::stdout())),
+    };
+
+    let pool = db::establish_connection().await?;
+    let mut export = ExchangeExport::open(&pool, &filter, args.batch_size).await?;
+    let mut encoder = ExportEncoder::new(format)?;
+
+    let mut exported = 0;
+    loop {
+        let exchanges = export.next_batch().await?;
+        if exchanges.is_empty() {
+            break;
+        }
+
+        output.write_all(&encoder.encode(&exchanges)?).await?;
+        exported += exchanges.len();
+        log::info!(
+            "Exported {} exchanges (up to slot {})",
+            exported,
+            exchanges
+                .last()
+                .map(|exchange| exchange.slot)
+                .unwrap_or_default()
+        );
+    }
+    output.write_all(&encoder.finish()?).await?;
+    output.flush().await?;
+
+    log::info!("Finished export of {} exchanges as {:?}", exported, format);
+
+    Ok(())
+}
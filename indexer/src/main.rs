@@ -1,12 +1,14 @@
-use crate::args::Args;
 use chrono::DateTime;
-use clap::Parser;
-use db::{Direction, NewIndexer, NewProgramSignature, NewSignature, UpdateIndexer};
+use db::{Direction, NewProgramSignature, NewSignature, UpdateIndexer};
 use solana_client::client_error::ClientError;
 use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use solana_client::rpc_config::RpcBlockConfig;
+use solana_client::rpc_response::RpcConfirmedTransactionStatusWithSignature;
 use solana_commitment_config::CommitmentConfig;
 use solana_sdk::pubkey::Pubkey;
 use solana_sdk::signature::Signature;
+use solana_transaction_status::TransactionDetails;
+use std::collections::{BTreeSet, HashMap, HashSet};
 use std::env;
 use std::str::FromStr;
 use std::time::Duration;
@@ -18,12 +20,11 @@ const SLEEP: Duration = Duration::from_secs(5);
 const GAP_FILL_LIMIT: usize = 100;
 const MAX_ATTEMPTS: usize = 5;
 #[tokio::main]
+#[allow(clippy::result_large_err)]
 pub async fn main() -> anyhow::Result<()> {
-    use chrono::Utc;
     dotenv::dotenv().ok();
 
-    let indexer_name_to_use =
-        String::from(env::var("INDEXER_NAME").expect("INDEXER_NAME must be set"));
+    let indexer_name_to_use = env::var("INDEXER_NAME").expect("INDEXER_NAME must be set");
     let startup_delay = Duration::from_millis(
         env::var("STARTUP_DELAY")
             .unwrap_or_else(|_| "100".to_string())
@@ -41,13 +42,13 @@ pub async fn main() -> anyhow::Result<()> {
     let db_indexer = match db::get_indexer_by_name(&pool, indexer_name_to_use.as_str()).await {
         Ok(indexer) => indexer,
         Err(_) => {
-            log::error!("No indexer named {:?} found!", &indexer_name_to_use);
+            log::error!("No indexer named {:?} found!", indexer_name_to_use);
             return Ok(());
         }
     };
 
     let client = RpcClient::new_with_commitment(
-        String::from(env::var("RPC_URL").expect("RPC_URL must be set")),
+        env::var("RPC_URL").expect("RPC_URL must be set"),
         CommitmentConfig::confirmed(),
     );
 
@@ -58,70 +59,73 @@ pub async fn main() -> anyhow::Result<()> {
 
     // ----------- STEP 1: GAP FILL if direction is UP -----------
     let mut gap_filled_count = 0;
-    if db_indexer.direction == Direction::UP {
-        if let Some(ref last_sig) = db_indexer.signature {
-            log::info!("Performing gap fill to ensure no missed signatures...");
-
-            let mut before: Option<Signature> = None;
-            let mut caught_up = false;
-
-            while !caught_up {
-                let config = GetConfirmedSignaturesForAddress2Config {
-                    before,
-                    until: None,
-                    limit: Some(GAP_FILL_LIMIT),
-                    commitment: CommitmentConfig::finalized().into(),
-                };
+    if db_indexer.direction == Direction::UP
+        && let Some(ref last_sig) = db_indexer.signature
+    {
+        log::info!("Performing gap fill to ensure no missed signatures...");
 
-                let signatures =
-                    client.get_signatures_for_address_with_config(&program_id, config)?;
+        let mut before: Option<Signature> = None;
+        let mut caught_up = false;
 
-                if signatures.is_empty() {
-                    break;
-                }
+        while !caught_up {
+            let config = GetConfirmedSignaturesForAddress2Config {
+                before,
+                until: None,
+                limit: Some(GAP_FILL_LIMIT),
+                commitment: CommitmentConfig::finalized().into(),
+            };
 
-                for sig_info in &signatures {
-                    if sig_info.signature == *last_sig {
-                        caught_up = true;
-                        break;
-                    }
-                    gap_filled_count += 1;
-
-                    db::create_signature(
-                        &pool,
-                        &NewSignature {
-                            signature: sig_info.signature.to_string(),
-                            slot: sig_info.slot as i64,
-                            timestamp: DateTime::from_timestamp(sig_info.block_time.unwrap(), 0)
-                                .unwrap(),
-                        },
-                    )
-                    .await?;
-
-                    db::create_program_signature(
-                        &pool,
-                        &NewProgramSignature {
-                            program_id: program_id.to_string(),
-                            signature: sig_info.signature.to_string(),
-                            processed: false,
-                        },
-                    )
-                    .await?;
-                }
+            let signatures = client.get_signatures_for_address_with_config(&program_id, config)?;
+
+            if signatures.is_empty() {
+                break;
+            }
 
-                before = Some(Signature::from_str(&signatures.last().unwrap().signature)?);
+            let indexes = transaction_indexes(&client, &signatures).await?;
 
-                // If less than limit, we've hit the beginning of available data.
-                if signatures.len() < GAP_FILL_LIMIT {
+            for sig_info in &signatures {
+                if sig_info.signature == *last_sig {
+                    caught_up = true;
                     break;
                 }
+                gap_filled_count += 1;
+
+                db::create_signature(
+                    &pool,
+                    &NewSignature {
+                        signature: sig_info.signature.to_string(),
+                        slot: sig_info.slot as i64,
+                        timestamp: DateTime::from_timestamp(sig_info.block_time.unwrap(), 0)
+                            .unwrap(),
+                    },
+                )
+                .await?;
+
+                db::create_program_signature(
+                    &pool,
+                    &NewProgramSignature {
+                        program_id: program_id.to_string(),
+                        signature: sig_info.signature.to_string(),
+                        slot: sig_info.slot as i64,
+                        transaction_index: indexes.get(&sig_info.signature).copied(),
+                        processed: false,
+                    },
+                )
+                .await?;
             }
 
-            log::info!(
-                "Gap fill complete [{}]. Now polling for new signatures.",
-                gap_filled_count
-            );
+            before = Some(Signature::from_str(&signatures.last().unwrap().signature)?);
+
+            // If less than limit, we've hit the beginning of available data.
+            if signatures.len() < GAP_FILL_LIMIT {
+                break;
+            }
         }
+
+        log::info!(
+            "Gap fill complete [{}]. Now polling for new signatures.",
+            gap_filled_count
+        );
     }
 
     if gap_filled_count > 0 {
@@ -194,6 +198,8 @@ pub async fn main() -> anyhow::Result<()> {
         )
         .await?;
 
+        let indexes = transaction_indexes(&client, &signatures).await?;
+
         for sig_info in signatures.clone() {
             db::create_signature(
                 &pool,
@@ -209,6 +215,8 @@ pub async fn main() -> anyhow::Result<()> {
                 &NewProgramSignature {
                     program_id: program_id.to_string(),
                     signature: sig_info.signature.to_string(),
+                    slot: sig_info.slot as i64,
+                    transaction_index: indexes.get(&sig_info.signature).copied(),
                     processed: false,
                 },
             )
@@ -262,7 +270,25 @@ pub async fn main() -> anyhow::Result<()> {
                 program_id
             );
             if db_indexer.direction == Direction::DOWN {
-                println!("Finished all");
+                // Processors hold their watermark below a DOWN indexer until it is finished
+                db::update_indexer(
+                    &pool,
+                    db_indexer.name.clone(),
+                    &UpdateIndexer {
+                        signature: None,
+                        block: None,
+                        timestamp: None,
+                        direction: None,
+                        finished: Some(true),
+                        fetch_limit: None,
+                    },
+                )
+                .await?;
+                log::info!(
+                    "[{:?}] finished backfilling {}",
+                    db_indexer.name,
+                    program_id
+                );
                 return Ok(());
             }
         } else {
@@ -277,6 +303,48 @@ pub async fn main() -> anyhow::Result<()> {
     }
 }
 
+/// Looks up the position of each signature in its block
+///
+/// The signatures of an address come without their position in the block, which processors
+/// need to handle transactions of the same slot in order. Each distinct slot is fetched once
+/// with signatures only.
+#[allow(clippy::result_large_err)]
+async fn transaction_indexes(
+    client: &RpcClient,
+    signatures: &[RpcConfirmedTransactionStatusWithSignature],
+) -> anyhow::Result<HashMap<String, i32>> {
+    let wanted: HashSet<&str> = signatures.iter().map(|s| s.signature.as_str()).collect();
+    let slots: BTreeSet<u64> = signatures.iter().map(|s| s.slot).collect();
+
+    let mut indexes = HashMap::new();
+    for slot in slots {
+        let block = rpc_with_retry(
+            || {
+                client.get_block_with_config(
+                    slot,
+                    RpcBlockConfig {
+                        encoding: None,
+                        transaction_details: Some(TransactionDetails::Signatures),
+                        rewards: Some(false),
+                        commitment: Some(CommitmentConfig::finalized()),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+            },
+            MAX_ATTEMPTS,
+        )
+        .await?;
+
+        for (index, signature) in block.signatures.unwrap_or_default().into_iter().enumerate() {
+            if wanted.contains(signature.as_str()) {
+                indexes.insert(signature, index as i32);
+            }
+        }
+    }
+
+    Ok(indexes)
+}
+
 async fn rpc_with_retry<F, T>(mut f: F, max_attempts: usize) -> Result<T, ClientError>
 where
     F: FnMut() -> Result<T, ClientError>,
@@ -1,8 +1,70 @@
-use clap::Parser;
+use chrono::{DateTime, Utc};
+use clap::{Parser, Subcommand, ValueEnum};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub struct Args {
     #[arg(short, long)]
     pub signature: Option<String>,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Mark processed signatures in a slot and/or time range as unprocessed so they are rewritten
+    Reprocess {
+        /// Lowest slot to reprocess (inclusive)
+        #[arg(long)]
+        from_slot: Option<i64>,
+
+        /// Highest slot to reprocess (inclusive)
+        #[arg(long)]
+        to_slot: Option<i64>,
+
+        /// Earliest block time to reprocess (RFC 3339, inclusive)
+        #[arg(long)]
+        from_timestamp: Option<DateTime<Utc>>,
+
+        /// Latest block time to reprocess (RFC 3339, inclusive)
+        #[arg(long)]
+        to_timestamp: Option<DateTime<Utc>>,
+    },
+
+    /// Store a snapshot of all marketplace program accounts (open orders, registered
+    /// currencies, fee reductions, ...), of the crafting recipes or of the player profiles and
+    /// factions
+    Snapshot {
+        /// Program to take the snapshot of
+        #[arg(long, value_enum, default_value_t = SnapshotProgram::Marketplace)]
+        program: SnapshotProgram,
+
+        /// Take a new snapshot every this many seconds instead of once
+        #[arg(long)]
+        interval_seconds: Option<u64>,
+    },
+
+    /// Store the members of the guilds listed in a JSON config file, read from their DAO realm
+    /// or given in the file, and attribute the players to them
+    Guilds {
+        /// Path of the guild config file
+        #[arg(long)]
+        config: String,
+
+        /// Update the members every this many seconds instead of once
+        #[arg(long)]
+        interval_seconds: Option<u64>,
+    },
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug)]
+pub enum SnapshotProgram {
+    Marketplace,
+    /// Recipes of the crafting program, decoded with the crafting IDL from IDL_DIR
+    Crafting,
+    /// Player profiles and names, decoded with the Player Profile IDL from IDL_DIR
+    Profiles,
+    /// Factions of the player profiles, decoded with the Profile Faction IDL from IDL_DIR
+    Factions,
 }
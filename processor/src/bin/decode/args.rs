@@ -0,0 +1,60 @@
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Show what the decoder and processor make of a transaction or instruction", long_about = None)]
+pub struct Args {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Text)]
+    pub format: Format,
+
+    /// Directory with additional IDLs, see `IdlRegistry::load_dir` (defaults to IDL_DIR)
+    #[arg(long)]
+    pub idl_dir: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Text,
+    Json,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum Command {
+    /// Fetch a transaction from RPC_URL and decode it
+    Signature {
+        /// Transaction signature
+        signature: String,
+    },
+
+    /// Decode the data and accounts of a single instruction
+    Instruction {
+        /// Instruction data, base58 encoded (or hex with --hex)
+        data: String,
+
+        /// Program the instruction is for (defaults to the marketplace)
+        #[arg(long)]
+        program: Option<String>,
+
+        /// Accounts of the instruction in order, comma separated
+        #[arg(long, value_delimiter = ',')]
+        accounts: Vec<String>,
+
+        /// Slot selecting the IDL version (defaults to the latest)
+        #[arg(long)]
+        slot: Option<u64>,
+
+        /// The data is hex instead of base58 encoded
+        #[arg(long)]
+        hex: bool,
+    },
+
+    /// Decode a transaction saved as `getTransaction` JSON with jsonParsed encoding
+    File {
+        /// Path of the JSON file, either the RPC response or its `result`
+        path: PathBuf,
+    },
+}
@@ -0,0 +1,599 @@
+//! Shows what the decoder and processor make of a transaction or instruction
+//!
+//! Prints the decoded instructions with named accounts, for marketplace exchanges the inner
+//! transfers and the exchange values the processor would store, and for SAGE instructions the
+//! fleet activity and crafting steps. Nothing is written to the database.
+
+use crate::args::{Args, Command, Format};
+use crate::output::{AccountOutput, EventOutput, InstructionOutput, TransactionOutput};
+
+use anyhow::{Context, bail};
+use clap::Parser;
+use decoder::idl::{DecodedIdlInstruction, IdlRegistry};
+use decoder::logs::parse_logs;
+use decoder::staratlas::sage::SageActivityKind;
+use decoder::staratlas::score::ScoreEventKind;
+use processor::convert::{processor_accounts, processor_data, processor_inner};
+use processor::processor::crafting::CraftingProcessor;
+use processor::processor::marketplace::MarketplaceProcessor;
+use processor::processor::sage::SageProcessor;
+use processor::processor::score::ScoreProcessor;
+use serde_json::Value;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::RpcTransactionConfig;
+use solana_commitment_config::CommitmentConfig;
+use solana_sdk::pubkey::Pubkey;
+use solana_sdk::signature::Signature;
+use solana_transaction_status::{
+    EncodedConfirmedTransactionWithStatusMeta, EncodedTransaction, UiInstruction, UiMessage,
+    UiParsedInstruction, UiTransactionEncoding,
+};
+use std::env;
+use std::str::FromStr;
+use std::sync::Arc;
+
+mod args;
+mod output;
+
+#[tokio::main]
+pub async fn main() -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+
+    let args = Args::parse();
+
+    env_logger::Builder::new()
+        .filter(None, log::LevelFilter::Warn)
+        .init();
+
+    let mut idls = IdlRegistry::builtin();
+    if let Some(idl_dir) = args
+        .idl_dir
+        .or_else(|| env::var("IDL_DIR").ok().map(Into::into))
+    {
+        idls.load_dir(&idl_dir)?;
+    }
+    let idls = Arc::new(idls);
+
+    match args.command {
+        Command::Signature { signature } => {
+            let client = RpcClient::new_with_commitment(
+                env::var("RPC_URL").context("RPC_URL must be set")?,
+                CommitmentConfig::confirmed(),
+            );
+            let transaction = client
+                .get_transaction_with_config(
+                    &Signature::from_str(&signature)?,
+                    RpcTransactionConfig {
+                        commitment: CommitmentConfig::confirmed().into(),
+                        encoding: UiTransactionEncoding::JsonParsed.into(),
+                        max_supported_transaction_version: Some(0),
+                    },
+                )
+                .await?;
+            print(args.format, &decode_transaction(&idls, transaction)?)?;
+        }
+        Command::File { path } => {
+            let json: Value = serde_json::from_str(&std::fs::read_to_string(&path)?)?;
+            let transaction = transaction_from_json(json)?;
+            print(args.format, &decode_transaction(&idls, transaction)?)?;
+        }
+        Command::Instruction {
+            data,
+            program,
+            accounts,
+            slot,
+            hex,
+        } => {
+            let program_id = match program {
+                Some(program) => Pubkey::from_str(&program)?,
+                None => decoder::staratlas::marketplace::ID,
+            };
+            let data = if hex {
+                hex::decode(data.trim_start_matches("0x"))?
+            } else {
+                bs58::decode(data).into_vec()?
+            };
+            let accounts = accounts
+                .iter()
+                .map(|account| Pubkey::from_str(account))
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let (mut instruction, decoded) = decode_instruction(
+                &idls,
+                &program_id,
+                slot.unwrap_or(u64::MAX),
+                &data,
+                &accounts,
+            );
+            // Without the transaction there are no inner transfers to show
+            if let Some(decoded) = &decoded
+                && program_id == decoder::staratlas::sage::ID
+                && let Some(kind) = SageActivityKind::from_instruction(&decoded.name)
+            {
+                instruction.sage_activity =
+                    Some(SageProcessor::map_activity(kind, 0, decoded, vec![]).into());
+            }
+            if let Some(decoded) = &decoded
+                && program_id == decoder::staratlas::score::ID
+                && let Some(kind) = ScoreEventKind::from_instruction(&decoded.name)
+            {
+                instruction.score_event =
+                    Some(ScoreProcessor::map_event(kind, 0, decoded, vec![]).into());
+            }
+            match args.format {
+                Format::Json => println!("{}", serde_json::to_string_pretty(&instruction)?),
+                Format::Text => instruction.print_text(),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn print(format: Format, transaction: &TransactionOutput) -> anyhow::Result<()> {
+    match format {
+        Format::Json => println!("{}", serde_json::to_string_pretty(transaction)?),
+        Format::Text => transaction.print_text(),
+    }
+    Ok(())
+}
+
+/// Reads a saved `getTransaction` result, accepting the whole JSON-RPC response as well
+fn transaction_from_json(json: Value) -> anyhow::Result<EncodedConfirmedTransactionWithStatusMeta> {
+    let json = match json {
+        Value::Object(mut object) if object.contains_key("jsonrpc") => object
+            .remove("result")
+            .filter(|result| !result.is_null())
+            .context("the RPC response has no result")?,
+        json => json,
+    };
+    serde_json::from_value(json).context("not a getTransaction result with jsonParsed encoding")
+}
+
+/// Decodes every instruction of a transaction the way the processor does
+fn decode_transaction(
+    idls: &Arc<IdlRegistry>,
+    transaction: EncodedConfirmedTransactionWithStatusMeta,
+) -> anyhow::Result<TransactionOutput> {
+    let slot = transaction.slot;
+    let block_time = transaction.block_time;
+    let meta = transaction
+        .transaction
+        .meta
+        .context("transaction without meta")?;
+    let EncodedTransaction::Json(json) = transaction.transaction.transaction else {
+        bail!("the transaction must be jsonParsed encoded");
+    };
+    let UiMessage::Parsed(message) = json.message else {
+        bail!("the transaction must be jsonParsed encoded");
+    };
+    let signature = json.signatures.first().cloned().unwrap_or_default();
+    let logs = Option::<Vec<String>>::from(meta.log_messages.clone())
+        .map(|messages| parse_logs(&messages))
+        .unwrap_or_default();
+
+    let mut instructions = Vec::with_capacity(message.instructions.len());
+    for (index, instruction) in message.instructions.into_iter().enumerate() {
+        let mut output = match instruction {
+            UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) => {
+                let program_id = Pubkey::from_str(&instruction.program_id)?;
+                let accounts = processor_accounts(instruction.accounts);
+                let data = processor_data(instruction.data);
+                let (mut output, decoded) =
+                    decode_instruction(idls, &program_id, slot, &data, &accounts);
+
+                let inner = processor_inner(meta.clone(), index);
+                let invokes_crafting = inner.iter().any(|inner| {
+                    matches!(
+                        inner,
+                        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(inner))
+                            if inner.program_id == decoder::staratlas::crafting::ID.to_string()
+                    )
+                });
+                if program_id == decoder::staratlas::crafting::ID || invokes_crafting {
+                    let events = CraftingProcessor::new(idls.clone()).events(
+                        slot,
+                        &signature,
+                        index,
+                        &program_id,
+                        &data,
+                        &accounts,
+                        inner,
+                    );
+                    match events {
+                        Ok(events) => {
+                            output.crafting_events = events.into_iter().map(Into::into).collect()
+                        }
+                        // The processor fails on it, keep the decoding error if there is one
+                        Err(err) => {
+                            output.error.get_or_insert_with(|| format!("{:#}", err));
+                        }
+                    }
+                }
+
+                if let Some(decoded) = &decoded
+                    && program_id == decoder::staratlas::sage::ID
+                    && let Some(kind) = SageActivityKind::from_instruction(&decoded.name)
+                {
+                    output.sage_activity = Some(
+                        SageProcessor::map_activity(
+                            kind,
+                            index,
+                            decoded,
+                            processor_inner(meta.clone(), index),
+                        )
+                        .into(),
+                    );
+                }
+
+                if let Some(decoded) = &decoded
+                    && program_id == decoder::staratlas::score::ID
+                    && let Some(kind) = ScoreEventKind::from_instruction(&decoded.name)
+                {
+                    output.score_event = Some(
+                        ScoreProcessor::map_event(
+                            kind,
+                            index,
+                            decoded,
+                            processor_inner(meta.clone(), index),
+                        )
+                        .into(),
+                    );
+                }
+
+                if let Some(decoded) = decoded
+                    && program_id == decoder::staratlas::marketplace::ID
+                    && decoded.name == "process_exchange"
+                {
+                    let inner = processor_inner(meta.clone(), index);
+                    let exchange = MarketplaceProcessor::map_inner_transfers(inner.clone())
+                        .and_then(|transfers| {
+                            output.inner_transfers = transfers;
+                            MarketplaceProcessor::exchange(
+                                slot,
+                                block_time.unwrap_or_default(),
+                                &signature,
+                                index,
+                                &decoded,
+                                inner,
+                            )
+                        });
+                    match exchange {
+                        Ok(exchange) => output.exchange = Some(exchange.into()),
+                        Err(err) => output.error = Some(err.to_string()),
+                    }
+                }
+                output
+            }
+            // Instructions of programs the RPC parses itself (system, token, ...)
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) => InstructionOutput {
+                program_id: instruction.program_id,
+                program: Some(instruction.program),
+                name: instruction
+                    .parsed
+                    .get("type")
+                    .and_then(Value::as_str)
+                    .map(ToString::to_string),
+                args: instruction.parsed.get("info").cloned(),
+                ..Default::default()
+            },
+            UiInstruction::Compiled(_) => bail!("the transaction must be jsonParsed encoded"),
+        };
+        output.index = Some(index);
+
+        for invocation in logs
+            .invocations
+            .iter()
+            .filter(|invocation| invocation.instruction_index == index)
+        {
+            if invocation.depth == 1 {
+                output.compute_units = invocation.compute_units_consumed;
+            }
+            let program_id = Pubkey::from_str(&invocation.program_id).ok();
+            for raw in &invocation.data {
+                let event = program_id
+                    .and_then(|program_id| idls.decode_event(&program_id, slot, raw).ok())
+                    .flatten();
+                output.events.push(EventOutput {
+                    program_id: invocation.program_id.clone(),
+                    name: event.as_ref().map(|event| event.name.clone()),
+                    data: event.map(|event| event.data),
+                    raw: hex::encode(raw),
+                });
+            }
+        }
+
+        instructions.push(output);
+    }
+
+    Ok(TransactionOutput {
+        signature: Some(signature),
+        slot,
+        block_time,
+        error: meta.err.map(|err| err.to_string()),
+        instructions,
+    })
+}
+
+/// Decodes an instruction with the IDL valid at the slot
+fn decode_instruction(
+    idls: &IdlRegistry,
+    program_id: &Pubkey,
+    slot: u64,
+    data: &[u8],
+    accounts: &[Pubkey],
+) -> (InstructionOutput, Option<DecodedIdlInstruction>) {
+    let mut output = InstructionOutput {
+        program_id: program_id.to_string(),
+        accounts: accounts
+            .iter()
+            .map(|pubkey| AccountOutput {
+                name: None,
+                pubkey: pubkey.to_string(),
+            })
+            .collect(),
+        ..Default::default()
+    };
+
+    let decoded = match idls.decode_instruction(program_id, slot, data, accounts) {
+        Ok(Some(decoded)) => decoded,
+        Ok(None) if idls.idl(program_id, slot).is_none() => {
+            output.error = Some("no IDL registered for the program at this slot".to_string());
+            return (output, None);
+        }
+        Ok(None) => {
+            output.error = Some(format!(
+                "no IDL instruction with discriminator {}",
+                hex::encode(&data[..data.len().min(8)])
+            ));
+            return (output, None);
+        }
+        Err(err) => {
+            output.error = Some(err.to_string());
+            return (output, None);
+        }
+    };
+
+    output.program = Some(decoded.program.clone());
+    output.idl_version = Some(decoded.version.clone());
+    output.name = Some(decoded.name.clone());
+    output.args = Some(decoded.args.clone());
+    for (account, (name, _)) in output.accounts.iter_mut().zip(&decoded.accounts) {
+        account.name = Some(name.clone());
+    }
+
+    (output, Some(decoded))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decoder::idl::sighash;
+    use serde_json::json;
+
+    const SYSTEM: &str = "11111111111111111111111111111111";
+
+    /// Returns the data of an `update_atlas_rate` marketplace instruction
+    fn update_atlas_rate(rate: u64) -> Vec<u8> {
+        let mut data = sighash("global", "update_atlas_rate");
+        data.extend(rate.to_le_bytes());
+        data
+    }
+
+    /// Returns a jsonParsed `getTransaction` result with the given instructions and logs
+    fn transaction_json(instructions: Vec<Value>, logs: Vec<String>) -> Value {
+        json!({
+            "slot": 250_000_000,
+            "blockTime": 1_700_000_000,
+            "transaction": {
+                "signatures": ["5sig"],
+                "message": {
+                    "accountKeys": [],
+                    "recentBlockhash": SYSTEM,
+                    "instructions": instructions,
+                },
+            },
+            "meta": {
+                "err": null,
+                "status": {"Ok": null},
+                "fee": 5000,
+                "preBalances": [],
+                "postBalances": [],
+                "innerInstructions": [],
+                "logMessages": logs,
+                "preTokenBalances": [],
+                "postTokenBalances": [],
+                "rewards": [],
+            },
+        })
+    }
+
+    fn partially_decoded(program_id: &Pubkey, data: &[u8], accounts: &[Pubkey]) -> Value {
+        json!({
+            "programId": program_id.to_string(),
+            "accounts": accounts.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            "data": bs58::encode(data).into_string(),
+            "stackHeight": null,
+        })
+    }
+
+    #[test]
+    fn decodes_an_instruction_with_named_accounts() {
+        let idls = IdlRegistry::builtin();
+        let accounts: Vec<Pubkey> = (0..6).map(|_| Pubkey::new_unique()).collect();
+
+        let (output, decoded) = decode_instruction(
+            &idls,
+            &decoder::staratlas::marketplace::ID,
+            u64::MAX,
+            &update_atlas_rate(42),
+            &accounts,
+        );
+
+        assert!(decoded.is_some());
+        assert_eq!(output.error, None);
+        assert_eq!(output.name.as_deref(), Some("update_atlas_rate"));
+        assert_eq!(output.args, Some(json!({"rate": 42})));
+        let names: Vec<Option<&str>> = output
+            .accounts
+            .iter()
+            .map(|account| account.name.as_deref())
+            .collect();
+        // Accounts beyond the IDL are kept without a name
+        assert_eq!(
+            names,
+            [
+                Some("funder"),
+                Some("update_authority_account"),
+                Some("market_vars_account"),
+                Some("atlas_rate"),
+                Some("system_program"),
+                None,
+            ]
+        );
+        assert_eq!(output.accounts[5].pubkey, accounts[5].to_string());
+    }
+
+    #[test]
+    fn reports_instructions_it_cannot_decode() {
+        let idls = IdlRegistry::builtin();
+
+        let (unknown, unknown_decoded) = decode_instruction(
+            &idls,
+            &decoder::staratlas::marketplace::ID,
+            u64::MAX,
+            &[1, 2, 3, 4, 5, 6, 7, 8, 9],
+            &[],
+        );
+        let (unregistered, unregistered_decoded) =
+            decode_instruction(&idls, &Pubkey::new_unique(), u64::MAX, &[1, 2, 3], &[]);
+
+        assert!(unknown_decoded.is_none());
+        assert_eq!(
+            unknown.error.as_deref(),
+            Some("no IDL instruction with discriminator 0102030405060708")
+        );
+        assert!(unregistered_decoded.is_none());
+        assert_eq!(
+            unregistered.error.as_deref(),
+            Some("no IDL registered for the program at this slot")
+        );
+    }
+
+    #[test]
+    fn decodes_every_instruction_of_a_transaction() {
+        let marketplace = decoder::staratlas::marketplace::ID;
+        let accounts: Vec<Pubkey> = (0..5).map(|_| Pubkey::new_unique()).collect();
+        let transfer = json!({
+            "program": "system",
+            "programId": SYSTEM,
+            "parsed": {"type": "transfer", "info": {"lamports": 10}},
+            "stackHeight": null,
+        });
+        let logs = vec![
+            format!("Program {} invoke [1]", marketplace),
+            "Program log: Instruction: UpdateAtlasRate".to_string(),
+            format!(
+                "Program {} consumed 1234 of 200000 compute units",
+                marketplace
+            ),
+            format!("Program {} success", marketplace),
+            format!("Program {} invoke [1]", SYSTEM),
+            format!("Program {} success", SYSTEM),
+        ];
+        let transaction = transaction_from_json(transaction_json(
+            vec![
+                partially_decoded(&marketplace, &update_atlas_rate(7), &accounts),
+                transfer,
+                partially_decoded(&marketplace, &[0; 8], &[]),
+            ],
+            logs,
+        ))
+        .unwrap();
+
+        let output = decode_transaction(&Arc::new(IdlRegistry::builtin()), transaction).unwrap();
+
+        assert_eq!(output.signature.as_deref(), Some("5sig"));
+        assert_eq!(output.slot, 250_000_000);
+        assert_eq!(output.error, None);
+        let [rate, transfer, unknown] = &output.instructions[..] else {
+            panic!("expected three instructions");
+        };
+        assert_eq!(rate.index, Some(0));
+        assert_eq!(rate.name.as_deref(), Some("update_atlas_rate"));
+        assert_eq!(rate.args, Some(json!({"rate": 7})));
+        assert_eq!(rate.compute_units, Some(1234));
+        assert_eq!(transfer.program.as_deref(), Some("system"));
+        assert_eq!(transfer.name.as_deref(), Some("transfer"));
+        assert_eq!(transfer.args, Some(json!({"lamports": 10})));
+        assert_eq!(unknown.index, Some(2));
+        assert!(unknown.name.is_none());
+        assert!(unknown.error.is_some());
+    }
+
+    #[test]
+    fn reads_rpc_responses_and_their_results() {
+        let result = transaction_json(vec![], vec![]);
+
+        let from_response = transaction_from_json(json!({
+            "jsonrpc": "2.0",
+            "id": 1,
+            "result": result.clone(),
+        }));
+        let from_result = transaction_from_json(result);
+        let missing = transaction_from_json(json!({"jsonrpc": "2.0", "id": 1, "result": null}));
+
+        assert_eq!(from_response.unwrap().slot, 250_000_000);
+        assert_eq!(from_result.unwrap().slot, 250_000_000);
+        assert_eq!(
+            missing.unwrap_err().to_string(),
+            "the RPC response has no result"
+        );
+    }
+
+    #[test]
+    fn rejects_transactions_not_json_parsed() {
+        let mut json = transaction_json(vec![], vec![]);
+        json["transaction"] = json!(["AQAB", "base64"]);
+
+        let transaction = transaction_from_json(json).unwrap();
+        let output = decode_transaction(&Arc::new(IdlRegistry::builtin()), transaction);
+
+        assert_eq!(
+            output.unwrap_err().to_string(),
+            "the transaction must be jsonParsed encoded"
+        );
+    }
+
+    #[test]
+    fn parses_instruction_arguments() {
+        let args = Args::try_parse_from([
+            "decode",
+            "--format",
+            "json",
+            "instruction",
+            "0xdeadbeef",
+            "--hex",
+            "--accounts",
+            &format!("{},{}", SYSTEM, SYSTEM),
+        ])
+        .unwrap();
+
+        assert_eq!(args.format, Format::Json);
+        let Command::Instruction {
+            data,
+            program,
+            accounts,
+            slot,
+            hex,
+        } = args.command
+        else {
+            panic!("not an instruction command");
+        };
+        assert_eq!(data, "0xdeadbeef");
+        assert_eq!(program, None);
+        assert_eq!(accounts.len(), 2);
+        assert_eq!(slot, None);
+        assert!(hex);
+    }
+}
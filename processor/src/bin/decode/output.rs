@@ -0,0 +1,354 @@
+use chrono::DateTime;
+use processor::processor::marketplace::MarketplaceExchangeInner;
+use serde::Serialize;
+use serde_json::Value;
+
+/// A decoded transaction
+#[derive(Debug, Serialize)]
+pub struct TransactionOutput {
+    pub signature: Option<String>,
+    pub slot: u64,
+    pub block_time: Option<i64>,
+    /// Error of a failed transaction
+    pub error: Option<String>,
+    pub instructions: Vec<InstructionOutput>,
+}
+
+/// A decoded instruction with what the processor derives from it
+#[derive(Debug, Default, Serialize)]
+pub struct InstructionOutput {
+    /// Index of the instruction in the transaction
+    pub index: Option<usize>,
+    pub program_id: String,
+    /// Program name from the IDL or the RPC's parser
+    pub program: Option<String>,
+    /// Version of the IDL the instruction was decoded with
+    pub idl_version: Option<String>,
+    pub name: Option<String>,
+    pub args: Option<Value>,
+    pub accounts: Vec<AccountOutput>,
+    /// Why the instruction or the exchange could not be decoded
+    pub error: Option<String>,
+    pub compute_units: Option<u64>,
+    pub events: Vec<EventOutput>,
+    pub inner_transfers: Vec<MarketplaceExchangeInner>,
+    /// The exchange the processor stores for the instruction
+    pub exchange: Option<ExchangeOutput>,
+    /// The SAGE fleet activity the processor stores for the instruction
+    pub sage_activity: Option<SageActivityOutput>,
+    /// The crafting steps the processor stores for the instruction and its inner instructions
+    pub crafting_events: Vec<CraftingEventOutput>,
+    /// The SCORE staking event the processor stores for the instruction
+    pub score_event: Option<ScoreEventOutput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct AccountOutput {
+    /// Name from the IDL, `None` for accounts beyond it
+    pub name: Option<String>,
+    pub pubkey: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EventOutput {
+    pub program_id: String,
+    pub name: Option<String>,
+    pub data: Option<Value>,
+    /// Hex encoded payload
+    pub raw: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ExchangeOutput {
+    pub side: String,
+    pub buyer: String,
+    pub seller: String,
+    pub asset_mint: String,
+    pub pair_mint: String,
+    pub price: f64,
+    pub size: i32,
+    pub volume: f64,
+    pub fee: f64,
+    pub buddy: f64,
+}
+
+impl From<db::ExchangeWithDependencies> for ExchangeOutput {
+    fn from(exchange: db::ExchangeWithDependencies) -> Self {
+        Self {
+            side: exchange.side,
+            buyer: exchange.buyer_wallet,
+            seller: exchange.seller_wallet,
+            asset_mint: exchange.asset_mint,
+            pair_mint: exchange.pair_mint,
+            price: exchange.price,
+            size: exchange.size,
+            volume: exchange.volume,
+            fee: exchange.fee,
+            buddy: exchange.buddy,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct SageActivityOutput {
+    pub kind: String,
+    pub fleet: Option<String>,
+    pub player_profile: Option<String>,
+    pub starbase: Option<String>,
+    pub sector: Option<(i64, i64)>,
+    pub resource_mint: Option<String>,
+    pub amount: Option<i64>,
+    pub label: Option<String>,
+    pub transfers: Vec<SageTransferOutput>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SageTransferOutput {
+    pub kind: String,
+    pub mint: Option<String>,
+    pub source: Option<String>,
+    pub destination: Option<String>,
+    pub amount: i64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CraftingEventOutput {
+    pub kind: String,
+    /// 0 for the instruction itself, otherwise 1 + the index among its inner instructions
+    pub position: i32,
+    pub crafting_process: Option<String>,
+    pub recipe: Option<String>,
+    pub player_profile: Option<String>,
+    pub starbase: Option<String>,
+    pub quantity: Option<i64>,
+    pub mint: Option<String>,
+    pub amount: Option<i64>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScoreEventOutput {
+    pub kind: String,
+    pub player: Option<String>,
+    pub ship_mint: Option<String>,
+    pub resource: Option<String>,
+    pub mint: Option<String>,
+    pub amount: Option<i64>,
+}
+
+impl From<db::NewScoreEvent> for ScoreEventOutput {
+    fn from(event: db::NewScoreEvent) -> Self {
+        Self {
+            kind: event.kind,
+            player: event.player,
+            ship_mint: event.ship_mint,
+            resource: event.resource,
+            mint: event.mint,
+            amount: event.amount,
+        }
+    }
+}
+
+impl From<db::NewCraftingEvent> for CraftingEventOutput {
+    fn from(event: db::NewCraftingEvent) -> Self {
+        Self {
+            kind: event.kind,
+            position: event.position,
+            crafting_process: event.crafting_process,
+            recipe: event.recipe,
+            player_profile: event.player_profile,
+            starbase: event.starbase,
+            quantity: event.quantity,
+            mint: event.mint,
+            amount: event.amount,
+        }
+    }
+}
+
+impl From<db::NewSageActivity> for SageActivityOutput {
+    fn from(activity: db::NewSageActivity) -> Self {
+        Self {
+            kind: activity.kind,
+            fleet: activity.fleet,
+            player_profile: activity.player_profile,
+            starbase: activity.starbase,
+            sector: activity.sector,
+            resource_mint: activity.resource_mint,
+            amount: activity.amount,
+            label: activity.label,
+            transfers: activity
+                .transfers
+                .into_iter()
+                .map(|transfer| SageTransferOutput {
+                    kind: transfer.kind,
+                    mint: transfer.mint,
+                    source: transfer.source,
+                    destination: transfer.destination,
+                    amount: transfer.amount,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TransactionOutput {
+    /// Prints the transaction in a human readable form
+    pub fn print_text(&self) {
+        if let Some(signature) = &self.signature {
+            println!("Signature:  {}", signature);
+        }
+        println!("Slot:       {}", self.slot);
+        if let Some(block_time) = self
+            .block_time
+            .and_then(|time| DateTime::from_timestamp(time, 0))
+        {
+            println!("Block time: {}", block_time.to_rfc3339());
+        }
+        match &self.error {
+            Some(error) => println!("Status:     failed: {}", error),
+            None => println!("Status:     ok"),
+        }
+        for instruction in &self.instructions {
+            println!();
+            instruction.print_text();
+        }
+    }
+}
+
+impl InstructionOutput {
+    /// Prints the instruction in a human readable form
+    pub fn print_text(&self) {
+        let mut header = match self.index {
+            Some(index) => format!("#{} {}", index, self.program_id),
+            None => self.program_id.clone(),
+        };
+        if let Some(program) = &self.program {
+            header.push_str(&format!(" ({}", program));
+            if let Some(version) = &self.idl_version {
+                header.push_str(&format!(" {}", version));
+            }
+            header.push(')');
+        }
+        header.push_str(&format!(" {}", self.name.as_deref().unwrap_or("<unknown>")));
+        if let Some(compute_units) = self.compute_units {
+            header.push_str(&format!(" [{} CU]", compute_units));
+        }
+        println!("{}", header);
+
+        if let Some(error) = &self.error {
+            println!("  error: {}", error);
+        }
+        if let Some(args) = &self.args {
+            println!("  args: {}", args);
+        }
+        if !self.accounts.is_empty() {
+            println!("  accounts:");
+            let width = self
+                .accounts
+                .iter()
+                .filter_map(|account| account.name.as_ref().map(String::len))
+                .max()
+                .unwrap_or_default();
+            for account in &self.accounts {
+                println!(
+                    "    {:width$}  {}",
+                    account.name.as_deref().unwrap_or("-"),
+                    account.pubkey,
+                    width = width
+                );
+            }
+        }
+        if !self.inner_transfers.is_empty() {
+            println!("  inner transfers:");
+            for transfer in &self.inner_transfers {
+                println!(
+                    "    {} mint={} source={} amount={} decimals={} transfer_fee={}",
+                    transfer.program_id,
+                    transfer.mint.as_deref().unwrap_or("-"),
+                    transfer.source.as_deref().unwrap_or("-"),
+                    display(transfer.amount),
+                    display(transfer.decimals),
+                    display(transfer.transfer_fee),
+                );
+            }
+        }
+        if let Some(exchange) = &self.exchange {
+            println!("  exchange:");
+            println!("    side    {}", exchange.side);
+            println!("    buyer   {}", exchange.buyer);
+            println!("    seller  {}", exchange.seller);
+            println!("    asset   {}", exchange.asset_mint);
+            println!("    pair    {}", exchange.pair_mint);
+            println!("    price   {}", exchange.price);
+            println!("    size    {}", exchange.size);
+            println!("    volume  {}", exchange.volume);
+            println!("    fee     {}", exchange.fee);
+            println!("    buddy   {}", exchange.buddy);
+        }
+        if let Some(activity) = &self.sage_activity {
+            println!("  sage activity: {}", activity.kind);
+            println!("    fleet     {}", display(activity.fleet.as_ref()));
+            println!(
+                "    profile   {}",
+                display(activity.player_profile.as_ref())
+            );
+            println!("    starbase  {}", display(activity.starbase.as_ref()));
+            println!(
+                "    sector    {}",
+                display(activity.sector.map(|(x, y)| format!("{},{}", x, y)))
+            );
+            println!("    resource  {}", display(activity.resource_mint.as_ref()));
+            println!("    amount    {}", display(activity.amount));
+            if let Some(label) = &activity.label {
+                println!("    label     {}", label);
+            }
+            for transfer in &activity.transfers {
+                println!(
+                    "    {} mint={} source={} destination={} amount={}",
+                    transfer.kind,
+                    display(transfer.mint.as_ref()),
+                    display(transfer.source.as_ref()),
+                    display(transfer.destination.as_ref()),
+                    transfer.amount,
+                );
+            }
+        }
+        if !self.crafting_events.is_empty() {
+            println!("  crafting:");
+            for event in &self.crafting_events {
+                println!(
+                    "    [{}] {} process={} recipe={} profile={} starbase={} quantity={} mint={} amount={}",
+                    event.position,
+                    event.kind,
+                    display(event.crafting_process.as_ref()),
+                    display(event.recipe.as_ref()),
+                    display(event.player_profile.as_ref()),
+                    display(event.starbase.as_ref()),
+                    display(event.quantity),
+                    display(event.mint.as_ref()),
+                    display(event.amount),
+                );
+            }
+        }
+        if let Some(event) = &self.score_event {
+            println!("  score event: {}", event.kind);
+            println!("    player    {}", display(event.player.as_ref()));
+            println!("    ship      {}", display(event.ship_mint.as_ref()));
+            println!("    resource  {}", display(event.resource.as_ref()));
+            println!("    mint      {}", display(event.mint.as_ref()));
+            println!("    amount    {}", display(event.amount));
+        }
+        if !self.events.is_empty() {
+            println!("  events:");
+            for event in &self.events {
+                match (&event.name, &event.data) {
+                    (Some(name), Some(data)) => println!("    {} {}", name, data),
+                    _ => println!("    {} <unknown> {}", event.program_id, event.raw),
+                }
+            }
+        }
+    }
+}
+
+fn display<T: ToString>(value: Option<T>) -> String {
+    value.map_or_else(|| "-".to_string(), |value| value.to_string())
+}
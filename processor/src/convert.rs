@@ -1,15 +1,14 @@
+use decoder::extra::token::{
+    TokenInstruction, TokenInstructionKind, decode_token_instruction, is_token_program,
+};
 use rust_decimal::Decimal;
 use solana_sdk::pubkey::Pubkey;
-use solana_transaction_status::{UiInstruction, UiTransactionStatusMeta};
+use solana_transaction_status::{UiInstruction, UiParsedInstruction, UiTransactionStatusMeta};
 use std::str::FromStr;
 
 pub fn processor_data(data: String) -> Vec<u8> {
     bs58::decode(data).into_vec().unwrap()
 }
-pub fn string_to_hex(data: String) -> String {
-    let data_bytes = processor_data(data);
-    hex::encode(data_bytes)
-}
 
 pub fn processor_accounts(data: Vec<String>) -> Vec<Pubkey> {
     data.iter()
@@ -36,6 +35,117 @@ pub fn processor_inner(
 
 pub fn convert_to_decimal(amount: u64, decimals: u8) -> Decimal {
     let scale = Decimal::new(1, decimals.into());
-    let decimal = Decimal::from(amount) * scale;
-    return decimal;
+
+    Decimal::from(amount) * scale
+}
+
+/// A token transfer, mint or burn parsed by the RPC
+#[derive(Debug, Clone)]
+pub struct TokenMovement {
+    /// Token instruction making the movement, see [`TokenInstructionKind::movement`]
+    pub kind: TokenInstructionKind,
+    /// Mint of the token (not logged by unchecked transfers)
+    pub mint: Option<String>,
+    /// Token account the tokens were taken from (none for mints)
+    pub source: Option<String>,
+    /// Token account the tokens were moved to (none for burns)
+    pub destination: Option<String>,
+    /// Amount in base units
+    pub amount: i64,
+}
+
+/// Decodes a token transfer, mint or burn of SPL Token or Token-2022
+///
+/// Instructions parsed by the RPC are read from their `jsonParsed` info, others (e.g. of
+/// extensions the RPC does not parse) are decoded from their data.
+pub fn token_instruction(instruction: &UiInstruction) -> Option<TokenInstruction> {
+    match instruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(parsed_instruction)) => {
+            let program_id = Pubkey::from_str(&parsed_instruction.program_id).ok()?;
+            if !is_token_program(&program_id) {
+                return None;
+            }
+            let kind = TokenInstructionKind::from_parsed_type(
+                parsed_instruction.parsed.get("type")?.as_str()?,
+            )?;
+            let info = parsed_instruction.parsed.get("info")?;
+            let field = |name: &str| {
+                info.get(name)
+                    .and_then(|value| value.as_str())
+                    .and_then(|value| Pubkey::from_str(value).ok())
+            };
+            // Checked instructions give the amount with its decimals
+            let token_amount = info.get("tokenAmount");
+            let amount = info
+                .get("amount")
+                .or_else(|| token_amount.and_then(|amount| amount.get("amount")))
+                .and_then(|amount| amount.as_str())
+                .and_then(|amount| amount.parse::<u64>().ok())?;
+
+            let (source, destination) = match kind.movement() {
+                "mint" => (None, field("account")),
+                "burn" => (field("account"), None),
+                _ => (field("source"), field("destination")),
+            };
+
+            Some(TokenInstruction {
+                program_id,
+                kind,
+                mint: field("mint"),
+                source,
+                destination,
+                amount,
+                decimals: token_amount
+                    .and_then(|amount| amount.get("decimals"))
+                    .and_then(|decimals| decimals.as_u64())
+                    .and_then(|decimals| u8::try_from(decimals).ok()),
+                fee: info
+                    .get("feeAmount")
+                    .and_then(|fee| fee.get("amount"))
+                    .and_then(|fee| fee.as_str())
+                    .and_then(|fee| fee.parse::<u64>().ok()),
+            })
+        }
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) => {
+            let program_id = Pubkey::from_str(&instruction.program_id).ok()?;
+            let data = bs58::decode(&instruction.data).into_vec().ok()?;
+            let accounts = instruction
+                .accounts
+                .iter()
+                .map(|account| Pubkey::from_str(account).ok())
+                .collect::<Option<Vec<_>>>()?;
+            decode_token_instruction(&program_id, &data, &accounts)
+        }
+        UiInstruction::Compiled(_) => None,
+    }
+}
+
+/// Collects the token transfers, mints and burns among parsed instructions
+pub fn token_movements(instructions: Vec<UiInstruction>) -> Vec<TokenMovement> {
+    instructions
+        .iter()
+        .filter_map(token_instruction)
+        .filter_map(|instruction| {
+            Some(TokenMovement {
+                kind: instruction.kind,
+                mint: instruction.mint.map(|mint| mint.to_string()),
+                source: instruction.source.map(|source| source.to_string()),
+                destination: instruction
+                    .destination
+                    .map(|destination| destination.to_string()),
+                amount: i64::try_from(instruction.amount).ok()?,
+            })
+        })
+        .collect()
+}
+
+/// Stack height of an instruction, 1 for transaction instructions
+pub fn stack_height(instruction: &UiInstruction) -> Option<u32> {
+    match instruction {
+        UiInstruction::Compiled(instruction) => instruction.stack_height,
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(instruction)) => instruction.stack_height,
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) => {
+            instruction.stack_height
+        }
+    }
 }
@@ -0,0 +1,232 @@
+//! Guild membership
+//!
+//! Guilds are configured in a JSON file, a list of guilds with their name and either the DAO
+//! realm their members are read from or the list of their member wallets (or both):
+//!
+//! ```json
+//! [
+//!   { "name": "Guild A", "tag": "GA", "realm": "<realm address>" },
+//!   { "name": "Guild B", "members": ["<wallet>", "<wallet>"] }
+//! ]
+//! ```
+//!
+//! Realm members are the owners of the realm's token owner records with a deposit of at least
+//! `min_deposit` (default 1), optionally only in `governing_token_mint`. Realms of another
+//! SPL Governance deployment set its address as `governance_program`.
+
+use crate::{MAX_ATTEMPTS, rpc_with_retry};
+use anyhow::Context;
+use db::{DbPool, NewGuild};
+use decoder::extra::governance::{self, REALM_OFFSET, decode_token_owner_record};
+use serde::Deserialize;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::pubkey::Pubkey;
+use std::collections::BTreeSet;
+use std::str::FromStr;
+
+/// A guild of the config file
+#[derive(Debug, Deserialize)]
+struct GuildConfig {
+    name: String,
+    #[serde(default)]
+    tag: Option<String>,
+    /// DAO realm the members are read from
+    #[serde(default)]
+    realm: Option<String>,
+    /// SPL Governance deployment of the realm
+    #[serde(default)]
+    governance_program: Option<String>,
+    /// Only count deposits of this mint (community or council)
+    #[serde(default)]
+    governing_token_mint: Option<String>,
+    /// Smallest deposit making a wallet a member
+    #[serde(default = "default_min_deposit")]
+    min_deposit: u64,
+    /// Member wallets
+    #[serde(default)]
+    members: Vec<String>,
+}
+
+fn default_min_deposit() -> u64 {
+    1
+}
+
+/// Reads the guild config, resolves the members and stores them
+pub async fn update_guilds(pool: &DbPool, client: &RpcClient, path: &str) -> anyhow::Result<()> {
+    let config = std::fs::read_to_string(path)
+        .with_context(|| format!("could not read guild config {}", path))?;
+    let configs =
+        parse_config(&config).with_context(|| format!("invalid guild config {}", path))?;
+
+    let mut guilds = Vec::with_capacity(configs.len());
+    for config in configs {
+        let mut members = listed_members(&config)?;
+        if let Some(realm) = &config.realm {
+            members.extend(realm_members(client, &config, realm).await?);
+        }
+        log::info!("Guild {} has {} members", config.name, members.len());
+
+        guilds.push(NewGuild {
+            source: if config.realm.is_some() {
+                "realm".to_string()
+            } else {
+                "list".to_string()
+            },
+            name: config.name,
+            tag: config.tag,
+            realm: config.realm,
+            members: members.into_iter().collect(),
+        });
+    }
+
+    let changed = db::replace_guilds(pool, &guilds).await?;
+    log::info!(
+        "Stored {} guilds, {} player guilds changed",
+        guilds.len(),
+        changed
+    );
+
+    Ok(())
+}
+
+/// Parses the guilds of a config file
+fn parse_config(config: &str) -> serde_json::Result<Vec<GuildConfig>> {
+    serde_json::from_str(config)
+}
+
+/// Returns the member wallets listed in the config of a guild
+fn listed_members(config: &GuildConfig) -> anyhow::Result<BTreeSet<String>> {
+    config
+        .members
+        .iter()
+        .map(|member| {
+            Pubkey::from_str(member)
+                .map(|wallet| wallet.to_string())
+                .with_context(|| format!("invalid member {} of {}", member, config.name))
+        })
+        .collect()
+}
+
+/// Reads the member wallets of a DAO realm from its token owner records
+async fn realm_members(
+    client: &RpcClient,
+    config: &GuildConfig,
+    realm: &str,
+) -> anyhow::Result<Vec<String>> {
+    let realm = Pubkey::from_str(realm)
+        .with_context(|| format!("invalid realm {} of {}", realm, config.name))?;
+    let program_id = match &config.governance_program {
+        Some(program) => Pubkey::from_str(program).with_context(|| {
+            format!("invalid governance program {} of {}", program, config.name)
+        })?,
+        None => governance::ID,
+    };
+    let mint = config
+        .governing_token_mint
+        .as_deref()
+        .map(Pubkey::from_str)
+        .transpose()
+        .with_context(|| format!("invalid governing token mint of {}", config.name))?;
+
+    let rpc_config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            REALM_OFFSET,
+            realm.to_bytes().to_vec(),
+        ))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let accounts = rpc_with_retry(
+        || client.get_program_accounts_with_config(&program_id, rpc_config.clone()),
+        MAX_ATTEMPTS,
+    )
+    .await?;
+
+    // Other accounts of the realm (governances, proposals, ...) are skipped
+    Ok(accounts
+        .iter()
+        .filter_map(|(_, account)| decode_token_owner_record(&account.data))
+        .filter(|record| mint.is_none_or(|mint| record.governing_token_mint == mint))
+        .filter(|record| record.governing_token_deposit_amount >= config.min_deposit)
+        .map(|record| record.governing_token_owner.to_string())
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_realm_and_listed_guilds_with_defaults() {
+        let wallet = Pubkey::new_unique().to_string();
+        let config = format!(
+            r#"[
+                {{ "name": "Guild A", "tag": "GA", "realm": "realm", "min_deposit": 100 }},
+                {{ "name": "Guild B", "members": ["{}"] }}
+            ]"#,
+            wallet
+        );
+
+        let guilds = parse_config(&config).unwrap();
+
+        let [realm, listed] = &guilds[..] else {
+            panic!("expected two guilds");
+        };
+        assert_eq!(realm.name, "Guild A");
+        assert_eq!(realm.tag.as_deref(), Some("GA"));
+        assert_eq!(realm.realm.as_deref(), Some("realm"));
+        assert_eq!(realm.governance_program, None);
+        assert_eq!(realm.governing_token_mint, None);
+        assert_eq!(realm.min_deposit, 100);
+        assert!(realm.members.is_empty());
+        assert_eq!(listed.name, "Guild B");
+        assert_eq!(listed.tag, None);
+        assert_eq!(listed.realm, None);
+        assert_eq!(listed.min_deposit, 1);
+        assert_eq!(listed.members, [wallet]);
+    }
+
+    #[test]
+    fn rejects_malformed_configs() {
+        assert!(parse_config(r#"[{ "members": [] }]"#).is_err());
+        assert!(parse_config(r#"{ "name": "Guild A" }"#).is_err());
+        assert!(parse_config(r#"[{ "name": "Guild A", "min_deposit": -1 }]"#).is_err());
+    }
+
+    #[test]
+    fn listed_members_are_deduplicated_wallets() {
+        let wallet = Pubkey::new_unique().to_string();
+        let guild = GuildConfig {
+            members: vec![wallet.clone(), wallet.clone()],
+            ..parse_config(r#"[{ "name": "Guild B" }]"#)
+                .unwrap()
+                .remove(0)
+        };
+
+        assert_eq!(
+            listed_members(&guild)
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>(),
+            [wallet]
+        );
+    }
+
+    #[test]
+    fn rejects_invalid_member_wallets() {
+        let guild = parse_config(r#"[{ "name": "Guild B", "members": ["not a wallet"] }]"#)
+            .unwrap()
+            .remove(0);
+
+        assert_eq!(
+            listed_members(&guild).unwrap_err().to_string(),
+            "invalid member not a wallet of Guild B"
+        );
+    }
+}
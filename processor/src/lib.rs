@@ -0,0 +1,8 @@
+//! Processing of indexed transactions into the derived tables
+//!
+//! Shared by the `processor` binary, which claims and processes the indexed signatures, and the
+//! `decode` binary, which shows what the processing makes of a transaction without storing it.
+
+pub mod convert;
+pub mod logs;
+pub mod processor;
@@ -0,0 +1,65 @@
+//! Program invocations and events from the log messages of a transaction
+
+use chrono::{DateTime, Utc};
+use db::{NewProgramEvent, NewProgramInvocation, TransactionLogs};
+use decoder::idl::IdlRegistry;
+use decoder::logs::parse_logs;
+use solana_sdk::pubkey::Pubkey;
+use std::str::FromStr;
+
+/// Parses the log messages of a transaction, decoding events with the IDLs valid at its slot
+pub fn transaction_logs(
+    idls: &IdlRegistry,
+    signature: &str,
+    slot: u64,
+    timestamp: DateTime<Utc>,
+    messages: &[String],
+) -> TransactionLogs {
+    let parsed = parse_logs(messages);
+    if parsed.truncated {
+        log::warn!("Logs of {} are truncated", signature);
+    }
+
+    let mut invocations = Vec::with_capacity(parsed.invocations.len());
+    let mut events = Vec::new();
+    for (position, invocation) in parsed.invocations.into_iter().enumerate() {
+        let program_id = Pubkey::from_str(&invocation.program_id).ok();
+        for (event_index, raw) in invocation.data.into_iter().enumerate() {
+            let event = program_id.and_then(|program_id| {
+                idls.decode_event(&program_id, slot, &raw)
+                    .unwrap_or_else(|err| {
+                        log::warn!("Could not decode event of {}: {}", signature, err);
+                        None
+                    })
+            });
+            events.push(NewProgramEvent {
+                position: position as i32,
+                event_index: event_index as i32,
+                program_id: invocation.program_id.clone(),
+                name: event.as_ref().map(|event| event.name.clone()),
+                data: event.map(|event| event.data),
+                raw,
+            });
+        }
+
+        invocations.push(NewProgramInvocation {
+            position: position as i32,
+            instruction_index: invocation.instruction_index as i32,
+            depth: invocation.depth as i16,
+            program_id: invocation.program_id,
+            instruction_name: invocation.instruction_name,
+            compute_units_consumed: invocation.compute_units_consumed.map(|units| units as i64),
+            compute_units_limit: invocation.compute_units_limit.map(|units| units as i64),
+            error: invocation.error,
+            logs: invocation.logs.join("\n"),
+        });
+    }
+
+    TransactionLogs {
+        signature: signature.to_string(),
+        slot: slot as i64,
+        timestamp,
+        invocations,
+        events,
+    }
+}
@@ -1,11 +1,16 @@
-use crate::args::Args;
-
-use crate::convert::{processor_accounts, processor_data, processor_inner};
-use crate::processor::marketplace::MarketplaceProcessor;
+use crate::args::{Args, Command, SnapshotProgram};
 
+use chrono::DateTime;
 use clap::Parser;
-use db::update_program_signature_processed;
-use solana_client::rpc_client::{GetConfirmedSignaturesForAddress2Config, RpcClient};
+use db::{DbPool, update_program_signature_processed};
+use decoder::idl::IdlRegistry;
+use processor::convert::{processor_accounts, processor_data, processor_inner};
+use processor::processor::PROCESSOR_VERSION;
+use processor::processor::crafting::CraftingProcessor;
+use processor::processor::marketplace::MarketplaceProcessor;
+use processor::processor::sage::SageProcessor;
+use processor::processor::score::ScoreProcessor;
+use solana_client::nonblocking::rpc_client::RpcClient;
 use solana_client::rpc_config::RpcTransactionConfig;
 use solana_commitment_config::CommitmentConfig;
 
@@ -17,12 +22,14 @@ use solana_transaction_status::{
 };
 use std::env;
 use std::str::FromStr;
+use std::sync::Arc;
 use std::time::Duration;
+use tokio::task::JoinSet;
 use tokio::time::sleep;
 
 mod args;
-mod convert;
-mod processor;
+mod guilds;
+mod snapshot;
 
 const SLEEP: Duration = Duration::from_secs(5);
 const MAX_ATTEMPTS: usize = 5;
@@ -44,16 +51,10 @@ pub async fn main() -> anyhow::Result<()> {
         .filter(None, log::LevelFilter::Info)
         .init();
 
-    let client = RpcClient::new_with_commitment(
-        String::from(env::var("RPC_URL").expect("RPC_URL must be set")),
+    let client = Arc::new(RpcClient::new_with_commitment(
+        env::var("RPC_URL").expect("RPC_URL must be set"),
         CommitmentConfig::confirmed(),
-    );
-
-    let transaction_config = RpcTransactionConfig {
-        commitment: CommitmentConfig::finalized().into(),
-        encoding: UiTransactionEncoding::JsonParsed.into(),
-        max_supported_transaction_version: Some(0),
-    };
+    ));
 
     let program_id = Pubkey::from_str(
         env::var("PROGRAM_ID")
@@ -61,123 +62,437 @@ pub async fn main() -> anyhow::Result<()> {
             .as_str(),
     )?;
 
+    let workers = env::var("WORKERS")
+        .unwrap_or_else(|_| "1".to_string())
+        .parse::<usize>()
+        .expect("WORKERS must be a positive number")
+        .max(1);
+    let batch_size = env::var("BATCH_SIZE")
+        .unwrap_or_else(|_| "100".to_string())
+        .parse::<i64>()
+        .expect("BATCH_SIZE must be a number");
+    let lease = Duration::from_secs(
+        env::var("LEASE_SECONDS")
+            .unwrap_or_else(|_| "600".to_string())
+            .parse::<u64>()
+            .expect("LEASE_SECONDS must be a number"),
+    );
+
+    // IDLs from IDL_DIR add versions for other slot ranges or replace the built-in ones
+    let mut idls = IdlRegistry::builtin();
+    if let Ok(idl_dir) = env::var("IDL_DIR") {
+        let loaded = idls.load_dir(&idl_dir)?;
+        log::info!("Loaded {} IDLs from {}", loaded, idl_dir);
+    }
+    if program_id == decoder::staratlas::sage::ID && idls.versions(&program_id).is_empty() {
+        anyhow::bail!("No SAGE IDL registered, add it to IDL_DIR");
+    }
+    if program_id == decoder::staratlas::crafting::ID && idls.versions(&program_id).is_empty() {
+        anyhow::bail!("No crafting IDL registered, add it to IDL_DIR");
+    }
+    if program_id == decoder::staratlas::score::ID && idls.versions(&program_id).is_empty() {
+        anyhow::bail!("No SCORE IDL registered, add it to IDL_DIR");
+    }
+    let idls = Arc::new(idls);
+
     let pool = db::establish_connection().await?;
 
+    if let Some(Command::Reprocess {
+        from_slot,
+        to_slot,
+        from_timestamp,
+        to_timestamp,
+    }) = args.command
+    {
+        let reset = db::reset_program_signatures_processed(
+            &pool,
+            &program_id.to_string(),
+            from_slot,
+            to_slot,
+            from_timestamp,
+            to_timestamp,
+        )
+        .await?;
+        log::info!(
+            "Marked {} signatures of {} for reprocessing with version {}",
+            reset,
+            program_id,
+            PROCESSOR_VERSION
+        );
+        db::update_program_watermark(&pool, &program_id.to_string()).await?;
+        return Ok(());
+    }
+
+    if let Some(Command::Snapshot {
+        program,
+        interval_seconds,
+    }) = args.command
+    {
+        let take_snapshot = || async {
+            match program {
+                SnapshotProgram::Marketplace => {
+                    snapshot::snapshot_marketplace(&pool, &client).await
+                }
+                SnapshotProgram::Crafting => {
+                    snapshot::snapshot_crafting(&pool, &client, &idls).await
+                }
+                SnapshotProgram::Profiles => {
+                    snapshot::snapshot_profiles(&pool, &client, &idls).await
+                }
+                SnapshotProgram::Factions => {
+                    snapshot::snapshot_factions(&pool, &client, &idls).await
+                }
+            }
+        };
+        let Some(interval) = interval_seconds else {
+            return take_snapshot().await;
+        };
+        loop {
+            if let Err(err) = take_snapshot().await {
+                log::error!("{:?} snapshot failed: {:?}", program, err);
+            }
+            sleep(Duration::from_secs(interval)).await;
+        }
+    }
+
+    if let Some(Command::Guilds {
+        config,
+        interval_seconds,
+    }) = args.command
+    {
+        let Some(interval) = interval_seconds else {
+            return guilds::update_guilds(&pool, &client, &config).await;
+        };
+        loop {
+            if let Err(err) = guilds::update_guilds(&pool, &client, &config).await {
+                log::error!("Guild update failed: {:?}", err);
+            }
+            sleep(Duration::from_secs(interval)).await;
+        }
+    }
+
+    if let Some(signature) = args.signature {
+        process_signature(&pool, &client, &idls, &program_id, &signature).await?;
+        db::refresh_pending_market_stats(&pool).await?;
+        return Ok(());
+    }
+
+    // Workers (and containers) claim disjoint batches of the oldest signatures and process them
+    // in parallel. The rows of a crashed worker are taken over once its lease expires.
+    let host = env::var("HOSTNAME").unwrap_or_else(|_| std::process::id().to_string());
+    let mut tasks = JoinSet::new();
+    for worker in 0..workers {
+        tasks.spawn(run_worker(
+            format!("{}-{}", host, worker),
+            pool.clone(),
+            client.clone(),
+            idls.clone(),
+            program_id,
+            batch_size,
+            lease,
+        ));
+    }
+
+    log::info!(
+        "Started {} workers for {} (batch size {}, lease {}s)",
+        workers,
+        program_id,
+        batch_size,
+        lease.as_secs()
+    );
+
+    // A failing worker stops the processor; its claimed rows are picked up again after the lease
+    while let Some(result) = tasks.join_next().await {
+        result??;
+    }
+
+    Ok(())
+}
+
+async fn run_worker(
+    worker_id: String,
+    pool: DbPool,
+    client: Arc<RpcClient>,
+    idls: Arc<IdlRegistry>,
+    program_id: Pubkey,
+    batch_size: i64,
+    lease: Duration,
+) -> anyhow::Result<()> {
     loop {
-        let db_signatures: Vec<String> = match args.signature.as_ref() {
-            Some(signature) => vec![signature.clone()],
-            None => db::get_unprocessed_program_signatures_by_program_id(
+        let claimed = db::claim_unprocessed_program_signatures(
+            &pool,
+            &program_id.to_string(),
+            &worker_id,
+            batch_size,
+            lease.as_secs() as i64,
+        )
+        .await?;
+
+        if claimed.is_empty() {
+            log::info!(
+                "[{}] All processed. Sleeping for {}s",
+                worker_id,
+                SLEEP.as_secs()
+            );
+            sleep(SLEEP).await;
+            continue;
+        }
+
+        // Batches are claimed and processed in block order, the watermark tells up to which
+        // slot the data is complete.
+        let signatures: Vec<String> = claimed
+            .into_iter()
+            .map(|program_signature| program_signature.signature)
+            .collect();
+        for (position, signature) in signatures.iter().enumerate() {
+            // A batch may outrun its lease, renew it so no other worker takes over the rows
+            // being processed
+            let remaining = &signatures[position..];
+            let renewed = db::renew_program_signature_claims(
                 &pool,
                 &program_id.to_string(),
-                1000,
-            )
-            .await?
-            .iter()
-            .map(|signature| signature.signature.clone())
-            .collect(),
-        };
-
-        for db_signature in db_signatures {
-            log::info!("Processing signature: {:?}", db_signature);
-
-            let transaction = rpc_with_retry(
-                || {
-                    client.get_transaction_with_config(
-                        &Signature::from_str(db_signature.as_str()).unwrap(),
-                        transaction_config,
-                    )
-                },
-                MAX_ATTEMPTS,
+                &worker_id,
+                remaining,
+                lease.as_secs() as i64,
             )
             .await?;
+            if renewed < remaining.len() as u64 {
+                log::warn!(
+                    "[{}] Lost the claim on {} signatures, leaving the batch to other workers",
+                    worker_id,
+                    remaining.len() as u64 - renewed
+                );
+                break;
+            }
+
+            process_signature(&pool, &client, &idls, &program_id, signature).await?;
+        }
+
+        // The market aggregates are refreshed once for the exchanges written by the batch
+        db::refresh_pending_market_stats(&pool).await?;
+
+        let watermark = db::update_program_watermark(&pool, &program_id.to_string()).await?;
+        log::info!("[{}] Processed up to slot {:?}", worker_id, watermark.slot);
+    }
+}
+
+async fn process_signature(
+    pool: &DbPool,
+    client: &RpcClient,
+    idls: &Arc<IdlRegistry>,
+    program_id: &Pubkey,
+    db_signature: &str,
+) -> anyhow::Result<()> {
+    log::info!("Processing signature: {:?}", db_signature);
+
+    let transaction_config = RpcTransactionConfig {
+        commitment: CommitmentConfig::finalized().into(),
+        encoding: UiTransactionEncoding::JsonParsed.into(),
+        max_supported_transaction_version: Some(0),
+    };
+    let signature = Signature::from_str(db_signature)?;
 
-            let transaction_meta = transaction.transaction.meta.unwrap();
-
-            if transaction_meta.status.is_ok() {
-                match transaction.transaction.transaction {
-                    EncodedTransaction::Json(json) => match json.message {
-                        UiMessage::Parsed(parsed) => {
-                            for (instruction_index, instruction) in
-                                parsed.instructions.into_iter().enumerate()
-                            {
-                                match instruction {
-                                    UiInstruction::Parsed(parsed) => match parsed {
-                                        UiParsedInstruction::PartiallyDecoded(instruction) => {
-                                            match Pubkey::from_str(instruction.program_id.as_str())?
-                                            {
-                                                decoder::staratlas::marketplace::ID => {
-                                                    MarketplaceProcessor::new(pool.clone())
-                                                        .process(
-                                                            transaction.slot,
-                                                            transaction.block_time.unwrap(),
-                                                            db_signature.clone(),
-                                                            instruction_index,
-                                                            processor_data(instruction.data),
-                                                            processor_accounts(
-                                                                instruction.accounts,
-                                                            ),
-                                                            processor_inner(
-                                                                transaction_meta.clone(),
-                                                                instruction_index,
-                                                            ),
-                                                        )
-                                                        .await?;
-
-                                                    //UPDATE DB
-                                                    update_program_signature_processed(
-                                                        &pool,
-                                                        &decoder::staratlas::marketplace::ID
-                                                            .to_string(),
-                                                        &db_signature,
-                                                        true,
-                                                    )
-                                                    .await?;
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                        UiParsedInstruction::Parsed(instruction) => {
-                                            match Pubkey::from_str(instruction.program_id.as_str())?
-                                            {
-                                                decoder::staratlas::marketplace::ID => {
-                                                    panic!("unimplemented for marketplace")
-                                                }
-                                                _ => {}
-                                            }
-                                        }
-                                    },
-                                    _ => panic!("Unhandled UiInstruction type"),
+    let transaction = rpc_with_retry(
+        || client.get_transaction_with_config(&signature, transaction_config),
+        MAX_ATTEMPTS,
+    )
+    .await?;
+
+    let transaction_meta = transaction.transaction.meta.unwrap();
+    let timestamp = DateTime::from_timestamp(transaction.block_time.unwrap(), 0).unwrap();
+
+    // Logs are stored for failed transactions too, they carry the error
+    if let Some(messages) = Option::<Vec<String>>::from(transaction_meta.log_messages.clone()) {
+        let transaction_logs = processor::logs::transaction_logs(
+            idls,
+            db_signature,
+            transaction.slot,
+            timestamp,
+            &messages,
+        );
+        db::replace_transaction_logs(pool, &transaction_logs).await?;
+    }
+
+    // Only instructions of the processed program are handled, other programs of the
+    // transaction are left to their own processor
+    let mut exchanges = vec![];
+    let mut sage_activities = vec![];
+    let mut crafting_events = vec![];
+    let mut score_events = vec![];
+    if transaction_meta.status.is_ok() {
+        match transaction.transaction.transaction {
+            EncodedTransaction::Json(json) => match json.message {
+                UiMessage::Parsed(parsed) => {
+                    for (instruction_index, instruction) in
+                        parsed.instructions.into_iter().enumerate()
+                    {
+                        match instruction {
+                            UiInstruction::Parsed(parsed) => match parsed {
+                                UiParsedInstruction::PartiallyDecoded(instruction) => {
+                                    let instruction_program =
+                                        Pubkey::from_str(instruction.program_id.as_str())?;
+
+                                    // The crafting program is invoked by other programs, mostly SAGE
+                                    if *program_id == decoder::staratlas::crafting::ID {
+                                        crafting_events.extend(
+                                            CraftingProcessor::new(idls.clone()).events(
+                                                transaction.slot,
+                                                db_signature,
+                                                instruction_index,
+                                                &instruction_program,
+                                                &processor_data(instruction.data),
+                                                &processor_accounts(instruction.accounts),
+                                                processor_inner(
+                                                    transaction_meta.clone(),
+                                                    instruction_index,
+                                                ),
+                                            )?,
+                                        );
+                                        continue;
+                                    }
+
+                                    if instruction_program != *program_id {
+                                        continue;
+                                    }
+
+                                    if instruction_program == decoder::staratlas::marketplace::ID {
+                                        exchanges.extend(
+                                            MarketplaceProcessor::new(idls.clone()).process(
+                                                transaction.slot,
+                                                transaction.block_time.unwrap(),
+                                                db_signature.to_string(),
+                                                instruction_index,
+                                                processor_data(instruction.data),
+                                                processor_accounts(instruction.accounts),
+                                                processor_inner(
+                                                    transaction_meta.clone(),
+                                                    instruction_index,
+                                                ),
+                                            )?,
+                                        );
+                                    } else if instruction_program == decoder::staratlas::sage::ID {
+                                        sage_activities.extend(
+                                            SageProcessor::new(idls.clone()).activity(
+                                                transaction.slot,
+                                                db_signature,
+                                                instruction_index,
+                                                &processor_data(instruction.data),
+                                                &processor_accounts(instruction.accounts),
+                                                processor_inner(
+                                                    transaction_meta.clone(),
+                                                    instruction_index,
+                                                ),
+                                            )?,
+                                        );
+                                    } else if instruction_program == decoder::staratlas::score::ID {
+                                        score_events.extend(
+                                            ScoreProcessor::new(idls.clone()).event(
+                                                transaction.slot,
+                                                db_signature,
+                                                instruction_index,
+                                                &processor_data(instruction.data),
+                                                &processor_accounts(instruction.accounts),
+                                                processor_inner(
+                                                    transaction_meta.clone(),
+                                                    instruction_index,
+                                                ),
+                                            )?,
+                                        );
+                                    }
+                                }
+                                UiParsedInstruction::Parsed(instruction) => {
+                                    if Pubkey::from_str(instruction.program_id.as_str())?
+                                        == decoder::staratlas::marketplace::ID
+                                    {
+                                        anyhow::bail!(
+                                            "Parsed marketplace instruction {} of {} is not supported",
+                                            instruction_index,
+                                            db_signature
+                                        )
+                                    }
                                 }
-                            }
+                            },
+                            _ => anyhow::bail!("Unhandled UiInstruction type in {}", db_signature),
                         }
-                        _ => panic!("Unhandled UiMessage type"),
-                    },
-                    _ => panic!("Unhandled EncodedTransaction type"),
+                    }
                 }
-            };
-
-            //UPDATE DB
-            update_program_signature_processed(
-                &pool,
-                &decoder::staratlas::marketplace::ID.to_string(),
-                &db_signature,
-                true,
-            )
-            .await?;
+                _ => anyhow::bail!("Unhandled UiMessage type in {}", db_signature),
+            },
+            _ => anyhow::bail!("Unhandled EncodedTransaction type in {}", db_signature),
         }
+    };
+
+    if *program_id == decoder::staratlas::marketplace::ID {
+        // Exchanges the transaction no longer produces are dropped with the rewrite
+        db::replace_exchanges(
+            pool,
+            &db::MarketplaceTransaction {
+                signature: db_signature.to_string(),
+                exchanges,
+            },
+        )
+        .await?;
+    }
+
+    if *program_id == decoder::staratlas::sage::ID {
+        db::replace_sage_activities(
+            pool,
+            &db::SageTransaction {
+                signature: db_signature.to_string(),
+                slot: transaction.slot as i64,
+                timestamp,
+                activities: sage_activities,
+            },
+        )
+        .await?;
+    }
+
+    if *program_id == decoder::staratlas::crafting::ID {
+        db::replace_crafting_events(
+            pool,
+            &db::CraftingTransaction {
+                signature: db_signature.to_string(),
+                slot: transaction.slot as i64,
+                timestamp,
+                events: crafting_events,
+            },
+        )
+        .await?;
+    }
 
-        log::info!("All processed. Sleeping for {}s", SLEEP.as_secs());
-        sleep(SLEEP).await;
+    if *program_id == decoder::staratlas::score::ID {
+        db::replace_score_events(
+            pool,
+            &db::ScoreTransaction {
+                signature: db_signature.to_string(),
+                slot: transaction.slot as i64,
+                timestamp,
+                events: score_events,
+            },
+        )
+        .await?;
     }
+
+    //UPDATE DB
+    update_program_signature_processed(
+        pool,
+        &program_id.to_string(),
+        &db_signature.to_string(),
+        true,
+    )
+    .await?;
+
+    Ok(())
 }
 
-async fn rpc_with_retry<F, T>(mut f: F, max_attempts: usize) -> Result<T, ClientError>
+async fn rpc_with_retry<F, Fut, T>(mut f: F, max_attempts: usize) -> Result<T, ClientError>
 where
-    F: FnMut() -> Result<T, ClientError>,
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, ClientError>>,
 {
     let mut attempt = 0;
     loop {
-        match f() {
+        match f().await {
             Ok(val) => return Ok(val),
             Err(e) => {
                 attempt += 1;
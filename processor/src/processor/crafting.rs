@@ -0,0 +1,325 @@
+use crate::convert::{
+    TokenMovement, processor_accounts, processor_data, stack_height, token_movements,
+};
+use anyhow::Context;
+use decoder::idl::{DecodedIdlInstruction, IdlRegistry};
+use decoder::staratlas::crafting::CraftingEventKind;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::{UiInstruction, UiParsedInstruction};
+use std::str::FromStr;
+use std::sync::Arc;
+
+pub struct CraftingProcessor {
+    /// Instructions are decoded with the IDL deployed at their slot
+    pub idls: Arc<IdlRegistry>,
+}
+
+/// Player and starbase of the SAGE instruction invoking the crafting program
+#[derive(Debug, Clone, Default)]
+pub struct CraftingContext {
+    pub player_profile: Option<String>,
+    pub starbase: Option<String>,
+}
+
+impl CraftingProcessor {
+    pub fn new(idls: Arc<IdlRegistry>) -> Self {
+        CraftingProcessor { idls }
+    }
+
+    /// Derives the crafting steps of a transaction instruction
+    ///
+    /// The crafting program is mostly invoked by SAGE, so the instruction itself and all its
+    /// inner instructions of the crafting program are looked at. The player and starbase are
+    /// taken from the SAGE instruction when an IDL for it is registered. Crafting and SAGE
+    /// instructions the IDL registered for the slot does not define or cannot decode are
+    /// errors.
+    #[allow(clippy::too_many_arguments)]
+    pub fn events(
+        &self,
+        slot: u64,
+        signature: &str,
+        index: usize,
+        program_id: &Pubkey,
+        data: &[u8],
+        accounts: &[Pubkey],
+        inner_instructions: Vec<UiInstruction>,
+    ) -> anyhow::Result<Vec<db::NewCraftingEvent>> {
+        let context = if *program_id == decoder::staratlas::sage::ID
+            && self.idls.idl(program_id, slot).is_some()
+        {
+            Self::context(&self.decode(program_id, slot, signature, data, accounts)?)
+        } else {
+            CraftingContext::default()
+        };
+
+        let mut events = vec![];
+        if *program_id == decoder::staratlas::crafting::ID {
+            let instruction = self.decode(program_id, slot, signature, data, accounts)?;
+            if let Some(kind) = CraftingEventKind::from_instruction(&instruction.name) {
+                events.push(Self::map_event(
+                    kind,
+                    index,
+                    0,
+                    &instruction,
+                    &context,
+                    token_movements(inner_instructions.clone()),
+                ));
+            }
+        }
+
+        for (inner_index, inner) in inner_instructions.iter().enumerate() {
+            let UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(instruction)) = inner
+            else {
+                continue;
+            };
+            let Ok(inner_program) = Pubkey::from_str(&instruction.program_id) else {
+                continue;
+            };
+            if inner_program != decoder::staratlas::crafting::ID {
+                continue;
+            }
+            let decoded = self.decode(
+                &inner_program,
+                slot,
+                signature,
+                &processor_data(instruction.data.clone()),
+                &processor_accounts(instruction.accounts.clone()),
+            )?;
+            let Some(kind) = CraftingEventKind::from_instruction(&decoded.name) else {
+                continue;
+            };
+
+            // The token movements it made follow it, deeper in the stack
+            let height = instruction.stack_height.unwrap_or(2);
+            let invoked = inner_instructions[inner_index + 1..]
+                .iter()
+                .take_while(|invoked| stack_height(invoked).is_some_and(|h| h > height))
+                .cloned()
+                .collect();
+
+            events.push(Self::map_event(
+                kind,
+                index,
+                inner_index + 1,
+                &decoded,
+                &context,
+                token_movements(invoked),
+            ));
+        }
+
+        Ok(events)
+    }
+
+    /// Decodes an instruction with the IDL registered for the slot, failing if it does not
+    /// define or cannot decode it
+    fn decode(
+        &self,
+        program_id: &Pubkey,
+        slot: u64,
+        signature: &str,
+        data: &[u8],
+        accounts: &[Pubkey],
+    ) -> anyhow::Result<DecodedIdlInstruction> {
+        let instruction = self
+            .idls
+            .decode_instruction(program_id, slot, data, accounts)
+            .with_context(|| {
+                format!(
+                    "Could not decode {} instruction [{}]",
+                    program_id, signature
+                )
+            })?;
+
+        // Not part of the IDL version registered for the slot
+        instruction.with_context(|| {
+            format!(
+                "Unknown {} instruction [{}] {}",
+                program_id,
+                signature,
+                hex::encode(data)
+            )
+        })
+    }
+
+    /// Takes the player and starbase from a decoded SAGE instruction
+    pub fn context(instruction: &DecodedIdlInstruction) -> CraftingContext {
+        let account = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| instruction.account(name))
+                .map(ToString::to_string)
+        };
+        CraftingContext {
+            player_profile: account(&["owning_profile", "player_profile", "profile"]),
+            starbase: account(&["starbase"]),
+        }
+    }
+
+    /// Maps a decoded crafting instruction to its step row
+    pub fn map_event(
+        kind: CraftingEventKind,
+        index: usize,
+        position: usize,
+        instruction: &DecodedIdlInstruction,
+        context: &CraftingContext,
+        movements: Vec<TokenMovement>,
+    ) -> db::NewCraftingEvent {
+        let account = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| instruction.account(name))
+                .map(ToString::to_string)
+        };
+
+        // An ingredient or output moves in a single token instruction
+        let movement = movements.first();
+
+        db::NewCraftingEvent {
+            instruction_index: index as i32,
+            position: position as i32,
+            kind: kind.as_str().to_string(),
+            crafting_process: account(&["crafting_process"]),
+            recipe: account(&["recipe"]),
+            player_profile: context.player_profile.clone(),
+            starbase: context.starbase.clone(),
+            quantity: instruction.arg("quantity").and_then(Value::as_i64),
+            mint: movement
+                .and_then(|movement| movement.mint.clone())
+                .or_else(|| account(&["token_mint", "mint"])),
+            amount: movement
+                .map(|movement| movement.amount)
+                .or_else(|| instruction.arg("amount").and_then(Value::as_i64)),
+            args: instruction.args.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decoder::idl::sighash;
+    use serde_json::json;
+    use solana_transaction_status::UiPartiallyDecodedInstruction;
+    use solana_transaction_status::parse_instruction::ParsedInstruction;
+
+    fn registry() -> Arc<IdlRegistry> {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        Arc::new(registry)
+    }
+
+    #[test]
+    fn burn_through_sage_records_player_starbase_and_burned_tokens() {
+        let sage: Vec<_> = (0..15).map(|_| Pubkey::new_unique()).collect();
+        let (starbase, process, recipe, mint) = (sage[5], sage[9], sage[10], sage[12]);
+        let mut data = sighash("global", "burn_crafting_consumables");
+        data.extend(0u16.to_le_bytes());
+        let mut burn = sighash("global", "burn_consumable_ingredient");
+        burn.extend(0u16.to_le_bytes());
+
+        let inner = vec![
+            UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+                UiPartiallyDecodedInstruction {
+                    program_id: decoder::staratlas::crafting::ID.to_string(),
+                    accounts: [process, recipe, sage[11], mint, sage[13]]
+                        .iter()
+                        .map(ToString::to_string)
+                        .collect(),
+                    data: bs58::encode(burn).into_string(),
+                    stack_height: Some(2),
+                },
+            )),
+            UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
+                program: "spl-token".to_string(),
+                program_id: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+                parsed: json!({
+                    "type": "burn",
+                    "info": {
+                        "account": sage[11].to_string(),
+                        "mint": mint.to_string(),
+                        "authority": sage[7].to_string(),
+                        "amount": "250",
+                    },
+                }),
+                stack_height: Some(3),
+            })),
+        ];
+
+        let events = CraftingProcessor::new(registry()).events(
+            1,
+            "sig",
+            4,
+            &decoder::staratlas::sage::ID,
+            &data,
+            &sage,
+            inner,
+        );
+
+        let events = events.unwrap();
+        assert_eq!(events.len(), 1);
+        let event = &events[0];
+        assert_eq!(event.kind, "input_consumed");
+        assert_eq!((event.instruction_index, event.position), (4, 1));
+        assert_eq!(event.crafting_process, Some(process.to_string()));
+        assert_eq!(event.recipe, Some(recipe.to_string()));
+        // key, profile, ... of the SAGE instruction
+        assert_eq!(event.player_profile, Some(sage[1].to_string()));
+        assert_eq!(event.starbase, Some(starbase.to_string()));
+        assert_eq!(event.mint, Some(mint.to_string()));
+        assert_eq!(event.amount, Some(250));
+    }
+
+    #[test]
+    fn fails_on_unknown_crafting_instructions() {
+        let unknown = sighash("global", "no_such_instruction");
+        let inner = vec![UiInstruction::Parsed(
+            UiParsedInstruction::PartiallyDecoded(UiPartiallyDecodedInstruction {
+                program_id: decoder::staratlas::crafting::ID.to_string(),
+                accounts: vec![],
+                data: bs58::encode(&unknown).into_string(),
+                stack_height: Some(2),
+            }),
+        )];
+        let processor = CraftingProcessor::new(registry());
+
+        let direct = processor.events(
+            1,
+            "sig",
+            0,
+            &decoder::staratlas::crafting::ID,
+            &unknown,
+            &[],
+            vec![],
+        );
+        let invoked = processor.events(1, "sig", 0, &Pubkey::new_unique(), &[], &[], inner);
+
+        for events in [direct, invoked] {
+            assert_eq!(
+                events.unwrap_err().to_string(),
+                format!(
+                    "Unknown {} instruction [sig] {}",
+                    decoder::staratlas::crafting::ID,
+                    hex::encode(&unknown)
+                )
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_instructions_of_other_programs() {
+        let events = CraftingProcessor::new(registry()).events(
+            1,
+            "sig",
+            0,
+            &Pubkey::new_unique(),
+            &[1, 2, 3],
+            &[],
+            vec![],
+        );
+
+        assert!(events.unwrap().is_empty());
+    }
+}
@@ -1,23 +1,36 @@
-use crate::convert::convert_to_decimal;
+use crate::convert::{convert_to_decimal, token_instruction};
+use crate::processor::PROCESSOR_VERSION;
+use anyhow::Context;
 use chrono::DateTime;
-use db::DbPool;
-use decoder::staratlas::marketplace::{DecodedInstruction, ProcessExchange};
-use rust_decimal::prelude::ToPrimitive;
+use decoder::extra::token::is_token_program;
+use decoder::extra::transfer_hook;
+use decoder::idl::{DecodedIdlInstruction, IdlRegistry};
 use rust_decimal::Decimal;
+use rust_decimal::prelude::ToPrimitive;
+use serde::Serialize;
 use solana_sdk::pubkey::Pubkey;
 use solana_transaction_status::{UiInstruction, UiParsedInstruction};
+use std::str::FromStr;
+use std::sync::Arc;
 
 pub struct MarketplaceProcessor {
-    pub pool: DbPool,
+    /// Instructions are decoded with the IDL deployed at their slot
+    pub idls: Arc<IdlRegistry>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct MarketplaceExchangeInner {
     pub program_id: String,
     pub mint: Option<String>,
     pub source: Option<String>,
     pub amount: Option<u64>,
     pub decimals: Option<u8>,
+    /// Transfer fee withheld by a Token-2022 mint with the transfer fee extension
+    ///
+    /// Not subtracted from `amount`: exchanges record the amounts the order settled at, the fee
+    /// is withheld from the receiving account by the mint and not part of the trade. Shown by
+    /// the decode binary.
+    pub transfer_fee: Option<u64>,
 }
 
 #[derive(Debug, Clone)]
@@ -29,14 +42,19 @@ pub struct MarketplaceExchangeInnerParsed {
     pub buddy_amount: Decimal,
     pub price: Decimal,
     pub volume: Decimal,
+    /// Decimals of the asset and currency, given by their checked transfers
+    pub asset_decimals: Option<u8>,
+    pub currency_decimals: Option<u8>,
 }
 
 impl MarketplaceProcessor {
-    pub fn new(pool: DbPool) -> Self {
-        MarketplaceProcessor { pool }
+    pub fn new(idls: Arc<IdlRegistry>) -> Self {
+        MarketplaceProcessor { idls }
     }
 
-    pub async fn process(
+    /// Derives the exchange of a marketplace instruction, `None` for instructions that make none
+    #[allow(clippy::too_many_arguments)]
+    pub fn process(
         &self,
         slot: u64,
         block_time: i64,
@@ -45,472 +63,247 @@ impl MarketplaceProcessor {
         data: Vec<u8>,
         accounts: Vec<Pubkey>,
         inner_instructions: Vec<UiInstruction>,
-    ) -> anyhow::Result<()> {
-        match decoder::staratlas::marketplace::decode_instruction(data.as_slice()) {
-            Some(DecodedInstruction::ProcessExchange(exchange)) => {
-                let accounts_map = ProcessExchange::map_accounts(accounts.as_slice());
-
-                let inner_data = Self::map_inner_exchange_transfers(
+    ) -> anyhow::Result<Option<db::ExchangeWithDependencies>> {
+        let instruction = self
+            .idls
+            .decode_instruction(
+                &decoder::staratlas::marketplace::ID,
+                slot,
+                data.as_slice(),
+                accounts.as_slice(),
+            )
+            .with_context(|| format!("Could not decode marketplace instruction [{}]", signature))?;
+
+        match instruction {
+            Some(instruction) if instruction.name == "process_exchange" => {
+                let exchange_data = Self::exchange(
+                    slot,
+                    block_time,
+                    &signature,
+                    index,
+                    &instruction,
                     inner_instructions,
-                    accounts_map["currency_mint"].to_string(),
-                );
-
-                // Create an ExchangeWithDependencies struct
-                let exchange_data = db::ExchangeWithDependencies {
-                    slot: slot as i32,
-                    signature: signature.clone(),
-                    index: index as i32,
-                    timestamp: DateTime::from_timestamp(block_time, 0).unwrap(),
-                    side: inner_data.side.clone(),
-                    buyer_wallet: accounts_map["order_taker"].to_string(),
-                    seller_wallet: accounts_map["order_initializer"].to_string(),
-                    asset_mint: accounts_map["asset_mint"].to_string(),
-                    pair_mint: accounts_map["currency_mint"].to_string(),
-                    price: inner_data.price.to_f64().unwrap_or_default(),
-                    size: inner_data.asset_amount.to_i32().unwrap_or_default(),
-                    volume: inner_data.volume.to_f64().unwrap_or_default(),
-                    fee: inner_data.fee_amount.to_f64().unwrap_or_default(),
-                    buddy: inner_data.buddy_amount.to_f64().unwrap_or_default(),
-                };
-
-                db::create_exchange_with_dependencies(&self.pool, &exchange_data).await?;
+                )?;
+
                 log::info!("Found process_exchange: {:?}", signature);
 
-                Ok(())
+                Ok(Some(exchange_data))
             }
 
             //Ignore
-            Some(DecodedInstruction::ProcessInitializeBuy(_))
-            | Some(DecodedInstruction::ProcessInitializeSell(_))
-            | Some(DecodedInstruction::ProcessCancel)
-            | Some(DecodedInstruction::InitializeOpenOrdersCounter)
-            | Some(DecodedInstruction::UpdateAtlasRate(_)) => Ok(()),
-
-            _ => match data.get(..8) {
-                // Unknown Instruction 2RKjPtr8JKgdPj3ojERKjigasPLZELjJLGpWpo1gGGzZTqH8gNFhT6yhX1X7j75bkLkDw2BjVMRDvbc8BGZ5NBdt
-                Some(discriminator)
-                    if discriminator == &[0x12, 0xa3, 0xf2, 0x63, 0x76, 0x0e, 0xc7, 0x56] =>
-                {
-                    Ok(())
-                }
-                // Unknown Instruction 2Ccukf9ZFX86geLZd7fUnaepKkPmWyytMevcwj9xmGyw7ZUHY3L3kgHaWwDG9Cw997js2aXwcuk7uep7PEpGeiua
-                Some(discriminator)
-                    if discriminator == &[0x82, 0xb8, 0x0d, 0xb0, 0x47, 0x23, 0x82, 0x38] =>
-                {
-                    Ok(())
-                }
-                _ => {
-                    panic!(
-                        "Unhandled marketplace instruction [{}] {:?}",
-                        signature,
-                        hex::encode(data)
-                    );
-                }
-            },
-        }
-    }
-
-    fn map_inner_exchange_transfers(
-        inner_instructions: Vec<UiInstruction>,
-        currency_mint: String,
-    ) -> MarketplaceExchangeInnerParsed {
-        let mut mapped_inner = vec![];
-        for (inner_idx, inner) in inner_instructions.clone().into_iter().enumerate() {
-            //println!("[inner][{}] {:?}", inner_idx, inner);
-            match inner {
-                UiInstruction::Parsed(ui_parsed_instruction) => match ui_parsed_instruction {
-                    UiParsedInstruction::Parsed(parsed_instruction) => {
-                        match parsed_instruction
-                            .parsed
-                            .get("type")
-                            .map(|s| s.as_str())
-                            .unwrap()
-                        {
-                            Some("transferChecked") => {
-                                mapped_inner.push(MarketplaceExchangeInner {
-                                    program_id: parsed_instruction.program_id,
-                                    mint: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|info| info.get("mint"))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.to_string()),
-                                    source: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|info| info.get("source"))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.to_string()),
-                                    amount: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|info| info.get("tokenAmount"))
-                                        .and_then(|token_amount| token_amount.get("amount"))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.parse::<u64>().unwrap()),
-                                    decimals: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|info| info.get("tokenAmount"))
-                                        .and_then(|token_amount| token_amount.get("decimals"))
-                                        .and_then(|v| v.as_u64())
-                                        .map(|s| s.try_into().unwrap()),
-                                });
-                            }
-                            Some("transfer") => {
-                                mapped_inner.push(MarketplaceExchangeInner {
-                                    program_id: parsed_instruction.program_id,
-                                    mint: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|info| info.get("mint"))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.to_string()),
-                                    source: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|info| info.get("source"))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.to_string()),
-                                    amount: parsed_instruction
-                                        .parsed
-                                        .get("info")
-                                        .and_then(|token_amount| token_amount.get("amount"))
-                                        .and_then(|v| v.as_str())
-                                        .map(|s| s.parse::<u64>().unwrap()),
-                                    decimals: None,
-                                });
-                            }
-                            _ => panic!("Unhandled parsed instruction type"),
-                        }
-                    }
-                    UiParsedInstruction::PartiallyDecoded(partially) => {
-                        if (partially.program_id == decoder::extra::transfer_hook::ID.to_string()) {
-                            continue;
-                        }
-
-                        if partially.program_id == decoder::staratlas::buddy::ID.to_string() {
-                            mapped_inner.push(MarketplaceExchangeInner {
-                                program_id: partially.program_id.clone(),
-                                mint: None,
-                                source: None,
-                                amount: None,
-                                decimals: None,
-                            });
-                            continue;
-                        }
-
-                        panic!(
-                            "Unhandled partially decoded instruction for program_id: {}",
-                            partially.program_id
-                        );
-                    }
-                },
-                _ => panic!("Unhandled UiInstruction type"),
+            Some(instruction)
+                if matches!(
+                    instruction.name.as_str(),
+                    "process_initialize_buy"
+                        | "process_initialize_sell"
+                        | "process_initialize_buy_pda"
+                        | "process_initialize_sell_pda"
+                        | "process_cancel"
+                        | "initialize_open_orders_counter"
+                        | "update_atlas_rate"
+                ) =>
+            {
+                Ok(None)
             }
-        }
-
-        //println!("mapped_inner={:?}", mapped_inner);
-
-        let mapped_inner_refs: Vec<&str> =
-            mapped_inner.iter().map(|s| s.program_id.as_str()).collect();
-
-        let mut side = "NONE".to_string();
-        let mut currency_amount = Decimal::default();
-        let mut asset_amount = Decimal::default();
-        let mut fee_amount = Decimal::default();
-        let mut buddy_amount = Decimal::default();
-
-        match mapped_inner_refs.as_slice() {
-            [
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-            ] => {
-                side = Self::get_side(currency_mint, &mut mapped_inner, 1);
-                match side.as_str() {
-                    "BUY" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-                    }
-                    "SELL" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
 
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-                    }
-                    _ => panic!("Unhandled side"),
-                }
-            }
-            [
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
-            ] => {
-                side = Self::get_side(currency_mint, &mut mapped_inner, 1);
-                match side.as_str() {
-                    "BUY" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
+            Some(instruction) => anyhow::bail!(
+                "Unhandled marketplace instruction {} [{}]",
+                instruction.name,
+                signature
+            ),
 
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
+            // Not part of the IDL version registered for the slot
+            None => anyhow::bail!(
+                "Unknown marketplace instruction [{}] {}",
+                signature,
+                hex::encode(data)
+            ),
+        }
+    }
 
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-                    }
-                    "SELL" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
+    /// Derives the exchange of a decoded `process_exchange` instruction from its inner transfers
+    pub fn exchange(
+        slot: u64,
+        block_time: i64,
+        signature: &str,
+        index: usize,
+        instruction: &DecodedIdlInstruction,
+        inner_instructions: Vec<UiInstruction>,
+    ) -> anyhow::Result<db::ExchangeWithDependencies> {
+        let account = |name: &str| {
+            instruction
+                .account(name)
+                .map(ToString::to_string)
+                .with_context(|| format!("process_exchange without {} account", name))
+        };
 
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
+        let inner_data = Self::parse_exchange_transfers(
+            &Self::map_inner_transfers(inner_instructions)?,
+            account("currency_mint")?,
+        )?;
+
+        Ok(db::ExchangeWithDependencies {
+            slot: slot as i32,
+            signature: signature.to_string(),
+            index: index as i32,
+            timestamp: DateTime::from_timestamp(block_time, 0)
+                .with_context(|| format!("Block time out of range: {}", block_time))?,
+            side: inner_data.side.clone(),
+            buyer_wallet: account("order_taker")?,
+            seller_wallet: account("order_initializer")?,
+            asset_mint: account("asset_mint")?,
+            pair_mint: account("currency_mint")?,
+            asset_decimals: inner_data.asset_decimals.map(i16::from),
+            pair_decimals: inner_data.currency_decimals.map(i16::from),
+            price: inner_data.price.to_f64().unwrap_or_default(),
+            size: inner_data.asset_amount.to_i32().unwrap_or_default(),
+            volume: inner_data.volume.to_f64().unwrap_or_default(),
+            fee: inner_data.fee_amount.to_f64().unwrap_or_default(),
+            buddy: inner_data.buddy_amount.to_f64().unwrap_or_default(),
+            processor_version: PROCESSOR_VERSION,
+        })
+    }
 
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-                    }
-                    _ => panic!("Unhandled side"),
+    /// Maps the token transfers and buddy invocations among the inner instructions of an exchange
+    ///
+    /// Transfers of SPL Token and Token-2022 are mapped alike, transfer hook executions of
+    /// known hooks and instructions the RPC parsed as moving no tokens (e.g. creating the
+    /// associated token account of the taker) are skipped. Token instructions the RPC could not
+    /// parse and that are no transfer are errors.
+    pub fn map_inner_transfers(
+        inner_instructions: Vec<UiInstruction>,
+    ) -> anyhow::Result<Vec<MarketplaceExchangeInner>> {
+        let mut mapped_inner = vec![];
+        for inner in inner_instructions.into_iter() {
+            if let Some(token) = token_instruction(&inner) {
+                if !token.kind.is_transfer() {
+                    anyhow::bail!("Unhandled token instruction {:?}", token.kind);
                 }
+                mapped_inner.push(MarketplaceExchangeInner {
+                    program_id: token.program_id.to_string(),
+                    mint: token.mint.map(|mint| mint.to_string()),
+                    source: token.source.map(|source| source.to_string()),
+                    amount: Some(token.amount),
+                    decimals: token.decimals,
+                    transfer_fee: token.fee,
+                });
+                continue;
             }
 
-            [
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
-                "tHookmPkFZDJGkS9us6sVsnYi2EKHCrVtw8zD6oXYPE",
-            ] => {
-                side = Self::get_side(currency_mint, &mut mapped_inner, 1);
-                match side.as_str() {
-                    "BUY" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
+            match inner {
+                // Parsed by the RPC but no token movement
+                UiInstruction::Parsed(UiParsedInstruction::Parsed(_)) => continue,
+                UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(partially)) => {
+                    let program_id =
+                        Pubkey::from_str(&partially.program_id).with_context(|| {
+                            format!("Invalid inner program_id: {}", partially.program_id)
+                        })?;
+                    let data = bs58::decode(&partially.data).into_vec().with_context(|| {
+                        format!("Invalid inner instruction data of {}", partially.program_id)
+                    })?;
+                    // The RPC parses the token instructions it knows, others must be decoded
+                    if is_token_program(&program_id) {
+                        anyhow::bail!(
+                            "Undecodable token instruction of {}: {}",
+                            partially.program_id,
+                            hex::encode(&data)
                         );
                     }
-                    "SELL" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
+                    if transfer_hook::is_execute(&program_id, &data) {
+                        continue;
                     }
-                    _ => panic!("Unhandled side"),
-                }
-            }
 
-            [
-                "BUDDYtQp7Di1xfojiCSVDksiYLQx511DPdj2nbtG9Yu5",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-            ] => {
-                side = Self::get_side(currency_mint, &mut mapped_inner, 3);
-                match side.as_str() {
-                    "BUY" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[3].clone().amount.unwrap(),
-                            mapped_inner[3].clone().decimals.unwrap(),
-                        );
+                    if program_id == decoder::staratlas::buddy::ID {
+                        mapped_inner.push(MarketplaceExchangeInner {
+                            program_id: partially.program_id,
+                            mint: None,
+                            source: None,
+                            amount: None,
+                            decimals: None,
+                            transfer_fee: None,
+                        });
+                        continue;
                     }
-                    "SELL" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
 
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[3].clone().amount.unwrap(),
-                            mapped_inner[3].clone().decimals.unwrap(),
-                        );
-                    }
-                    _ => panic!("Unhandled side"),
+                    anyhow::bail!(
+                        "Unhandled partially decoded instruction for program_id: {}",
+                        partially.program_id
+                    );
                 }
-            }
-
-            [
-                "BUDDYtQp7Di1xfojiCSVDksiYLQx511DPdj2nbtG9Yu5",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-            ] => {
-                side = Self::get_side(currency_mint, &mut mapped_inner, 3);
-                match side.as_str() {
-                    "BUY" => {
-                        buddy_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner
-                                .iter()
-                                .find(|inner| {
-                                    inner.source == mapped_inner[1].source
-                                        && inner.decimals.is_some()
-                                })
-                                .unwrap()
-                                .decimals
-                                .unwrap(),
-                        );
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[3].clone().amount.unwrap(),
-                            mapped_inner[3].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[4].clone().amount.unwrap(),
-                            mapped_inner[4].clone().decimals.unwrap(),
-                        );
-                    }
-                    "SELL" => {
-                        buddy_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner
-                                .iter()
-                                .find(|inner| {
-                                    inner.source == mapped_inner[1].source
-                                        && inner.decimals.is_some()
-                                })
-                                .unwrap()
-                                .decimals
-                                .unwrap(),
-                        );
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[3].clone().amount.unwrap(),
-                            mapped_inner[3].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[4].clone().amount.unwrap(),
-                            mapped_inner[4].clone().decimals.unwrap(),
-                        );
-                    }
-                    _ => panic!("Unhandled side"),
+                UiInstruction::Compiled(_) => {
+                    anyhow::bail!("Unhandled compiled inner instruction, expected jsonParsed")
                 }
             }
+        }
 
-            [
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-                "TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb",
-                "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA",
-            ] => {
-                side = Self::get_side(currency_mint, &mut mapped_inner, 1);
-                match side.as_str() {
-                    "BUY" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
-
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-                    }
-                    "SELL" => {
-                        fee_amount = convert_to_decimal(
-                            mapped_inner[0].clone().amount.unwrap(),
-                            mapped_inner[0].clone().decimals.unwrap(),
-                        );
-
-                        currency_amount = convert_to_decimal(
-                            mapped_inner[1].clone().amount.unwrap(),
-                            mapped_inner[1].clone().decimals.unwrap(),
-                        );
+        Ok(mapped_inner)
+    }
 
-                        asset_amount = convert_to_decimal(
-                            mapped_inner[2].clone().amount.unwrap(),
-                            mapped_inner[2].clone().decimals.unwrap(),
-                        );
-                    }
-                    _ => panic!("Unhandled side"),
+    /// Derives side, amounts and price of an exchange from its mapped inner transfers
+    ///
+    /// The marketplace fee is transferred first, followed by the asset and the currency in the
+    /// order of the side. A buddy invocation comes first and may add the buddy fee transfer.
+    /// Transfer amounts include transfer fees withheld by Token-2022 mints.
+    pub fn parse_exchange_transfers(
+        mapped_inner: &[MarketplaceExchangeInner],
+        currency_mint: String,
+    ) -> anyhow::Result<MarketplaceExchangeInnerParsed> {
+        let buddy = decoder::staratlas::buddy::ID.to_string();
+        let layout: Vec<&str> = mapped_inner
+            .iter()
+            .map(|inner| {
+                if inner.program_id == buddy {
+                    "buddy"
+                } else {
+                    "token"
                 }
-            }
-
-            _ => panic!(
+            })
+            .collect();
+
+        let (side_index, fee_index, buddy_index) = match layout.as_slice() {
+            ["token", "token", "token"] => (1, 0, None),
+            ["buddy", "token", "token", "token"] => (3, 1, None),
+            ["buddy", "token", "token", "token", "token"] => (3, 2, Some(1)),
+            _ => anyhow::bail!(
                 "Unhandled inner instructions [{}]",
-                mapped_inner_refs.join(", ")
+                mapped_inner
+                    .iter()
+                    .map(|inner| inner.program_id.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+        };
+        let amount = |index: usize| -> anyhow::Result<Decimal> {
+            let inner = &mapped_inner[index];
+            Ok(convert_to_decimal(
+                inner.amount.context("Exchange transfer without amount")?,
+                inner
+                    .decimals
+                    .context("Exchange transfer without decimals, expected a checked transfer")?,
+            ))
+        };
+
+        let side = Self::get_side(currency_mint, mapped_inner, side_index)?;
+        let fee_amount = amount(fee_index)?;
+        let (asset_index, currency_index) = match side.as_str() {
+            "BUY" => (fee_index + 1, fee_index + 2),
+            _ => (fee_index + 2, fee_index + 1),
+        };
+        let (asset_amount, currency_amount) = (amount(asset_index)?, amount(currency_index)?);
+        // The buddy fee is an unchecked transfer, the decimals come from a checked transfer of
+        // the same source
+        let buddy_amount = match buddy_index {
+            Some(index) => convert_to_decimal(
+                mapped_inner[index]
+                    .amount
+                    .context("Buddy transfer without amount")?,
+                mapped_inner
+                    .iter()
+                    .filter(|inner| inner.source == mapped_inner[index].source)
+                    .find_map(|inner| inner.decimals)
+                    .context("No checked transfer giving the decimals of the buddy fee")?,
             ),
+            None => Decimal::ZERO,
         };
 
         let price = (fee_amount + currency_amount + buddy_amount)
@@ -518,9 +311,7 @@ impl MarketplaceProcessor {
             .unwrap_or_default();
         let volume = fee_amount + currency_amount;
 
-        //println!("mapped_inner={:?}", mapped_inner);
-
-        MarketplaceExchangeInnerParsed {
+        Ok(MarketplaceExchangeInnerParsed {
             side,
             currency_amount,
             asset_amount,
@@ -528,22 +319,225 @@ impl MarketplaceProcessor {
             buddy_amount,
             price,
             volume,
-        }
+            asset_decimals: mapped_inner[asset_index].decimals,
+            currency_decimals: mapped_inner[currency_index].decimals,
+        })
     }
 
     fn get_side(
         currency_mint: String,
-        mapped_inner: &mut Vec<MarketplaceExchangeInner>,
+        mapped_inner: &[MarketplaceExchangeInner],
         idx: usize,
-    ) -> String {
-        match mapped_inner[idx]
-            .clone()
+    ) -> anyhow::Result<String> {
+        let mint = mapped_inner[idx]
             .mint
-            .unwrap()
-            .contains(&currency_mint)
-        {
+            .as_deref()
+            .context("Exchange transfer without mint, expected a checked transfer")?;
+        Ok(match mint.contains(&currency_mint) {
             true => "SELL".to_string(),
             false => "BUY".to_string(),
-        }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{Value, json};
+    use solana_transaction_status::UiPartiallyDecodedInstruction;
+    use solana_transaction_status::parse_instruction::ParsedInstruction;
+
+    const TOKEN: &str = "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA";
+
+    fn parsed(program: &str, program_id: &str, parsed: Value) -> UiInstruction {
+        UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
+            program: program.to_string(),
+            program_id: program_id.to_string(),
+            parsed,
+            stack_height: Some(2),
+        }))
+    }
+
+    fn transfer_checked(
+        mint: &Pubkey,
+        source: &Pubkey,
+        amount: u64,
+        decimals: u8,
+    ) -> UiInstruction {
+        parsed(
+            "spl-token",
+            TOKEN,
+            json!({
+                "type": "transferChecked",
+                "info": {
+                    "source": source.to_string(),
+                    "mint": mint.to_string(),
+                    "destination": Pubkey::new_unique().to_string(),
+                    "authority": Pubkey::new_unique().to_string(),
+                    "tokenAmount": {
+                        "amount": amount.to_string(),
+                        "decimals": decimals,
+                    },
+                },
+            }),
+        )
+    }
+
+    #[test]
+    fn buy_skips_instructions_moving_no_tokens() {
+        let (asset, currency, buyer) = (
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+            Pubkey::new_unique(),
+        );
+        let inner = vec![
+            // Creates the asset token account of the buyer
+            parsed(
+                "spl-associated-token-account",
+                "ATokenGPvbdGVxr1b2hvZbsiqW5xWH25efTNsLJA8knL",
+                json!({ "type": "createIdempotent", "info": {} }),
+            ),
+            parsed(
+                "system",
+                "11111111111111111111111111111111",
+                json!({ "type": "createAccount", "info": {} }),
+            ),
+            parsed(
+                "spl-token",
+                TOKEN,
+                json!({ "type": "initializeAccount3", "info": {} }),
+            ),
+            transfer_checked(&currency, &buyer, 50_000_000, 8),
+            transfer_checked(&asset, &Pubkey::new_unique(), 2, 0),
+            transfer_checked(&currency, &buyer, 9_950_000_000, 8),
+        ];
+
+        let mapped = MarketplaceProcessor::map_inner_transfers(inner).unwrap();
+        let exchange =
+            MarketplaceProcessor::parse_exchange_transfers(&mapped, currency.to_string()).unwrap();
+
+        assert_eq!(mapped.len(), 3);
+        assert_eq!(exchange.side, "BUY");
+        assert_eq!(exchange.fee_amount, Decimal::new(5, 1));
+        assert_eq!(exchange.asset_amount, Decimal::from(2));
+        assert_eq!(exchange.currency_amount, Decimal::new(995, 1));
+        assert_eq!(exchange.price, Decimal::from(50));
+        assert_eq!(exchange.volume, Decimal::from(100));
+        assert_eq!(exchange.asset_decimals, Some(0));
+        assert_eq!(exchange.currency_decimals, Some(8));
+    }
+
+    #[test]
+    fn token_mint_among_inner_instructions_is_an_error() {
+        let mint_to = parsed(
+            "spl-token",
+            TOKEN,
+            json!({
+                "type": "mintTo",
+                "info": {
+                    "mint": Pubkey::new_unique().to_string(),
+                    "account": Pubkey::new_unique().to_string(),
+                    "amount": "1",
+                },
+            }),
+        );
+
+        assert!(MarketplaceProcessor::map_inner_transfers(vec![mint_to]).is_err());
+    }
+
+    #[test]
+    fn unknown_program_among_inner_instructions_is_an_error() {
+        let unknown = UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+            UiPartiallyDecodedInstruction {
+                program_id: Pubkey::new_unique().to_string(),
+                accounts: vec![],
+                data: bs58::encode([1, 2, 3]).into_string(),
+                stack_height: Some(2),
+            },
+        ));
+
+        assert!(MarketplaceProcessor::map_inner_transfers(vec![unknown]).is_err());
+    }
+
+    fn partially_decoded(program_id: &Pubkey, data: &[u8]) -> UiInstruction {
+        UiInstruction::Parsed(UiParsedInstruction::PartiallyDecoded(
+            UiPartiallyDecodedInstruction {
+                program_id: program_id.to_string(),
+                accounts: vec![],
+                data: bs58::encode(data).into_string(),
+                stack_height: Some(3),
+            },
+        ))
+    }
+
+    #[test]
+    fn executions_of_known_transfer_hooks_are_skipped() {
+        let mut execute = transfer_hook::EXECUTE_DISCRIMINATOR.to_vec();
+        execute.extend(2u64.to_le_bytes());
+        let asset = Pubkey::new_unique();
+
+        let mapped = MarketplaceProcessor::map_inner_transfers(vec![
+            transfer_checked(&asset, &Pubkey::new_unique(), 2, 0),
+            partially_decoded(&transfer_hook::ID, &execute),
+        ])
+        .unwrap();
+        // The discriminator alone does not make a program a transfer hook
+        let unknown_hook = MarketplaceProcessor::map_inner_transfers(vec![partially_decoded(
+            &Pubkey::new_unique(),
+            &execute,
+        )]);
+
+        assert_eq!(mapped.len(), 1);
+        assert!(unknown_hook.is_err());
+    }
+
+    #[test]
+    fn undecodable_token_instruction_is_an_error() {
+        // An extension instruction the RPC did not parse
+        let unknown = partially_decoded(&decoder::extra::token::TOKEN_2022_ID, &[40, 1, 2]);
+
+        let err = MarketplaceProcessor::map_inner_transfers(vec![unknown]).unwrap_err();
+
+        assert!(err.to_string().starts_with(
+            "Undecodable token instruction of TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"
+        ));
+    }
+
+    #[test]
+    fn unknown_transfer_layout_is_an_error() {
+        let currency = Pubkey::new_unique();
+        let mapped = MarketplaceProcessor::map_inner_transfers(vec![
+            transfer_checked(&currency, &Pubkey::new_unique(), 1, 8),
+            transfer_checked(&currency, &Pubkey::new_unique(), 1, 8),
+        ])
+        .unwrap();
+
+        let err = MarketplaceProcessor::parse_exchange_transfers(&mapped, currency.to_string())
+            .unwrap_err();
+
+        assert!(err.to_string().starts_with("Unhandled inner instructions"));
+    }
+
+    #[test]
+    fn unchecked_exchange_transfer_is_an_error() {
+        let mapped = vec![
+            MarketplaceExchangeInner {
+                program_id: TOKEN.to_string(),
+                mint: None,
+                source: None,
+                amount: Some(1),
+                decimals: None,
+                transfer_fee: None,
+            };
+            3
+        ];
+
+        assert!(
+            MarketplaceProcessor::parse_exchange_transfers(
+                &mapped,
+                Pubkey::new_unique().to_string()
+            )
+            .is_err()
+        );
     }
 }
@@ -1 +1,10 @@
+pub mod crafting;
 pub mod marketplace;
+pub mod sage;
+pub mod score;
+
+/// Version of the processing logic, stored on every derived row.
+///
+/// Bump this whenever a decoder or processor fix changes the rows written for a
+/// transaction, then reprocess the affected range to rewrite them.
+pub const PROCESSOR_VERSION: i32 = 2;
@@ -0,0 +1,283 @@
+use crate::convert::token_movements;
+use anyhow::Context;
+use decoder::idl::{DecodedIdlInstruction, IdlRegistry};
+use decoder::staratlas::sage::SageActivityKind;
+use serde_json::Value;
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::UiInstruction;
+use std::sync::Arc;
+
+pub struct SageProcessor {
+    /// Instructions are decoded with the IDL deployed at their slot
+    pub idls: Arc<IdlRegistry>,
+}
+
+impl SageProcessor {
+    pub fn new(idls: Arc<IdlRegistry>) -> Self {
+        SageProcessor { idls }
+    }
+
+    /// Derives the fleet activity of a SAGE instruction, `None` for instructions that are not
+    /// fleet activity
+    ///
+    /// Instructions the IDL registered for the slot does not define or cannot decode are
+    /// errors.
+    pub fn activity(
+        &self,
+        slot: u64,
+        signature: &str,
+        index: usize,
+        data: &[u8],
+        accounts: &[Pubkey],
+        inner_instructions: Vec<UiInstruction>,
+    ) -> anyhow::Result<Option<db::NewSageActivity>> {
+        let instruction = self
+            .idls
+            .decode_instruction(&decoder::staratlas::sage::ID, slot, data, accounts)
+            .with_context(|| format!("Could not decode SAGE instruction [{}]", signature))?;
+
+        // Not part of the IDL version registered for the slot
+        let Some(instruction) = instruction else {
+            anyhow::bail!(
+                "Unknown SAGE instruction [{}] {}",
+                signature,
+                hex::encode(data)
+            );
+        };
+
+        let Some(kind) = SageActivityKind::from_instruction(&instruction.name) else {
+            return Ok(None);
+        };
+        Ok(Some(Self::map_activity(
+            kind,
+            index,
+            &instruction,
+            inner_instructions,
+        )))
+    }
+
+    /// Maps a decoded instruction to its activity row
+    pub fn map_activity(
+        kind: SageActivityKind,
+        index: usize,
+        instruction: &DecodedIdlInstruction,
+        inner_instructions: Vec<UiInstruction>,
+    ) -> db::NewSageActivity {
+        let account = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| instruction.account(name))
+                .map(ToString::to_string)
+        };
+        let movements = token_movements(inner_instructions);
+
+        // Mined resources are minted into the fleet's cargo, the instruction only knows the mine item
+        let resource_mint = account(&["token_mint", "mint"]).or_else(|| {
+            movements
+                .iter()
+                .find(|movement| movement.kind.movement() == "mint")
+                .and_then(|movement| movement.mint.clone())
+        });
+
+        let transfers: Vec<_> = movements
+            .into_iter()
+            .map(|movement| db::NewSageTransfer {
+                kind: movement.kind.movement().to_string(),
+                mint: movement.mint,
+                source: movement.source,
+                destination: movement.destination,
+                amount: movement.amount,
+            })
+            .collect();
+
+        let sector = instruction
+            .arg("to_sector")
+            .and_then(Value::as_array)
+            .and_then(|sector| match sector.as_slice() {
+                [x, y] => Some((x.as_i64()?, y.as_i64()?)),
+                _ => None,
+            });
+
+        let label = instruction
+            .arg("fleet_label")
+            .and_then(Value::as_array)
+            .and_then(|bytes| {
+                bytes
+                    .iter()
+                    .map(|byte| byte.as_u64().and_then(|byte| u8::try_from(byte).ok()))
+                    .collect::<Option<Vec<u8>>>()
+            })
+            .map(|bytes| {
+                String::from_utf8_lossy(&bytes)
+                    .trim_end_matches('\0')
+                    .to_string()
+            });
+
+        db::NewSageActivity {
+            instruction_index: index as i32,
+            kind: kind.as_str().to_string(),
+            instruction: instruction.name.clone(),
+            fleet: account(&["fleet"]),
+            player_profile: account(&["owning_profile", "player_profile", "profile"]),
+            starbase: account(&["starbase"]),
+            sector,
+            resource_mint,
+            amount: instruction.arg("amount").and_then(Value::as_i64),
+            label,
+            args: instruction.args.clone(),
+            transfers,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decoder::idl::sighash;
+    use serde_json::json;
+
+    fn registry() -> Arc<IdlRegistry> {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        Arc::new(registry)
+    }
+
+    /// Instruction data as SAGE serializes it: the discriminator followed by the borsh input
+    fn data(name: &str, input: &[&[u8]]) -> Vec<u8> {
+        let mut data = sighash("global", name);
+        data.extend(input.concat());
+        data
+    }
+
+    fn accounts(len: usize) -> Vec<Pubkey> {
+        (0..len).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn start_subwarp_records_sector_fleet_and_owner() {
+        let accounts = accounts(6);
+        let activity = SageProcessor::new(registry())
+            .activity(
+                1,
+                "sig",
+                2,
+                &data(
+                    "start_subwarp",
+                    &[
+                        &1u16.to_le_bytes(),
+                        &(-40i64).to_le_bytes(),
+                        &30i64.to_le_bytes(),
+                    ],
+                ),
+                &accounts,
+                vec![],
+            )
+            .unwrap()
+            .unwrap();
+
+        // key, owning_profile, owning_profile_faction, fleet, game_id, game_state
+        assert_eq!(activity.kind, "subwarp_start");
+        assert_eq!(activity.instruction, "startSubwarp");
+        assert_eq!(activity.instruction_index, 2);
+        assert_eq!(activity.sector, Some((-40, 30)));
+        assert_eq!(activity.fleet, Some(accounts[3].to_string()));
+        assert_eq!(activity.player_profile, Some(accounts[1].to_string()));
+        assert_eq!(
+            activity.args,
+            json!({"input": {"keyIndex": 1, "toSector": [-40, 30]}})
+        );
+    }
+
+    #[test]
+    fn create_fleet_records_label_starbase_and_profile() {
+        let accounts = accounts(14);
+        let mut label = [0u8; 32];
+        label[..7].copy_from_slice(b"Miner 1");
+        let activity = SageProcessor::new(registry())
+            .activity(
+                1,
+                "sig",
+                0,
+                &data(
+                    "create_fleet",
+                    &[&[3], &label, &0u32.to_le_bytes(), &0u16.to_le_bytes()],
+                ),
+                &accounts,
+                vec![],
+            )
+            .unwrap()
+            .unwrap();
+
+        // key, profile, profile_faction, game_id, game_state, funder, starbase, starbase_player,
+        // fleet, ...
+        assert_eq!(activity.kind, "fleet_created");
+        assert_eq!(activity.label.as_deref(), Some("Miner 1"));
+        assert_eq!(activity.player_profile, Some(accounts[1].to_string()));
+        assert_eq!(activity.starbase, Some(accounts[6].to_string()));
+        assert_eq!(activity.fleet, Some(accounts[8].to_string()));
+        assert_eq!(activity.sector, None);
+    }
+
+    #[test]
+    fn cargo_deposit_records_amount_and_mint() {
+        let accounts = accounts(19);
+        let activity = SageProcessor::new(registry())
+            .activity(
+                1,
+                "sig",
+                0,
+                &data(
+                    "deposit_cargo_to_fleet",
+                    &[&2500u64.to_le_bytes(), &0u16.to_le_bytes()],
+                ),
+                &accounts,
+                vec![],
+            )
+            .unwrap()
+            .unwrap();
+
+        // Fleet and owner (6), funds_to, starbase, starbase_player, cargo_pod_from, cargo_pod_to,
+        // token_to, cargo_type, cargo_stats_definition, token_from, token_mint, ...
+        assert_eq!(activity.kind, "cargo_deposit");
+        assert_eq!(activity.amount, Some(2500));
+        assert_eq!(activity.resource_mint, Some(accounts[15].to_string()));
+    }
+
+    #[test]
+    fn skips_instructions_that_are_not_fleet_activity() {
+        let activity = SageProcessor::new(registry()).activity(
+            1,
+            "sig",
+            0,
+            &data("close_crafting_process", &[&0u16.to_le_bytes()]),
+            &[],
+            vec![],
+        );
+
+        assert!(activity.unwrap().is_none());
+    }
+
+    #[test]
+    fn fails_on_unknown_and_undecodable_instructions() {
+        let processor = SageProcessor::new(registry());
+
+        let unknown =
+            processor.activity(1, "sig", 0, &data("no_such_instruction", &[]), &[], vec![]);
+        // Truncated input of a known instruction
+        let truncated =
+            processor.activity(1, "sig", 0, &data("start_subwarp", &[&[1]]), &[], vec![]);
+
+        assert!(
+            unknown
+                .unwrap_err()
+                .to_string()
+                .starts_with("Unknown SAGE instruction [sig]")
+        );
+        assert_eq!(
+            truncated.unwrap_err().to_string(),
+            "Could not decode SAGE instruction [sig]"
+        );
+    }
+}
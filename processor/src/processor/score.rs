@@ -0,0 +1,265 @@
+use crate::convert::token_movements;
+use anyhow::Context;
+use decoder::idl::{DecodedIdlInstruction, IdlRegistry, to_snake_case};
+use decoder::staratlas::score::{ATLAS_MINT, ScoreEventKind};
+use solana_sdk::pubkey::Pubkey;
+use solana_transaction_status::UiInstruction;
+use std::sync::Arc;
+
+pub struct ScoreProcessor {
+    /// Instructions are decoded with the IDL deployed at their slot
+    pub idls: Arc<IdlRegistry>,
+}
+
+impl ScoreProcessor {
+    pub fn new(idls: Arc<IdlRegistry>) -> Self {
+        ScoreProcessor { idls }
+    }
+
+    /// Derives the staking event of a SCORE instruction, `None` for instructions that move no
+    /// ships, resources or rewards
+    ///
+    /// Instructions the IDL registered for the slot does not define or cannot decode are
+    /// errors.
+    pub fn event(
+        &self,
+        slot: u64,
+        signature: &str,
+        index: usize,
+        data: &[u8],
+        accounts: &[Pubkey],
+        inner_instructions: Vec<UiInstruction>,
+    ) -> anyhow::Result<Option<db::NewScoreEvent>> {
+        let instruction = self
+            .idls
+            .decode_instruction(&decoder::staratlas::score::ID, slot, data, accounts)
+            .with_context(|| format!("Could not decode SCORE instruction [{}]", signature))?;
+
+        // Not part of the IDL version registered for the slot
+        let Some(instruction) = instruction else {
+            anyhow::bail!(
+                "Unknown SCORE instruction [{}] {}",
+                signature,
+                hex::encode(data)
+            );
+        };
+
+        let Some(kind) = ScoreEventKind::from_instruction(&instruction.name) else {
+            return Ok(None);
+        };
+        Ok(Some(Self::map_event(
+            kind,
+            index,
+            &instruction,
+            inner_instructions,
+        )))
+    }
+
+    /// Maps a decoded instruction to its event row
+    ///
+    /// Stakes and resupplies give the amount as `*_quantity` argument. Withdrawals and claims
+    /// move everything there is, their amount is taken from the token movement of the moved
+    /// mint, or from the only movement as SCORE uses unchecked transfers that do not log it.
+    pub fn map_event(
+        kind: ScoreEventKind,
+        index: usize,
+        instruction: &DecodedIdlInstruction,
+        inner_instructions: Vec<UiInstruction>,
+    ) -> db::NewScoreEvent {
+        let account = |names: &[&str]| {
+            names
+                .iter()
+                .find_map(|name| instruction.account(name))
+                .map(ToString::to_string)
+        };
+        let ship_mint = account(&["ship_mint"]);
+
+        let mint = match kind {
+            ScoreEventKind::Stake | ScoreEventKind::Unstake => ship_mint.clone(),
+            ScoreEventKind::Resupply(resource) | ScoreEventKind::ResupplyWithdrawn(resource) => {
+                account(resource.mint_accounts())
+            }
+            ScoreEventKind::Claim => Some(ATLAS_MINT.to_string()),
+        };
+
+        let quantity = instruction.args.as_object().and_then(|args| {
+            args.iter()
+                .find(|(name, _)| to_snake_case(name).ends_with("quantity"))
+                .and_then(|(_, quantity)| quantity.as_i64())
+        });
+        let amount = quantity.or_else(|| {
+            let movements = token_movements(inner_instructions);
+            movements
+                .iter()
+                .find(|movement| movement.mint.is_some() && movement.mint == mint)
+                .or(match movements.as_slice() {
+                    [movement] => Some(movement),
+                    _ => None,
+                })
+                .map(|movement| movement.amount)
+        });
+
+        db::NewScoreEvent {
+            instruction_index: index as i32,
+            kind: kind.as_str().to_string(),
+            instruction: instruction.name.clone(),
+            player: account(&["player_account", "player"]),
+            ship_mint,
+            resource: kind
+                .resource()
+                .map(|resource| resource.as_str().to_string()),
+            mint,
+            amount,
+            args: instruction.args.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use decoder::idl::sighash;
+    use serde_json::json;
+    use solana_transaction_status::UiParsedInstruction;
+    use solana_transaction_status::parse_instruction::ParsedInstruction;
+
+    fn processor() -> ScoreProcessor {
+        let mut registry = IdlRegistry::new();
+        registry
+            .load_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/../idls"))
+            .unwrap();
+        ScoreProcessor::new(Arc::new(registry))
+    }
+
+    fn data(name: &str, bumps: usize, quantity: Option<u64>) -> Vec<u8> {
+        let mut data = sighash("global", name);
+        data.extend(vec![255; bumps]);
+        data.extend(quantity.map(u64::to_le_bytes).unwrap_or_default());
+        data
+    }
+
+    fn accounts(len: usize) -> Vec<Pubkey> {
+        (0..len).map(|_| Pubkey::new_unique()).collect()
+    }
+
+    #[test]
+    fn stake_takes_ships_from_the_quantity_argument() {
+        let accounts = accounts(11);
+        let event = processor()
+            .event(
+                1,
+                "sig",
+                0,
+                &data("process_initial_deposit", 4, Some(12)),
+                &accounts,
+                vec![],
+            )
+            .unwrap()
+            .unwrap();
+
+        // player_account, ..., escrow_authority, ship escrow, ship source, ship_mint
+        assert_eq!(event.kind, "stake");
+        assert_eq!(event.player, Some(accounts[0].to_string()));
+        assert_eq!(event.ship_mint, Some(accounts[7].to_string()));
+        assert_eq!(event.mint, event.ship_mint);
+        assert_eq!(event.amount, Some(12));
+    }
+
+    #[test]
+    fn resupply_records_resource_and_its_mint() {
+        let accounts = accounts(9);
+        let event = processor()
+            .event(
+                1,
+                "sig",
+                0,
+                &data("process_rearm", 4, Some(3000)),
+                &accounts,
+                vec![],
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.kind, "resupply");
+        assert_eq!(event.resource.as_deref(), Some("ammo"));
+        assert_eq!(event.mint, Some(accounts[4].to_string()));
+        assert_eq!(event.ship_mint, Some(accounts[7].to_string()));
+        assert_eq!(event.amount, Some(3000));
+    }
+
+    #[test]
+    fn claim_takes_rewards_from_the_unchecked_transfer() {
+        let accounts = accounts(9);
+        let transfer = UiInstruction::Parsed(UiParsedInstruction::Parsed(ParsedInstruction {
+            program: "spl-token".to_string(),
+            program_id: "TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA".to_string(),
+            parsed: json!({
+                "type": "transfer",
+                "info": {
+                    "source": accounts[5].to_string(),
+                    "destination": accounts[4].to_string(),
+                    "authority": accounts[6].to_string(),
+                    "amount": "123456789",
+                },
+            }),
+            stack_height: Some(2),
+        }));
+
+        let event = processor()
+            .event(
+                1,
+                "sig",
+                0,
+                &data("process_harvest", 4, None),
+                &accounts,
+                vec![transfer],
+            )
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(event.kind, "claim");
+        assert_eq!(event.mint, Some(ATLAS_MINT.to_string()));
+        assert_eq!(event.amount, Some(123456789));
+    }
+
+    #[test]
+    fn skips_instructions_that_move_nothing() {
+        let event = processor().event(
+            1,
+            "sig",
+            0,
+            &data("process_settle", 2, None),
+            &accounts(5),
+            vec![],
+        );
+
+        assert!(event.unwrap().is_none());
+    }
+
+    #[test]
+    fn fails_on_unknown_and_undecodable_instructions() {
+        let unknown = processor().event(
+            1,
+            "sig",
+            0,
+            &data("no_such_instruction", 0, None),
+            &[],
+            vec![],
+        );
+        // A stake cut short after its second bump
+        let mut stake = data("process_initial_deposit", 2, None);
+        stake.truncate(10);
+        let truncated = processor().event(1, "sig", 0, &stake, &accounts(11), vec![]);
+
+        assert!(
+            unknown
+                .unwrap_err()
+                .to_string()
+                .starts_with("Unknown SCORE instruction [sig]")
+        );
+        assert_eq!(
+            truncated.unwrap_err().to_string(),
+            "Could not decode SCORE instruction [sig]"
+        );
+    }
+}
@@ -0,0 +1,460 @@
+//! Snapshots of program accounts
+//!
+//! Pulls every account owned by the marketplace program via `getProgramAccounts`, decodes
+//! it and replaces the stored accounts. The stored open orders and registered currencies are
+//! authoritative, so they can be reconciled against the state derived from the processed
+//! transactions. Crafting recipes are taken the same way, they are only changed by admins. Player
+//! profiles and their factions are too, their names and factions become the ones of the players
+//! owning them.
+
+use crate::{MAX_ATTEMPTS, rpc_with_retry};
+use anyhow::Context;
+use chrono::DateTime;
+use db::{
+    CraftingSnapshot, DbPool, FactionSnapshot, MarketplaceSnapshot, NewAtlasRate, NewFeeReduction,
+    NewMarketVars, NewOpenOrdersCounter, NewOrderAccount, NewProfile, NewProfileFaction,
+    NewProfileKey, NewRecipe, NewRecipeItem, NewRegisteredCurrency, ProfileSnapshot,
+};
+use decoder::idl::{IdlRegistry, to_snake_case};
+use decoder::staratlas::crafting::{RecipeItem, recipe_items};
+use decoder::staratlas::marketplace::accounts::{MarketplaceAccount, OrderSide, decode_account};
+use decoder::staratlas::player_profile::{player_name, profile_keys};
+use decoder::staratlas::profile_faction::profile_faction;
+use serde_json::Value;
+use solana_account_decoder::UiAccountEncoding;
+use solana_client::nonblocking::rpc_client::RpcClient;
+use solana_client::rpc_config::{RpcAccountInfoConfig, RpcProgramAccountsConfig};
+use solana_client::rpc_filter::{Memcmp, RpcFilterType};
+use solana_sdk::account::Account;
+use solana_sdk::pubkey::Pubkey;
+use std::collections::HashMap;
+
+/// Takes a snapshot of the marketplace program accounts and stores it
+pub async fn snapshot_marketplace(pool: &DbPool, client: &RpcClient) -> anyhow::Result<()> {
+    let program_id = decoder::staratlas::marketplace::ID;
+
+    // The accounts are read at this slot or later
+    let slot = rpc_with_retry(|| client.get_slot(), MAX_ATTEMPTS).await?;
+    let config = RpcProgramAccountsConfig {
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            min_context_slot: Some(slot),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let accounts = rpc_with_retry(
+        || client.get_program_accounts_with_config(&program_id, config.clone()),
+        MAX_ATTEMPTS,
+    )
+    .await?;
+    log::info!(
+        "Fetched {} marketplace accounts at slot {}",
+        accounts.len(),
+        slot
+    );
+
+    let mut snapshot = MarketplaceSnapshot {
+        slot: slot as i64,
+        ..Default::default()
+    };
+    let mut undecoded = 0;
+
+    for (address, account) in accounts {
+        let address = address.to_string();
+        match decode_account(&account.data) {
+            Some(MarketplaceAccount::OrderAccount(order)) => {
+                snapshot.orders.push(NewOrderAccount {
+                    address,
+                    order_initializer: order.order_initializer_pubkey.to_string(),
+                    currency_mint: order.currency_mint.to_string(),
+                    asset_mint: order.asset_mint.to_string(),
+                    initializer_currency_token_account: order
+                        .initializer_currency_token_account
+                        .to_string(),
+                    initializer_asset_token_account: order
+                        .initializer_asset_token_account
+                        .to_string(),
+                    side: match order.order_side {
+                        OrderSide::Buy => "BUY".to_string(),
+                        OrderSide::Sell => "SELL".to_string(),
+                    },
+                    price: to_i64(order.price)?,
+                    origination_qty: to_i64(order.order_origination_qty)?,
+                    remaining_qty: to_i64(order.order_remaining_qty)?,
+                    created_at: DateTime::from_timestamp(order.created_at_timestamp, 0)
+                        .context("order creation time out of range")?,
+                });
+            }
+            Some(MarketplaceAccount::RegisteredCurrency(currency)) => {
+                snapshot.currencies.push(NewRegisteredCurrency {
+                    address,
+                    token_mint: currency.token_mint.to_string(),
+                    sa_currency_vault: currency.sa_currency_vault.to_string(),
+                    royalty: to_i64(currency.royalty)?,
+                    royalty_tier_stake_amounts: currency
+                        .royalty_tiers
+                        .iter()
+                        .map(|tier| to_i64(tier.stake_amount))
+                        .collect::<anyhow::Result<_>>()?,
+                    royalty_tier_discounts: currency
+                        .royalty_tiers
+                        .iter()
+                        .map(|tier| to_i64(tier.discount))
+                        .collect::<anyhow::Result<_>>()?,
+                });
+            }
+            Some(MarketplaceAccount::FeeReduction(fee_reduction)) => {
+                snapshot.fee_reductions.push(NewFeeReduction {
+                    address,
+                    account: fee_reduction.account.to_string(),
+                    discount: to_i64(fee_reduction.discount)?,
+                });
+            }
+            Some(MarketplaceAccount::MarketVars(market_vars)) => {
+                snapshot.market_vars.push(NewMarketVars {
+                    address,
+                    update_authority_master: market_vars.update_authority_master.to_string(),
+                });
+            }
+            Some(MarketplaceAccount::AtlasRateAccount(atlas_rate)) => {
+                snapshot.atlas_rates.push(NewAtlasRate {
+                    address,
+                    atlas_rate: to_i64(atlas_rate.atlas_rate)?,
+                });
+            }
+            Some(MarketplaceAccount::OpenOrdersCounter(counter)) => {
+                snapshot.open_orders_counters.push(NewOpenOrdersCounter {
+                    address,
+                    open_order_count: to_i64(counter.open_order_count)?,
+                });
+            }
+            None => {
+                log::warn!("Could not decode marketplace account {}", address);
+                undecoded += 1;
+            }
+        }
+    }
+
+    // The counters should add up to the open orders if both reflect the same state
+    let counted_orders: i64 = snapshot
+        .open_orders_counters
+        .iter()
+        .map(|counter| counter.open_order_count)
+        .sum();
+    if counted_orders != snapshot.orders.len() as i64 {
+        log::warn!(
+            "Open orders counters add up to {} orders, but {} order accounts exist",
+            counted_orders,
+            snapshot.orders.len()
+        );
+    }
+
+    if !db::replace_marketplace_snapshot(pool, &snapshot).await? {
+        log::warn!("Skipped snapshot at slot {}, a newer one is stored", slot);
+        return Ok(());
+    }
+
+    log::info!(
+        "Stored snapshot at slot {}: {} orders, {} currencies, {} fee reductions, {} market vars, {} ATLAS rates, {} open orders counters, {} undecoded",
+        slot,
+        snapshot.orders.len(),
+        snapshot.currencies.len(),
+        snapshot.fee_reductions.len(),
+        snapshot.market_vars.len(),
+        snapshot.atlas_rates.len(),
+        snapshot.open_orders_counters.len(),
+        undecoded
+    );
+
+    Ok(())
+}
+
+/// Takes a snapshot of the crafting recipes and stores it
+pub async fn snapshot_crafting(
+    pool: &DbPool,
+    client: &RpcClient,
+    idls: &IdlRegistry,
+) -> anyhow::Result<()> {
+    let program_id = decoder::staratlas::crafting::ID;
+
+    let slot = rpc_with_retry(|| client.get_slot(), MAX_ATTEMPTS).await?;
+    let accounts = fetch_idl_accounts(client, idls, &program_id, slot, "recipe").await?;
+    log::info!(
+        "Fetched {} recipe accounts at slot {}",
+        accounts.len(),
+        slot
+    );
+
+    let mut snapshot = CraftingSnapshot {
+        slot: slot as i64,
+        ..Default::default()
+    };
+    let mut undecoded = 0;
+
+    for (address, account) in accounts {
+        let recipe = idls
+            .decode_account(&program_id, slot, &account.data)
+            .unwrap_or_else(|err| {
+                log::warn!("Could not decode recipe {}: {}", address, err);
+                None
+            });
+        let Some((recipe, items)) = recipe.and_then(|recipe| {
+            recipe_items(idls.idl(&program_id, slot)?, &recipe).map(|items| (recipe, items))
+        }) else {
+            log::warn!("Could not decode recipe {}", address);
+            undecoded += 1;
+            continue;
+        };
+
+        let field = |name: &str| recipe.field(name);
+        let address_field =
+            |name: &str| field(name).and_then(Value::as_str).map(ToString::to_string);
+        let amount_field = |name: &str| field(name).and_then(Value::as_u64).map(to_i64).transpose();
+        let item = |role: &str, item: &RecipeItem| {
+            Ok(NewRecipeItem {
+                role: role.to_string(),
+                mint: item.mint.to_string(),
+                amount: to_i64(item.amount)?,
+            })
+        };
+
+        snapshot.recipes.push(NewRecipe {
+            address: address.to_string(),
+            domain: address_field("domain"),
+            category: address_field("category"),
+            duration: field("duration").and_then(Value::as_i64),
+            // An enum variant name, or its index in IDLs declaring a plain integer
+            status: field("status").map(|status| match status {
+                Value::String(status) => status.clone(),
+                status => status.to_string(),
+            }),
+            fee_amount: amount_field("fee_amount")?,
+            usage_count: amount_field("usage_count")?,
+            data: recipe.data.clone(),
+            items: items
+                .consumables
+                .iter()
+                .map(|consumable| item("consumable", consumable))
+                .chain(
+                    items
+                        .non_consumables
+                        .iter()
+                        .map(|non_consumable| item("non_consumable", non_consumable)),
+                )
+                .chain(items.outputs.iter().map(|output| item("output", output)))
+                .collect::<anyhow::Result<_>>()?,
+        });
+    }
+
+    if !db::replace_crafting_snapshot(pool, &snapshot).await? {
+        log::warn!("Skipped snapshot at slot {}, a newer one is stored", slot);
+        return Ok(());
+    }
+
+    log::info!(
+        "Stored snapshot at slot {}: {} recipes, {} undecoded",
+        slot,
+        snapshot.recipes.len(),
+        undecoded
+    );
+
+    Ok(())
+}
+
+/// Takes a snapshot of the player profiles and their names and stores it
+///
+/// Storing the snapshot also copies the profile names to the players owning them.
+pub async fn snapshot_profiles(
+    pool: &DbPool,
+    client: &RpcClient,
+    idls: &IdlRegistry,
+) -> anyhow::Result<()> {
+    let program_id = decoder::staratlas::player_profile::ID;
+
+    let slot = rpc_with_retry(|| client.get_slot(), MAX_ATTEMPTS).await?;
+    let profiles = fetch_idl_accounts(client, idls, &program_id, slot, "profile").await?;
+    let names = fetch_idl_accounts(client, idls, &program_id, slot, "player_name").await?;
+    log::info!(
+        "Fetched {} profile and {} player name accounts at slot {}",
+        profiles.len(),
+        names.len(),
+        slot
+    );
+
+    let decode = |address: &Pubkey, data: &[u8]| {
+        idls.decode_account(&program_id, slot, data)
+            .unwrap_or_else(|err| {
+                log::warn!("Could not decode profile account {}: {}", address, err);
+                None
+            })
+    };
+
+    let mut undecoded = 0;
+    let mut profile_names = HashMap::new();
+    for (address, account) in names {
+        match decode(&address, &account.data).and_then(|name| player_name(&name)) {
+            Some((profile, name)) => {
+                profile_names.insert(profile, name);
+            }
+            None => {
+                log::warn!("Could not decode player name {}", address);
+                undecoded += 1;
+            }
+        }
+    }
+
+    let mut snapshot = ProfileSnapshot {
+        slot: slot as i64,
+        ..Default::default()
+    };
+    for (address, account) in profiles {
+        let Some((profile, keys)) = decode(&address, &account.data).and_then(|profile| {
+            profile_keys(idls.idl(&program_id, slot)?, &profile).map(|keys| (profile, keys))
+        }) else {
+            log::warn!("Could not decode profile {}", address);
+            undecoded += 1;
+            continue;
+        };
+
+        let field = |name: &str| profile.field(name).and_then(Value::as_i64);
+        snapshot.profiles.push(NewProfile {
+            address: address.to_string(),
+            auth_key_count: field("auth_key_count").unwrap_or_default() as i16,
+            key_threshold: field("key_threshold").unwrap_or_default() as i16,
+            created_at: field("created_at").and_then(|time| DateTime::from_timestamp(time, 0)),
+            name: profile_names.remove(&address),
+            keys: keys
+                .into_iter()
+                .map(|key| NewProfileKey {
+                    key: key.key.to_string(),
+                    scope: key.scope.to_string(),
+                    expire_time: key.expire_time,
+                    permissions: key.permissions.to_vec(),
+                    is_auth: key.is_auth,
+                })
+                .collect(),
+        });
+    }
+
+    let Some(usernames) = db::replace_profile_snapshot(pool, &snapshot).await? else {
+        log::warn!("Skipped snapshot at slot {}, a newer one is stored", slot);
+        return Ok(());
+    };
+
+    log::info!(
+        "Stored snapshot at slot {}: {} profiles, {} names of missing profiles, {} usernames changed, {} undecoded",
+        slot,
+        snapshot.profiles.len(),
+        profile_names.len(),
+        usernames,
+        undecoded
+    );
+
+    Ok(())
+}
+
+/// Takes a snapshot of the profile factions and stores it
+///
+/// Storing the snapshot also updates the factions of the players owning the profiles.
+pub async fn snapshot_factions(
+    pool: &DbPool,
+    client: &RpcClient,
+    idls: &IdlRegistry,
+) -> anyhow::Result<()> {
+    let program_id = decoder::staratlas::profile_faction::ID;
+
+    let slot = rpc_with_retry(|| client.get_slot(), MAX_ATTEMPTS).await?;
+    let accounts =
+        fetch_idl_accounts(client, idls, &program_id, slot, "profile_faction_account").await?;
+    log::info!(
+        "Fetched {} profile faction accounts at slot {}",
+        accounts.len(),
+        slot
+    );
+
+    let mut snapshot = FactionSnapshot {
+        slot: slot as i64,
+        ..Default::default()
+    };
+    let mut undecoded = 0;
+
+    for (address, account) in accounts {
+        let faction = idls
+            .decode_account(&program_id, slot, &account.data)
+            .unwrap_or_else(|err| {
+                log::warn!("Could not decode profile faction {}: {}", address, err);
+                None
+            });
+        let Some((profile, faction)) = faction.and_then(|faction| profile_faction(&faction)) else {
+            log::warn!("Could not decode profile faction {}", address);
+            undecoded += 1;
+            continue;
+        };
+
+        snapshot.factions.push(NewProfileFaction {
+            profile: profile.to_string(),
+            faction: faction.as_str().to_string(),
+        });
+    }
+
+    let Some(factions) = db::replace_faction_snapshot(pool, &snapshot).await? else {
+        log::warn!("Skipped snapshot at slot {}, a newer one is stored", slot);
+        return Ok(());
+    };
+
+    log::info!(
+        "Stored snapshot at slot {}: {} profile factions, {} player factions changed, {} undecoded",
+        slot,
+        snapshot.factions.len(),
+        factions,
+        undecoded
+    );
+
+    Ok(())
+}
+
+/// Fetches the accounts of a program with the given IDL account type
+///
+/// The IDL valid at the slot gives the discriminator the accounts are filtered by.
+async fn fetch_idl_accounts(
+    client: &RpcClient,
+    idls: &IdlRegistry,
+    program_id: &Pubkey,
+    slot: u64,
+    account_name: &str,
+) -> anyhow::Result<Vec<(Pubkey, Account)>> {
+    let idl = idls
+        .idl(program_id, slot)
+        .with_context(|| format!("no IDL registered for {}, add it to IDL_DIR", program_id))?;
+    let discriminator = idl
+        .accounts
+        .iter()
+        .find(|account| to_snake_case(&account.name) == account_name)
+        .with_context(|| format!("the {} IDL has no {} account", idl.name(), account_name))?
+        .discriminator();
+    let config = RpcProgramAccountsConfig {
+        filters: Some(vec![RpcFilterType::Memcmp(Memcmp::new_raw_bytes(
+            0,
+            discriminator,
+        ))]),
+        account_config: RpcAccountInfoConfig {
+            encoding: Some(UiAccountEncoding::Base64),
+            min_context_slot: Some(slot),
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let accounts = rpc_with_retry(
+        || client.get_program_accounts_with_config(program_id, config.clone()),
+        MAX_ATTEMPTS,
+    )
+    .await?;
+
+    Ok(accounts)
+}
+
+/// Converts a raw on-chain amount to the signed integer it is stored as
+fn to_i64(value: u64) -> anyhow::Result<i64> {
+    i64::try_from(value).with_context(|| format!("amount {} out of range", value))
+}